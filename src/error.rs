@@ -43,6 +43,96 @@ pub enum VaultError {
 
     #[error("Quote asset must use Vault Deposit/Withdraw path")]
     QuoteAssetMustUseVaultPath,
+
+    #[error("Token not registered")]
+    TokenNotRegistered,
+
+    #[error("Token is disabled")]
+    TokenDisabled,
+
+    #[error("Mint account required: legacy transfer layout is disabled")]
+    TransferCheckedRequired,
+
+    #[error("Net amount received does not match the transfer-fee policy")]
+    TransferFeeMismatch,
+
+    #[error("Mint has unsafe extensions or authorities not accepted by governance")]
+    UnsafeMint,
+
+    #[error("No pending governance authority transfer")]
+    NoPendingGovernanceTransfer,
+
+    #[error("Signer is not a governance member")]
+    NotGovernanceMember,
+
+    #[error("Invalid governance action or governance set parameters")]
+    InvalidGovernanceAction,
+
+    #[error("Governance proposal expired or voided by a membership change")]
+    GovernanceProposalExpired,
+
+    #[error("Governance proposal has not reached the approval threshold")]
+    GovernanceThresholdNotMet,
+
+    #[error("Governance proposal already executed")]
+    GovernanceProposalExecuted,
+
+    #[error("Signer is not the timelock admin")]
+    InvalidTimelockAdmin,
+
+    #[error("Timelock action ETA not reached")]
+    TimelockNotReady,
+
+    #[error("Timelock action grace period elapsed")]
+    TimelockActionStale,
+
+    #[error("Timelock action already executed or cancelled")]
+    TimelockActionClosed,
+
+    #[error("Signer does not hold the required role")]
+    MissingRole,
+
+    #[error("authorized_callers is full; register the relayer with RegisterRelayer")]
+    AuthorizedCallersFull,
+
+    #[error("Amount exceeds the relayer's per-transaction limit")]
+    RelayerLimitExceeded,
+
+    #[error("Relayer rolling-window rate limit exceeded")]
+    RateLimitExceeded,
+
+    #[error("Outflow circuit breaker tripped; USDC outflows are paused until governance resets it")]
+    OutflowPaused,
+
+    #[error("All guardian slots are in use")]
+    GuardianSetFull,
+
+    #[error("Signer is not a guardian")]
+    NotGuardian,
+
+    #[error("Escape hatch is not open")]
+    EscapeHatchClosed,
+
+    #[error("Amount is below the configured minimum")]
+    AmountBelowMinimum,
+
+    #[error("Amount is above the configured per-transaction maximum")]
+    AmountAboveMaximum,
+
+    #[error("Deposit would exceed the per-account balance cap")]
+    AccountCapExceeded,
+
+    #[error("Merkle proof does not match the posted liability root and total")]
+    InvalidMerkleProof,
+
+    #[error("Sequence is not newer than the one stored in the account")]
+    StaleSequence,
+
+    #[error("On-chain state does not match the expected previous values")]
+    StateMismatch,
+
+    #[error("Relayer registry is active; roles are granted through AuthorizedRelayer PDAs")]
+    RelayerPdaRequired,
 }
 
 impl From<VaultError> for ProgramError {
@@ -1,12 +1,27 @@
 //! Vault Program Instructions
 //!
-//! 18 active instructions for user fund custody.
+//! 63 active instructions for user fund custody.
 //! Only two on-chain programs remain: Vault (fund custody) + Exchange (on-chain audit).
 
+use crate::merkle::SumNode;
+use crate::state::{
+    PauseCategory, SpotTokenBalance, TimelockActionType, UserAccount, VaultRole, RELAYER_LABEL_LEN, TIMELOCK_ACTION_TYPE_COUNT,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Vault Program 指令 (18 active variants)
+/// Vault Program 指令 (63 active variants)
+///
+/// 按角色授权的指令 (标注 "X role") 可在账户列表最后追加 signer 的 AuthorizedRelayer PDA
+/// (seeds: ["authorized_relayer", signer])，位于所有可选账户之后。被 SetRelayerRateLimit 限流的
+/// relayer 在 RelayerDeposit / RelayerWithdraw / RelayerWithdrawAndTransfer / RelayerSpotDeposit /
+/// RelayerSpotWithdraw 中还须紧随其后传入所转 token 的可写 RelayerRateLimit PDA
+/// (seeds: ["rate_limit", signer, token_index_le_u16]，USDC 为 token_index 0)。
+///
+/// Relayer 余额指令与状态写入携带 DB 分配的单调递增 `sequence`，按目标 PDA 内存储的序号排序:
+/// `sequence` <= 已存序号时，RelayerDeposit / RelayerWithdraw / RelayerWithdrawAndTransfer /
+/// RelayerSpotDeposit / RelayerSpotWithdraw 以 `StaleSequence` 失败，UserAccount /
+/// SpotTokenBalance (set-to-value) 记录 `StaleSequence` 日志后不做修改。
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub enum VaultInstruction {
     /// Index 0: 初始化 Vault 配置
@@ -15,7 +30,7 @@ pub enum VaultInstruction {
     /// 0. `[writable, signer]` Governance Authority
     /// 1. `[writable]` VaultConfig PDA
     /// 2. `[]` USDC Mint
-    /// 3. `[writable]` Vault Token Account
+    /// 3. `[writable]` USDC Token Vault PDA (seeds: ["token_vault", usdc_mint], created here)
     /// 4. `[]` System Program
     /// 5. `[]` Token Program
     /// 6. `[]` Rent Sysvar
@@ -35,32 +50,46 @@ pub enum VaultInstruction {
 
     /// Index 2: 入金
     ///
+    /// 按 Vault Token Account 实际到账金额入账 (Token-2022 转账手续费由用户承担)。
+    ///
     /// Accounts:
     /// 0. `[signer]` User
     /// 1. `[writable]` UserAccount PDA
     /// 2. `[writable]` User USDC Token Account
-    /// 3. `[writable]` Vault USDC Token Account
+    /// 3. `[writable]` USDC Token Vault PDA (seeds: ["token_vault", usdc_mint])
     /// 4. `[writable]` VaultConfig
     /// 5. `[]` Token Program
+    /// 6. `[]` USDC Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
     Deposit {
         amount: u64,
     },
 
     /// Index 3: 出金
     ///
+    /// Sets the return data to the borsh-serialized `WithdrawOutcome`. When the
+    /// outflow breaker trips, the transaction succeeds (so the latch persists) but
+    /// nothing is debited or transferred and the outcome is `BreakerTripped`.
+    ///
     /// Accounts:
     /// 0. `[signer]` User
     /// 1. `[writable]` UserAccount PDA
     /// 2. `[writable]` User USDC Token Account
-    /// 3. `[writable]` Vault USDC Token Account
-    /// 4. `[]` VaultConfig
+    /// 3. `[writable]` USDC Token Vault PDA (seeds: ["token_vault", usdc_mint])
+    /// 4. `[writable]` VaultConfig
     /// 5. `[]` Token Program
+    /// 6. `[]` USDC Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
     Withdraw {
         amount: u64,
     },
 
     /// Index 4: 添加授权调用方 (Governance Authority only)
     ///
+    /// 已废弃: 等同于授予 Depositor + Withdrawer + BridgeTransferer + StateWriter
+    /// (旧版 authorized_callers 的全部 relayer 权限)。新代码请用 GrantRole 按需授予。
+    /// relayer 注册表启用后 (VaultConfig.relayer_registry_active) 返回 RelayerPdaRequired。
+    ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority
     /// 1. `[writable]` VaultConfig
@@ -70,6 +99,8 @@ pub enum VaultInstruction {
 
     /// Index 5: 移除授权调用方 (Governance Authority only)
     ///
+    /// Revokes every role held by `caller` and frees its slot.
+    ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority
     /// 1. `[writable]` VaultConfig
@@ -79,15 +110,24 @@ pub enum VaultInstruction {
 
     /// Index 6: 暂停/恢复 (Governance Authority only)
     ///
+    /// 仅作用于用户签名路径 (Deposit / Withdraw / SpotDeposit / SpotWithdraw)；
+    /// 其他类别用 SetPauseFlag。
+    /// Pauser 角色可暂停 (paused=true)，不能恢复。
+    /// Timelock 启用后，Timelock admin 仍可直接暂停 (paused=true)，需传入 TimelockConfig。
+    ///
     /// Accounts:
-    /// 0. `[signer]` Governance Authority
+    /// 0. `[signer]` Governance Authority (or Pauser / Timelock admin when pausing)
     /// 1. `[writable]` VaultConfig
+    /// 2. `[]` TimelockConfig PDA (optional; required for the admin pause exemption)
     SetPaused {
         paused: bool,
     },
 
     /// Index 7: 更新治理权限 (Governance Authority only)
     ///
+    /// Deprecated: 不再立即生效，行为等同 ProposeGovernanceAuthority —
+    /// 仅写入 pending_governance_authority，需新 key 签名 AcceptGovernanceAuthority。
+    ///
     /// Accounts:
     /// 0. `[signer]` Current Governance Authority
     /// 1. `[writable]` VaultConfig
@@ -95,7 +135,7 @@ pub enum VaultInstruction {
         new_governance_authority: Pubkey,
     },
 
-    /// Index 8: Relayer 代理入金 (Governance Authority/Relayer only)
+    /// Index 8: Relayer 代理入金 (Depositor role)
     ///
     /// PDA seeds: ["user", user_wallet, account_index_le_u32]
     ///
@@ -104,38 +144,51 @@ pub enum VaultInstruction {
     /// 1. `[writable]` UserAccount PDA (会自动创建)
     /// 2. `[writable]` VaultConfig
     /// 3. `[]` System Program (用于创建账户)
+    /// 4. `[]` AuthorizedRelayer PDA (optional, trailing)
+    /// 5. `[writable]` RelayerRateLimit PDA (required after the AuthorizedRelayer PDA once rate limited)
     RelayerDeposit {
         user_wallet: Pubkey,
         amount: u64,
         account_index: u32,
+        sequence: u64,
     },
 
-    /// Index 9: Relayer 代理出金 (Governance Authority/Relayer only)
+    /// Index 9: Relayer 代理出金 (Withdrawer role)
     ///
     /// PDA seeds: ["user", user_wallet, account_index_le_u32]
     ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` UserAccount PDA
-    /// 2. `[]` VaultConfig
+    /// 2. `[writable]` VaultConfig
+    /// 3. `[]` AuthorizedRelayer PDA (optional, trailing)
+    /// 4. `[writable]` RelayerRateLimit PDA (required after the AuthorizedRelayer PDA once rate limited)
     RelayerWithdraw {
         user_wallet: Pubkey,
         amount: u64,
         account_index: u32,
+        sequence: u64,
     },
 
     /// Index 10: Spot Token 入金 (用户直接调用)
     ///
     /// SPL Token 转入 Vault + 更新 SpotTokenBalance PDA (auto-init)
     ///
+    /// `amount_e6` must equal the value derived on-chain from `amount` and the
+    /// registered decimals (rounded down). The credit itself is derived the same
+    /// way from the net amount the vault received (after any Token-2022 transfer fee).
+    ///
     /// Accounts:
     /// 0. `[signer]` User
     /// 1. `[writable]` SpotTokenBalance PDA (seeds: ["spot_balance", user, token_index])
     /// 2. `[writable]` User Token Account (SPL Token)
-    /// 3. `[writable]` Vault Token Account (SPL Token)
+    /// 3. `[writable]` Token Vault PDA (seeds: ["token_vault", mint], must match TokenRegistryEntry)
     /// 4. `[]` VaultConfig
-    /// 5. `[]` Token Program
-    /// 6. `[]` System Program (for auto-init)
+    /// 5. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 6. `[]` Token Program
+    /// 7. `[]` System Program (for auto-init)
+    /// 8. `[]` Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
     SpotDeposit {
         token_index: u16,
         amount: u64,
@@ -147,13 +200,20 @@ pub enum VaultInstruction {
     ///
     /// Vault 中的 Token 转回给用户 + 更新 SpotTokenBalance PDA
     ///
+    /// The debited e6 value is derived on-chain from `amount` and the registered
+    /// decimals (rounded up); `amount_e6` must equal it. Token-2022 transfer fees
+    /// follow the token's `vault_pays_transfer_fee` policy (see SetTokenFeePolicy).
+    ///
     /// Accounts:
     /// 0. `[signer]` User
     /// 1. `[writable]` SpotTokenBalance PDA (seeds: ["spot_balance", user, token_index])
     /// 2. `[writable]` User Token Account (SPL Token)
-    /// 3. `[writable]` Vault Token Account (SPL Token)
+    /// 3. `[writable]` Token Vault PDA (seeds: ["token_vault", mint], must match TokenRegistryEntry)
     /// 4. `[]` VaultConfig
-    /// 5. `[]` Token Program
+    /// 5. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 6. `[]` Token Program
+    /// 7. `[]` Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
     SpotWithdraw {
         token_index: u16,
         amount: u64,
@@ -161,38 +221,54 @@ pub enum VaultInstruction {
         amount_e6: i64,
     },
 
-    /// Index 12: Relayer 代理 Spot 入金 (Governance Authority/Relayer only)
+    /// Index 12: Relayer 代理 Spot 入金 (Depositor role)
     ///
     /// 更新 SpotTokenBalance PDA (auto-init if needed)
     ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` SpotTokenBalance PDA (seeds: ["spot_balance", user_wallet, token_index])
-    /// 2. `[]` VaultConfig
-    /// 3. `[]` System Program (for auto-init)
+    /// 2. `[]` VaultConfig (writable while the escape hatch is enabled)
+    /// 3. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 4. `[]` System Program (for auto-init)
+    /// 5. `[]` AuthorizedRelayer PDA (optional, trailing)
+    /// 6. `[writable]` RelayerRateLimit PDA (required after the AuthorizedRelayer PDA once rate limited)
     RelayerSpotDeposit {
         user_wallet: Pubkey,
         token_index: u16,
         amount: u64,
         account_index: u32,
         amount_e6: i64,
+        sequence: u64,
     },
 
-    /// Index 13: Relayer 代理 Spot 出金 (Governance Authority/Relayer only)
+    /// Index 13: Relayer 代理 Spot 出金 (Withdrawer role)
+    ///
+    /// When transfer accounts are passed, `amount_e6` must equal the value derived
+    /// on-chain from `amount` and the registered decimals (rounded up).
     ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` SpotTokenBalance PDA (seeds: ["spot_balance", user_wallet, token_index])
-    /// 2. `[]` VaultConfig
+    /// 2. `[]` VaultConfig (writable while the escape hatch is enabled)
+    /// 3. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 4. `[writable]` Token Vault PDA (optional, must match TokenRegistryEntry)
+    /// 5. `[writable]` User Token Account (optional)
+    /// 6. `[]` Token Program (optional)
+    /// 7. `[]` Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
+    /// 8. `[]` AuthorizedRelayer PDA (optional, trailing)
+    /// 9. `[writable]` RelayerRateLimit PDA (required after the AuthorizedRelayer PDA once rate limited)
     RelayerSpotWithdraw {
         user_wallet: Pubkey,
         token_index: u16,
         amount: u64,
         account_index: u32,
         amount_e6: i64,
+        sequence: u64,
     },
 
-    /// Index 14: Relayer 代理出金并转账 (Governance Authority/Relayer only)
+    /// Index 14: Relayer 代理出金并转账 (BridgeTransferer role)
     ///
     /// 功能：
     /// 1. 扣除用户 Vault 余额（记账）
@@ -200,28 +276,39 @@ pub enum VaultInstruction {
     ///
     /// 用途：跨链桥出金 — Relayer 从 Vault 提取 USDC 后调用 Bridge.stake 跨链
     ///
+    /// Sets the return data to the borsh-serialized `WithdrawOutcome`. On
+    /// `BreakerTripped` the transaction succeeds but no balance, sequence or rate
+    /// limit changes and no USDC moves; the relayer must not treat it as a payout.
+    ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` UserAccount PDA
-    /// 2. `[]` VaultConfig
-    /// 3. `[writable]` Vault Token Account
+    /// 2. `[writable]` VaultConfig
+    /// 3. `[writable]` USDC Token Vault PDA (= VaultConfig.vault_token_account)
     /// 4. `[writable]` Relayer Token Account (接收方)
     /// 5. `[]` Token Program
+    /// 6. `[]` USDC Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
+    /// 7. `[]` AuthorizedRelayer PDA (optional, trailing)
+    /// 8. `[writable]` RelayerRateLimit PDA (required after the AuthorizedRelayer PDA once rate limited)
     RelayerWithdrawAndTransfer {
         user_wallet: Pubkey,
         amount: u64,
         account_index: u32,
+        sequence: u64,
     },
 
-    /// Index 15: UserAccount state (Relayer-only, set-to-value)
+    /// Index 15: UserAccount state (StateWriter role, set-to-value)
     ///
     /// Sets UserAccount PDA fields to exact values (idempotent, not add/subtract).
+    /// The VaultConfig liability counters move by the difference.
     ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` UserAccount PDA
-    /// 2. `[]` VaultConfig
+    /// 2. `[writable]` VaultConfig
     /// 3. `[]` System Program (for auto-init if PDA doesn't exist)
+    /// 4. `[]` AuthorizedRelayer PDA (optional, last)
     UserAccount {
         user_wallet: Pubkey,
         account_index: u32,
@@ -229,32 +316,899 @@ pub enum VaultInstruction {
         locked_margin_e6: i64,
         spot_locked_e6: i64,
         oracle_locked_e6: i64,
+        sequence: u64,
     },
 
-    /// Index 16: SpotTokenBalance state (Relayer-only, set-to-value)
+    /// Index 16: SpotTokenBalance state (StateWriter role, set-to-value)
     ///
     /// Sets SpotTokenBalance PDA fields to exact values (idempotent).
+    /// The token's liability counter moves by the difference.
     ///
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` SpotTokenBalance PDA
-    /// 2. `[]` VaultConfig
-    /// 3. `[]` System Program (for auto-init if PDA doesn't exist)
+    /// 2. `[]` VaultConfig (writable while the escape hatch is enabled)
+    /// 3. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 4. `[]` System Program (for auto-init if PDA doesn't exist)
+    /// 5. `[]` AuthorizedRelayer PDA (optional, last)
     SpotTokenBalance {
         user_wallet: Pubkey,
         account_index: u32,
         token_index: u16,
         available_e6: i64,
         locked_e6: i64,
+        sequence: u64,
     },
 
-    /// Index 17: Migrate VaultConfig from V1 (569 bytes) to V2 (505 bytes)
+    /// Index 17: Migrate VaultConfig to V3 (1024 bytes)
     ///
-    /// Removes deprecated ledger_program and fund_program fields.
+    /// V1 (569 bytes): removes deprecated ledger_program and fund_program fields.
+    /// V2 (505 bytes): grows the account for pending_governance_authority.
+    /// The governance authority tops up rent for the larger account.
     ///
     /// Accounts:
-    /// 0. `[signer]` Governance Authority
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
     /// 1. `[writable]` VaultConfig PDA
     /// 2. `[]` System Program
     MigrateVaultConfig,
+
+    /// Index 18: 上架 Spot Token (Governance Authority only)
+    ///
+    /// Creates the TokenRegistryEntry PDA binding `token_index` to its mint,
+    /// decimals (read from the mint), token program (mint owner) and vault token account.
+    ///
+    /// The mint is scanned for `token_compat::MINT_RISK_*` flags (mint/freeze authority,
+    /// PermanentDelegate, TransferHook, DefaultAccountState=Frozen, unknown extensions).
+    /// Listing fails unless every detected flag is included in `allowed_risk_flags`;
+    /// both bitmasks are recorded in the entry and enforced on deposits.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[]` VaultConfig
+    /// 2. `[writable]` TokenRegistryEntry PDA (seeds: ["token", token_index])
+    /// 3. `[]` Mint
+    /// 4. `[]` Token Vault PDA (seeds: ["token_vault", mint], see InitializeTokenVault)
+    /// 5. `[]` System Program
+    AddToken {
+        token_index: u16,
+        allowed_risk_flags: u8,
+    },
+
+    /// Index 19: 更新 Spot Token 的 Vault Token Account (Governance Authority only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[]` VaultConfig
+    /// 2. `[writable]` TokenRegistryEntry PDA
+    /// 3. `[]` Token Vault PDA (seeds: ["token_vault", entry.mint])
+    UpdateToken {
+        token_index: u16,
+    },
+
+    /// Index 20: 禁用/启用 Spot Token (Governance Authority only)
+    ///
+    /// A disabled token rejects SpotDeposit/RelayerSpotDeposit; withdrawals stay open.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[]` VaultConfig
+    /// 2. `[writable]` TokenRegistryEntry PDA
+    DisableToken {
+        token_index: u16,
+        disabled: bool,
+    },
+
+    /// Index 21: 设置是否强制 transfer_checked (Governance Authority only)
+    ///
+    /// OC-M6 过渡期开关。required=true 时，所有转账指令必须在账户列表末尾传入 Mint，
+    /// 不含 Mint 的旧账户布局返回 TransferCheckedRequired。
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig
+    SetRequireTransferChecked {
+        required: bool,
+    },
+
+    /// Index 22: 设置转账手续费承担方 (Governance Authority only)
+    ///
+    /// 仅对带 TransferFeeConfig 的 Token-2022 mint 有意义。入金始终按 vault 实际到账
+    /// 入账；出金时 vault_pays_transfer_fee=false 由用户承担 (到账 amount - fee)，
+    /// true 由 vault 承担 (vault 按 gross-up 发送，用户到账 amount，需传 Mint 账户)。
+    /// token_index = 0 设置 USDC (Withdraw / RelayerWithdrawAndTransfer / EscapeWithdraw)。
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA (writable for USDC only)
+    /// 2. `[writable]` TokenRegistryEntry PDA (Spot tokens only)
+    SetTokenFeePolicy {
+        token_index: u16,
+        vault_pays_transfer_fee: bool,
+    },
+
+    /// Index 23: 创建 Token Vault PDA (Governance Authority only)
+    ///
+    /// Creates the deterministic vault token account ["token_vault", mint] for
+    /// SPL Token or Token-2022, with the VaultConfig PDA as authority. All transfer
+    /// paths require exactly this account.
+    ///
+    /// Migration: if the mint already has a configured vault account (USDC:
+    /// VaultConfig.vault_token_account; Spot: TokenRegistryEntry.vault_token_account),
+    /// pass it as account 6 — its balance is swept into the new vault and the
+    /// configuration is re-pointed. Listed Spot mints must also pass account 7. If the
+    /// PDA vault already exists (e.g. created without the TokenRegistryEntry), calling
+    /// again with accounts 6 and 7 sweeps into it; with nothing to migrate it fails.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[writable]` VaultConfig
+    /// 2. `[]` Mint
+    /// 3. `[writable]` Token Vault PDA (seeds: ["token_vault", mint])
+    /// 4. `[]` Token Program (mint owner)
+    /// 5. `[]` System Program
+    /// 6. `[writable]` Legacy vault token account (optional)
+    /// 7. `[writable]` TokenRegistryEntry PDA (optional; Spot mints being migrated)
+    InitializeTokenVault,
+
+    /// Index 24: 提议新的治理权限 (Governance Authority only)
+    ///
+    /// 写入 VaultConfig.pending_governance_authority，覆盖之前未接受的提议。
+    /// 新 key 必须签名 AcceptGovernanceAuthority 才生效。
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Current Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    ProposeGovernanceAuthority {
+        new_authority: Pubkey,
+    },
+
+    /// Index 25: 接受治理权限 (Pending Governance Authority only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Pending Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    AcceptGovernanceAuthority,
+
+    /// Index 26: 取消待处理的治理权限转移 (Governance Authority only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Current Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    CancelGovernanceTransfer,
+
+    /// Index 27: 启用多签治理 (Governance Authority only)
+    ///
+    /// Creates the GovernanceSet PDA and hands VaultConfig.governance_authority to the
+    /// governance signer PDA (seeds: ["governance_signer"]). Afterwards governance
+    /// instructions run only through proposals (Index 28-30). Fund the signer PDA with
+    /// SOL if governance actions must pay rent (AddToken, InitializeTokenVault, ...).
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` GovernanceSet PDA (seeds: ["governance_set"])
+    /// 3. `[]` System Program
+    InitializeGovernanceSet {
+        members: Vec<Pubkey>,
+        threshold: u8,
+        proposal_ttl_secs: i64,
+    },
+
+    /// Index 28: 创建治理提案 (Governance member only)
+    ///
+    /// `instruction_data` is a borsh-serialized governance VaultInstruction
+    /// (see `VaultInstruction::is_governance_action`). The remaining accounts are the
+    /// exact accounts it will be executed with; their keys are pinned in the proposal.
+    /// The proposer's approval is recorded.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Member (rent payer)
+    /// 1. `[writable]` GovernanceSet PDA
+    /// 2. `[writable]` GovernanceProposal PDA (seeds: ["governance_proposal", next_proposal_id_le_u64])
+    /// 3. `[]` System Program
+    /// 4. `[..]` Remaining: accounts of the governance instruction
+    CreateGovernanceProposal {
+        instruction_data: Vec<u8>,
+    },
+
+    /// Index 29: 批准治理提案 (Governance member only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Member
+    /// 1. `[]` GovernanceSet PDA
+    /// 2. `[writable]` GovernanceProposal PDA
+    ApproveGovernanceProposal {
+        proposal_id: u64,
+    },
+
+    /// Index 30: 执行治理提案 (Governance member only)
+    ///
+    /// Requires `threshold` approvals before expiry. Invokes the stored instruction
+    /// with the governance signer PDA signing as governance authority.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Member
+    /// 1. `[]` GovernanceSet PDA
+    /// 2. `[writable]` GovernanceProposal PDA
+    /// 3. `[]` Vault Program (CPI target)
+    /// 4. `[..]` Remaining: accounts of the governance instruction (same keys and order as at creation)
+    ExecuteGovernanceProposal {
+        proposal_id: u64,
+    },
+
+    /// Index 31: 更新多签成员/阈值 (Governance signer PDA only, via proposal)
+    ///
+    /// Bumps GovernanceSet.set_version, voiding all open proposals.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority (governance signer PDA)
+    /// 1. `[]` VaultConfig PDA
+    /// 2. `[writable]` GovernanceSet PDA
+    UpdateGovernanceSet {
+        members: Vec<Pubkey>,
+        threshold: u8,
+        proposal_ttl_secs: i64,
+    },
+
+    /// Index 32: 启用 Timelock (Governance Authority only)
+    ///
+    /// Creates the TimelockConfig PDA with the current governance authority as admin and
+    /// hands VaultConfig.governance_authority to the timelock signer PDA
+    /// (seeds: ["timelock_signer"]). Afterwards governance instructions run only through
+    /// QueueTimelockAction / ExecuteTimelockAction. Fund the signer PDA with SOL if
+    /// queued actions must pay rent.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer, becomes admin)
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` TimelockConfig PDA (seeds: ["timelock_config"])
+    /// 3. `[]` System Program
+    InitializeTimelock {
+        /// Minimum delay (seconds) per `TimelockActionType`
+        min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT],
+    },
+
+    /// Index 33: 排队治理操作 (Timelock admin only)
+    ///
+    /// ETA = now + min_delay_secs[action type]. Same encoding as CreateGovernanceProposal:
+    /// the remaining accounts are pinned and must be passed unchanged on execution.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Timelock admin (rent payer)
+    /// 1. `[writable]` TimelockConfig PDA
+    /// 2. `[writable]` TimelockAction PDA (seeds: ["timelock_action", next_action_id_le_u64])
+    /// 3. `[]` System Program
+    /// 4. `[..]` Remaining: accounts of the governance instruction
+    QueueTimelockAction {
+        instruction_data: Vec<u8>,
+    },
+
+    /// Index 34: 执行到期的治理操作 (permissionless)
+    ///
+    /// Executable from the ETA until TIMELOCK_GRACE_PERIOD_SECS after it.
+    ///
+    /// Accounts:
+    /// 0. `[]` TimelockConfig PDA
+    /// 1. `[writable]` TimelockAction PDA
+    /// 2. `[]` Vault Program (CPI target)
+    /// 3. `[..]` Remaining: accounts of the governance instruction (same keys and order as when queued)
+    ExecuteTimelockAction {
+        action_id: u64,
+    },
+
+    /// Index 35: 取消排队的治理操作 (Timelock admin only)
+    ///
+    /// Guardians cancel with GuardianCancelTimelockAction, which records a reason code.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Timelock admin
+    /// 1. `[]` VaultConfig PDA
+    /// 2. `[]` TimelockConfig PDA
+    /// 3. `[writable]` TimelockAction PDA
+    CancelTimelockAction {
+        action_id: u64,
+    },
+
+    /// Index 36: 更新 Timelock admin / 最小延迟 (Governance Authority only, i.e. via the timelock)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority (timelock signer PDA)
+    /// 1. `[]` VaultConfig PDA
+    /// 2. `[writable]` TimelockConfig PDA
+    UpdateTimelockConfig {
+        admin: Pubkey,
+        min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT],
+    },
+
+    /// Index 37: 授予角色 (Governance Authority only)
+    ///
+    /// Adds `role` to `account`'s AuthorizedRelayer PDA when it is passed, otherwise
+    /// to its authorized_callers slot (taking a free slot if needed). Slots can no longer
+    /// be written once the relayer registry is active (RelayerPdaRequired).
+    /// The governance authority implicitly holds every role and cannot be listed.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` AuthorizedRelayer PDA of `account` (optional)
+    GrantRole {
+        account: Pubkey,
+        role: VaultRole,
+    },
+
+    /// Index 38: 撤销角色 (Governance Authority only)
+    ///
+    /// Without an AuthorizedRelayer PDA the authorized_callers slot is updated, and
+    /// freed once `account` holds no role.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` AuthorizedRelayer PDA of `account` (optional)
+    RevokeRole {
+        account: Pubkey,
+        role: VaultRole,
+    },
+
+    /// Index 39: 注册 Relayer (Governance Authority only)
+    ///
+    /// Creates the AuthorizedRelayer PDA for `relayer`. `roles` is a VaultRole bitmask;
+    /// per-transaction caps are in e6 (0 = no cap). Fails for a key still listed in
+    /// authorized_callers. The first registration activates the relayer registry:
+    /// authorized_callers slots can no longer be written.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` AuthorizedRelayer PDA (seeds: ["authorized_relayer", relayer])
+    /// 3. `[]` System Program
+    RegisterRelayer {
+        relayer: Pubkey,
+        label: [u8; RELAYER_LABEL_LEN],
+        roles: u8,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+    },
+
+    /// Index 40: 更新 Relayer (Governance Authority only)
+    ///
+    /// Overwrites label, roles, enabled flag and per-transaction caps.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[]` VaultConfig PDA
+    /// 2. `[writable]` AuthorizedRelayer PDA
+    UpdateRelayer {
+        relayer: Pubkey,
+        label: [u8; RELAYER_LABEL_LEN],
+        roles: u8,
+        enabled: bool,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+    },
+
+    /// Index 41: 将 authorized_callers 迁移到 AuthorizedRelayer PDA (Governance Authority only)
+    ///
+    /// Moves every listed caller, with its roles, into its AuthorizedRelayer PDA
+    /// (created if missing, roles merged otherwise) and clears the slot. Activates the
+    /// relayer registry, so from then on every relayer must pass its PDA.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[]` System Program
+    /// 3. `[writable]` Remaining: one AuthorizedRelayer PDA per listed caller, in slot order
+    MigrateAuthorizedCallers,
+
+    /// Index 42: 设置 Relayer 滚动窗口限额 (Governance Authority only)
+    ///
+    /// Creates or updates the relayer's RelayerRateLimit PDA for `token_index` and marks
+    /// the AuthorizedRelayer as rate limited. The window is 24 buckets of `bucket_secs`;
+    /// caps are per window in e6 of that token (0 = no cap). For USDC (`token_index = 0`)
+    /// they cover RelayerDeposit and RelayerWithdraw / RelayerWithdrawAndTransfer; for a
+    /// Spot token, RelayerSpotDeposit and RelayerSpotWithdraw. A rate-limited relayer
+    /// needs a PDA for every token it moves. Changing `bucket_secs` clears the recorded buckets.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority (rent payer)
+    /// 1. `[]` VaultConfig PDA
+    /// 2. `[writable]` AuthorizedRelayer PDA (seeds: ["authorized_relayer", relayer])
+    /// 3. `[writable]` RelayerRateLimit PDA (seeds: ["rate_limit", relayer, token_index_le_u16])
+    /// 4. `[]` System Program
+    SetRelayerRateLimit {
+        relayer: Pubkey,
+        token_index: u16,
+        bucket_secs: i64,
+        max_deposit_per_window_e6: u64,
+        max_withdraw_per_window_e6: u64,
+    },
+
+    /// Index 43: 设置 USDC 出金熔断器 (Governance Authority only)
+    ///
+    /// Net outflow from the USDC token vault (Withdraw, RelayerWithdrawAndTransfer)
+    /// within `window_secs` may not exceed `limit_bps` of the vault balance at the
+    /// start of the window. An outflow that would cross the limit moves no funds,
+    /// latches `outflow_paused` and succeeds so the latch persists; later outflows
+    /// fail with `OutflowPaused` until ResetOutflowBreaker. While enabled, those
+    /// instructions must pass VaultConfig as writable. `limit_bps` = 0 disables it.
+    ///
+    /// Spot outflows (SpotWithdraw, RelayerSpotWithdraw, EscapeSpotWithdraw) are not
+    /// covered; cap them with `VaultLimits` and per-relayer rate limits instead.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    SetOutflowBreaker {
+        limit_bps: u16,
+        window_secs: i64,
+    },
+
+    /// Index 44: 解除出金熔断 (Governance Authority only)
+    ///
+    /// Clears `outflow_paused` and opens a fresh window, restoring the full window headroom.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    ResetOutflowBreaker,
+
+    /// Index 45: 按类别暂停/恢复 (Governance Authority only)
+    ///
+    /// Sets or clears one `VaultConfig.pause_flags` bit. Authorization matches SetPaused:
+    /// a Pauser or the Timelock admin may pause, only governance may resume.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority (or Pauser / Timelock admin when pausing)
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[]` TimelockConfig PDA (optional; required for the admin pause exemption)
+    SetPauseFlag {
+        category: PauseCategory,
+        paused: bool,
+    },
+
+    /// Index 46: 添加 Guardian (Governance Authority only)
+    ///
+    /// Takes the first free of the MAX_GUARDIANS slots in `guardians`.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    AddGuardian {
+        guardian: Pubkey,
+    },
+
+    /// Index 47: 移除 Guardian (Governance Authority only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    RemoveGuardian {
+        guardian: Pubkey,
+    },
+
+    /// Index 48: Guardian 暂停 (Guardian only)
+    ///
+    /// Sets `pause_flags` bits (PauseCategory::bit); clearing them needs governance
+    /// (SetPauseFlag). Records the reason code and timestamp in `guardian_actions`.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Guardian
+    /// 1. `[writable]` VaultConfig PDA
+    GuardianPause {
+        pause_flags: u16,
+        reason_code: u16,
+    },
+
+    /// Index 49: Guardian 取消排队的治理操作 (Guardian only)
+    ///
+    /// Records the reason code and timestamp in `guardian_actions`.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Guardian
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` TimelockAction PDA
+    GuardianCancelTimelockAction {
+        action_id: u64,
+        reason_code: u16,
+    },
+
+    /// Index 50: 设置逃生舱 (Governance Authority only)
+    ///
+    /// Opens escape mode after `inactivity_secs` without relayer activity or state
+    /// writes (0 = disabled). Escape withdrawals pay the available balance plus
+    /// `locked_share_bps` of the locked balance. Resets the heartbeat to now.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    SetEscapeHatch {
+        inactivity_secs: i64,
+        locked_share_bps: u16,
+    },
+
+    /// Index 51: 逃生舱 USDC 出金 (用户直接调用, escape mode only)
+    ///
+    /// Pays `available_balance_e6` plus the configured share of `locked_margin_e6`,
+    /// `spot_locked_e6` and `oracle_locked_e6`, then zeroes them. The unpaid locked
+    /// amount is logged as `forfeited_e6`. Ignores pause flags and the outflow breaker.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` User
+    /// 1. `[writable]` UserAccount PDA
+    /// 2. `[writable]` User USDC Token Account
+    /// 3. `[writable]` USDC Token Vault PDA (= VaultConfig.vault_token_account)
+    /// 4. `[writable]` VaultConfig
+    /// 5. `[]` Token Program
+    /// 6. `[]` USDC Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
+    EscapeWithdraw,
+
+    /// Index 52: 逃生舱 Spot Token 出金 (用户直接调用, escape mode only)
+    ///
+    /// Pays `available_e6` plus the configured share of `locked_e6`, converted to
+    /// native units (rounded down). Only the paid amount is debited: the rounding
+    /// dust stays in `available_e6`, `locked_e6` is zeroed and its unpaid part is
+    /// logged as `forfeited_e6`. Ignores pause flags.
+    ///
+    /// Accounts:
+    /// 0. `[signer]` User
+    /// 1. `[writable]` SpotTokenBalance PDA
+    /// 2. `[writable]` User Token Account
+    /// 3. `[writable]` Token Vault PDA (must match TokenRegistryEntry)
+    /// 4. `[]` VaultConfig
+    /// 5. `[writable]` TokenRegistryEntry PDA
+    /// 6. `[]` Token Program
+    /// 7. `[]` Mint (optional; enables transfer_checked, required when
+    ///    `VaultConfig.require_transfer_checked` is on)
+    EscapeSpotWithdraw {
+        token_index: u16,
+        account_index: u32,
+    },
+
+    /// Index 53: 设置出入金限额 (Governance Authority only)
+    ///
+    /// `token_index = 0` sets the USDC limits in VaultConfig; any other index sets
+    /// the limits of that Spot token in its TokenRegistryEntry. Amounts are in the
+    /// token's e6 units; 0 = no limit. Every deposit and withdraw instruction
+    /// enforces them (escape withdrawals excepted).
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` TokenRegistryEntry PDA (Spot tokens only)
+    SetLimits {
+        token_index: u16,
+        min_deposit_e6: u64,
+        min_withdraw_e6: u64,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+        max_account_balance_e6: u64,
+    },
+
+    /// Index 54: 通用账户迁移 — 将 VaultConfig / UserAccount / SpotTokenBalance 升级到当前布局
+    ///
+    /// The account type is taken from its discriminator. Any supported older layout is
+    /// decoded, reallocated if its size changed (payer tops up rent) and rewritten with
+    /// the current `layout_version`. Idempotent: an account already at the current
+    /// layout is left untouched and the instruction succeeds.
+    /// VaultConfig requires the governance authority as payer; UserAccount and
+    /// SpotTokenBalance migrations are permissionless (the PDA is checked instead).
+    /// A UserAccount / SpotTokenBalance with `liability_counted = false` is added in
+    /// full to the liability counters of account 3, which must then be writable.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Payer (Governance Authority for VaultConfig)
+    /// 1. `[writable]` Account to migrate
+    /// 2. `[]` System Program
+    /// 3. `[writable]` VaultConfig PDA (UserAccount) or TokenRegistryEntry PDA
+    ///    of the balance's token (SpotTokenBalance); not passed for VaultConfig
+    MigrateAccount,
+
+    /// Index 55: 偿付能力检查 (permissionless)
+    ///
+    /// Reads the vault token account balance (converted to e6, rounded down) and the
+    /// tracked liabilities — VaultConfig for USDC (`token_index = 0`), the
+    /// TokenRegistryEntry otherwise — and writes the reserve/liability ratio with a
+    /// timestamp into the Solvency PDA (created on first use, payer funds rent).
+    /// When `VaultConfig.solvency_auto_pause` is on and reserves have been short since
+    /// a check at least `SOLVENCY_AUTO_PAUSE_DELAY_SECS` earlier, sets the outflow
+    /// pause flags (`SOLVENCY_PAUSE_FLAGS`). A check with reserves covered resets it.
+    ///
+    /// Liabilities are only as trustworthy as the StateWriter's set-to-value writes:
+    /// a faulty or compromised writer can inflate them and force the pause after the
+    /// delay, or hide a shortfall. Accounts not yet counted are missing entirely.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Payer
+    /// 1. `[]` VaultConfig PDA (writable while solvency auto-pause is enabled)
+    /// 2. `[writable]` Solvency PDA (seeds: ["solvency", token_index_le_u16])
+    /// 3. `[]` Token Vault PDA (= VaultConfig.vault_token_account or TokenRegistryEntry.vault_token_account)
+    /// 4. `[]` System Program
+    /// 5. `[]` TokenRegistryEntry PDA (Spot tokens only)
+    CheckSolvency {
+        token_index: u16,
+    },
+
+    /// Index 56: 开关偿付不足自动暂停 (Governance Authority only)
+    ///
+    /// Accounts:
+    /// 0. `[signer]` Governance Authority
+    /// 1. `[writable]` VaultConfig PDA
+    SetSolvencyAutoPause {
+        enabled: bool,
+    },
+
+    /// Index 57: 发布负债 Merkle root (StateWriter role)
+    ///
+    /// Creates the ReservesEpoch PDA for `epoch` (relayer pays rent) holding the root
+    /// of `merkle::LiabilityTree` (a Merkle sum tree) over every user's DB balance and
+    /// the liability sum. The root hash commits to the sum, so a `total_liabilities_e6`
+    /// that differs from the tree's makes every `VerifyInclusion` against it fail.
+    /// `epoch` must be greater than `VaultConfig.latest_liability_epoch`.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Relayer
+    /// 1. `[writable]` VaultConfig PDA
+    /// 2. `[writable]` ReservesEpoch PDA (seeds: ["reserves_epoch", epoch_le_u64])
+    /// 3. `[]` System Program
+    PostLiabilityRoot {
+        epoch: u64,
+        root: [u8; 32],
+        total_liabilities_e6: i64,
+        leaf_count: u32,
+    },
+
+    /// Index 58: 验证用户负债被计入某 epoch 的 root (permissionless)
+    ///
+    /// Rejects negative balances, hashes the leaf (wallet, account_index, balances),
+    /// walks `proof` (sibling `(hash, sum)` nodes, bottom-up, at most
+    /// `merkle::MAX_PROOF_DEPTH`) adding up the sums, and fails with
+    /// `InvalidMerkleProof` unless it reaches both the posted root and the posted
+    /// `total_liabilities_e6`. On success bumps
+    /// `verified_count` and logs `InclusionVerified`.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` ReservesEpoch PDA
+    VerifyInclusion {
+        epoch: u64,
+        wallet: Pubkey,
+        account_index: u32,
+        available_balance_e6: i64,
+        locked_e6: i64,
+        proof: Vec<SumNode>,
+    },
+
+    /// Index 59: 批量 UserAccount state (StateWriter role, set-to-value)
+    ///
+    /// Applies each `UserAccountUpdate` exactly like `UserAccount`: missing PDAs are
+    /// created with the signer paying rent, stale sequences are skipped. Relayer
+    /// authority, pause state and the heartbeat are checked once for the batch.
+    /// Any other error (wrong PDA, wallet mismatch) fails the whole batch. Logs one
+    /// `BatchUserAccount[i]` line per entry and sets the return data to the
+    /// borsh-serialized `Vec<StateWriteResult>`, in update order. At most
+    /// `MAX_BATCH_USER_ACCOUNT_UPDATES` (8) entries, which is what fits in one
+    /// legacy transaction.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority/Relayer (rent payer)
+    /// 1. `[writable]` VaultConfig
+    /// 2. `[]` System Program
+    /// 3. `[writable]` UserAccount PDA of each update, in update order (N accounts)
+    /// 4. `[]` AuthorizedRelayer PDA (optional, last)
+    BatchUserAccount {
+        updates: Vec<UserAccountUpdate>,
+    },
+
+    /// Index 60: 批量 SpotTokenBalance state (StateWriter role, set-to-value)
+    ///
+    /// Applies each `SpotTokenBalanceUpdate` exactly like `SpotTokenBalance`, across
+    /// any mix of wallets, sub-accounts and tokens. Missing PDAs are created with the
+    /// signer as the single rent payer; stale sequences are skipped. `mode` decides
+    /// what an invalid entry (wrong PDA derivation, unregistered token, ...) does:
+    /// `AllOrNothing` fails the batch, `SkipInvalid` logs the reason and moves on.
+    /// Sets the return data to the borsh-serialized `Vec<StateWriteResult>`. At most
+    /// `MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES` (7) entries, which is what fits in one
+    /// legacy transaction when every entry names a different token.
+    ///
+    /// Accounts:
+    /// 0. `[writable, signer]` Governance Authority/Relayer (rent payer)
+    /// 1. `[]` VaultConfig (writable while the escape hatch is enabled)
+    /// 2. `[]` System Program
+    /// 3. `[writable]` SpotTokenBalance PDA, then `[writable]` TokenRegistryEntry PDA,
+    ///    for each update in update order (2N accounts)
+    /// 4. `[]` AuthorizedRelayer PDA (optional, last)
+    BatchSpotTokenBalance {
+        updates: Vec<SpotTokenBalanceUpdate>,
+        mode: BatchMode,
+    },
+
+    /// Index 61: UserAccount compare-and-set (StateWriter role)
+    ///
+    /// Same as `UserAccount`, but the write only goes through if the PDA still holds
+    /// `expected` (all zero for a PDA that does not exist yet). Otherwise fails with
+    /// `StateMismatch` and logs both values, so the backend can detect on-chain changes
+    /// (e.g. a user Deposit / Withdraw) instead of overwriting them. A stale sequence
+    /// is still a no-op.
+    ///
+    /// Accounts: same as `UserAccount`
+    CasUserAccount {
+        update: UserAccountUpdate,
+        expected: ExpectedUserAccount,
+    },
+
+    /// Index 62: SpotTokenBalance compare-and-set (StateWriter role)
+    ///
+    /// Same as `SpotTokenBalance`, guarded by `expected` like `CasUserAccount`.
+    ///
+    /// Accounts: same as `SpotTokenBalance`
+    CasSpotTokenBalance {
+        update: SpotTokenBalanceUpdate,
+        expected: ExpectedSpotTokenBalance,
+    },
+}
+
+/// 单笔交易序列化后的上限 (bytes)，即 `solana_sdk::packet::PACKET_DATA_SIZE`
+pub const MAX_TRANSACTION_SIZE: usize = 1232;
+
+/// 批量写入 legacy 交易中与条目数无关的字节数，依次为: 签名 (1 + 64)、message header (3)、
+/// 5 个静态账户 (1 + 5 × 32: signer, VaultConfig, System Program, program id,
+/// AuthorizedRelayer PDA)、blockhash (32)、指令头 (1 + 1 + 1 + 2)、
+/// 指令数据前缀 (discriminator 1 + Vec 长度 4 + BatchMode 1)
+const BATCH_TRANSACTION_OVERHEAD: usize = 65 + 3 + 161 + 32 + 5 + 6;
+
+/// borsh 编码的 `UserAccountUpdate` 大小: 32 + 4 + 8 × 5
+pub const USER_ACCOUNT_UPDATE_SIZE: usize = 76;
+
+/// borsh 编码的 `SpotTokenBalanceUpdate` 大小: 32 + 4 + 2 + 8 × 3
+pub const SPOT_TOKEN_BALANCE_UPDATE_SIZE: usize = 62;
+
+/// BatchUserAccount 最大条目数: 每条 76 bytes 数据 + 1 个 UserAccount PDA (32 key + 1 index)
+/// = 109 bytes，(1232 - 272) / 109 = 8。按 relayer 自付手续费的单签名 legacy 交易计算；
+/// 8 条全部新建 PDA 时须在 200k CU 默认预算内，由 `test_batch_user_account_full_batch` 检查
+/// (`cargo test-sbf` 下按 SBF 实际计量)。
+pub const MAX_BATCH_USER_ACCOUNT_UPDATES: usize =
+    (MAX_TRANSACTION_SIZE - BATCH_TRANSACTION_OVERHEAD) / (USER_ACCOUNT_UPDATE_SIZE + 33);
+
+/// BatchSpotTokenBalance 最大条目数: 每条 62 bytes 数据 + SpotTokenBalance PDA 与
+/// TokenRegistryEntry PDA (2 × 33) = 128 bytes，(1232 - 272) / 128 = 7。按每条 token 不同
+/// (registry 账户不去重) 的最坏情况计算；计算量由 `test_batch_spot_token_balance_full_batch` 覆盖。
+pub const MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES: usize =
+    (MAX_TRANSACTION_SIZE - BATCH_TRANSACTION_OVERHEAD) / (SPOT_TOKEN_BALANCE_UPDATE_SIZE + 66);
+
+/// BatchUserAccount 的单条更新 (字段与 UserAccount 指令一致)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserAccountUpdate {
+    pub user_wallet: Pubkey,
+    pub account_index: u32,
+    pub available_balance_e6: i64,
+    pub locked_margin_e6: i64,
+    pub spot_locked_e6: i64,
+    pub oracle_locked_e6: i64,
+    pub sequence: u64,
+}
+
+/// BatchSpotTokenBalance 的单条更新 (字段与 SpotTokenBalance 指令一致)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpotTokenBalanceUpdate {
+    pub user_wallet: Pubkey,
+    pub account_index: u32,
+    pub token_index: u16,
+    pub available_e6: i64,
+    pub locked_e6: i64,
+    pub sequence: u64,
+}
+
+/// CasUserAccount 的期望当前值 (PDA 不存在时视为全零)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpectedUserAccount {
+    pub available_balance_e6: i64,
+    pub locked_margin_e6: i64,
+    pub spot_locked_e6: i64,
+    pub oracle_locked_e6: i64,
+}
+
+impl ExpectedUserAccount {
+    /// 账户当前值 (与 UserAccount 指令写入的字段一致)
+    pub fn of(account: &UserAccount) -> Self {
+        Self {
+            available_balance_e6: account.available_balance_e6,
+            locked_margin_e6: account.locked_margin_e6,
+            spot_locked_e6: account.spot_locked_e6,
+            oracle_locked_e6: account.oracle_locked_e6,
+        }
+    }
+}
+
+/// CasSpotTokenBalance 的期望当前值 (PDA 不存在时视为全零)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpectedSpotTokenBalance {
+    pub available_e6: i64,
+    pub locked_e6: i64,
+}
+
+impl ExpectedSpotTokenBalance {
+    /// 账户当前值 (与 SpotTokenBalance 指令写入的字段一致)
+    pub fn of(balance: &SpotTokenBalance) -> Self {
+        Self { available_e6: balance.available_e6, locked_e6: balance.locked_e6 }
+    }
+}
+
+/// 批量状态写入遇到无效条目时的处理方式
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchMode {
+    /// 任一条目无效则整批失败
+    AllOrNothing,
+    /// 跳过无效条目并记录原因，其余条目照常写入
+    SkipInvalid,
+}
+
+/// 批量状态写入的单条结果 (BatchUserAccount / BatchSpotTokenBalance 的 return data)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateWriteResult {
+    /// 已有 PDA 被覆盖
+    Updated,
+    /// PDA 不存在，已创建
+    Created,
+    /// sequence 不新于已存序号，未修改
+    Stale,
+    /// 条目无效，已跳过 (仅 BatchMode::SkipInvalid)
+    Skipped,
+}
+
+/// USDC 出金结果 (Withdraw / RelayerWithdrawAndTransfer 的 return data)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WithdrawOutcome {
+    /// 已扣账并转出
+    Transferred,
+    /// 本次出金触发出金熔断: 未扣账、未转账，熔断已锁定
+    BreakerTripped,
+}
+
+impl VaultInstruction {
+    /// Timelock category of a governance instruction; None for non-governance instructions
+    pub fn timelock_action_type(&self) -> Option<TimelockActionType> {
+        match self {
+            VaultInstruction::AddAuthorizedCaller { .. }
+            | VaultInstruction::RemoveAuthorizedCaller { .. }
+            | VaultInstruction::GrantRole { .. }
+            | VaultInstruction::RevokeRole { .. }
+            | VaultInstruction::RegisterRelayer { .. }
+            | VaultInstruction::UpdateRelayer { .. }
+            | VaultInstruction::MigrateAuthorizedCallers
+            | VaultInstruction::SetRelayerRateLimit { .. } => Some(TimelockActionType::AuthorizedCallers),
+            VaultInstruction::UpdateGovernanceAuthority { .. }
+            | VaultInstruction::ProposeGovernanceAuthority { .. }
+            | VaultInstruction::AcceptGovernanceAuthority
+            | VaultInstruction::CancelGovernanceTransfer
+            | VaultInstruction::InitializeGovernanceSet { .. }
+            | VaultInstruction::UpdateGovernanceSet { .. }
+            | VaultInstruction::InitializeTimelock { .. } => Some(TimelockActionType::GovernanceTransfer),
+            VaultInstruction::SetPaused { .. }
+            | VaultInstruction::SetOutflowBreaker { .. }
+            | VaultInstruction::ResetOutflowBreaker
+            | VaultInstruction::SetPauseFlag { .. }
+            | VaultInstruction::SetEscapeHatch { .. }
+            | VaultInstruction::SetSolvencyAutoPause { .. } => Some(TimelockActionType::Pause),
+            VaultInstruction::MigrateVaultConfig | VaultInstruction::MigrateAccount => {
+                Some(TimelockActionType::Migration)
+            }
+            VaultInstruction::AddToken { .. }
+            | VaultInstruction::UpdateToken { .. }
+            | VaultInstruction::DisableToken { .. }
+            | VaultInstruction::SetTokenFeePolicy { .. }
+            | VaultInstruction::InitializeTokenVault => Some(TimelockActionType::TokenRegistry),
+            VaultInstruction::SetRequireTransferChecked { .. } | VaultInstruction::SetLimits { .. } => {
+                Some(TimelockActionType::TransferPolicy)
+            }
+            VaultInstruction::UpdateTimelockConfig { .. }
+            | VaultInstruction::AddGuardian { .. }
+            | VaultInstruction::RemoveGuardian { .. } => Some(TimelockActionType::TimelockConfig),
+            _ => None,
+        }
+    }
+
+    /// Instructions that may be wrapped in a governance proposal or timelock action
+    pub fn is_governance_action(&self) -> bool {
+        self.timelock_action_type().is_some()
+    }
 }
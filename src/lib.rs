@@ -1,7 +1,7 @@
 //! 1024 DEX Vault Program
 //! 
 //! User fund custody program (DB-First architecture).
-//! 18 active instructions for deposit/withdraw, Spot token management,
+//! 63 active instructions for deposit/withdraw, Spot token management,
 //! on-chain state mirrors (UserAccount, SpotTokenBalance), and governance.
 
 use solana_program::{
@@ -13,6 +13,8 @@ use solana_program::{
 
 pub mod error;
 pub mod instruction;
+pub mod merkle;
+pub mod migration;
 pub mod processor;
 pub mod state;
 pub mod token_compat;
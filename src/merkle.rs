@@ -0,0 +1,249 @@
+//! Liability Merkle Sum Tree (Proof of Reserves)
+//!
+//! Relayer 每个 epoch 将 DB 中全部用户负债构建为 Merkle 求和树，通过 PostLiabilityRoot
+//! 将 root 与负债总和写入 ReservesEpoch PDA。用户拿到自己的叶子与 proof 后，可用
+//! VerifyInclusion 在链上证明其余额被计入该 epoch 的负债。
+//!
+//! 每个节点承诺 (hash, sum)，sum 为子树内叶子负债之和 (u64，不可能为负)：
+//! - 叶子: hash = sha256(0x00 || borsh(LiabilityLeaf))，sum = available + locked
+//!   (两项均不得为负，否则不能作为叶子)
+//! - 内部节点: hash = sha256(0x01 || lo.hash || lo.sum_le || hi.hash || hi.sum_le)，
+//!   sum = lo.sum + hi.sum；子节点按 hash 字节序排序，proof 无需方向位
+//! - 奇数个节点时最后一个直接晋升到上一层
+//!
+//! root 的 hash 因此绑定了 root 的 sum：发布者无法公布与叶子不符的总负债，也无法用
+//! 负余额叶子抵消其他用户的负债。[`verify_proof`] 沿路径累加 sum，要求 hash 与总和
+//! 同时等于发布值。
+//!
+//! [`LiabilityTree`] 供链下构建树与 proof；[`verify_proof`] 链上链下通用。
+
+use crate::state::UserAccount;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+/// 叶子哈希前缀 (与内部节点区分，防止第二原像攻击)
+const LEAF_PREFIX: &[u8] = &[0x00];
+
+/// 内部节点哈希前缀
+const NODE_PREFIX: &[u8] = &[0x01];
+
+/// proof 最大深度 (2^32 个叶子)，限制 VerifyInclusion 的计算量
+pub const MAX_PROOF_DEPTH: usize = 32;
+
+/// 单个用户子账户在某 epoch 的 USDC 负债
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiabilityLeaf {
+    pub wallet: Pubkey,
+    pub account_index: u32,
+    /// 可用余额 (e6)，不得为负
+    pub available_balance_e6: i64,
+    /// 锁定余额 (e6): locked_margin + spot_locked + oracle_locked，不得为负
+    pub locked_e6: i64,
+}
+
+impl LiabilityLeaf {
+    /// 从 UserAccount 构建叶子。负余额 (用户欠款) 不是 vault 的负债，按 0 计入，
+    /// 否则会抵消其他用户的负债。
+    pub fn from_user_account(account: &UserAccount) -> Self {
+        Self {
+            wallet: account.wallet,
+            account_index: account.account_index,
+            available_balance_e6: account.available_balance_e6.max(0),
+            locked_e6: account.locked_e6().max(0),
+        }
+    }
+
+    /// 该叶子计入的负债 (e6)；任一余额为负或溢出时返回 None
+    pub fn liability_e6(&self) -> Option<u64> {
+        let available = u64::try_from(self.available_balance_e6).ok()?;
+        let locked = u64::try_from(self.locked_e6).ok()?;
+        available.checked_add(locked)
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        // 固定大小结构体，borsh 序列化不会失败
+        let data = borsh::to_vec(self).unwrap_or_default();
+        hashv(&[LEAF_PREFIX, &data]).to_bytes()
+    }
+
+    /// 叶子节点；负余额时返回 None
+    pub fn node(&self) -> Option<SumNode> {
+        Some(SumNode { hash: self.hash(), sum: self.liability_e6()? })
+    }
+}
+
+/// Merkle 求和树节点: 子树哈希与子树负债之和 (e6)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SumNode {
+    pub hash: [u8; 32],
+    pub sum: u64,
+}
+
+/// 对两个子节点求父节点 (子节点按 hash 字节序排序)；sum 溢出时返回 None
+pub fn hash_pair(a: &SumNode, b: &SumNode) -> Option<SumNode> {
+    let (lo, hi) = if a.hash <= b.hash { (a, b) } else { (b, a) };
+    let sum = lo.sum.checked_add(hi.sum)?;
+    let hash = hashv(&[NODE_PREFIX, &lo.hash, &lo.sum.to_le_bytes(), &hi.hash, &hi.sum.to_le_bytes()]).to_bytes();
+    Some(SumNode { hash, sum })
+}
+
+/// 从叶子沿 proof 计算 root，要求 hash 与 sum 都等于发布的 root / total_liabilities_e6
+pub fn verify_proof(leaf: &LiabilityLeaf, proof: &[SumNode], root: &[u8; 32], total_liabilities_e6: i64) -> bool {
+    if proof.len() > MAX_PROOF_DEPTH {
+        return false;
+    }
+    let computed = proof.iter().try_fold(leaf.node(), |node, sibling| Some(hash_pair(&node?, sibling)));
+    match (computed.flatten(), u64::try_from(total_liabilities_e6)) {
+        (Some(node), Ok(total)) => node.hash == *root && node.sum == total,
+        _ => false,
+    }
+}
+
+/// 链下构建的负债 Merkle 求和树
+#[derive(Debug, Clone)]
+pub struct LiabilityTree {
+    /// levels[0] = 叶子节点，最后一层 = [root]
+    levels: Vec<Vec<SumNode>>,
+}
+
+impl LiabilityTree {
+    /// 按给定顺序构建树；叶子含负余额或负债总和超出 i64 时返回 None
+    pub fn new(leaves: &[LiabilityLeaf]) -> Option<Self> {
+        let mut levels = vec![leaves.iter().map(LiabilityLeaf::node).collect::<Option<Vec<_>>>()?];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()?
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [single] => Some(*single),
+                    _ => unreachable!(),
+                })
+                .collect::<Option<Vec<_>>>()?;
+            levels.push(next);
+        }
+        let tree = Self { levels };
+        i64::try_from(tree.root_node().sum).ok()?;
+        Some(tree)
+    }
+
+    /// 根节点 (空树为全零)
+    fn root_node(&self) -> SumNode {
+        self.levels.last().and_then(|level| level.first()).copied().unwrap_or_default()
+    }
+
+    /// Merkle root (空树为全零)
+    pub fn root(&self) -> [u8; 32] {
+        self.root_node().hash
+    }
+
+    pub fn leaf_count(&self) -> u32 {
+        self.levels[0].len() as u32
+    }
+
+    /// Σ leaf.liability_e6() (= root 的 sum)
+    pub fn total_liabilities_e6(&self) -> i64 {
+        self.root_node().sum as i64
+    }
+
+    /// 第 index 个叶子的 proof (自底向上的兄弟节点)
+    pub fn proof(&self, index: usize) -> Option<Vec<SumNode>> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                proof.push(*sibling);
+            }
+            position /= 2;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(available_balance_e6: i64, locked_e6: i64) -> LiabilityLeaf {
+        LiabilityLeaf { wallet: Pubkey::new_unique(), account_index: 0, available_balance_e6, locked_e6 }
+    }
+
+    #[test]
+    fn test_every_leaf_proves_inclusion() {
+        for count in 1..=9 {
+            let leaves: Vec<_> = (0..count).map(|i| leaf(i * 1_000, i)).collect();
+            let tree = LiabilityTree::new(&leaves).unwrap();
+            let total = tree.total_liabilities_e6();
+            assert_eq!(tree.leaf_count(), count as u32);
+            assert_eq!(total, (0..count).map(|i| i * 1_001).sum::<i64>());
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(verify_proof(leaf, &proof, &tree.root(), total), "count={} index={}", count, index);
+            }
+            assert!(tree.proof(count as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn test_tampered_leaf_or_proof_rejected() {
+        let leaves: Vec<_> = (0..5).map(|i| leaf(100 + i, 0)).collect();
+        let tree = LiabilityTree::new(&leaves).unwrap();
+        let (root, total) = (tree.root(), tree.total_liabilities_e6());
+        let proof = tree.proof(2).unwrap();
+
+        let mut inflated = leaves[2];
+        inflated.available_balance_e6 += 1;
+        assert!(!verify_proof(&inflated, &proof, &root, total));
+
+        let mut bad_hash = proof.clone();
+        bad_hash[0].hash[0] ^= 1;
+        assert!(!verify_proof(&leaves[2], &bad_hash, &root, total));
+
+        // 兄弟节点 sum 被篡改: hash 不再匹配
+        let mut bad_sum = proof.clone();
+        bad_sum[0].sum -= 1;
+        assert!(!verify_proof(&leaves[2], &bad_sum, &root, total));
+
+        assert!(!verify_proof(&leaves[2], &proof[..proof.len() - 1], &root, total));
+        assert!(!verify_proof(&leaves[2], &vec![SumNode::default(); MAX_PROOF_DEPTH + 1], &root, total));
+    }
+
+    #[test]
+    fn test_posted_total_must_match_root_sum() {
+        let leaves: Vec<_> = (0..4).map(|i| leaf(1_000 + i, 10)).collect();
+        let tree = LiabilityTree::new(&leaves).unwrap();
+        let proof = tree.proof(1).unwrap();
+        let total = tree.total_liabilities_e6();
+
+        assert!(verify_proof(&leaves[1], &proof, &tree.root(), total));
+        assert!(!verify_proof(&leaves[1], &proof, &tree.root(), total - 1));
+        assert!(!verify_proof(&leaves[1], &proof, &tree.root(), total + 1));
+        assert!(!verify_proof(&leaves[1], &proof, &tree.root(), -1));
+    }
+
+    #[test]
+    fn test_negative_leaf_rejected() {
+        // 负余额叶子不能抵消其他用户的负债
+        assert_eq!(leaf(-1, 0).liability_e6(), None);
+        assert_eq!(leaf(0, -1).liability_e6(), None);
+        assert!(LiabilityTree::new(&[leaf(500, 0), leaf(-500, 0)]).is_none());
+
+        let honest = leaf(500, 0);
+        let negative = leaf(-500, 0);
+        let sibling = SumNode { hash: negative.hash(), sum: 0 };
+        let root = hash_pair(&honest.node().unwrap(), &sibling).unwrap();
+        assert!(!verify_proof(&negative, &[honest.node().unwrap()], &root.hash, 0));
+    }
+
+    #[test]
+    fn test_empty_tree_and_overflow() {
+        let tree = LiabilityTree::new(&[]).unwrap();
+        assert_eq!((tree.root(), tree.leaf_count(), tree.total_liabilities_e6()), ([0u8; 32], 0, 0));
+        assert!(LiabilityTree::new(&[leaf(i64::MAX, 0), leaf(1, 0)]).is_none());
+        assert!(LiabilityTree::new(&[leaf(i64::MAX, 1)]).is_none());
+        assert!(hash_pair(&SumNode { hash: [1; 32], sum: u64::MAX }, &SumNode { hash: [2; 32], sum: 1 }).is_none());
+    }
+}
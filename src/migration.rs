@@ -0,0 +1,401 @@
+//! Versioned Account Layouts & In-place Migration
+//!
+//! VaultConfig / UserAccount / SpotTokenBalance 都带有 `layout_version` 字段。
+//! 每种账户实现 [`VersionedAccount`]：`decode_versioned` 识别所有受支持的历史布局
+//! (按数据长度 + layout_version)，解码为当前结构体并返回来源版本。
+//! MigrateAccount 借此原地升级账户：必要时补足租金并 realloc，再按当前布局写回。
+//!
+//! layout_version = 0 表示引入版本号之前创建的账户。它们的新字段来自全零 reserved，
+//! 因此可直接按当前布局解码，迁移时只需写入版本号。
+//!
+//! 只有 VaultConfig 存在多种历史大小 (569 / 505 / 1024 bytes)，因此只有它有逐版本解码器。
+//! UserAccount 始终为 153 bytes、SpotTokenBalance 始终为 98 bytes：account_index、
+//! oracle_locked_e6 等字段都是从 reserved 中划出的，之前创建的账户在这些位置上为 0，
+//! 按当前布局解码即得到 account_index = 0 / oracle_locked_e6 = 0。不存在更旧的大小，
+//! 其他长度一律按 InvalidAccountData 拒绝。
+//!
+//! 程序升级后，未迁移的 VaultConfig (V1 569 / V2 505 bytes) 仍可被所有指令使用：
+//! [`load_vault_config`] 经 `decode_versioned` 解码旧布局，[`store_vault_config`] 在
+//! 首次写入时原地扩容到当前布局 (懒迁移)。扩容不转入租金，账户需已持有当前大小的
+//! 免租 lamports (升级前向 VaultConfig PDA 转入 SOL)，否则写入失败，需先运行
+//! MigrateVaultConfig。程序升级要到下一个 slot 才生效，无法与迁移放在同一笔交易。
+
+use crate::state::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+/// 可原地迁移的程序账户
+pub trait VersionedAccount: BorshSerialize + Sized {
+    /// 账户类型名 (日志用)
+    const NAME: &'static str;
+    /// 当前布局版本
+    const CURRENT_VERSION: u8;
+    /// 当前布局大小 (bytes)
+    const CURRENT_SIZE: usize;
+
+    /// 解码任一受支持的布局，返回 (当前布局的账户, 存储时的布局版本)
+    fn decode_versioned(data: &[u8]) -> Result<(Self, u8), ProgramError>;
+
+    fn set_layout_version(&mut self, version: u8);
+}
+
+/// 账户是否需要迁移 (版本号或大小与当前布局不一致)
+pub fn needs_migration<T: VersionedAccount>(from_version: u8, data_len: usize) -> bool {
+    from_version != T::CURRENT_VERSION || data_len != T::CURRENT_SIZE
+}
+
+/// 读取账户前 8 字节的 discriminator
+pub fn read_discriminator(data: &[u8]) -> Result<u64, ProgramError> {
+    data.get(..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+/// 按当前布局解码 (长度必须一致)，0 版本视为未带版本号的当前布局
+fn decode_current<T: BorshDeserialize>(
+    data: &[u8],
+    discriminator: u64,
+    size: usize,
+    layout_version: impl Fn(&T) -> u8,
+) -> Result<(T, u8), ProgramError> {
+    if data.len() != size || read_discriminator(data)? != discriminator {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let account = T::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)?;
+    let version = layout_version(&account);
+    Ok((account, version))
+}
+
+impl VaultConfig {
+    /// Callers listed before roles existed have `authorized_caller_roles = 0` (zeroed reserved
+    /// space, also in accounts already grown to 1024 bytes); they keep LEGACY_RELAYER_ROLES.
+    /// Revoking a caller's last role clears its slot, so a listed caller with no roles is
+    /// always such a legacy entry.
+    fn backfill_legacy_caller_roles(&mut self) {
+        for (caller, roles) in self.authorized_callers.iter().zip(self.authorized_caller_roles.iter_mut()) {
+            if *caller != Pubkey::default() && *roles == 0 {
+                *roles = LEGACY_RELAYER_ROLES;
+            }
+        }
+    }
+}
+
+impl VersionedAccount for VaultConfig {
+    const NAME: &'static str = "VaultConfig";
+    const CURRENT_VERSION: u8 = VaultConfig::LAYOUT_VERSION;
+    const CURRENT_SIZE: usize = VAULT_CONFIG_SIZE;
+
+    fn decode_versioned(data: &[u8]) -> Result<(Self, u8), ProgramError> {
+        let from_version = match data.len() {
+            VAULT_CONFIG_SIZE => {
+                let (mut config, version) =
+                    decode_current(data, Self::DISCRIMINATOR, VAULT_CONFIG_SIZE, |config: &Self| config.layout_version)?;
+                config.backfill_legacy_caller_roles();
+                return Ok((config, version));
+            }
+            VAULT_CONFIG_SIZE_V1 => 1,
+            VAULT_CONFIG_SIZE_V2 => 2,
+            len => {
+                msg!(
+                    "VaultConfig unexpected size: {} (expected V1={}, V2={} or {})",
+                    len, VAULT_CONFIG_SIZE_V1, VAULT_CONFIG_SIZE_V2, VAULT_CONFIG_SIZE
+                );
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        if read_discriminator(data)? != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Both layouts share the first 424 bytes:
+        // [0..424] = disc + governance_authority + usdc_mint + vault_token_account + authorized_callers
+        //
+        // V1: [424..488] = ledger_program + fund_program (SKIP)
+        //     [488..538] = delegation_program(32) + total_deposits(8) + total_locked(8) + is_paused(1) + reserved[0](1)
+        // V2: [424..474] = delegation_program(32) + total_deposits(8) + total_locked(8) + is_paused(1) + require_transfer_checked(1)
+        //
+        // V1 reserved[0] / V2 reserved are all zero, so require_transfer_checked,
+        // pending_governance_authority and the new reserved area start zeroed.
+        // Existing authorized_callers had every relayer power; they keep it as
+        // LEGACY_RELAYER_ROLES.
+        let mut new_data = Vec::with_capacity(VAULT_CONFIG_SIZE);
+        new_data.extend_from_slice(&data[0..424]);
+        if from_version == 1 {
+            new_data.extend_from_slice(&data[488..538]);
+        } else {
+            new_data.extend_from_slice(&data[424..474]);
+        }
+        new_data.resize(VAULT_CONFIG_SIZE, 0);
+
+        let mut config = Self::deserialize(&mut &new_data[..]).map_err(|_| ProgramError::InvalidAccountData)?;
+        config.backfill_legacy_caller_roles();
+        // The former V-6 per-transaction deposit ceiling carries over as a limit
+        config.limits = VaultLimits::usdc_default();
+        Ok((config, from_version))
+    }
+
+    fn set_layout_version(&mut self, version: u8) {
+        self.layout_version = version;
+    }
+}
+
+impl VersionedAccount for UserAccount {
+    const NAME: &'static str = "UserAccount";
+    const CURRENT_VERSION: u8 = UserAccount::LAYOUT_VERSION;
+    const CURRENT_SIZE: usize = USER_ACCOUNT_SIZE;
+
+    fn decode_versioned(data: &[u8]) -> Result<(Self, u8), ProgramError> {
+        decode_current(data, Self::DISCRIMINATOR, USER_ACCOUNT_SIZE, |account: &Self| account.layout_version)
+    }
+
+    fn set_layout_version(&mut self, version: u8) {
+        self.layout_version = version;
+    }
+}
+
+impl VersionedAccount for SpotTokenBalance {
+    const NAME: &'static str = "SpotTokenBalance";
+    const CURRENT_VERSION: u8 = SpotTokenBalance::LAYOUT_VERSION;
+    const CURRENT_SIZE: usize = SPOT_TOKEN_BALANCE_SIZE;
+
+    fn decode_versioned(data: &[u8]) -> Result<(Self, u8), ProgramError> {
+        decode_current(data, Self::DISCRIMINATOR, SPOT_TOKEN_BALANCE_SIZE, |balance: &Self| balance.layout_version)
+    }
+
+    fn set_layout_version(&mut self, version: u8) {
+        self.layout_version = version;
+    }
+}
+
+/// 读取 VaultConfig，接受当前布局与迁移前的 V1 / V2 布局
+pub fn load_vault_config(account_info: &AccountInfo) -> Result<VaultConfig, ProgramError> {
+    VaultConfig::decode_versioned(&account_info.data.borrow()).map(|(config, _)| config)
+}
+
+/// 写回 VaultConfig。旧布局账户在此原地扩容并写入当前版本号 (见模块文档)。
+pub fn store_vault_config(account_info: &AccountInfo, config: &VaultConfig) -> ProgramResult {
+    let from_len = account_info.data_len();
+    if from_len == VAULT_CONFIG_SIZE {
+        config.serialize(&mut &mut account_info.data.borrow_mut()[..])?;
+        return Ok(());
+    }
+
+    let required_lamports = Rent::get()?.minimum_balance(VAULT_CONFIG_SIZE);
+    if account_info.lamports() < required_lamports {
+        msg!(
+            "❌ VaultConfig uses the {}-byte layout: run MigrateVaultConfig or fund it to {} lamports",
+            from_len, required_lamports
+        );
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    account_info.realloc(VAULT_CONFIG_SIZE, false)?;
+    let mut config = config.clone();
+    config.set_layout_version(VaultConfig::CURRENT_VERSION);
+    config.serialize(&mut &mut account_info.data.borrow_mut()[..])?;
+
+    msg!(
+        "AccountMigrated: type={} account={} from_size={} to_version={} ({} bytes) on write",
+        VaultConfig::NAME, account_info.key, from_len, VaultConfig::CURRENT_VERSION, VAULT_CONFIG_SIZE
+    );
+    Ok(())
+}
+
+/// 调整账户大小并由 payer 补足租金 (缩容时多余 lamports 留在账户中)
+pub fn realloc_with_rent<'a>(
+    account_info: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_size: usize,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(new_size);
+    let current_lamports = account_info.lamports();
+    if current_lamports < required_lamports {
+        invoke(
+            &system_instruction::transfer(payer.key, account_info.key, required_lamports - current_lamports),
+            &[payer.clone(), account_info.clone(), system_program.clone()],
+        )?;
+    }
+    if account_info.data_len() != new_size {
+        account_info.realloc(new_size, false)?;
+    }
+    Ok(())
+}
+
+/// 将账户升级到当前布局 (幂等)
+///
+/// `authorize` 在解码后、写入前调用，用于按账户内容校验调用方。
+/// 返回来源版本；已是当前布局时不做任何修改。
+pub fn migrate_account<'a, T: VersionedAccount>(
+    account_info: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    authorize: impl FnOnce(&T) -> ProgramResult,
+) -> Result<u8, ProgramError> {
+    let from_len = account_info.data_len();
+    let (mut account, from_version) = T::decode_versioned(&account_info.data.borrow())?;
+    authorize(&account)?;
+
+    if !needs_migration::<T>(from_version, from_len) {
+        msg!("{} already at layout v{} ({} bytes)", T::NAME, T::CURRENT_VERSION, from_len);
+        return Ok(from_version);
+    }
+
+    realloc_with_rent(account_info, payer, system_program, T::CURRENT_SIZE)?;
+    account.set_layout_version(T::CURRENT_VERSION);
+    account.serialize(&mut &mut account_info.data.borrow_mut()[..])?;
+
+    msg!(
+        "AccountMigrated: type={} account={} from_version={} ({} bytes) to_version={} ({} bytes)",
+        T::NAME, account_info.key, from_version, from_len, T::CURRENT_VERSION, T::CURRENT_SIZE
+    );
+    Ok(from_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_vault_config() -> VaultConfig {
+        let mut config = VaultConfig::zeroed();
+        config.governance_authority = Pubkey::new_unique();
+        config.usdc_mint = Pubkey::new_unique();
+        config.authorized_callers[2] = Pubkey::new_unique();
+        config.delegation_program = Pubkey::new_unique();
+        config.total_deposits = 7_000_000;
+        config.total_locked = 1_000_000;
+        config.is_paused = true;
+        config
+    }
+
+    fn assert_legacy_fields(decoded: &VaultConfig, original: &VaultConfig) {
+        assert_eq!(decoded.governance_authority, original.governance_authority);
+        assert_eq!(decoded.usdc_mint, original.usdc_mint);
+        assert_eq!(decoded.authorized_callers, original.authorized_callers);
+        assert_eq!(decoded.delegation_program, original.delegation_program);
+        assert_eq!(decoded.total_deposits, 7_000_000);
+        assert_eq!(decoded.total_locked, 1_000_000);
+        assert!(decoded.is_paused);
+        assert_eq!(decoded.roles_of(&original.authorized_callers[2]), LEGACY_RELAYER_ROLES);
+        assert_eq!(decoded.limits, VaultLimits::usdc_default());
+        assert_eq!(decoded.layout_version, 0);
+    }
+
+    #[test]
+    fn test_decode_vault_config_v2() {
+        let config = current_vault_config();
+        let mut data = config.try_to_vec().unwrap();
+        data.truncate(VAULT_CONFIG_SIZE_V2);
+        data[474..].fill(0);
+
+        let (decoded, version) = VaultConfig::decode_versioned(&data).unwrap();
+        assert_eq!(version, 2);
+        assert_legacy_fields(&decoded, &config);
+        assert!(needs_migration::<VaultConfig>(version, data.len()));
+    }
+
+    #[test]
+    fn test_decode_vault_config_v1() {
+        let config = current_vault_config();
+        let v3 = config.try_to_vec().unwrap();
+        // V1 = shared prefix + ledger_program + fund_program + V2 body + 31 reserved
+        let mut data = Vec::with_capacity(VAULT_CONFIG_SIZE_V1);
+        data.extend_from_slice(&v3[..424]);
+        data.extend_from_slice(&[0xAB; 64]);
+        data.extend_from_slice(&v3[424..474]);
+        data.resize(VAULT_CONFIG_SIZE_V1, 0);
+
+        let (decoded, version) = VaultConfig::decode_versioned(&data).unwrap();
+        assert_eq!(version, 1);
+        assert_legacy_fields(&decoded, &config);
+    }
+
+    #[test]
+    fn test_decode_vault_config_current() {
+        let mut config = current_vault_config();
+        let data = config.try_to_vec().unwrap();
+        let (_, version) = VaultConfig::decode_versioned(&data).unwrap();
+        assert_eq!(version, 0);
+        assert!(needs_migration::<VaultConfig>(version, data.len()));
+
+        config.set_layout_version(VaultConfig::LAYOUT_VERSION);
+        let data = config.try_to_vec().unwrap();
+        let (decoded, version) = VaultConfig::decode_versioned(&data).unwrap();
+        assert_eq!(version, VaultConfig::LAYOUT_VERSION);
+        assert_eq!(decoded.total_deposits, 7_000_000);
+        assert!(!needs_migration::<VaultConfig>(version, data.len()));
+
+        assert!(VaultConfig::decode_versioned(&data[..600]).is_err());
+        let mut wrong_disc = data;
+        wrong_disc[..8].copy_from_slice(&UserAccount::DISCRIMINATOR.to_le_bytes());
+        assert!(VaultConfig::decode_versioned(&wrong_disc).is_err());
+    }
+
+    #[test]
+    fn test_decode_vault_config_current_backfills_caller_roles() {
+        // Grown to 1024 bytes before roles existed: listed callers have no role bits
+        let mut config = current_vault_config();
+        let legacy = Pubkey::new_unique();
+        let pauser = Pubkey::new_unique();
+        config.authorized_callers[0] = legacy;
+        config.authorized_callers[1] = pauser;
+        config.authorized_caller_roles[1] = VaultRole::Pauser.bit();
+        let (decoded, _) = VaultConfig::decode_versioned(&config.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.roles_of(&legacy), LEGACY_RELAYER_ROLES);
+        assert_eq!(decoded.roles_of(&pauser), VaultRole::Pauser.bit());
+        assert_eq!(decoded.authorized_caller_roles[3], 0);
+    }
+
+    #[test]
+    fn test_decode_unversioned_user_account() {
+        // Pre-version layout: everything after oracle_locked_e6 was reserved zero.
+        // Accounts created before account_index / oracle_locked_e6 have zeros there too,
+        // so this one image covers every historical UserAccount layout.
+        let wallet = Pubkey::new_unique();
+        let mut data = vec![0u8; USER_ACCOUNT_SIZE];
+        data[..8].copy_from_slice(&UserAccount::DISCRIMINATOR.to_le_bytes());
+        data[8..40].copy_from_slice(wallet.as_ref());
+        data[40] = 254;
+        data[41..49].copy_from_slice(&5_000_000i64.to_le_bytes());
+
+        let (account, version) = UserAccount::decode_versioned(&data).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(account.wallet, wallet);
+        assert_eq!(account.bump, 254);
+        assert_eq!(account.available_balance_e6, 5_000_000);
+        assert_eq!((account.account_index, account.oracle_locked_e6), (0, 0));
+        assert!(needs_migration::<UserAccount>(version, data.len()));
+
+        assert!(UserAccount::decode_versioned(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn test_decode_unversioned_spot_token_balance() {
+        let wallet = Pubkey::new_unique();
+        let mut balance = SpotTokenBalance::new(wallet, 9, 253, 1_700_000_000);
+        balance.available_e6 = 123;
+        balance.layout_version = 0;
+        let data = balance.try_to_vec().unwrap();
+
+        let (decoded, version) = SpotTokenBalance::decode_versioned(&data).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(decoded.wallet, wallet);
+        assert_eq!(decoded.token_index, 9);
+        assert_eq!(decoded.available_e6, 123);
+        assert!(needs_migration::<SpotTokenBalance>(version, data.len()));
+
+        let fresh = SpotTokenBalance::new(wallet, 9, 253, 0).try_to_vec().unwrap();
+        let (_, version) = SpotTokenBalance::decode_versioned(&fresh).unwrap();
+        assert!(!needs_migration::<SpotTokenBalance>(version, fresh.len()));
+    }
+}
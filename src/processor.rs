@@ -2,25 +2,40 @@
 //!
 //! 职责: 用户资金托管 — DB-First + 实时链上审计架构中的链上 Vault 组件
 //!
-//! ## 功能域 (18 active instructions)
+//! ## 功能域 (64 active instructions)
 //!
 //! | # | 域 | Handler 范围 | 说明 |
 //! |---|------|-------------|------|
 //! | 1 | Core VaultSettlement | `process_initialize` ~ `process_withdraw` | 初始化、用户入金/出金 |
 //! | 2 | Relayer VaultSettlement | `process_relayer_deposit` ~ `process_relayer_withdraw_and_transfer` | 代理入金/出金（含跨链提取） |
 //! | 3 | Spot VaultSettlement | `process_spot_deposit` ~ `process_relayer_spot_withdraw` | Spot 资产入金/出金 |
-//! | 4 | State  | `process_user_account` ~ `process_spot_token_balance` | 链上 PDA 状态写入 |
-//! | 5 | Governance Authority | `process_add_authorized_caller` ~ `process_migrate_vault_config` | 配置管理、升级迁移 |
+//! | 4 | State  | `process_user_account` ~ `process_batch_spot_token_balance` | 链上 PDA 状态写入 (单条/批量/CAS) |
+//! | 5 | Governance Authority | `process_add_authorized_caller` ~ `process_migrate_vault_config` | 角色授予/撤销 (RBAC)、配置管理、分类暂停、出金熔断器、出入金限额、两步治理转移、升级迁移 |
+//! | 6 | Token Registry | `process_add_token` ~ `process_initialize_token_vault` | Spot Token 上架/更新/禁用、Token Vault 创建 |
+//! | 7 | Governance Multisig | `process_initialize_governance_set` ~ `process_update_governance_set` | M-of-N 治理提案、批准、执行 |
+//! | 8 | Timelock / Guardian | `process_initialize_timelock` ~ `process_guardian_cancel_timelock_action` | 敏感治理操作延迟执行、Guardian 暂停与取消 |
+//! | 9 | Relayer Registry | `process_register_relayer` ~ `process_set_relayer_rate_limit` | 每个 Relayer 一个 AuthorizedRelayer PDA、滚动窗口限额 |
+//! | 10 | Escape Hatch | `process_set_escape_hatch` ~ `process_escape_spot_withdraw` | Relayer 无心跳超时后用户按链上 PDA 自助出金 |
+//! | 11 | Account Migration | `process_migrate_account` | 按 layout_version 原地升级 VaultConfig / UserAccount / SpotTokenBalance (见 `migration` 模块) |
+//! | 12 | Solvency | `process_check_solvency` ~ `process_set_solvency_auto_pause` | 储备/负债比检查 (permissionless)、偿付不足自动暂停出金 |
+//! | 13 | Proof of Reserves | `process_post_liability_root` ~ `process_verify_inclusion` | Relayer 发布负债 Merkle root、用户验证余额被计入 (见 `merkle` 模块) |
 //!
 //! ## 架构要点
 //!
 //! - 只有托管出入金路径涉及真实 SPL Token 转账（用户/Relayer）
 //! - 链上仅保留托管与审计状态；业务结算与资金计算在 DB 内完成
 //! - 状态指令幂等地将状态写入链上
+//! - Relayer / 运维指令按角色授权 (`VaultRole`)，governance_authority 隐含全部角色
 
 use crate::{
     error::VaultError,
-    instruction::VaultInstruction,
+    instruction::{
+        BatchMode, ExpectedSpotTokenBalance, ExpectedUserAccount, SpotTokenBalanceUpdate, StateWriteResult,
+        UserAccountUpdate, VaultInstruction, WithdrawOutcome, MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES,
+        MAX_BATCH_USER_ACCOUNT_UPDATES,
+    },
+    merkle::{self, LiabilityLeaf, SumNode},
+    migration::{self, load_vault_config, read_discriminator, store_vault_config},
     state::*,
     token_compat,
     utils::*,
@@ -29,8 +44,10 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    hash::hashv,
+    instruction::{AccountMeta, Instruction},
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     pubkey::Pubkey,
     rent::Rent,
@@ -58,6 +75,45 @@ fn deserialize_checked(data: &[u8], expected_discriminator: u64) -> Result<(), P
     Ok(())
 }
 
+/// 创建由 `owner` 持有的 PDA 账户 (`seeds` 含 bump)。
+/// 地址已有 lamports 时改走 补足租金 → allocate → assign，否则 create_account 会因
+/// AccountAlreadyInUse 失败，任何人向可预测的 PDA 转 1 lamport 即可永久阻断创建。
+fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(space);
+    if new_account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(payer.key, new_account.key, lamports, space as u64, owner),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let required = lamports.saturating_sub(new_account.lamports());
+    if required > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, new_account.key, required),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(new_account.key, space as u64),
+        &[new_account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(new_account.key, owner),
+        &[new_account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
 /// Program state handler
 pub struct Processor;
 
@@ -97,19 +153,19 @@ impl Processor {
             }
             VaultInstruction::SetPaused { paused } => {
                 msg!("Instruction: SetPaused");
-                Self::process_set_paused(accounts, paused)
+                Self::process_set_paused(program_id, accounts, paused)
             }
             VaultInstruction::UpdateGovernanceAuthority { new_governance_authority } => {
-                msg!("Instruction: UpdateGovernanceAuthority");
-                Self::process_update_governance_authority(accounts, new_governance_authority)
+                msg!("Instruction: UpdateGovernanceAuthority (deprecated, proposes only)");
+                Self::process_propose_governance_authority(program_id, accounts, new_governance_authority)
             }
-            VaultInstruction::RelayerDeposit { user_wallet, amount, account_index } => {
+            VaultInstruction::RelayerDeposit { user_wallet, amount, account_index, sequence } => {
                 msg!("Instruction: RelayerDeposit");
-                Self::process_relayer_deposit(program_id, accounts, user_wallet, amount, account_index)
+                Self::process_relayer_deposit(program_id, accounts, user_wallet, amount, account_index, sequence)
             }
-            VaultInstruction::RelayerWithdraw { user_wallet, amount, account_index } => {
+            VaultInstruction::RelayerWithdraw { user_wallet, amount, account_index, sequence } => {
                 msg!("Instruction: RelayerWithdraw");
-                Self::process_relayer_withdraw(program_id, accounts, user_wallet, amount, account_index)
+                Self::process_relayer_withdraw(program_id, accounts, user_wallet, amount, account_index, sequence)
             }
             VaultInstruction::SpotDeposit { token_index, amount, account_index, amount_e6 } => {
                 msg!("Instruction: SpotDeposit");
@@ -119,30 +175,244 @@ impl Processor {
                 msg!("Instruction: SpotWithdraw");
                 Self::process_spot_withdraw(program_id, accounts, token_index, amount, account_index, amount_e6)
             }
-            VaultInstruction::RelayerSpotDeposit { user_wallet, token_index, amount, account_index, amount_e6 } => {
+            VaultInstruction::RelayerSpotDeposit { user_wallet, token_index, amount, account_index, amount_e6, sequence } => {
                 msg!("Instruction: RelayerSpotDeposit");
-                Self::process_relayer_spot_deposit(program_id, accounts, user_wallet, token_index, amount, account_index, amount_e6)
+                Self::process_relayer_spot_deposit(program_id, accounts, user_wallet, token_index, amount, account_index, amount_e6, sequence)
             }
-            VaultInstruction::RelayerSpotWithdraw { user_wallet, token_index, amount, account_index, amount_e6 } => {
+            VaultInstruction::RelayerSpotWithdraw { user_wallet, token_index, amount, account_index, amount_e6, sequence } => {
                 msg!("Instruction: RelayerSpotWithdraw");
-                Self::process_relayer_spot_withdraw(program_id, accounts, user_wallet, token_index, amount, account_index, amount_e6)
+                Self::process_relayer_spot_withdraw(program_id, accounts, user_wallet, token_index, amount, account_index, amount_e6, sequence)
             }
-            VaultInstruction::RelayerWithdrawAndTransfer { user_wallet, amount, account_index } => {
+            VaultInstruction::RelayerWithdrawAndTransfer { user_wallet, amount, account_index, sequence } => {
                 msg!("Instruction: RelayerWithdrawAndTransfer");
-                Self::process_relayer_withdraw_and_transfer(program_id, accounts, user_wallet, amount, account_index)
+                Self::process_relayer_withdraw_and_transfer(program_id, accounts, user_wallet, amount, account_index, sequence)
             }
-            VaultInstruction::UserAccount { user_wallet, account_index, available_balance_e6, locked_margin_e6, spot_locked_e6, oracle_locked_e6 } => {
+            VaultInstruction::UserAccount { user_wallet, account_index, available_balance_e6, locked_margin_e6, spot_locked_e6, oracle_locked_e6, sequence } => {
                 msg!("Instruction: UserAccount");
-                Self::process_user_account(program_id, accounts, user_wallet, account_index, available_balance_e6, locked_margin_e6, spot_locked_e6, oracle_locked_e6)
+                let update = UserAccountUpdate {
+                    user_wallet, account_index, available_balance_e6, locked_margin_e6, spot_locked_e6, oracle_locked_e6, sequence,
+                };
+                Self::process_user_account(program_id, accounts, &update, None)
             }
-            VaultInstruction::SpotTokenBalance { user_wallet, account_index, token_index, available_e6, locked_e6 } => {
+            VaultInstruction::SpotTokenBalance { user_wallet, account_index, token_index, available_e6, locked_e6, sequence } => {
                 msg!("Instruction: SpotTokenBalance");
-                Self::process_spot_token_balance(program_id, accounts, user_wallet, account_index, token_index, available_e6, locked_e6)
+                let update = SpotTokenBalanceUpdate { user_wallet, account_index, token_index, available_e6, locked_e6, sequence };
+                Self::process_spot_token_balance(program_id, accounts, &update, None)
             }
             VaultInstruction::MigrateVaultConfig => {
-                msg!("Instruction: MigrateVaultConfig (V1 569→V2 505)");
+                msg!("Instruction: MigrateVaultConfig (→V3 1024)");
                 Self::process_migrate_vault_config(program_id, accounts)
             }
+            VaultInstruction::AddToken { token_index, allowed_risk_flags } => {
+                msg!("Instruction: AddToken");
+                Self::process_add_token(program_id, accounts, token_index, allowed_risk_flags)
+            }
+            VaultInstruction::UpdateToken { token_index } => {
+                msg!("Instruction: UpdateToken");
+                Self::process_update_token(program_id, accounts, token_index)
+            }
+            VaultInstruction::DisableToken { token_index, disabled } => {
+                msg!("Instruction: DisableToken");
+                Self::process_disable_token(program_id, accounts, token_index, disabled)
+            }
+            VaultInstruction::SetRequireTransferChecked { required } => {
+                msg!("Instruction: SetRequireTransferChecked");
+                Self::process_set_require_transfer_checked(program_id, accounts, required)
+            }
+            VaultInstruction::SetTokenFeePolicy { token_index, vault_pays_transfer_fee } => {
+                msg!("Instruction: SetTokenFeePolicy");
+                Self::process_set_token_fee_policy(program_id, accounts, token_index, vault_pays_transfer_fee)
+            }
+            VaultInstruction::InitializeTokenVault => {
+                msg!("Instruction: InitializeTokenVault");
+                Self::process_initialize_token_vault(program_id, accounts)
+            }
+            VaultInstruction::ProposeGovernanceAuthority { new_authority } => {
+                msg!("Instruction: ProposeGovernanceAuthority");
+                Self::process_propose_governance_authority(program_id, accounts, new_authority)
+            }
+            VaultInstruction::AcceptGovernanceAuthority => {
+                msg!("Instruction: AcceptGovernanceAuthority");
+                Self::process_accept_governance_authority(program_id, accounts)
+            }
+            VaultInstruction::CancelGovernanceTransfer => {
+                msg!("Instruction: CancelGovernanceTransfer");
+                Self::process_cancel_governance_transfer(program_id, accounts)
+            }
+            VaultInstruction::InitializeGovernanceSet { members, threshold, proposal_ttl_secs } => {
+                msg!("Instruction: InitializeGovernanceSet");
+                Self::process_initialize_governance_set(program_id, accounts, members, threshold, proposal_ttl_secs)
+            }
+            VaultInstruction::CreateGovernanceProposal { instruction_data } => {
+                msg!("Instruction: CreateGovernanceProposal");
+                Self::process_create_governance_proposal(program_id, accounts, instruction_data)
+            }
+            VaultInstruction::ApproveGovernanceProposal { proposal_id } => {
+                msg!("Instruction: ApproveGovernanceProposal");
+                Self::process_approve_governance_proposal(program_id, accounts, proposal_id)
+            }
+            VaultInstruction::ExecuteGovernanceProposal { proposal_id } => {
+                msg!("Instruction: ExecuteGovernanceProposal");
+                Self::process_execute_governance_proposal(program_id, accounts, proposal_id)
+            }
+            VaultInstruction::UpdateGovernanceSet { members, threshold, proposal_ttl_secs } => {
+                msg!("Instruction: UpdateGovernanceSet");
+                Self::process_update_governance_set(program_id, accounts, members, threshold, proposal_ttl_secs)
+            }
+            VaultInstruction::InitializeTimelock { min_delay_secs } => {
+                msg!("Instruction: InitializeTimelock");
+                Self::process_initialize_timelock(program_id, accounts, min_delay_secs)
+            }
+            VaultInstruction::QueueTimelockAction { instruction_data } => {
+                msg!("Instruction: QueueTimelockAction");
+                Self::process_queue_timelock_action(program_id, accounts, instruction_data)
+            }
+            VaultInstruction::ExecuteTimelockAction { action_id } => {
+                msg!("Instruction: ExecuteTimelockAction");
+                Self::process_execute_timelock_action(program_id, accounts, action_id)
+            }
+            VaultInstruction::CancelTimelockAction { action_id } => {
+                msg!("Instruction: CancelTimelockAction");
+                Self::process_cancel_timelock_action(program_id, accounts, action_id)
+            }
+            VaultInstruction::UpdateTimelockConfig { admin, min_delay_secs } => {
+                msg!("Instruction: UpdateTimelockConfig");
+                Self::process_update_timelock_config(program_id, accounts, admin, min_delay_secs)
+            }
+            VaultInstruction::GrantRole { account, role } => {
+                msg!("Instruction: GrantRole");
+                Self::process_grant_role(program_id, accounts, account, role)
+            }
+            VaultInstruction::RevokeRole { account, role } => {
+                msg!("Instruction: RevokeRole");
+                Self::process_revoke_role(program_id, accounts, account, role)
+            }
+            VaultInstruction::RegisterRelayer { relayer, label, roles, max_deposit_per_tx_e6, max_withdraw_per_tx_e6 } => {
+                msg!("Instruction: RegisterRelayer");
+                Self::process_register_relayer(program_id, accounts, relayer, label, roles, max_deposit_per_tx_e6, max_withdraw_per_tx_e6)
+            }
+            VaultInstruction::UpdateRelayer { relayer, label, roles, enabled, max_deposit_per_tx_e6, max_withdraw_per_tx_e6 } => {
+                msg!("Instruction: UpdateRelayer");
+                Self::process_update_relayer(
+                    program_id, accounts, relayer, label, roles, enabled, max_deposit_per_tx_e6, max_withdraw_per_tx_e6,
+                )
+            }
+            VaultInstruction::MigrateAuthorizedCallers => {
+                msg!("Instruction: MigrateAuthorizedCallers");
+                Self::process_migrate_authorized_callers(program_id, accounts)
+            }
+            VaultInstruction::SetRelayerRateLimit {
+                relayer,
+                token_index,
+                bucket_secs,
+                max_deposit_per_window_e6,
+                max_withdraw_per_window_e6,
+            } => {
+                msg!("Instruction: SetRelayerRateLimit");
+                Self::process_set_relayer_rate_limit(
+                    program_id,
+                    accounts,
+                    relayer,
+                    token_index,
+                    bucket_secs,
+                    [max_deposit_per_window_e6, max_withdraw_per_window_e6],
+                )
+            }
+            VaultInstruction::SetOutflowBreaker { limit_bps, window_secs } => {
+                msg!("Instruction: SetOutflowBreaker");
+                Self::process_set_outflow_breaker(program_id, accounts, limit_bps, window_secs)
+            }
+            VaultInstruction::ResetOutflowBreaker => {
+                msg!("Instruction: ResetOutflowBreaker");
+                Self::process_reset_outflow_breaker(program_id, accounts)
+            }
+            VaultInstruction::SetPauseFlag { category, paused } => {
+                msg!("Instruction: SetPauseFlag");
+                Self::process_set_pause_flag(program_id, accounts, category, paused)
+            }
+            VaultInstruction::AddGuardian { guardian } => {
+                msg!("Instruction: AddGuardian");
+                Self::process_update_guardians(program_id, accounts, guardian, true)
+            }
+            VaultInstruction::RemoveGuardian { guardian } => {
+                msg!("Instruction: RemoveGuardian");
+                Self::process_update_guardians(program_id, accounts, guardian, false)
+            }
+            VaultInstruction::GuardianPause { pause_flags, reason_code } => {
+                msg!("Instruction: GuardianPause");
+                Self::process_guardian_pause(program_id, accounts, pause_flags, reason_code)
+            }
+            VaultInstruction::GuardianCancelTimelockAction { action_id, reason_code } => {
+                msg!("Instruction: GuardianCancelTimelockAction");
+                Self::process_guardian_cancel_timelock_action(program_id, accounts, action_id, reason_code)
+            }
+            VaultInstruction::SetEscapeHatch { inactivity_secs, locked_share_bps } => {
+                msg!("Instruction: SetEscapeHatch");
+                Self::process_set_escape_hatch(program_id, accounts, inactivity_secs, locked_share_bps)
+            }
+            VaultInstruction::EscapeWithdraw => {
+                msg!("Instruction: EscapeWithdraw");
+                Self::process_escape_withdraw(program_id, accounts)
+            }
+            VaultInstruction::EscapeSpotWithdraw { token_index, account_index } => {
+                msg!("Instruction: EscapeSpotWithdraw");
+                Self::process_escape_spot_withdraw(program_id, accounts, token_index, account_index)
+            }
+            VaultInstruction::SetLimits {
+                token_index,
+                min_deposit_e6,
+                min_withdraw_e6,
+                max_deposit_per_tx_e6,
+                max_withdraw_per_tx_e6,
+                max_account_balance_e6,
+            } => {
+                msg!("Instruction: SetLimits");
+                let limits = VaultLimits {
+                    min_deposit_e6,
+                    min_withdraw_e6,
+                    max_deposit_per_tx_e6,
+                    max_withdraw_per_tx_e6,
+                    max_account_balance_e6,
+                };
+                Self::process_set_limits(program_id, accounts, token_index, limits)
+            }
+            VaultInstruction::MigrateAccount => {
+                msg!("Instruction: MigrateAccount");
+                Self::process_migrate_account(program_id, accounts)
+            }
+            VaultInstruction::CheckSolvency { token_index } => {
+                msg!("Instruction: CheckSolvency");
+                Self::process_check_solvency(program_id, accounts, token_index)
+            }
+            VaultInstruction::SetSolvencyAutoPause { enabled } => {
+                msg!("Instruction: SetSolvencyAutoPause");
+                Self::process_set_solvency_auto_pause(program_id, accounts, enabled)
+            }
+            VaultInstruction::PostLiabilityRoot { epoch, root, total_liabilities_e6, leaf_count } => {
+                msg!("Instruction: PostLiabilityRoot");
+                Self::process_post_liability_root(program_id, accounts, epoch, root, total_liabilities_e6, leaf_count)
+            }
+            VaultInstruction::VerifyInclusion { epoch, wallet, account_index, available_balance_e6, locked_e6, proof } => {
+                msg!("Instruction: VerifyInclusion");
+                let leaf = LiabilityLeaf { wallet, account_index, available_balance_e6, locked_e6 };
+                Self::process_verify_inclusion(program_id, accounts, epoch, leaf, &proof)
+            }
+            VaultInstruction::BatchUserAccount { updates } => {
+                msg!("Instruction: BatchUserAccount");
+                Self::process_batch_user_account(program_id, accounts, &updates)
+            }
+            VaultInstruction::BatchSpotTokenBalance { updates, mode } => {
+                msg!("Instruction: BatchSpotTokenBalance");
+                Self::process_batch_spot_token_balance(program_id, accounts, &updates, mode)
+            }
+            VaultInstruction::CasUserAccount { update, expected } => {
+                msg!("Instruction: CasUserAccount");
+                Self::process_user_account(program_id, accounts, &update, Some(&expected))
+            }
+            VaultInstruction::CasSpotTokenBalance { update, expected } => {
+                msg!("Instruction: CasSpotTokenBalance");
+                Self::process_spot_token_balance(program_id, accounts, &update, Some(&expected))
+            }
         }
     }
 
@@ -161,7 +431,8 @@ impl Processor {
         let vault_config_info = next_account_info(account_info_iter)?;
         let usdc_mint = next_account_info(account_info_iter)?;
         let vault_token_account = next_account_info(account_info_iter)?;
-        let _system_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
 
@@ -193,6 +464,11 @@ impl Processor {
             &[&[b"vault_config", &[vault_config_bump]]],
         )?;
 
+        // USDC vault: program-created token account at ["token_vault", usdc_mint]
+        Self::create_token_vault(
+            program_id, governance_authority, vault_token_account, usdc_mint, token_program, system_program, &vault_config_pda,
+        )?;
+
         let vault_config = VaultConfig {
             discriminator: VaultConfig::DISCRIMINATOR,
             governance_authority: *governance_authority.key,
@@ -203,10 +479,27 @@ impl Processor {
             total_deposits: 0,
             total_locked: 0,
             is_paused: false,
-            reserved: [0u8; 32],
+            require_transfer_checked: false,
+            pending_governance_authority: Pubkey::default(),
+            authorized_caller_roles: [0u8; 10],
+            outflow_breaker: OutflowBreaker::default(),
+            pause_flags: 0,
+            guardians: [Pubkey::default(); MAX_GUARDIANS],
+            guardian_actions: [GuardianActionRecord::default(); GUARDIAN_ACTION_HISTORY],
+            guardian_action_count: 0,
+            escape_hatch: EscapeHatch::default(),
+            limits: VaultLimits::usdc_default(),
+            total_withdrawn: 0,
+            total_liabilities_e6: 0,
+            layout_version: VaultConfig::LAYOUT_VERSION,
+            solvency_auto_pause: false,
+            latest_liability_epoch: 0,
+            usdc_vault_pays_transfer_fee: false,
+            relayer_registry_active: false,
+            reserved: [0u8; 17],
         };
 
-        vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
+        store_vault_config(vault_config_info, &vault_config)?;
 
         msg!("Vault initialized");
         msg!("Delegation Program: {}", delegation_program);
@@ -258,7 +551,10 @@ impl Processor {
             spot_locked_e6: 0,
             account_index,
             oracle_locked_e6: 0,
-            reserved: [0; 44],
+            layout_version: UserAccount::LAYOUT_VERSION,
+            liability_counted: true,
+            sequence: 0,
+            reserved: [0; 34],
         };
 
         user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
@@ -276,6 +572,8 @@ impl Processor {
         let vault_token_account = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
+        // OC-M6: optional trailing USDC Mint (new layout → transfer_checked)
+        let usdc_mint_info = next_account_info(account_info_iter).ok();
 
         assert_signer(user)?;
         assert_writable(user_account_info)?;
@@ -298,31 +596,19 @@ impl Processor {
             return Err(VaultError::InvalidAccount.into());
         }
 
-        let mut vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        if vault_config.is_paused {
-            return Err(VaultError::VaultPaused.into());
-        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        Self::assert_not_paused(&vault_config, PauseCategory::UserDeposit)?;
+        Self::check_amount_limits(&vault_config.limits, true, amount)?;
 
-        // V-2: Verify vault_token_account matches VaultConfig
+        // V-2: Verify vault_token_account matches VaultConfig and is the USDC token vault PDA
         if vault_token_account.key != &vault_config.vault_token_account {
             msg!("❌ Invalid vault token account");
             return Err(VaultError::InvalidAccount.into());
         }
+        Self::verify_token_vault(vault_token_account, &vault_config.usdc_mint, program_id)?;
 
         // OC-H1: Verify user token account mint matches VaultConfig.usdc_mint
-        {
-            let user_ta_data = user_token_account.data.borrow();
-            if user_ta_data.len() < 40 {
-                msg!("❌ User token account data too short ({} bytes), expected >= 40", user_ta_data.len());
-                return Err(VaultError::InvalidAccount.into());
-            }
-            let mint_bytes: [u8; 32] = user_ta_data[..32].try_into().unwrap_or([0u8; 32]);
-            let user_mint = Pubkey::new_from_array(mint_bytes);
-            if user_mint != vault_config.usdc_mint {
-                msg!("❌ User token account mint mismatch: expected {}, got {}", vault_config.usdc_mint, user_mint);
-                return Err(VaultError::InvalidAccount.into());
-            }
-        }
+        Self::verify_user_usdc_token_account(user_token_account, &vault_config)?;
 
         // V-1: Verify UserAccount PDA
         let user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
@@ -331,30 +617,40 @@ impl Processor {
             msg!("❌ Invalid UserAccount PDA");
             return Err(VaultError::InvalidPda.into());
         }
-        drop(user_account);
 
         // SPL Token Transfer (用户 → Vault)
-        token_compat::transfer(
+        let mint = Self::resolve_usdc_mint(usdc_mint_info, &vault_config)?;
+        let received = Self::transfer_tokens(
             token_program,
             user_token_account,
             vault_token_account,
             user,
+            mint,
+            vault_config.require_transfer_checked,
             amount,
             None,
         )?;
+        // Token-2022 transfer fee: only credit what the vault actually received
+        if received == 0 {
+            msg!("❌ Vault received nothing (transfer fee >= amount)");
+            return Err(VaultError::InvalidAmount.into());
+        }
 
         // 更新UserAccount
         let mut user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
-        user_account.available_balance_e6 = checked_add(user_account.available_balance_e6, amount as i64)?;
-        user_account.total_deposited_e6 = checked_add(user_account.total_deposited_e6, amount as i64)?;
+        let before = user_account.clone();
+        user_account.available_balance_e6 = checked_add(user_account.available_balance_e6, received as i64)?;
+        Self::check_account_cap(&vault_config.limits, user_account.available_balance_e6)?;
+        user_account.total_deposited_e6 = checked_add(user_account.total_deposited_e6, received as i64)?;
         user_account.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
         user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
         // 更新VaultConfig
-        vault_config.total_deposits = checked_add_u64(vault_config.total_deposits, amount)?;
-        vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
+        vault_config.total_deposits = checked_add_u64(vault_config.total_deposits, received)?;
+        store_vault_config(vault_config_info, &vault_config)?;
 
-        msg!("Deposited {} e6 for {}", amount, user.key);
+        msg!("Deposited {} e6 for {} (sent {})", received, user.key, amount);
         Ok(())
     }
 
@@ -367,9 +663,12 @@ impl Processor {
         let vault_token_account = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
+        // OC-M6: optional trailing USDC Mint (new layout → transfer_checked)
+        let usdc_mint_info = next_account_info(account_info_iter).ok();
 
         assert_signer(user)?;
         assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
 
         // V-1: Verify token_program is a known SPL Token program
         if !token_compat::is_valid_token_program(token_program.key) {
@@ -389,31 +688,19 @@ impl Processor {
             return Err(VaultError::InvalidAccount.into());
         }
 
-        let vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        if vault_config.is_paused {
-            return Err(VaultError::VaultPaused.into());
-        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        Self::assert_not_paused(&vault_config, PauseCategory::UserWithdraw)?;
+        Self::check_amount_limits(&vault_config.limits, false, amount)?;
 
-        // V-2: Verify vault_token_account matches VaultConfig
+        // V-2: Verify vault_token_account matches VaultConfig and is the USDC token vault PDA
         if vault_token_account.key != &vault_config.vault_token_account {
             msg!("❌ Invalid vault token account");
             return Err(VaultError::InvalidAccount.into());
         }
+        Self::verify_token_vault(vault_token_account, &vault_config.usdc_mint, program_id)?;
 
         // OC-H1: Verify user token account mint matches VaultConfig.usdc_mint
-        {
-            let user_ta_data = user_token_account.data.borrow();
-            if user_ta_data.len() < 40 {
-                msg!("❌ Withdraw: user token account data too short ({} bytes), expected >= 40", user_ta_data.len());
-                return Err(VaultError::InvalidAccount.into());
-            }
-            let mint_bytes: [u8; 32] = user_ta_data[..32].try_into().unwrap_or([0u8; 32]);
-            let user_mint = Pubkey::new_from_array(mint_bytes);
-            if user_mint != vault_config.usdc_mint {
-                msg!("❌ Withdraw: user token account mint mismatch: expected {}, got {}", vault_config.usdc_mint, user_mint);
-                return Err(VaultError::InvalidAccount.into());
-            }
-        }
+        Self::verify_user_usdc_token_account(user_token_account, &vault_config)?;
 
         // V-1: Verify UserAccount PDA + OC-M2 discriminator
         deserialize_checked(&user_account_info.data.borrow(), UserAccount::DISCRIMINATOR)?;
@@ -428,29 +715,318 @@ impl Processor {
             return Err(VaultError::InsufficientBalance.into());
         }
 
+        // vault 承担手续费时按 gross-up 金额出金，计入 total_withdrawn 与熔断窗口
+        let mint = Self::resolve_usdc_mint(usdc_mint_info, &vault_config)?;
+        let vault_pays_transfer_fee = vault_config.usdc_vault_pays_transfer_fee;
+        let send_amount = Self::withdraw_send_amount(vault_pays_transfer_fee, mint, amount)?;
+
+        if !Self::enforce_outflow_breaker(vault_config_info, &mut vault_config, vault_token_account, send_amount)? {
+            set_return_data(&WithdrawOutcome::BreakerTripped.try_to_vec()?);
+            return Ok(());
+        }
+
+        let before = user_account.clone();
         user_account.available_balance_e6 = checked_sub(user_account.available_balance_e6, amount as i64)?;
         user_account.total_withdrawn_e6 = checked_add(user_account.total_withdrawn_e6, amount as i64)?;
         user_account.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
         user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
+        vault_config.total_withdrawn = checked_add_u64(vault_config.total_withdrawn, send_amount)?;
+        store_vault_config(vault_config_info, &vault_config)?;
+
         // SPL Token Transfer (Vault → 用户)
-        token_compat::transfer(
+        let received = Self::transfer_tokens(
             token_program,
             vault_token_account,
             user_token_account,
             vault_config_info,
-            amount,
+            mint,
+            vault_config.require_transfer_checked,
+            send_amount,
             Some(&[b"vault_config", &[vault_config_bump]]),
         )?;
+        Self::verify_withdraw_received(vault_pays_transfer_fee, amount, received)?;
+        set_return_data(&WithdrawOutcome::Transferred.try_to_vec()?);
 
         msg!("Withdrawn {} e6 for {}", amount, user.key);
         Ok(())
     }
 
+    /// OC-H1: 用户 Token Account 的 mint 必须是 VaultConfig.usdc_mint
+    fn verify_user_usdc_token_account(user_token_account: &AccountInfo, vault_config: &VaultConfig) -> ProgramResult {
+        let user_ta_data = user_token_account.data.borrow();
+        if user_ta_data.len() < 40 {
+            msg!("❌ User token account data too short ({} bytes), expected >= 40", user_ta_data.len());
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let mint_bytes: [u8; 32] = user_ta_data[..32].try_into().unwrap_or([0u8; 32]);
+        let user_mint = Pubkey::new_from_array(mint_bytes);
+        if user_mint != vault_config.usdc_mint {
+            msg!("❌ User token account mint mismatch: expected {}, got {}", vault_config.usdc_mint, user_mint);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(())
+    }
+
     // =========================================================================
     // Governance Authority: 权限管理、暂停、紧急释放
     // =========================================================================
 
+    /// 逃生舱心跳: 逃生舱启用时，relayer 指令与状态写入刷新 last_heartbeat_ts
+    /// (此时 VaultConfig 须可写)。
+    fn record_heartbeat(vault_config_info: &AccountInfo, vault_config: &mut VaultConfig) -> ProgramResult {
+        if !vault_config.escape_hatch.is_enabled() {
+            return Ok(());
+        }
+        assert_writable(vault_config_info)?;
+        vault_config.escape_hatch.last_heartbeat_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        store_vault_config(vault_config_info, vault_config)?;
+        Ok(())
+    }
+
+    /// 负债计数: UserAccount 变更后按差额更新 VaultConfig 计数，并标记 liability_counted。
+    /// `before` 为变更前的账户 (新建账户传 None)；尚未计入的旧账户整笔计入。
+    fn track_user_liability(vault_config: &mut VaultConfig, before: Option<&UserAccount>, after: &mut UserAccount) -> ProgramResult {
+        let before = before.filter(|account| account.liability_counted);
+        vault_config.apply_user_delta(before, after).ok_or(VaultError::Overflow)?;
+        after.liability_counted = true;
+        Ok(())
+    }
+
+    /// 负债计数: SpotTokenBalance 变更后按差额更新 TokenRegistryEntry.total_liabilities_e6
+    fn track_spot_liability(entry: &mut TokenRegistryEntry, before: &SpotTokenBalance, after: &mut SpotTokenBalance) -> ProgramResult {
+        let before = Some(before).filter(|balance| balance.liability_counted);
+        entry.apply_balance_delta(before, after).ok_or(VaultError::Overflow)?;
+        after.liability_counted = true;
+        Ok(())
+    }
+
+    /// DB 序号排序: `sequence` 必须大于账户内已存序号，否则 StaleSequence；通过后写入新序号。
+    fn advance_sequence(stored: &mut u64, sequence: u64) -> ProgramResult {
+        if sequence <= *stored {
+            msg!("❌ StaleSequence: sequence={} stored={}", sequence, stored);
+            return Err(VaultError::StaleSequence.into());
+        }
+        *stored = sequence;
+        Ok(())
+    }
+
+    /// CAS 状态写入: 账户当前值必须等于期望值，否则 StateMismatch (`expected` 为 None 时不检查)
+    fn check_expected<T: PartialEq + std::fmt::Debug>(actual: &T, expected: Option<&T>) -> ProgramResult {
+        match expected {
+            Some(expected) if expected != actual => {
+                msg!("❌ StateMismatch: expected={:?} actual={:?}", expected, actual);
+                Err(VaultError::StateMismatch.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// VaultLimits: 单笔最小 / 最大金额 (e6，0 = 不限)
+    fn check_amount_limits(limits: &VaultLimits, deposit: bool, amount_e6: u64) -> ProgramResult {
+        let (min, max) = limits.amount_bounds(deposit);
+        let kind = if deposit { "deposit" } else { "withdrawal" };
+        if amount_e6 < min {
+            msg!("❌ {} of {} e6 below minimum {}", kind, amount_e6, min);
+            return Err(VaultError::AmountBelowMinimum.into());
+        }
+        if max != 0 && amount_e6 > max {
+            msg!("❌ {} of {} e6 above per-transaction maximum {}", kind, amount_e6, max);
+            return Err(VaultError::AmountAboveMaximum.into());
+        }
+        Ok(())
+    }
+
+    /// VaultLimits: 入金后单个 (子) 账户的可用余额上限
+    fn check_account_cap(limits: &VaultLimits, balance_e6: i64) -> ProgramResult {
+        if !limits.allows_account_balance(balance_e6) {
+            msg!("❌ Account balance {} e6 would exceed cap {}", balance_e6, limits.max_account_balance_e6);
+            return Err(VaultError::AccountCapExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// 统一的暂停检查: 每个受暂停约束的 handler 以其 PauseCategory 调用
+    /// (pause_flags 对应位；用户路径另受 is_paused 约束)。
+    fn assert_not_paused(vault_config: &VaultConfig, category: PauseCategory) -> ProgramResult {
+        if vault_config.is_category_paused(category) {
+            msg!("❌ {:?} is paused", category);
+            return Err(VaultError::VaultPaused.into());
+        }
+        Ok(())
+    }
+
+    /// 分离 signer (accounts[0]) 的尾部 relayer 账户: `[.., AuthorizedRelayer PDA]` 或
+    /// `[.., AuthorizedRelayer PDA, RelayerRateLimit PDA]` (仅限流指令，传入所转 token 的 token_index)。
+    /// 其余账户按原有布局解析，可选 Mint / legacy 布局不受影响。
+    #[allow(clippy::type_complexity)]
+    fn split_relayer_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        rate_limit_token_index: Option<u16>,
+    ) -> (&'a [AccountInfo<'b>], Option<&'a AccountInfo<'b>>, Option<&'a AccountInfo<'b>>) {
+        let Some(signer) = accounts.first() else {
+            return (accounts, None, None);
+        };
+        let authorized_relayer_pda = AuthorizedRelayer::derive_pda(program_id, signer.key).0;
+        match accounts {
+            [rest @ .., authorized_relayer, rate_limit]
+                if rest.len() > 1
+                    && authorized_relayer.key == &authorized_relayer_pda
+                    && rate_limit_token_index.is_some_and(|token_index| {
+                        rate_limit.key == &RelayerRateLimit::derive_pda(program_id, signer.key, token_index).0
+                    }) =>
+            {
+                (rest, Some(authorized_relayer), Some(rate_limit))
+            }
+            [rest @ .., authorized_relayer] if !rest.is_empty() && authorized_relayer.key == &authorized_relayer_pda => {
+                (rest, Some(authorized_relayer), None)
+            }
+            _ => (accounts, None, None),
+        }
+    }
+
+    fn load_authorized_relayer(
+        program_id: &Pubkey,
+        authorized_relayer_info: &AccountInfo,
+        relayer: &Pubkey,
+    ) -> Result<AuthorizedRelayer, ProgramError> {
+        let (authorized_relayer_pda, _) = AuthorizedRelayer::derive_pda(program_id, relayer);
+        if authorized_relayer_info.key != &authorized_relayer_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if authorized_relayer_info.data_is_empty() {
+            return Err(VaultError::NotInitialized.into());
+        }
+        let data = authorized_relayer_info.data.borrow();
+        deserialize_checked(&data, AuthorizedRelayer::DISCRIMINATOR)?;
+        Ok(deserialize_account::<AuthorizedRelayer>(&data)?)
+    }
+
+    /// signer 是否持有 role: governance_authority 持有全部角色；传入 AuthorizedRelayer PDA 时
+    /// 以 PDA 为准 (已禁用则无任何角色)，否则查 authorized_callers 角色位。
+    /// 注册表启用后槽位不再写入且 PDA 与槽位 key 互斥，MigrateAuthorizedCallers 清空槽位后
+    /// 所有非 governance relayer 都必须传入 PDA。传入的 PDA 一并返回，用于单笔限额检查。
+    fn signer_role(
+        program_id: &Pubkey,
+        vault_config: &VaultConfig,
+        signer: &Pubkey,
+        authorized_relayer_info: Option<&AccountInfo>,
+        role: VaultRole,
+    ) -> Result<(bool, Option<AuthorizedRelayer>), ProgramError> {
+        let authorized_relayer = authorized_relayer_info
+            .map(|info| Self::load_authorized_relayer(program_id, info, signer))
+            .transpose()?;
+        let has_role = match &authorized_relayer {
+            _ if *signer == vault_config.governance_authority => true,
+            Some(relayer) => {
+                if !relayer.enabled {
+                    msg!("❌ Relayer {} is disabled", signer);
+                }
+                relayer.has_role(role)
+            }
+            None => vault_config.has_role(signer, role),
+        };
+        Ok((has_role, authorized_relayer))
+    }
+
+    /// 校验 VaultConfig PDA 并要求 signer 持有 role (governance_authority 隐含全部角色)
+    fn load_vault_config_with_role(
+        program_id: &Pubkey,
+        vault_config_info: &AccountInfo,
+        signer: &AccountInfo,
+        authorized_relayer_info: Option<&AccountInfo>,
+        role: VaultRole,
+    ) -> Result<(VaultConfig, Option<AuthorizedRelayer>), ProgramError> {
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            msg!("❌ Invalid VaultConfig PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.discriminator != VaultConfig::DISCRIMINATOR {
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let (has_role, authorized_relayer) =
+            Self::signer_role(program_id, &vault_config, signer.key, authorized_relayer_info, role)?;
+        if !has_role {
+            msg!("❌ {} does not hold role {:?}", signer.key, role);
+            return Err(VaultError::MissingRole.into());
+        }
+        Ok((vault_config, authorized_relayer))
+    }
+
+    /// 校验 VaultConfig PDA 并要求 signer 为 governance_authority (启用 timelock 后即 timelock signer PDA)
+    fn load_vault_config_as_governance(
+        program_id: &Pubkey,
+        vault_config_info: &AccountInfo,
+        governance_authority: &AccountInfo,
+    ) -> Result<VaultConfig, ProgramError> {
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            msg!("❌ Invalid VaultConfig PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        Ok(vault_config)
+    }
+
+    /// AuthorizedRelayer 单笔限额 (e6)；未传入 PDA 或限额为 0 时不限制
+    fn check_relayer_tx_limit(authorized_relayer: Option<&AuthorizedRelayer>, role: VaultRole, amount_e6: u64) -> ProgramResult {
+        if let Some(relayer) = authorized_relayer {
+            let max = relayer.max_per_tx_e6(role);
+            if max != 0 && amount_e6 > max {
+                msg!("❌ Relayer {} per-tx limit exceeded: {} > {}", relayer.relayer, amount_e6, max);
+                return Err(VaultError::RelayerLimitExceeded.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// RelayerRateLimit 滚动窗口额度。仅对 `rate_limited` 的 AuthorizedRelayer 生效，
+    /// 此时必须传入其所转 token 的 RelayerRateLimit PDA (可写，地址已由 split_relayer_accounts 校验)；
+    /// governance_authority 与 authorized_callers 不受限。
+    fn consume_relayer_rate_limit(
+        authorized_relayer: Option<&AuthorizedRelayer>,
+        rate_limit_info: Option<&AccountInfo>,
+        kind: RateLimitKind,
+        amount_e6: u64,
+    ) -> ProgramResult {
+        let Some(relayer) = authorized_relayer.filter(|relayer| relayer.rate_limited) else {
+            return Ok(());
+        };
+        let Some(rate_limit_info) = rate_limit_info else {
+            msg!("❌ Relayer {} is rate limited: RelayerRateLimit PDA of the token required", relayer.relayer);
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        assert_writable(rate_limit_info)?;
+        let mut rate_limit = {
+            let data = rate_limit_info.data.borrow();
+            deserialize_checked(&data, RelayerRateLimit::DISCRIMINATOR)?;
+            deserialize_account::<RelayerRateLimit>(&data)?
+        };
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        if !rate_limit.try_record(kind, amount_e6, now) {
+            msg!(
+                "❌ Relayer {} {:?} rate limit exceeded: {} + {} > {} per window",
+                relayer.relayer,
+                kind,
+                rate_limit.window_total(kind, now),
+                amount_e6,
+                rate_limit.max_per_window_e6[kind as usize]
+            );
+            return Err(VaultError::RateLimitExceeded.into());
+        }
+        rate_limit.serialize(&mut &mut rate_limit_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    /// 已废弃: 等同于 GrantRole 授予 LEGACY_RELAYER_ROLES (旧 authorized_callers 的全部 relayer 权限)
     fn process_add_authorized_caller(accounts: &[AccountInfo], caller: Pubkey) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
@@ -459,40 +1035,31 @@ impl Processor {
         assert_signer(governance_authority)?;
         assert_writable(vault_config_info)?;
 
-        let mut vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
+        let mut vault_config = load_vault_config(vault_config_info)?;
         
         if vault_config.governance_authority != *governance_authority.key {
             return Err(VaultError::InvalidGovernanceAuthority.into());
         }
 
-        // 检查是否已存在
-        let already_exists = vault_config.authorized_callers.iter().any(|pk| *pk == caller);
-        if already_exists {
-            msg!("Caller already authorized: {}", caller);
-            return Ok(());
+        if caller == Pubkey::default() {
+            return Err(VaultError::InvalidAccount.into());
         }
-
-        // 找到一个空槽位并添加
-        let mut added = false;
-        for slot in vault_config.authorized_callers.iter_mut() {
-            if *slot == Pubkey::default() {
-                *slot = caller;
-                added = true;
-                break;
-            }
+        if vault_config.relayer_registry_active {
+            msg!("❌ Relayer registry is active; use RegisterRelayer");
+            return Err(VaultError::RelayerPdaRequired.into());
         }
 
-        if added {
-            vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
-            msg!("Added authorized caller: {}", caller);
-        } else {
-            msg!("❌ No empty slot available for authorized caller");
-            return Err(VaultError::InvalidAccount.into());
+        if !vault_config.grant_roles(caller, LEGACY_RELAYER_ROLES) {
+            msg!("❌ No empty slot available for authorized caller; use RegisterRelayer");
+            return Err(VaultError::AuthorizedCallersFull.into());
         }
 
+        store_vault_config(vault_config_info, &vault_config)?;
+        msg!("Added authorized caller: {}", caller);
         Ok(())
     }
 
+    /// 移除 authorized caller 及其全部角色
     fn process_remove_authorized_caller(accounts: &[AccountInfo], caller: Pubkey) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
@@ -501,131 +1068,528 @@ impl Processor {
         assert_signer(governance_authority)?;
         assert_writable(vault_config_info)?;
 
-        let mut vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
+        let mut vault_config = load_vault_config(vault_config_info)?;
         
         if vault_config.governance_authority != *governance_authority.key {
             return Err(VaultError::InvalidGovernanceAuthority.into());
         }
 
-        // 找到并移除 caller (设为默认值)
-        let mut removed = false;
-        for slot in vault_config.authorized_callers.iter_mut() {
-            if *slot == caller {
-                *slot = Pubkey::default();
-                removed = true;
-                break;
-            }
-        }
-
-        if removed {
-            vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
-            msg!("Removed authorized caller: {}", caller);
-        } else {
+        if !vault_config.revoke_roles(&caller, ALL_VAULT_ROLES) {
             msg!("❌ Caller not found in authorized list: {}", caller);
             return Err(VaultError::UnauthorizedUser.into());
         }
 
+        store_vault_config(vault_config_info, &vault_config)?;
+        msg!("Removed authorized caller: {}", caller);
         Ok(())
     }
 
-    fn process_set_paused(accounts: &[AccountInfo], paused: bool) -> ProgramResult {
+    /// 授予角色 (Governance Authority only)
+    ///
+    /// 传入 account 的 AuthorizedRelayer PDA 时写入该 PDA，否则写入 authorized_callers 槽位
+    /// (仅限 relayer 注册表启用之前)。
+    fn process_grant_role(program_id: &Pubkey, accounts: &[AccountInfo], account: Pubkey, role: VaultRole) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let authorized_relayer_info = next_account_info(account_info_iter).ok();
 
         assert_signer(governance_authority)?;
         assert_writable(vault_config_info)?;
 
-        let mut vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
         if vault_config.governance_authority != *governance_authority.key {
             return Err(VaultError::InvalidGovernanceAuthority.into());
         }
 
-        vault_config.is_paused = paused;
-        vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
+        if account == Pubkey::default() || account == vault_config.governance_authority {
+            msg!("❌ Cannot grant roles to {}", account);
+            return Err(VaultError::InvalidAccount.into());
+        }
 
-        msg!("Vault {}", if paused { "paused" } else { "resumed" });
+        if let Some(info) = authorized_relayer_info {
+            assert_writable(info)?;
+            let mut authorized_relayer = Self::load_authorized_relayer(program_id, info, &account)?;
+            authorized_relayer.roles |= role.bit();
+            authorized_relayer.updated_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            authorized_relayer.serialize(&mut &mut info.data.borrow_mut()[..])?;
+            msg!("RoleGranted: account={} role={:?} roles=0b{:06b} (AuthorizedRelayer)", account, role, authorized_relayer.roles);
+            return Ok(());
+        }
+
+        if vault_config.relayer_registry_active {
+            msg!("❌ Relayer registry is active; pass the AuthorizedRelayer PDA of {}", account);
+            return Err(VaultError::RelayerPdaRequired.into());
+        }
+        if !vault_config.grant_roles(account, role.bit()) {
+            msg!("❌ No empty slot available for {}", account);
+            return Err(VaultError::AuthorizedCallersFull.into());
+        }
+
+        store_vault_config(vault_config_info, &vault_config)?;
+        msg!("RoleGranted: account={} role={:?} roles=0b{:06b}", account, role, vault_config.roles_of(&account));
         Ok(())
     }
 
-    fn process_update_governance_authority(accounts: &[AccountInfo], new_governance_authority: Pubkey) -> ProgramResult {
+    /// 撤销角色 (Governance Authority only)
+    ///
+    /// 传入 AuthorizedRelayer PDA 时更新该 PDA；否则更新 authorized_callers，角色清空时释放槽位。
+    fn process_revoke_role(program_id: &Pubkey, accounts: &[AccountInfo], account: Pubkey, role: VaultRole) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let current_governance_authority = next_account_info(account_info_iter)?;
+        let governance_authority = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let authorized_relayer_info = next_account_info(account_info_iter).ok();
 
-        assert_signer(current_governance_authority)?;
+        assert_signer(governance_authority)?;
         assert_writable(vault_config_info)?;
 
-        let mut vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        
-        if vault_config.governance_authority != *current_governance_authority.key {
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
             return Err(VaultError::InvalidGovernanceAuthority.into());
         }
 
-        vault_config.governance_authority = new_governance_authority;
-        vault_config.serialize(&mut &mut vault_config_info.data.borrow_mut()[..])?;
+        if let Some(info) = authorized_relayer_info {
+            assert_writable(info)?;
+            let mut authorized_relayer = Self::load_authorized_relayer(program_id, info, &account)?;
+            if authorized_relayer.roles & role.bit() == 0 {
+                msg!("❌ {} does not hold role {:?}", account, role);
+                return Err(VaultError::MissingRole.into());
+            }
+            authorized_relayer.roles &= !role.bit();
+            authorized_relayer.updated_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            authorized_relayer.serialize(&mut &mut info.data.borrow_mut()[..])?;
+            msg!("RoleRevoked: account={} role={:?} roles=0b{:06b} (AuthorizedRelayer)", account, role, authorized_relayer.roles);
+            return Ok(());
+        }
+
+        if !vault_config.has_role(&account, role) || !vault_config.revoke_roles(&account, role.bit()) {
+            msg!("❌ {} does not hold role {:?}", account, role);
+            return Err(VaultError::MissingRole.into());
+        }
 
-        msg!("Governance authority updated to: {}", new_governance_authority);
+        store_vault_config(vault_config_info, &vault_config)?;
+        msg!("RoleRevoked: account={} role={:?} roles=0b{:06b}", account, role, vault_config.roles_of(&account));
         Ok(())
     }
-    // =========================================================================
-    // Relayer 指令实现
-    // =========================================================================
 
-    /// Relayer 代理入金
-    /// 
-    /// 功能：
-    /// 1. 验证 Governance Authority 签名
-    /// 2. 如果 UserAccount 不存在，自动创建
-    /// 3. 增加用户余额
-    /// 
-    /// 测试网特性：Governance Authority 可自由给任何用户入金（凭证模式）
-    /// OC-L5: RelayerDeposit intentionally skips `is_paused` check.
-    /// Rationale: When the vault is paused (e.g. during incident response), cross-chain bridge
-    /// deposits must still be processed to avoid stuck user funds on the source chain.
-    /// The pause only affects user-initiated Deposit/Withdraw (which require user signature).
-    /// Relayer operations (governed by governance_authority) bypass pause by design.
-    fn process_relayer_deposit(
+    /// 暂停类指令 (SetPaused / SetPauseFlag) 的授权
+    ///
+    /// Pauser 角色只能暂停；恢复始终需要 governance_authority。
+    /// Timelock 启用时 governance_authority 是 timelock signer PDA；紧急暂停不排队，
+    /// Timelock admin 传入 TimelockConfig 即可直接暂停。恢复仍需排队。
+    fn authorize_pause_change(
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        user_wallet: Pubkey,
-        amount: u64,
-        account_index: u32,
+        vault_config: &VaultConfig,
+        signer: &AccountInfo,
+        authorized_relayer_info: Option<&AccountInfo>,
+        timelock_config_info: Option<&AccountInfo>,
+        paused: bool,
     ) -> ProgramResult {
+        let (is_pauser, _) = Self::signer_role(
+            program_id, vault_config, signer.key, authorized_relayer_info, VaultRole::Pauser,
+        )?;
+        if paused && is_pauser {
+            if vault_config.governance_authority != *signer.key {
+                msg!("Pauser pause: {}", signer.key);
+            }
+        } else if vault_config.governance_authority != *signer.key {
+            let is_timelock_admin_pause = match timelock_config_info {
+                Some(info) if paused => {
+                    let timelock_config = Self::load_timelock_config(program_id, info)?;
+                    timelock_config.admin == *signer.key
+                        && vault_config.governance_authority == Self::timelock_signer_pda(program_id, &timelock_config)?
+                }
+                _ => false,
+            };
+            if !is_timelock_admin_pause {
+                return Err(VaultError::InvalidGovernanceAuthority.into());
+            }
+            msg!("Timelock admin emergency pause: {}", signer.key);
+        }
+        Ok(())
+    }
+
+    /// 暂停/恢复 (is_paused: 仅用户路径，见 PauseCategory::is_user_path)
+    fn process_set_paused(program_id: &Pubkey, accounts: &[AccountInfo], paused: bool) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
-        let user_account_info = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
-        let system_program = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter).ok();
 
-        // 1. 验证 governance authority 签名和账户可写
         assert_signer(governance_authority)?;
-        assert_writable(user_account_info)?;
-        // VaultConfig 不需要写入 (不更新 total_deposits)
+        assert_writable(vault_config_info)?;
 
-        // OC-H2: Verify signer is governance_authority or authorized_caller
-        let vault_config_data = vault_config_info.data.borrow();
-        if !VaultConfig::is_valid_relayer_from_bytes(&vault_config_data, governance_authority.key) {
-            msg!("❌ Invalid relayer: {} (not governance_authority nor authorized_caller)", governance_authority.key);
-            return Err(VaultError::InvalidRelayer.into());
-        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        Self::authorize_pause_change(
+            program_id, &vault_config, governance_authority, authorized_relayer_info, timelock_config_info, paused,
+        )?;
 
-        if amount == 0 {
-            return Err(VaultError::InvalidAmount.into());
+        vault_config.is_paused = paused;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("Vault {}", if paused { "paused" } else { "resumed" });
+        Ok(())
+    }
+
+    /// 按类别暂停/恢复单个 pause_flags 位 (授权同 SetPaused)
+    fn process_set_pause_flag(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        category: PauseCategory,
+        paused: bool,
+    ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter).ok();
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        Self::authorize_pause_change(
+            program_id, &vault_config, governance_authority, authorized_relayer_info, timelock_config_info, paused,
+        )?;
+
+        if paused {
+            vault_config.pause_flags |= category.bit();
+        } else {
+            vault_config.pause_flags &= !category.bit();
+        }
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("PauseFlagSet: category={:?} paused={} pause_flags=0b{:08b}", category, paused, vault_config.pause_flags);
+        Ok(())
+    }
+
+    /// 两步治理权限转移 — 第一步: 当前治理权限提议新 key
+    ///
+    /// 仅写入 pending_governance_authority；governance_authority 在新 key 签名
+    /// AcceptGovernanceAuthority 之前保持不变，因此错误的 pubkey 不会锁死治理。
+    fn process_propose_governance_authority(program_id: &Pubkey, accounts: &[AccountInfo], new_authority: Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let current_governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(current_governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        
+        if vault_config.governance_authority != *current_governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        // 清除提议请使用 CancelGovernanceTransfer
+        if new_authority == Pubkey::default() {
+            msg!("❌ Proposed governance authority must not be the default pubkey");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let previous_pending = vault_config.pending_governance_authority;
+        vault_config.pending_governance_authority = new_authority;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        if previous_pending != Pubkey::default() {
+            msg!("Replaced pending governance authority: {}", previous_pending);
+        }
+        msg!(
+            "GovernanceTransferProposed: current={} pending={}",
+            vault_config.governance_authority, new_authority
+        );
+        Ok(())
+    }
+
+    /// 两步治理权限转移 — 第二步: 新 key 签名接受
+    fn process_accept_governance_authority(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pending_governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(pending_governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+
+        if vault_config.pending_governance_authority == Pubkey::default() {
+            return Err(VaultError::NoPendingGovernanceTransfer.into());
+        }
+        if vault_config.pending_governance_authority != *pending_governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let previous_authority = vault_config.governance_authority;
+        vault_config.governance_authority = *pending_governance_authority.key;
+        vault_config.pending_governance_authority = Pubkey::default();
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "GovernanceTransferAccepted: previous={} new={}",
+            previous_authority, pending_governance_authority.key
+        );
+        Ok(())
+    }
+
+    /// 取消待处理的治理权限转移 (当前治理权限)
+    fn process_cancel_governance_transfer(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let cancelled = vault_config.pending_governance_authority;
+        if cancelled == Pubkey::default() {
+            return Err(VaultError::NoPendingGovernanceTransfer.into());
+        }
+
+        vault_config.pending_governance_authority = Pubkey::default();
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "GovernanceTransferCancelled: current={} cancelled={}",
+            vault_config.governance_authority, cancelled
+        );
+        Ok(())
+    }
+
+    /// OC-M6: 结束/恢复 legacy transfer 过渡期
+    ///
+    /// required=true 后，Deposit/Withdraw/SpotDeposit/SpotWithdraw/RelayerSpotWithdraw/
+    /// RelayerWithdrawAndTransfer 必须传入 Mint 账户 (transfer_checked)。
+    fn process_set_require_transfer_checked(program_id: &Pubkey, accounts: &[AccountInfo], required: bool) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        vault_config.require_transfer_checked = required;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("transfer_checked {}", if required { "required (legacy layouts disabled)" } else { "optional (legacy layouts enabled)" });
+        Ok(())
+    }
+
+    /// 设置 USDC 出金熔断器: 每 window_secs 内净流出上限为窗口起始余额的 limit_bps
+    ///
+    /// limit_bps = 0 关闭熔断器。参数变更会重新开启窗口，但不清除已触发状态。
+    fn process_set_outflow_breaker(program_id: &Pubkey, accounts: &[AccountInfo], limit_bps: u16, window_secs: i64) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        if limit_bps as u64 > OUTFLOW_BPS_DENOMINATOR || (limit_bps != 0 && window_secs <= 0) {
+            msg!("❌ Invalid outflow breaker: limit_bps={} window_secs={}", limit_bps, window_secs);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let breaker = &mut vault_config.outflow_breaker;
+        breaker.limit_bps = limit_bps;
+        breaker.window_secs = window_secs;
+        breaker.window_start_ts = 0;
+        breaker.window_start_balance = 0;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("OutflowBreakerSet: limit_bps={} window_secs={}", limit_bps, window_secs);
+        Ok(())
+    }
+
+    /// 设置出入金限额: token_index = 0 → VaultConfig.limits (USDC)，其余 → TokenRegistryEntry.limits
+    fn process_set_limits(program_id: &Pubkey, accounts: &[AccountInfo], token_index: u16, limits: VaultLimits) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        for deposit in [true, false] {
+            let (min, max) = limits.amount_bounds(deposit);
+            if max != 0 && min > max {
+                msg!("❌ Invalid limits: minimum {} above maximum {}", min, max);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        if token_index == 0 {
+            assert_writable(vault_config_info)?;
+            vault_config.limits = limits;
+            store_vault_config(vault_config_info, &vault_config)?;
+        } else {
+            let token_registry_info = next_account_info(account_info_iter)?;
+            assert_writable(token_registry_info)?;
+            let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+            entry.limits = limits;
+            entry.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+        }
+
+        msg!(
+            "LimitsSet: token_index={} min_deposit={} min_withdraw={} max_deposit_per_tx={} max_withdraw_per_tx={} max_account_balance={}",
+            token_index,
+            limits.min_deposit_e6,
+            limits.min_withdraw_e6,
+            limits.max_deposit_per_tx_e6,
+            limits.max_withdraw_per_tx_e6,
+            limits.max_account_balance_e6
+        );
+        Ok(())
+    }
+
+    /// 解除已触发的出金熔断器并重新开启窗口
+    fn process_reset_outflow_breaker(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
         }
 
-        // V-6: Per-relayer daily deposit rate limit.
-        // The on-chain program cannot query clock-based daily aggregates efficiently,
-        // so we enforce a per-TX ceiling here.  The backend (gateway) should enforce
-        // the aggregate daily limit before calling this instruction.
-        // Max single deposit: 10M USDC (prevents fat-finger or exploit in a single TX).
-        const MAX_SINGLE_DEPOSIT_E6: u64 = 10_000_000_000_000; // $10M
-        if amount > MAX_SINGLE_DEPOSIT_E6 {
-            msg!("❌ V-6: Deposit amount {} exceeds per-TX limit {}", amount, MAX_SINGLE_DEPOSIT_E6);
+        let was_paused = vault_config.outflow_breaker.outflow_paused;
+        vault_config.outflow_breaker.reset();
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("OutflowBreakerReset: was_paused={} tripped_ts={}", was_paused, vault_config.outflow_breaker.tripped_ts);
+        Ok(())
+    }
+    // =========================================================================
+    // Relayer 指令实现
+    // =========================================================================
+
+    /// Relayer 代理入金
+    /// 
+    /// 功能：
+    /// 1. 验证 Governance Authority 签名
+    /// 2. 如果 UserAccount 不存在，自动创建
+    /// 3. 增加用户余额
+    /// 
+    /// 测试网特性：Governance Authority 可自由给任何用户入金（凭证模式）
+    /// OC-L5: RelayerDeposit intentionally skips `is_paused` check.
+    /// Rationale: When the vault is paused (e.g. during incident response), cross-chain bridge
+    /// deposits must still be processed to avoid stuck user funds on the source chain.
+    /// The pause only affects user-initiated Deposit/Withdraw (which require user signature).
+    /// Relayer operations bypass `is_paused` by design; stopping them takes the explicit
+    /// `PauseCategory::RelayerCredit` flag.
+    fn process_relayer_deposit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        user_wallet: Pubkey,
+        amount: u64,
+        account_index: u32,
+        sequence: u64,
+    ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, rate_limit_info) = Self::split_relayer_accounts(program_id, accounts, Some(0));
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let user_account_info = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        // 1. 验证 governance authority 签名和账户可写
+        assert_signer(governance_authority)?;
+        assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
+
+        // RBAC: VaultConfig PDA + Depositor role
+        let (mut vault_config, authorized_relayer) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::Depositor,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::RelayerCredit)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+        Self::check_relayer_tx_limit(authorized_relayer.as_ref(), VaultRole::Depositor, amount)?;
+        Self::consume_relayer_rate_limit(
+            authorized_relayer.as_ref(), rate_limit_info, RateLimitKind::Deposit, amount,
+        )?;
+
+        if amount == 0 {
             return Err(VaultError::InvalidAmount.into());
         }
 
+        // V-6: Per-TX deposit ceiling and minimum (VaultConfig.limits, SetLimits).
+        // Rolling-window aggregates are enforced on-chain per relayer by its
+        // RelayerRateLimit PDA (see consume_relayer_rate_limit above); the limits
+        // only guard single-TX fat-finger or exploit amounts.
+        Self::check_amount_limits(&vault_config.limits, true, amount)?;
+
         // 3. 验证 UserAccount PDA
         let (user_account_pda, bump) = UserAccount::derive_pda(program_id, &user_wallet, account_index);
         if user_account_info.key != &user_account_pda {
@@ -636,6 +1600,7 @@ impl Processor {
         // 4. 检查 UserAccount 是否存在，不存在则创建
         if user_account_info.data_is_empty() {
             msg!("Creating new UserAccount for {}", user_wallet);
+            Self::check_account_cap(&vault_config.limits, amount as i64)?;
             
             let rent = Rent::get()?;
             let space = USER_ACCOUNT_SIZE;
@@ -655,7 +1620,7 @@ impl Processor {
             )?;
 
             // 初始化新账户
-            let user_account = UserAccount {
+            let mut user_account = UserAccount {
                 discriminator: UserAccount::DISCRIMINATOR,
                 wallet: user_wallet,
                 bump,
@@ -668,8 +1633,13 @@ impl Processor {
                 spot_locked_e6: 0,
                 account_index,
                 oracle_locked_e6: 0,
-                reserved: [0; 44],
+                layout_version: UserAccount::LAYOUT_VERSION,
+                liability_counted: true,
+                sequence: 0,
+                reserved: [0; 34],
             };
+            Self::advance_sequence(&mut user_account.sequence, sequence)?;
+            Self::track_user_liability(&mut vault_config, None, &mut user_account)?;
             user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
             msg!("✅ Created UserAccount and deposited {} e6 for {}", amount, user_wallet);
@@ -683,17 +1653,21 @@ impl Processor {
                 return Err(VaultError::InvalidAccount.into());
             }
 
+            let before = user_account.clone();
+            Self::advance_sequence(&mut user_account.sequence, sequence)?;
             user_account.available_balance_e6 = checked_add(user_account.available_balance_e6, amount as i64)?;
+            Self::check_account_cap(&vault_config.limits, user_account.available_balance_e6)?;
             user_account.total_deposited_e6 = checked_add(user_account.total_deposited_e6, amount as i64)?;
             user_account.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
             user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
             msg!("✅ RelayerDeposit {} e6 for {} (total: {})", 
                 amount, user_wallet, user_account.available_balance_e6);
         }
 
-        // 注意: 跳过更新 VaultConfig.total_deposits (兼容旧版结构)
-        // 这是测试网的简化实现
+        vault_config.total_deposits = checked_add_u64(vault_config.total_deposits, amount)?;
+        store_vault_config(vault_config_info, &vault_config)?;
 
         Ok(())
     }
@@ -708,14 +1682,17 @@ impl Processor {
     /// OC-L5: RelayerWithdraw intentionally skips `is_paused` check.
     /// Rationale: Same as RelayerDeposit — governance-authorized operations bypass pause.
     /// During pause, the bridge relayer must still be able to execute pending withdrawals
-    /// that have already been committed on the source chain.
+    /// that have already been committed on the source chain. `PauseCategory::RelayerDebit`
+    /// stops them explicitly.
     fn process_relayer_withdraw(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         user_wallet: Pubkey,
         amount: u64,
         account_index: u32,
+        sequence: u64,
     ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, rate_limit_info) = Self::split_relayer_accounts(program_id, accounts, Some(0));
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let user_account_info = next_account_info(account_info_iter)?;
@@ -724,13 +1701,19 @@ impl Processor {
         // 1. 验证 governance authority 签名和账户可写
         assert_signer(governance_authority)?;
         assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
 
-        // OC-H2: Verify signer is governance_authority or authorized_caller
-        let vault_config_data = vault_config_info.data.borrow();
-        if !VaultConfig::is_valid_relayer_from_bytes(&vault_config_data, governance_authority.key) {
-            msg!("❌ Invalid relayer: {} (not governance_authority nor authorized_caller)", governance_authority.key);
-            return Err(VaultError::InvalidRelayer.into());
-        }
+        // RBAC: VaultConfig PDA + Withdrawer role
+        let (mut vault_config, authorized_relayer) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::Withdrawer,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::RelayerDebit)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+        Self::check_amount_limits(&vault_config.limits, false, amount)?;
+        Self::check_relayer_tx_limit(authorized_relayer.as_ref(), VaultRole::Withdrawer, amount)?;
+        Self::consume_relayer_rate_limit(
+            authorized_relayer.as_ref(), rate_limit_info, RateLimitKind::Withdraw, amount,
+        )?;
 
         if amount == 0 {
             return Err(VaultError::InvalidAmount.into());
@@ -764,11 +1747,17 @@ impl Processor {
             return Err(VaultError::InsufficientBalance.into());
         }
 
+        let before = user_account.clone();
+        Self::advance_sequence(&mut user_account.sequence, sequence)?;
         user_account.available_balance_e6 = checked_sub(user_account.available_balance_e6, amount as i64)?;
         user_account.total_withdrawn_e6 = checked_add(user_account.total_withdrawn_e6, amount as i64)?;
         user_account.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
         user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
+        vault_config.total_withdrawn = checked_add_u64(vault_config.total_withdrawn, amount)?;
+        store_vault_config(vault_config_info, &vault_config)?;
+
         msg!("✅ RelayerWithdraw {} e6 for {} (remaining: {})", 
             amount, user_wallet, user_account.available_balance_e6);
         
@@ -785,17 +1774,20 @@ impl Processor {
     /// Accounts:
     /// 0. `[signer]` Governance Authority/Relayer
     /// 1. `[writable]` UserAccount PDA
-    /// 2. `[]` VaultConfig
+    /// 2. `[writable]` VaultConfig
     /// 3. `[writable]` Vault Token Account
     /// 4. `[writable]` Relayer Token Account
     /// 5. `[]` Token Program
+    /// 6. `[]` USDC Mint (optional; required when `require_transfer_checked` is on)
     fn process_relayer_withdraw_and_transfer(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         user_wallet: Pubkey,
         amount: u64,
         account_index: u32,
+        sequence: u64,
     ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, rate_limit_info) = Self::split_relayer_accounts(program_id, accounts, Some(0));
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let user_account_info = next_account_info(account_info_iter)?;
@@ -803,6 +1795,8 @@ impl Processor {
         let vault_token_account = next_account_info(account_info_iter)?;
         let relayer_token_account = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
+        // OC-M6: optional trailing USDC Mint (new layout → transfer_checked)
+        let usdc_mint_info = next_account_info(account_info_iter).ok();
 
         // 1. Verify vault_authority (governance authority/relayer) is signed
         if !governance_authority.is_signer {
@@ -810,25 +1804,25 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
         assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
         assert_writable(vault_token_account)?;
         assert_writable(relayer_token_account)?;
 
-        // V-3: Verify VaultConfig PDA (must match other entrypoints, e.g. process_deposit / process_withdraw)
-        let (expected_vault_config_pda, _) =
-            Pubkey::find_program_address(&[b"vault_config"], program_id);
-        if vault_config_info.key != &expected_vault_config_pda {
-            msg!("❌ Invalid VaultConfig PDA");
-            return Err(VaultError::InvalidAccount.into());
-        }
+        // V-3 + RBAC: VaultConfig PDA + BridgeTransferer role
+        let (mut vault_config, authorized_relayer) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::BridgeTransferer,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::BridgeTransfer)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+        Self::check_amount_limits(&vault_config.limits, false, amount)?;
+        Self::check_relayer_tx_limit(authorized_relayer.as_ref(), VaultRole::BridgeTransferer, amount)?;
 
-        // OC-H2: Verify signer is governance_authority or authorized_caller
-        let vault_config_data = vault_config_info.data.borrow();
-        if !VaultConfig::is_valid_relayer_from_bytes(&vault_config_data, governance_authority.key) {
-            msg!("❌ Invalid relayer: {} (not governance_authority nor authorized_caller)", governance_authority.key);
-            return Err(VaultError::InvalidRelayer.into());
+        // V-2: Source must be the configured USDC token vault PDA
+        if vault_token_account.key != &vault_config.vault_token_account {
+            msg!("❌ Invalid vault token account");
+            return Err(VaultError::InvalidAccount.into());
         }
-
-        drop(vault_config_data);
+        Self::verify_token_vault(vault_token_account, &vault_config.usdc_mint, program_id)?;
 
         if amount == 0 {
             return Err(VaultError::InvalidAmount.into());
@@ -866,22 +1860,45 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        let mint = Self::resolve_usdc_mint(usdc_mint_info, &vault_config)?;
+        let vault_pays_transfer_fee = vault_config.usdc_vault_pays_transfer_fee;
+        let send_amount = Self::withdraw_send_amount(vault_pays_transfer_fee, mint, amount)?;
+
+        // 熔断触发时不动用户状态、不消耗速率额度，sequence 留给 reset 后重试
+        if !Self::enforce_outflow_breaker(vault_config_info, &mut vault_config, vault_token_account, send_amount)? {
+            set_return_data(&WithdrawOutcome::BreakerTripped.try_to_vec()?);
+            return Ok(());
+        }
+        Self::consume_relayer_rate_limit(
+            authorized_relayer.as_ref(), rate_limit_info, RateLimitKind::Withdraw, amount,
+        )?;
+
+        let before = user_account.clone();
+        Self::advance_sequence(&mut user_account.sequence, sequence)?;
         user_account.available_balance_e6 = checked_sub(user_account.available_balance_e6, amount as i64)?;
         user_account.total_withdrawn_e6 = checked_add(user_account.total_withdrawn_e6, amount as i64)?;
         user_account.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
         user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
 
+        vault_config.total_withdrawn = checked_add_u64(vault_config.total_withdrawn, send_amount)?;
+        store_vault_config(vault_config_info, &vault_config)?;
+
         let (_vault_config_pda, vault_config_bump) =
             Pubkey::find_program_address(&[b"vault_config"], program_id);
 
-        token_compat::transfer(
+        let received = Self::transfer_tokens(
             token_program,
             vault_token_account,
             relayer_token_account,
             vault_config_info,
-            amount,
+            mint,
+            vault_config.require_transfer_checked,
+            send_amount,
             Some(&[b"vault_config", &[vault_config_bump]]),
         )?;
+        Self::verify_withdraw_received(vault_pays_transfer_fee, amount, received)?;
+        set_return_data(&WithdrawOutcome::Transferred.try_to_vec()?);
 
         msg!("✅ RelayerWithdrawAndTransfer {} e6 for {} → relayer {} (remaining: {})",
             amount, user_wallet, governance_authority.key, user_account.available_balance_e6);
@@ -890,29 +1907,360 @@ impl Processor {
     }
 
     // =========================================================================
-    // SpotTokenBalance PDA helpers (Dynamic Token Balance Architecture)
+    // Token transfer helpers (OC-M6: transfer_checked migration)
     // =========================================================================
 
-    /// Verify a SpotTokenBalance PDA address matches the expected derivation.
-    /// Returns the bump on success.
-    fn verify_spot_balance_pda(
-        account_info: &AccountInfo,
-        program_id: &Pubkey,
-        wallet: &Pubkey,
-        account_index: u32,
-        token_index: u16,
-    ) -> Result<u8, ProgramError> {
-        let (expected_pda, bump) = derive_spot_token_balance_pda_with_index(program_id, wallet, account_index, token_index);
-        if account_info.key != &expected_pda {
-            msg!("❌ Invalid SpotTokenBalance PDA: expected={}, got={}, account_index={}", expected_pda, account_info.key, account_index);
-            return Err(VaultError::InvalidPda.into());
+    /// USDC 出金熔断器: 已触发则拒绝出金；否则把本次出金计入窗口。
+    /// 会超出窗口上限的出金触发熔断并持久化，返回 `false`，调用方不得转账，
+    /// 以 `WithdrawOutcome::BreakerTripped` 作为 return data 返回 Ok。
+    /// 启用时 VaultConfig 必须可写。
+    fn enforce_outflow_breaker(
+        vault_config_info: &AccountInfo,
+        vault_config: &mut VaultConfig,
+        vault_token_account: &AccountInfo,
+        amount: u64,
+    ) -> Result<bool, ProgramError> {
+        if vault_config.outflow_breaker.outflow_paused {
+            msg!("❌ Outflow circuit breaker tripped at {}", vault_config.outflow_breaker.tripped_ts);
+            return Err(VaultError::OutflowPaused.into());
+        }
+        if !vault_config.outflow_breaker.is_enabled() {
+            return Ok(true);
+        }
+        assert_writable(vault_config_info)?;
+
+        let balance_before = token_compat::get_token_account_amount(vault_token_account)?;
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let (net_outflow, tripped) = vault_config.outflow_breaker.record_outflow(balance_before, amount, now);
+        store_vault_config(vault_config_info, vault_config)?;
+
+        if tripped {
+            let breaker = &vault_config.outflow_breaker;
+            msg!(
+                "OutflowBreakerTripped: net_outflow={} limit={} window_start_balance={} limit_bps={} window_secs={} blocked_amount={}",
+                net_outflow,
+                breaker.window_limit(),
+                breaker.window_start_balance,
+                breaker.limit_bps,
+                breaker.window_secs,
+                amount
+            );
+        }
+        Ok(!tripped)
+    }
+
+    /// Resolve the optional trailing USDC Mint account of a USDC transfer path.
+    /// Returns `(mint, decimals)` when supplied; the mint must be `VaultConfig.usdc_mint`.
+    fn resolve_usdc_mint<'a, 'b>(
+        mint_info: Option<&'b AccountInfo<'a>>,
+        vault_config: &VaultConfig,
+    ) -> Result<Option<(&'b AccountInfo<'a>, u8)>, ProgramError> {
+        let Some(mint_info) = mint_info else {
+            return Ok(None);
+        };
+        if mint_info.key != &vault_config.usdc_mint {
+            msg!("❌ USDC mint mismatch: expected {}, got {}", vault_config.usdc_mint, mint_info.key);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let decimals = token_compat::get_mint_decimals(mint_info).map_err(|_| VaultError::InvalidAccount)?;
+        Ok(Some((mint_info, decimals)))
+    }
+
+    /// Resolve the optional trailing Mint account of a Spot transfer path.
+    /// Returns `(mint, decimals)` when supplied; both must match the TokenRegistryEntry.
+    fn resolve_registered_mint<'a, 'b>(
+        mint_info: Option<&'b AccountInfo<'a>>,
+        entry: &TokenRegistryEntry,
+    ) -> Result<Option<(&'b AccountInfo<'a>, u8)>, ProgramError> {
+        let Some(mint_info) = mint_info else {
+            return Ok(None);
+        };
+        if mint_info.key != &entry.mint {
+            msg!("❌ Mint mismatch: expected {}, got {}", entry.mint, mint_info.key);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let decimals = token_compat::get_mint_decimals(mint_info).map_err(|_| VaultError::InvalidAccount)?;
+        if decimals != entry.decimals {
+            msg!("❌ Mint decimals changed: registered {}, mint {}", entry.decimals, decimals);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(Some((mint_info, decimals)))
+    }
+
+    /// OC-M6: Move tokens with `transfer_checked` when the Mint account is supplied
+    /// (new account layout). Without it, fall back to the legacy `transfer`, which is
+    /// only accepted while `VaultConfig.require_transfer_checked` is off.
+    ///
+    /// Returns the amount the destination actually received (measured balance delta),
+    /// which is less than `amount` when a Token-2022 transfer fee is withheld.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_tokens<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        mint: Option<(&AccountInfo<'a>, u8)>,
+        require_transfer_checked: bool,
+        amount: u64,
+        signer_seeds: Option<&[&[u8]]>,
+    ) -> Result<u64, ProgramError> {
+        let balance_before = token_compat::get_token_account_amount(destination).map_err(|_| VaultError::InvalidAccount)?;
+
+        match mint {
+            Some((mint_info, decimals)) => token_compat::transfer_checked(
+                token_program, source, mint_info, destination, authority, amount, decimals, signer_seeds,
+            )?,
+            None => {
+                if require_transfer_checked {
+                    msg!("❌ Legacy account layout rejected: Mint account required (transfer_checked)");
+                    return Err(VaultError::TransferCheckedRequired.into());
+                }
+                #[allow(deprecated)]
+                token_compat::transfer(token_program, source, destination, authority, amount, signer_seeds)?
+            }
+        }
+
+        let balance_after = token_compat::get_token_account_amount(destination).map_err(|_| VaultError::InvalidAccount)?;
+        balance_after.checked_sub(balance_before).ok_or_else(|| VaultError::Overflow.into())
+    }
+
+    /// Native amount the vault must send for a withdrawal of `amount`.
+    ///
+    /// `vault_pays_transfer_fee=false` (default): the vault sends `amount`, the user
+    /// receives `amount - fee`. `true`: the vault grosses up so the user receives
+    /// exactly `amount`; this needs the Mint account to read the current fee.
+    /// The policy is `VaultConfig.usdc_vault_pays_transfer_fee` for USDC and
+    /// `TokenRegistryEntry.vault_pays_transfer_fee` for Spot tokens.
+    fn withdraw_send_amount(
+        vault_pays_transfer_fee: bool,
+        mint: Option<(&AccountInfo, u8)>,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        if !vault_pays_transfer_fee {
+            return Ok(amount);
+        }
+        let Some((mint_info, _)) = mint else {
+            msg!("❌ Vault-paid transfer fees: Mint account required");
+            return Err(VaultError::TransferCheckedRequired.into());
+        };
+        let epoch = solana_program::clock::Clock::get()?.epoch;
+        match token_compat::get_transfer_fee(&mint_info.data.borrow(), epoch) {
+            Some(fee) => fee.calculate_pre_fee_amount(amount).ok_or_else(|| VaultError::Overflow.into()),
+            None => Ok(amount),
+        }
+    }
+
+    /// Check what the user received on a withdrawal against the fee policy.
+    fn verify_withdraw_received(vault_pays_transfer_fee: bool, amount: u64, received: u64) -> ProgramResult {
+        if vault_pays_transfer_fee && received != amount {
+            msg!("❌ Vault-paid transfer fee: user received {} (expected {})", received, amount);
+            return Err(VaultError::TransferFeeMismatch.into());
+        }
+        if received < amount {
+            msg!("Transfer fee withheld: {} (user pays)", amount - received);
+        }
+        Ok(())
+    }
+
+    // =========================================================================
+    // SpotTokenBalance PDA helpers (Dynamic Token Balance Architecture)
+    // =========================================================================
+
+    /// Verify a SpotTokenBalance PDA address matches the expected derivation.
+    /// Returns the bump on success.
+    fn verify_spot_balance_pda(
+        account_info: &AccountInfo,
+        program_id: &Pubkey,
+        wallet: &Pubkey,
+        account_index: u32,
+        token_index: u16,
+    ) -> Result<u8, ProgramError> {
+        let (expected_pda, bump) = derive_spot_token_balance_pda_with_index(program_id, wallet, account_index, token_index);
+        if account_info.key != &expected_pda {
+            msg!("❌ Invalid SpotTokenBalance PDA: expected={}, got={}, account_index={}", expected_pda, account_info.key, account_index);
+            return Err(VaultError::InvalidPda.into());
         }
         Ok(bump)
     }
 
+    /// Load a TokenRegistryEntry PDA and verify its derivation, discriminator and token_index.
+    fn load_token_registry_entry(
+        account_info: &AccountInfo,
+        program_id: &Pubkey,
+        token_index: u16,
+    ) -> Result<TokenRegistryEntry, ProgramError> {
+        let (expected_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+        if account_info.key != &expected_pda {
+            msg!("❌ Invalid TokenRegistryEntry PDA: expected={}, got={}", expected_pda, account_info.key);
+            return Err(VaultError::InvalidPda.into());
+        }
+        if account_info.data_is_empty() || account_info.owner != program_id {
+            msg!("❌ token_index={} is not registered", token_index);
+            return Err(VaultError::TokenNotRegistered.into());
+        }
+        deserialize_checked(&account_info.data.borrow(), TokenRegistryEntry::DISCRIMINATOR)?;
+        let entry = deserialize_account::<TokenRegistryEntry>(&account_info.data.borrow())?;
+        if entry.token_index != token_index {
+            msg!("❌ TokenRegistryEntry token_index mismatch: expected {}, got {}", token_index, entry.token_index);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(entry)
+    }
+
+    /// Verify the token accounts of a Spot transfer against the TokenRegistryEntry:
+    /// token_program and vault_token_account must be the registered ones, and the
+    /// user's token account must hold the registered mint.
+    fn verify_registered_token_accounts(
+        program_id: &Pubkey,
+        entry: &TokenRegistryEntry,
+        vault_token_account: &AccountInfo,
+        user_token_account: &AccountInfo,
+        token_program: &AccountInfo,
+    ) -> ProgramResult {
+        if token_program.key != &entry.token_program {
+            msg!("❌ Token program mismatch: expected {}, got {}", entry.token_program, token_program.key);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        if vault_token_account.key != &entry.vault_token_account {
+            msg!("❌ Vault token account mismatch: expected {}, got {}", entry.vault_token_account, vault_token_account.key);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Self::verify_token_vault(vault_token_account, &entry.mint, program_id)?;
+        let (user_ta_mint, _) = token_compat::get_token_account_mint_and_owner(user_token_account)
+            .map_err(|_| VaultError::InvalidAccount)?;
+        if user_ta_mint != entry.mint {
+            msg!("❌ Mint mismatch: user={}, registry={}", user_ta_mint, entry.mint);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Deposit gate: token must be enabled and its mint must not carry risk flags
+    /// governance has not accepted. When the Mint account is supplied it is
+    /// re-scanned, so authorities/extensions changed after listing are caught.
+    fn verify_token_accepts_deposits(entry: &TokenRegistryEntry, mint_info: Option<&AccountInfo>) -> ProgramResult {
+        if !entry.is_enabled {
+            msg!("❌ token_index={} is disabled", entry.token_index);
+            return Err(VaultError::TokenDisabled.into());
+        }
+        let mut risk_flags = entry.mint_risk_flags;
+        if let Some(mint_info) = mint_info {
+            risk_flags |= token_compat::get_mint_risk_flags(&mint_info.data.borrow());
+        }
+        let unapproved = entry.unapproved_risk_flags(risk_flags);
+        if unapproved != 0 {
+            msg!("❌ token_index={} mint risk flags 0b{:06b} not accepted by governance", entry.token_index, unapproved);
+            return Err(VaultError::UnsafeMint.into());
+        }
+        Ok(())
+    }
+
+    /// S-6: Derive the e6 amount of a Spot transfer from the native `amount` and the
+    /// registered decimals, and cross-check it against the caller-supplied `amount_e6`.
+    /// Deposits round down (`Rounding::Down`), withdrawals round up (`Rounding::Up`),
+    /// so the credited/debited e6 value never favours the user over the vault.
+    fn derive_spot_amount_e6(
+        entry: &TokenRegistryEntry,
+        amount: u64,
+        amount_e6: i64,
+        rounding: Rounding,
+    ) -> Result<i64, ProgramError> {
+        let derived_e6 = native_to_e6(amount, entry.decimals, rounding)?;
+        if derived_e6 <= 0 {
+            msg!("❌ Amount {} is below 1 e6 unit at {} decimals", amount, entry.decimals);
+            return Err(VaultError::InvalidAmount.into());
+        }
+        if derived_e6 != amount_e6 {
+            msg!("❌ amount_e6 mismatch: native={} decimals={} → expected e6={}, got {}",
+                amount, entry.decimals, derived_e6, amount_e6);
+            return Err(VaultError::InvalidAmount.into());
+        }
+        Ok(derived_e6)
+    }
+
+    /// Verify a vault token account for listing: owned by `token_program`, holds `mint`,
+    /// and its token authority is the VaultConfig PDA.
+    fn verify_vault_token_account_for_listing(
+        program_id: &Pubkey,
+        vault_token_account: &AccountInfo,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+        vault_config_pda: &Pubkey,
+    ) -> ProgramResult {
+        Self::verify_token_vault(vault_token_account, mint, program_id)?;
+        if vault_token_account.owner != token_program {
+            msg!("❌ vault_token_account is not owned by token program {}", token_program);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let (vault_ta_mint, vault_ta_owner) = token_compat::get_token_account_mint_and_owner(vault_token_account)
+            .map_err(|_| VaultError::InvalidAccount)?;
+        if &vault_ta_mint != mint {
+            msg!("❌ vault_token_account mint mismatch: expected {}, got {}", mint, vault_ta_mint);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        if &vault_ta_owner != vault_config_pda {
+            msg!("❌ vault_token_account owner ({}) != VaultConfig PDA ({})", vault_ta_owner, vault_config_pda);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Require `vault_token_account` to be the token vault PDA ["token_vault", mint].
+    fn verify_token_vault(vault_token_account: &AccountInfo, mint: &Pubkey, program_id: &Pubkey) -> ProgramResult {
+        let (expected, _) = derive_token_vault_pda(program_id, mint);
+        if vault_token_account.key != &expected {
+            msg!("❌ Vault token account {} is not the token vault PDA {} for mint {}", vault_token_account.key, expected, mint);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Create the token vault PDA ["token_vault", mint] as a token account of
+    /// `mint` (SPL Token or Token-2022) with the VaultConfig PDA as authority.
+    /// Account size comes from the token program (`GetAccountDataSize`), so
+    /// Token-2022 account extensions required by the mint are covered.
+    fn create_token_vault<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        token_vault_info: &AccountInfo<'a>,
+        mint_info: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        vault_config_pda: &Pubkey,
+    ) -> ProgramResult {
+        let (token_vault_pda, bump) = derive_token_vault_pda(program_id, mint_info.key);
+        if token_vault_info.key != &token_vault_pda {
+            msg!("❌ Invalid token vault PDA: expected {}, got {}", token_vault_pda, token_vault_info.key);
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !token_vault_info.data_is_empty() {
+            msg!("❌ Token vault for mint {} already exists", mint_info.key);
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+        if !token_compat::is_valid_token_program(token_program.key) || mint_info.owner != token_program.key {
+            msg!("❌ Mint {} is not owned by token program {}", mint_info.key, token_program.key);
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let space = token_compat::get_account_data_size(token_program, mint_info)? as usize;
+        create_pda_account(
+            payer,
+            token_vault_info,
+            system_program,
+            space,
+            token_program.key,
+            &[TOKEN_VAULT_SEED, mint_info.key.as_ref(), &[bump]],
+        )?;
+        token_compat::initialize_account3(token_program, token_vault_info, mint_info, vault_config_pda)?;
+
+        msg!("✅ Token vault {} created for mint {} ({} bytes)", token_vault_info.key, mint_info.key, space);
+        Ok(())
+    }
+
     /// Auto-initialize a SpotTokenBalance PDA if it doesn't exist yet.
     /// If the account is empty, creates it with `invoke_signed` and initializes fields.
     /// If it already has data, returns the deserialized balance.
+    #[allow(clippy::too_many_arguments)]
     fn auto_init_spot_balance<'a>(
         payer: &AccountInfo<'a>,
         balance_account: &AccountInfo<'a>,
@@ -928,10 +2276,6 @@ impl Processor {
                 .map_err(|_| ProgramError::InvalidAccountData);
         }
 
-        let rent = Rent::get()?;
-        let space = SPOT_TOKEN_BALANCE_SIZE;
-        let lamports = rent.minimum_balance(space);
-
         let account_index_bytes = account_index.to_le_bytes();
         let seeds: &[&[u8]] = &[
             SPOT_BALANCE_SEED,
@@ -940,39 +2284,7 @@ impl Processor {
             &token_index.to_le_bytes(),
             &[bump],
         ];
-
-        if balance_account.lamports() > 0 {
-            let required = lamports.saturating_sub(balance_account.lamports());
-            if required > 0 {
-                invoke(
-                    &system_instruction::transfer(payer.key, balance_account.key, required),
-                    &[payer.clone(), balance_account.clone(), system_program.clone()],
-                )?;
-            }
-            let account_index_bytes = account_index.to_le_bytes();
-            invoke_signed(
-                &system_instruction::allocate(balance_account.key, space as u64),
-                &[balance_account.clone(), system_program.clone()],
-                &[seeds],
-            )?;
-            invoke_signed(
-                &system_instruction::assign(balance_account.key, program_id),
-                &[balance_account.clone(), system_program.clone()],
-                &[seeds],
-            )?;
-        } else {
-            invoke_signed(
-                &system_instruction::create_account(
-                    payer.key,
-                    balance_account.key,
-                    lamports,
-                    space as u64,
-                    program_id,
-                ),
-                &[payer.clone(), balance_account.clone(), system_program.clone()],
-                &[seeds],
-            )?;
-        }
+        create_pda_account(payer, balance_account, system_program, SPOT_TOKEN_BALANCE_SIZE, program_id, seeds)?;
 
         let current_ts = solana_program::clock::Clock::get()?.unix_timestamp;
         let balance = SpotTokenBalance::new(*wallet, token_index, bump, current_ts);
@@ -987,7 +2299,7 @@ impl Processor {
     // =========================================================================
 
     /// Spot Token 入金 (用户直接调用)
-    /// Accounts: user(signer) + balance_pda(w) + user_token + vault_token + vault_config + token_program + system_program
+    /// Accounts: user(signer) + balance_pda(w) + user_token + vault_token + vault_config + token_registry + token_program + system_program [+ mint]
     fn process_spot_deposit(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1012,10 +2324,14 @@ impl Processor {
         let user_token_account = next_account_info(account_info_iter)?;
         let vault_token_account = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
         let system_program = next_account_info(account_info_iter)?;
+        // OC-M6: optional trailing Mint (new layout → transfer_checked)
+        let mint_info = next_account_info(account_info_iter).ok();
 
         assert_signer(user)?;
+        assert_writable(token_registry_info)?;
 
         // V-1: Verify token_program is a known SPL Token program
         if !token_compat::is_valid_token_program(token_program.key) {
@@ -1030,66 +2346,53 @@ impl Processor {
             return Err(VaultError::InvalidPda.into());
         }
 
-        let vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        if vault_config.is_paused {
-            return Err(VaultError::VaultPaused.into());
-        }
-
-        // S-2: Verify vault_token_account is owned by vault_config PDA (prevents
-        // attackers from passing their own token account as the deposit destination).
-        // SPL Token Account layout: [mint(32), owner(32), ...]. The owner at bytes
-        // 32..64 must be the VaultConfig PDA so that only the vault program can
-        // authorize withdrawals from it.
-        let vault_ta_data = vault_token_account.try_borrow_data()?;
-        if vault_ta_data.len() < 64 {
-            msg!("❌ vault_token_account is not a valid SPL token account");
-            return Err(VaultError::InvalidAccount.into());
-        }
-        let vault_ta_mint = Pubkey::try_from(&vault_ta_data[0..32])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        let vault_ta_owner = Pubkey::try_from(&vault_ta_data[32..64])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        if vault_ta_owner != expected_vault_config_pda {
-            msg!("❌ vault_token_account owner ({}) != VaultConfig PDA ({})", vault_ta_owner, expected_vault_config_pda);
-            return Err(VaultError::InvalidAccount.into());
-        }
-        drop(vault_ta_data);
-
-        // S-4: Cross-check mints — user's token account and vault's token account
-        // must hold the same token type.
-        let user_ta_data = user_token_account.try_borrow_data()?;
-        if user_ta_data.len() < 32 {
-            msg!("❌ user_token_account is not a valid SPL token account");
-            return Err(VaultError::InvalidAccount.into());
-        }
-        let user_ta_mint = Pubkey::try_from(&user_ta_data[0..32])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        drop(user_ta_data);
-        if user_ta_mint != vault_ta_mint {
-            msg!("❌ Mint mismatch: user={}, vault={}", user_ta_mint, vault_ta_mint);
-            return Err(VaultError::InvalidAccount.into());
-        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        Self::assert_not_paused(&vault_config, PauseCategory::SpotDeposit)?;
+
+        // S-2/S-4: token_index must be listed and enabled; vault_token_account,
+        // token_program and the user's mint must match the TokenRegistryEntry
+        // (listing verified that the vault account holds the registered mint and
+        // is owned by the VaultConfig PDA).
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        let mint = Self::resolve_registered_mint(mint_info, &entry)?;
+        Self::verify_token_accepts_deposits(&entry, mint.map(|(mint_info, _)| mint_info))?;
+        Self::verify_registered_token_accounts(program_id, &entry, vault_token_account, user_token_account, token_program)?;
+        // S-6: amount_e6 describes the gross `amount`; the credit below uses the net received
+        let gross_e6 = Self::derive_spot_amount_e6(&entry, amount, amount_e6, Rounding::Down)?;
+        Self::check_amount_limits(&entry.limits, true, gross_e6 as u64)?;
 
         let bump = Self::verify_spot_balance_pda(balance_pda_info, program_id, user.key, account_index, token_index)?;
 
         let mut balance = Self::auto_init_spot_balance(
             user, balance_pda_info, system_program, program_id, user.key, account_index, token_index, bump,
         )?;
+        let before = balance.clone();
 
-        token_compat::transfer(
-            token_program, user_token_account, vault_token_account, user, amount, None,
+        let received = Self::transfer_tokens(
+            token_program, user_token_account, vault_token_account, user,
+            mint, vault_config.require_transfer_checked, amount, None,
         )?;
 
-        balance.available_e6 = balance.available_e6.checked_add(amount_e6).ok_or(VaultError::Overflow)?;
+        // Token-2022 transfer fee: credit only what the vault actually received
+        let credit_e6 = native_to_e6(received, entry.decimals, Rounding::Down)?;
+        if credit_e6 <= 0 {
+            msg!("❌ Net received {} is below one e6 unit after transfer fee", received);
+            return Err(VaultError::InvalidAmount.into());
+        }
+
+        balance.available_e6 = balance.available_e6.checked_add(credit_e6).ok_or(VaultError::Overflow)?;
+        Self::check_account_cap(&entry.limits, balance.available_e6)?;
         balance.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
         balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
 
-        msg!("✅ SpotDeposit: token_index={}, amount_native={}, amount_e6={}", token_index, amount, amount_e6);
+        msg!("✅ SpotDeposit: token_index={}, amount_native={}, received_native={}, amount_e6={}", token_index, amount, received, credit_e6);
         Ok(())
     }
 
     /// Spot Token 出金 (用户直接调用)
-    /// Accounts: user(signer) + balance_pda(w) + user_token + vault_token + vault_config + token_program
+    /// Accounts: user(signer) + balance_pda(w) + user_token + vault_token + vault_config + token_registry + token_program [+ mint]
     fn process_spot_withdraw(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1114,9 +2417,13 @@ impl Processor {
         let user_token_account = next_account_info(account_info_iter)?;
         let vault_token_account = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
+        // OC-M6: optional trailing Mint (new layout → transfer_checked)
+        let mint_info = next_account_info(account_info_iter).ok();
 
         assert_signer(user)?;
+        assert_writable(token_registry_info)?;
 
         // V-1: Verify token_program is a known SPL Token program
         if !token_compat::is_valid_token_program(token_program.key) {
@@ -1131,64 +2438,48 @@ impl Processor {
             return Err(VaultError::InvalidPda.into());
         }
 
-        let vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        if vault_config.is_paused {
-            return Err(VaultError::VaultPaused.into());
-        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        Self::assert_not_paused(&vault_config, PauseCategory::SpotWithdraw)?;
+
+        // S-3/S-5: Withdrawals stay open for disabled tokens, but the accounts
+        // must still match the TokenRegistryEntry.
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        Self::verify_registered_token_accounts(program_id, &entry, vault_token_account, user_token_account, token_program)?;
+        let debit_e6 = Self::derive_spot_amount_e6(&entry, amount, amount_e6, Rounding::Up)?;
+        Self::check_amount_limits(&entry.limits, false, debit_e6 as u64)?;
+        let mint = Self::resolve_registered_mint(mint_info, &entry)?;
 
         Self::verify_spot_balance_pda(balance_pda_info, program_id, user.key, account_index, token_index)?;
 
         deserialize_checked(&balance_pda_info.data.borrow(), SpotTokenBalance::DISCRIMINATOR)?;
         let mut balance = deserialize_account::<SpotTokenBalance>(&balance_pda_info.data.borrow())?;
-        if balance.available_e6 < amount_e6 {
-            msg!("❌ Insufficient balance: available_e6={}, required_e6={}", balance.available_e6, amount_e6);
+        let before = balance.clone();
+        if balance.available_e6 < debit_e6 {
+            msg!("❌ Insufficient balance: available_e6={}, required_e6={}", balance.available_e6, debit_e6);
             return Err(VaultError::InsufficientBalance.into());
         }
 
-        balance.available_e6 = checked_sub(balance.available_e6, amount_e6)?;
+        balance.available_e6 = checked_sub(balance.available_e6, debit_e6)?;
         balance.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
 
-        // S-3: Verify vault_token_account is owned by vault_config PDA
-        let vault_ta_data = vault_token_account.try_borrow_data()?;
-        if vault_ta_data.len() < 64 {
-            msg!("❌ vault_token_account is not a valid SPL token account");
-            return Err(VaultError::InvalidAccount.into());
-        }
-        let vault_ta_mint = Pubkey::try_from(&vault_ta_data[0..32])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        let vault_ta_owner = Pubkey::try_from(&vault_ta_data[32..64])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        if vault_ta_owner != vault_config_pda {
-            msg!("❌ vault_token_account owner mismatch");
-            return Err(VaultError::InvalidAccount.into());
-        }
-        drop(vault_ta_data);
-
-        // S-5: Cross-check mints for withdrawal
-        let user_ta_data = user_token_account.try_borrow_data()?;
-        if user_ta_data.len() < 32 {
-            return Err(VaultError::InvalidAccount.into());
-        }
-        let user_ta_mint = Pubkey::try_from(&user_ta_data[0..32])
-            .map_err(|_| VaultError::InvalidAccount)?;
-        drop(user_ta_data);
-        if user_ta_mint != vault_ta_mint {
-            msg!("❌ Mint mismatch: user={}, vault={}", user_ta_mint, vault_ta_mint);
-            return Err(VaultError::InvalidAccount.into());
-        }
-
-        token_compat::transfer(
-            token_program, vault_token_account, user_token_account, vault_config_info, amount,
+        let send_amount = Self::withdraw_send_amount(entry.vault_pays_transfer_fee, mint, amount)?;
+        let received = Self::transfer_tokens(
+            token_program, vault_token_account, user_token_account, vault_config_info,
+            mint, vault_config.require_transfer_checked, send_amount,
             Some(&[b"vault_config", &[vault_config_bump]]),
         )?;
+        Self::verify_withdraw_received(entry.vault_pays_transfer_fee, amount, received)?;
 
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
         balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
-        msg!("✅ SpotWithdraw: token_index={}, amount_native={}, amount_e6={}", token_index, amount, amount_e6);
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+        msg!("✅ SpotWithdraw: token_index={}, amount_native={}, amount_e6={}", token_index, amount, debit_e6);
         Ok(())
     }
 
     /// Relayer 代理 Spot 入金
-    /// Accounts: governance_authority(signer) + balance_pda(w) + vault_config + system_program
+    /// Accounts: governance_authority(signer) + balance_pda(w) + vault_config + token_registry + system_program
+    #[allow(clippy::too_many_arguments)]
     fn process_relayer_spot_deposit(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1197,6 +2488,7 @@ impl Processor {
         amount: u64,
         account_index: u32,
         amount_e6: i64,
+        sequence: u64,
     ) -> ProgramResult {
         if token_index == 0 {
             msg!("❌ USDC (token_index=0) must use RelayerDeposit (#25), not RelayerSpotDeposit.");
@@ -1208,37 +2500,53 @@ impl Processor {
             return Err(VaultError::InvalidAmount.into());
         }
         
+        let (accounts, authorized_relayer_info, rate_limit_info) = Self::split_relayer_accounts(program_id, accounts, Some(token_index));
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let balance_pda_info = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
         let system_program = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
-        let vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        // OC-H2: Accept governance_authority OR authorized_caller
-        if vault_config.governance_authority != *governance_authority.key
-            && !vault_config.is_authorized_caller(governance_authority.key)
-        {
-            return Err(VaultError::InvalidRelayer.into());
-        }
+        assert_writable(token_registry_info)?;
+        // RBAC: VaultConfig PDA + Depositor role
+        let (mut vault_config, authorized_relayer) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::Depositor,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::RelayerCredit)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+        Self::check_relayer_tx_limit(authorized_relayer.as_ref(), VaultRole::Depositor, amount_e6 as u64)?;
+        Self::consume_relayer_rate_limit(
+            authorized_relayer.as_ref(), rate_limit_info, RateLimitKind::Deposit, amount_e6 as u64,
+        )?;
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        Self::verify_token_accepts_deposits(&entry, None)?;
+        Self::check_amount_limits(&entry.limits, true, amount_e6 as u64)?;
 
         let bump = Self::verify_spot_balance_pda(balance_pda_info, program_id, &user_wallet, account_index, token_index)?;
         let mut balance = Self::auto_init_spot_balance(
             governance_authority, balance_pda_info, system_program, program_id, &user_wallet, account_index, token_index, bump,
         )?;
+        let before = balance.clone();
+        Self::advance_sequence(&mut balance.sequence, sequence)?;
 
         balance.available_e6 = balance.available_e6.checked_add(amount_e6).ok_or(VaultError::Overflow)?;
+        Self::check_account_cap(&entry.limits, balance.available_e6)?;
         balance.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
         balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
 
         msg!("✅ RelayerSpotDeposit: user={}, token_index={}, amount_native={}, amount_e6={}", user_wallet, token_index, amount, amount_e6);
         Ok(())
     }
 
     /// Relayer 代理 Spot 出金 (with SPL token transfer)
-    /// Accounts: governance_authority(signer) + balance_pda(w) + vault_config + vault_token_account(w) + user_token_account(w) + token_program
-    /// If only 3 accounts are passed (legacy), PDA is debited without SPL transfer (backward-compat).
+    /// Accounts: governance_authority(signer) + balance_pda(w) + vault_config + token_registry + vault_token_account(w) + user_token_account(w) + token_program [+ mint]
+    /// If only 4 accounts are passed (legacy), PDA is debited without SPL transfer (backward-compat).
+    #[allow(clippy::too_many_arguments)]
     fn process_relayer_spot_withdraw(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1247,6 +2555,7 @@ impl Processor {
         amount: u64,
         account_index: u32,
         amount_e6: i64,
+        sequence: u64,
     ) -> ProgramResult {
         if token_index == 0 {
             msg!("❌ USDC (token_index=0) must use RelayerWithdraw (#26), not RelayerSpotWithdraw.");
@@ -1258,36 +2567,51 @@ impl Processor {
             return Err(VaultError::InvalidAmount.into());
         }
 
+        let (accounts, authorized_relayer_info, rate_limit_info) = Self::split_relayer_accounts(program_id, accounts, Some(token_index));
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let balance_pda_info = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
-        let vault_config = deserialize_account::<VaultConfig>(&vault_config_info.data.borrow())?;
-        // OC-H2: Accept governance_authority OR authorized_caller
-        if vault_config.governance_authority != *governance_authority.key
-            && !vault_config.is_authorized_caller(governance_authority.key)
-        {
-            return Err(VaultError::InvalidRelayer.into());
-        }
+        assert_writable(token_registry_info)?;
+        // RBAC: VaultConfig PDA + Withdrawer role
+        let (mut vault_config, authorized_relayer) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::Withdrawer,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::RelayerDebit)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+        Self::check_relayer_tx_limit(authorized_relayer.as_ref(), VaultRole::Withdrawer, amount_e6 as u64)?;
+        Self::consume_relayer_rate_limit(
+            authorized_relayer.as_ref(), rate_limit_info, RateLimitKind::Withdraw, amount_e6 as u64,
+        )?;
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        Self::check_amount_limits(&entry.limits, false, amount_e6 as u64)?;
 
         Self::verify_spot_balance_pda(balance_pda_info, program_id, &user_wallet, account_index, token_index)?;
         deserialize_checked(&balance_pda_info.data.borrow(), SpotTokenBalance::DISCRIMINATOR)?;
         let mut balance = deserialize_account::<SpotTokenBalance>(&balance_pda_info.data.borrow())?;
+        let before = balance.clone();
+        Self::advance_sequence(&mut balance.sequence, sequence)?;
         if balance.available_e6 < amount_e6 {
             msg!("❌ Insufficient balance: available_e6={}, required_e6={}", balance.available_e6, amount_e6);
             return Err(VaultError::InsufficientBalance.into());
         }
         balance.available_e6 = checked_sub(balance.available_e6, amount_e6)?;
         balance.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
         balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
 
         // SPL token transfer: if additional accounts are provided, transfer real tokens.
         let vault_token_account = next_account_info(account_info_iter);
         if let Ok(vault_ta) = vault_token_account {
             let user_token_account = next_account_info(account_info_iter)?;
             let token_program = next_account_info(account_info_iter)?;
+            // OC-M6: optional trailing Mint (new layout → transfer_checked)
+            let mint_info = next_account_info(account_info_iter).ok();
 
             if !token_compat::is_valid_token_program(token_program.key) {
                 msg!("❌ Invalid token program for RelayerSpotWithdraw transfer");
@@ -1300,38 +2624,18 @@ impl Processor {
                 return Err(VaultError::InvalidPda.into());
             }
 
-            let vault_ta_data = vault_ta.try_borrow_data()?;
-            if vault_ta_data.len() < 64 {
-                msg!("❌ vault_token_account is not a valid SPL token account");
-                return Err(VaultError::InvalidAccount.into());
-            }
-            let vault_ta_mint = Pubkey::try_from(&vault_ta_data[0..32])
-                .map_err(|_| VaultError::InvalidAccount)?;
-            let vault_ta_owner = Pubkey::try_from(&vault_ta_data[32..64])
-                .map_err(|_| VaultError::InvalidAccount)?;
-            if vault_ta_owner != vault_config_pda {
-                msg!("❌ vault_token_account not owned by vault_config PDA");
-                return Err(VaultError::InvalidAccount.into());
-            }
-            drop(vault_ta_data);
-
-            let user_ta_data = user_token_account.try_borrow_data()?;
-            if user_ta_data.len() < 32 {
-                return Err(VaultError::InvalidAccount.into());
-            }
-            let user_ta_mint = Pubkey::try_from(&user_ta_data[0..32])
-                .map_err(|_| VaultError::InvalidAccount)?;
-            drop(user_ta_data);
-
-            if user_ta_mint != vault_ta_mint {
-                msg!("❌ Mint mismatch: user={}, vault={}", user_ta_mint, vault_ta_mint);
-                return Err(VaultError::InvalidAccount.into());
-            }
+            Self::verify_registered_token_accounts(program_id, &entry, vault_ta, user_token_account, token_program)?;
+            // S-6: the e6 debit above must correspond to the native amount transferred out
+            Self::derive_spot_amount_e6(&entry, amount, amount_e6, Rounding::Up)?;
+            let mint = Self::resolve_registered_mint(mint_info, &entry)?;
 
-            token_compat::transfer(
-                token_program, vault_ta, user_token_account, vault_config_info, amount,
+            let send_amount = Self::withdraw_send_amount(entry.vault_pays_transfer_fee, mint, amount)?;
+            let received = Self::transfer_tokens(
+                token_program, vault_ta, user_token_account, vault_config_info,
+                mint, vault_config.require_transfer_checked, send_amount,
                 Some(&[b"vault_config", &[vault_config_bump]]),
             )?;
+            Self::verify_withdraw_received(entry.vault_pays_transfer_fee, amount, received)?;
 
             msg!("✅ RelayerSpotWithdraw+Transfer: user={}, token_index={}, amount_native={}, amount_e6={}", user_wallet, token_index, amount, amount_e6);
         } else {
@@ -1350,23 +2654,20 @@ impl Processor {
     ///
     /// # Relayer Verification Design (H-3)
     ///
-    /// Vault uses `VaultConfig.governance_authority` (single key) for relayer authorization,
-    /// while Exchange uses an `authorized_relayers` list. This is intentional:
-    /// - Current deployment has exactly one Relayer key per environment.
-    /// - Vault governance_authority == Relayer in all three environments (local/staging/mainnet).
-    /// - If multi-Relayer support is needed in the future, add an
-    ///   `authorized_relayers: Vec<Pubkey>` field to VaultConfig and upgrade
-    ///   the on-chain program. This is a low-risk future change.
+    /// State writes require the `StateWriter` role (see `load_vault_config_with_role`):
+    /// - governance_authority implicitly holds every role, so a single-key deployment
+    ///   where governance_authority == Relayer keeps working unchanged.
+    /// - Other Relayer keys are granted `StateWriter` via GrantRole; a key that can
+    ///   overwrite state does not automatically get to credit deposits or move USDC.
+    /// - In multisig / timelock mode governance_authority is a signer PDA, which never
+    ///   signs a relayer transaction; the Relayer key must then be granted its roles.
     fn process_user_account(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        user_wallet: Pubkey,
-        account_index: u32,
-        available_balance_e6: i64,
-        locked_margin_e6: i64,
-        spot_locked_e6: i64,
-        oracle_locked_e6: i64,
+        update: &UserAccountUpdate,
+        expected: Option<&ExpectedUserAccount>,
     ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let user_account_info = next_account_info(account_info_iter)?;
@@ -1374,81 +2675,111 @@ impl Processor {
         let system_program = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
-        assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
 
-        let vault_config_data = vault_config_info.data.borrow();
-        if vault_config_data.len() < 40 {
-            return Err(VaultError::InvalidAccount.into());
-        }
-        // OC-H2: Verify signer is governance_authority or authorized_caller
-        if !VaultConfig::is_valid_relayer_from_bytes(&vault_config_data, governance_authority.key) {
-            msg!("UserAccount: invalid relayer {} (not governance_authority nor authorized_caller)", governance_authority.key);
-            return Err(VaultError::InvalidRelayer.into());
+        // RBAC: VaultConfig PDA + StateWriter role
+        let (mut vault_config, _) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::StateWriter,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::StateWrite)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let result = Self::write_user_account(
+            program_id, governance_authority, user_account_info, system_program, &mut vault_config, update, expected, now,
+        )?;
+        if result == StateWriteResult::Stale {
+            return Ok(());
         }
+        store_vault_config(vault_config_info, &vault_config)?;
 
-        let (user_account_pda, bump) = UserAccount::derive_pda(program_id, &user_wallet, account_index);
+        msg!("UserAccount: wallet={} idx={} avail={} locked={} spot={} oracle={}",
+            update.user_wallet, update.account_index, update.available_balance_e6,
+            update.locked_margin_e6, update.spot_locked_e6, update.oracle_locked_e6);
+        Ok(())
+    }
+
+    /// 单条 UserAccount set-to-value 写入 (UserAccount / BatchUserAccount 共用)。
+    /// PDA 不存在时由 payer 出租金创建；sequence 不新于已存序号时不做修改并返回 Stale。
+    /// `expected` 为 CAS 期望值，与账户当前值 (新建时为全零) 不一致时 StateMismatch。
+    /// 负债计数只更新到 `vault_config`，由调用方写回。
+    #[allow(clippy::too_many_arguments)]
+    fn write_user_account<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        user_account_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        vault_config: &mut VaultConfig,
+        update: &UserAccountUpdate,
+        expected: Option<&ExpectedUserAccount>,
+        now: i64,
+    ) -> Result<StateWriteResult, ProgramError> {
+        assert_writable(user_account_info)?;
+
+        let (user_account_pda, bump) = UserAccount::derive_pda(program_id, &update.user_wallet, update.account_index);
         if user_account_info.key != &user_account_pda {
             return Err(VaultError::InvalidPda.into());
         }
 
         if user_account_info.data_is_empty() {
-            let account_index_bytes = account_index.to_le_bytes();
-            let rent = Rent::get()?;
-            let space = USER_ACCOUNT_SIZE;
-            let lamports = rent.minimum_balance(space);
-
-            invoke_signed(
-                &system_instruction::create_account(
-                    governance_authority.key,
-                    user_account_info.key,
-                    lamports,
-                    space as u64,
-                    program_id,
-                ),
-                &[governance_authority.clone(), user_account_info.clone(), system_program.clone()],
-                &[&[b"user", user_wallet.as_ref(), &account_index_bytes, &[bump]]],
+            Self::check_expected(&ExpectedUserAccount::default(), expected)?;
+            let account_index_bytes = update.account_index.to_le_bytes();
+            create_pda_account(
+                payer,
+                user_account_info,
+                system_program,
+                USER_ACCOUNT_SIZE,
+                program_id,
+                &[b"user", update.user_wallet.as_ref(), &account_index_bytes, &[bump]],
             )?;
 
-            let user_account = UserAccount {
+            let mut user_account = UserAccount {
                 discriminator: UserAccount::DISCRIMINATOR,
-                wallet: user_wallet,
+                wallet: update.user_wallet,
                 bump,
-                available_balance_e6,
-                locked_margin_e6,
+                available_balance_e6: update.available_balance_e6,
+                locked_margin_e6: update.locked_margin_e6,
                 unrealized_pnl_e6: 0,
                 total_deposited_e6: 0,
                 total_withdrawn_e6: 0,
-                last_update_ts: solana_program::clock::Clock::get()?.unix_timestamp,
-                spot_locked_e6,
-                account_index,
-                oracle_locked_e6,
-                reserved: [0; 44],
+                last_update_ts: now,
+                spot_locked_e6: update.spot_locked_e6,
+                account_index: update.account_index,
+                oracle_locked_e6: update.oracle_locked_e6,
+                layout_version: UserAccount::LAYOUT_VERSION,
+                liability_counted: true,
+                sequence: update.sequence,
+                reserved: [0; 34],
             };
+            Self::track_user_liability(vault_config, None, &mut user_account)?;
             user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
-        } else {
-            let mut user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
-            if user_account.wallet != user_wallet {
-                return Err(VaultError::InvalidAccount.into());
-            }
-
-            let current_ts = solana_program::clock::Clock::get()?.unix_timestamp;
-            if current_ts < user_account.last_update_ts {
-                msg!("UserAccount: stale update rejected (current={} < stored={})",
-                    current_ts, user_account.last_update_ts);
-                return Ok(());
-            }
+            return Ok(StateWriteResult::Created);
+        }
 
-            user_account.available_balance_e6 = available_balance_e6;
-            user_account.locked_margin_e6 = locked_margin_e6;
-            user_account.spot_locked_e6 = spot_locked_e6;
-            user_account.oracle_locked_e6 = oracle_locked_e6;
-            user_account.last_update_ts = current_ts;
-            user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
+        let mut user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
+        if user_account.wallet != update.user_wallet {
+            return Err(VaultError::InvalidAccount.into());
         }
 
-        msg!("UserAccount: wallet={} idx={} avail={} locked={} spot={} oracle={}",
-            user_wallet, account_index, available_balance_e6, locked_margin_e6, spot_locked_e6, oracle_locked_e6);
-        Ok(())
+        // 较旧的 DB 快照不覆盖较新的状态 (no-op)
+        if update.sequence <= user_account.sequence {
+            msg!("StaleSequence: UserAccount wallet={} idx={} sequence={} stored={} (skipped)",
+                update.user_wallet, update.account_index, update.sequence, user_account.sequence);
+            return Ok(StateWriteResult::Stale);
+        }
+        Self::check_expected(&ExpectedUserAccount::of(&user_account), expected)?;
+
+        let before = user_account.clone();
+        user_account.sequence = update.sequence;
+        user_account.available_balance_e6 = update.available_balance_e6;
+        user_account.locked_margin_e6 = update.locked_margin_e6;
+        user_account.spot_locked_e6 = update.spot_locked_e6;
+        user_account.oracle_locked_e6 = update.oracle_locked_e6;
+        user_account.last_update_ts = now;
+        // set-to-value: the counters move by the difference
+        Self::track_user_liability(vault_config, Some(&before), &mut user_account)?;
+        user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
+        Ok(StateWriteResult::Updated)
     }
 
     /// SpotTokenBalance PDA state writer (set-to-value, not increment).
@@ -1456,57 +2787,215 @@ impl Processor {
     fn process_spot_token_balance(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        user_wallet: Pubkey,
-        account_index: u32,
-        token_index: u16,
-        available_e6: i64,
-        locked_e6: i64,
+        update: &SpotTokenBalanceUpdate,
+        expected: Option<&ExpectedSpotTokenBalance>,
     ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let balance_pda_info = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
         let system_program = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
-        assert_writable(balance_pda_info)?;
 
-        let vault_config_data = vault_config_info.data.borrow();
-        if vault_config_data.len() < 40 {
-            return Err(VaultError::InvalidAccount.into());
-        }
-        // OC-H2: Verify signer is governance_authority or authorized_caller
-        if !VaultConfig::is_valid_relayer_from_bytes(&vault_config_data, governance_authority.key) {
-            msg!("SpotTokenBalance: invalid relayer {} (not governance_authority nor authorized_caller)", governance_authority.key);
-            return Err(VaultError::InvalidRelayer.into());
+        // RBAC: VaultConfig PDA + StateWriter role
+        let (mut vault_config, _) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::StateWriter,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::StateWrite)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let result = Self::write_spot_token_balance(
+            program_id, governance_authority, balance_pda_info, token_registry_info, system_program, update, expected, now,
+        )?;
+        if result == StateWriteResult::Stale {
+            return Ok(());
         }
 
+        msg!("SpotTokenBalance: wallet={} idx={} token={} avail={} locked={}",
+            update.user_wallet, update.account_index, update.token_index, update.available_e6, update.locked_e6);
+        Ok(())
+    }
+
+    /// 单条 SpotTokenBalance set-to-value 写入 (SpotTokenBalance / BatchSpotTokenBalance 共用)。
+    /// CAS、sequence 与负债计数先在内存中对当前值 (PDA 不存在时为全零的新余额) 完成，
+    /// 全部通过后才由 payer 出租金创建 PDA 并写入，失败时不会留下新建的账户。
+    /// 已有账户 sequence 不新于已存序号时不做修改并返回 Stale；新建账户总是返回 Created。
+    /// `expected` 为 CAS 期望值 (新建时为全零)。TokenRegistryEntry 的负债计数直接写回。
+    #[allow(clippy::too_many_arguments)]
+    fn write_spot_token_balance<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        balance_pda_info: &AccountInfo<'a>,
+        token_registry_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        update: &SpotTokenBalanceUpdate,
+        expected: Option<&ExpectedSpotTokenBalance>,
+        now: i64,
+    ) -> Result<StateWriteResult, ProgramError> {
+        assert_writable(balance_pda_info)?;
+        assert_writable(token_registry_info)?;
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, update.token_index)?;
+
         let (balance_pda, bump) = derive_spot_token_balance_pda_with_index(
-            program_id, &user_wallet, account_index, token_index,
+            program_id, &update.user_wallet, update.account_index, update.token_index,
         );
         if balance_pda_info.key != &balance_pda {
+            msg!("❌ Invalid SpotTokenBalance PDA: expected={}, got={}", balance_pda, balance_pda_info.key);
             return Err(VaultError::InvalidPda.into());
         }
 
-        let mut balance = Self::auto_init_spot_balance(
-            governance_authority, balance_pda_info, system_program, program_id,
-            &user_wallet, account_index, token_index, bump,
+        let created = balance_pda_info.data_is_empty();
+        let mut balance = if created {
+            SpotTokenBalance::new(update.user_wallet, update.token_index, bump, now)
+        } else {
+            deserialize_account::<SpotTokenBalance>(&balance_pda_info.data.borrow())
+                .map_err(|_| ProgramError::InvalidAccountData)?
+        };
+        let before = balance.clone();
+
+        // 较旧的 DB 快照不覆盖较新的状态 (no-op)
+        if !created && update.sequence <= balance.sequence {
+            msg!("StaleSequence: SpotTokenBalance wallet={} idx={} token={} sequence={} stored={} (skipped)",
+                update.user_wallet, update.account_index, update.token_index, update.sequence, balance.sequence);
+            return Ok(StateWriteResult::Stale);
+        }
+        Self::check_expected(&ExpectedSpotTokenBalance::of(&before), expected)?;
+
+        balance.available_e6 = update.available_e6;
+        balance.locked_e6 = update.locked_e6;
+        balance.sequence = update.sequence;
+        balance.last_update_ts = now;
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
+
+        if created {
+            Self::auto_init_spot_balance(
+                payer, balance_pda_info, system_program, program_id,
+                &update.user_wallet, update.account_index, update.token_index, bump,
+            )?;
+        }
+        balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+        Ok(if created { StateWriteResult::Created } else { StateWriteResult::Updated })
+    }
+
+    /// 批量 UserAccount state writer: 角色、暂停与心跳只检查一次，逐条调用 `write_user_account`。
+    /// 陈旧条目跳过，其余错误使整批失败；逐条结果写入日志与 return data。
+    fn process_batch_user_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        updates: &[UserAccountUpdate],
+    ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let user_account_infos = account_info_iter.as_slice();
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        if updates.is_empty() || updates.len() > MAX_BATCH_USER_ACCOUNT_UPDATES {
+            msg!("❌ BatchUserAccount: {} updates (allowed 1..={})", updates.len(), MAX_BATCH_USER_ACCOUNT_UPDATES);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if user_account_infos.len() != updates.len() {
+            msg!("❌ BatchUserAccount: {} updates but {} UserAccount PDAs", updates.len(), user_account_infos.len());
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        // RBAC: VaultConfig PDA + StateWriter role
+        let (mut vault_config, _) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::StateWriter,
         )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::StateWrite)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let mut results = Vec::with_capacity(updates.len());
+        for (i, (update, user_account_info)) in updates.iter().zip(user_account_infos).enumerate() {
+            let result = Self::write_user_account(
+                program_id, governance_authority, user_account_info, system_program, &mut vault_config, update, None, now,
+            )?;
+            msg!("BatchUserAccount[{}]: wallet={} idx={} sequence={} result={:?}",
+                i, update.user_wallet, update.account_index, update.sequence, result);
+            results.push(result);
+        }
+        store_vault_config(vault_config_info, &vault_config)?;
+        set_return_data(&results.try_to_vec()?);
 
-        let current_ts = solana_program::clock::Clock::get()?.unix_timestamp;
-        if current_ts < balance.last_update_ts {
-            msg!("SpotTokenBalance: stale update rejected (current={} < stored={})",
-                current_ts, balance.last_update_ts);
-            return Ok(());
+        let count = |kind: StateWriteResult| results.iter().filter(|result| **result == kind).count();
+        msg!("BatchUserAccount: entries={} created={} updated={} stale={}",
+            results.len(), count(StateWriteResult::Created), count(StateWriteResult::Updated), count(StateWriteResult::Stale));
+        Ok(())
+    }
+
+    /// 批量 SpotTokenBalance state writer: 角色、暂停与心跳只检查一次，逐条调用
+    /// `write_spot_token_balance`，signer 为唯一的租金支付方。无效条目按 `mode` 使整批失败
+    /// 或记录原因后跳过；逐条结果写入日志与 return data。
+    fn process_batch_spot_token_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        updates: &[SpotTokenBalanceUpdate],
+        mode: BatchMode,
+    ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let entry_infos = account_info_iter.as_slice();
+
+        assert_signer(governance_authority)?;
+
+        if updates.is_empty() || updates.len() > MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES {
+            msg!("❌ BatchSpotTokenBalance: {} updates (allowed 1..={})", updates.len(), MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if entry_infos.len() != updates.len() * 2 {
+            msg!("❌ BatchSpotTokenBalance: {} updates need {} accounts, got {}",
+                updates.len(), updates.len() * 2, entry_infos.len());
+            return Err(ProgramError::NotEnoughAccountKeys);
         }
 
-        balance.available_e6 = available_e6;
-        balance.locked_e6 = locked_e6;
-        balance.last_update_ts = current_ts;
-        balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        // RBAC: VaultConfig PDA + StateWriter role
+        let (mut vault_config, _) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, governance_authority, authorized_relayer_info, VaultRole::StateWriter,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::StateWrite)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let mut results = Vec::with_capacity(updates.len());
+        for (i, (update, infos)) in updates.iter().zip(entry_infos.chunks_exact(2)).enumerate() {
+            let (balance_pda_info, token_registry_info) = (&infos[0], &infos[1]);
+            let result = match Self::write_spot_token_balance(
+                program_id, governance_authority, balance_pda_info, token_registry_info, system_program, update, None, now,
+            ) {
+                Ok(result) => result,
+                Err(err) if mode == BatchMode::SkipInvalid => {
+                    msg!("BatchSpotTokenBalance[{}]: wallet={} idx={} token={} skipped: {:?}",
+                        i, update.user_wallet, update.account_index, update.token_index, err);
+                    results.push(StateWriteResult::Skipped);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            msg!("BatchSpotTokenBalance[{}]: wallet={} idx={} token={} sequence={} result={:?}",
+                i, update.user_wallet, update.account_index, update.token_index, update.sequence, result);
+            results.push(result);
+        }
+        set_return_data(&results.try_to_vec()?);
 
-        msg!("SpotTokenBalance: wallet={} idx={} token={} avail={} locked={}",
-            user_wallet, account_index, token_index, available_e6, locked_e6);
+        let count = |kind: StateWriteResult| results.iter().filter(|result| **result == kind).count();
+        msg!("BatchSpotTokenBalance: entries={} mode={:?} created={} updated={} stale={} skipped={}",
+            results.len(), mode, count(StateWriteResult::Created), count(StateWriteResult::Updated),
+            count(StateWriteResult::Stale), count(StateWriteResult::Skipped));
         Ok(())
     }
 
@@ -1532,7 +3021,7 @@ impl Processor {
         let account_info_iter = &mut accounts.iter();
         let governance_authority = next_account_info(account_info_iter)?;
         let vault_config_info = next_account_info(account_info_iter)?;
-        let _system_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
 
         assert_signer(governance_authority)?;
 
@@ -1542,49 +3031,2053 @@ impl Processor {
             return Err(VaultError::InvalidPda.into());
         }
 
-        let data = vault_config_info.data.borrow();
-        let current_len = data.len();
-
-        if current_len == VAULT_CONFIG_SIZE {
-            msg!("VaultConfig already migrated to V2 (505 bytes)");
+        if vault_config_info.data_len() == VAULT_CONFIG_SIZE {
+            msg!("VaultConfig already migrated to V3 ({} bytes)", VAULT_CONFIG_SIZE);
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if current_len != VAULT_CONFIG_SIZE_V1 {
-            msg!("VaultConfig unexpected size: {} (expected V1={})", current_len, VAULT_CONFIG_SIZE_V1);
-            return Err(ProgramError::InvalidAccountData);
+        // Layout splice (V1/V2 → current) lives in migration::VersionedAccount for VaultConfig
+        migration::migrate_account(vault_config_info, governance_authority, system_program, |config: &VaultConfig| {
+            Self::assert_migration_governance(config, governance_authority)
+        })?;
+        Ok(())
+    }
+
+    // =========================================================================
+    // Token Registry: Spot Token 上架/更新/禁用 (Governance Authority only)
+    // =========================================================================
+
+    /// 上架 Spot Token — 创建 TokenRegistryEntry PDA
+    ///
+    /// decimals 从 Mint 账户读取，token_program 取 Mint 的 owner，
+    /// vault_token_account 必须持有该 Mint 且 authority 为 VaultConfig PDA。
+    /// Mint 的风险标志 (authority / Token-2022 扩展) 必须全部被 allowed_risk_flags 覆盖。
+    fn process_add_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        token_index: u16,
+        allowed_risk_flags: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let vault_token_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(token_registry_info)?;
+
+        Self::load_vault_config_as_governance(program_id, vault_config_info, governance_authority)?;
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+
+        if token_index == 0 {
+            msg!("❌ USDC (token_index=0) is the quote asset and cannot be listed as a Spot token");
+            return Err(VaultError::QuoteAssetMustUseVaultPath.into());
         }
 
-        // Verify governance_authority matches stored governance_authority (bytes 8..40)
-        let stored_governance_authority = Pubkey::try_from(&data[8..40])
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        if &stored_governance_authority != governance_authority.key {
-            msg!("MigrateVaultConfig: governance_authority mismatch");
-            return Err(VaultError::UnauthorizedUser.into());
+        let (entry_pda, bump) = TokenRegistryEntry::derive_pda(program_id, token_index);
+        if token_registry_info.key != &entry_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !token_registry_info.data_is_empty() {
+            msg!("❌ token_index={} already registered", token_index);
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+
+        let token_program = *mint_info.owner;
+        let decimals = token_compat::get_mint_decimals(mint_info).map_err(|_| {
+            msg!("❌ {} is not an initialized SPL Token / Token-2022 mint", mint_info.key);
+            VaultError::InvalidAccount
+        })?;
+        Self::verify_vault_token_account_for_listing(program_id, vault_token_account, mint_info.key, &token_program, &vault_config_pda)?;
+
+        // Dangerous extensions / authorities require an explicit governance override
+        let mint_risk_flags = token_compat::get_mint_risk_flags(&mint_info.data.borrow());
+        let unapproved = mint_risk_flags & !allowed_risk_flags;
+        if unapproved != 0 {
+            msg!("❌ Mint {} risk flags 0b{:06b} not accepted (allowed 0b{:06b})",
+                mint_info.key, mint_risk_flags, allowed_risk_flags);
+            return Err(VaultError::UnsafeMint.into());
+        }
+
+        create_pda_account(
+            governance_authority,
+            token_registry_info,
+            system_program,
+            TOKEN_REGISTRY_ENTRY_SIZE,
+            program_id,
+            &[TOKEN_REGISTRY_SEED, &token_index.to_le_bytes(), &[bump]],
+        )?;
+
+        let entry = TokenRegistryEntry {
+            discriminator: TokenRegistryEntry::DISCRIMINATOR,
+            token_index,
+            mint: *mint_info.key,
+            decimals,
+            token_program,
+            vault_token_account: *vault_token_account.key,
+            is_enabled: true,
+            bump,
+            last_update_ts: solana_program::clock::Clock::get()?.unix_timestamp,
+            vault_pays_transfer_fee: false,
+            mint_risk_flags,
+            allowed_risk_flags,
+            limits: VaultLimits::default(),
+            total_liabilities_e6: 0,
+            reserved: [0u8; 13],
+        };
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+
+        msg!("✅ AddToken: token_index={} mint={} decimals={} vault={} risk_flags=0b{:06b}",
+            token_index, entry.mint, decimals, entry.vault_token_account, mint_risk_flags);
+        Ok(())
+    }
+
+    /// 更新 Spot Token 的 vault_token_account (mint/decimals/token_program 不可变)
+    fn process_update_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        token_index: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
+        let vault_token_account = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(token_registry_info)?;
+
+        Self::load_vault_config_as_governance(program_id, vault_config_info, governance_authority)?;
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        Self::verify_vault_token_account_for_listing(program_id, vault_token_account, &entry.mint, &entry.token_program, &vault_config_pda)?;
+
+        let old_vault_token_account = entry.vault_token_account;
+        entry.vault_token_account = *vault_token_account.key;
+        entry.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+
+        msg!("✅ UpdateToken: token_index={} vault {} → {}",
+            token_index, old_vault_token_account, entry.vault_token_account);
+        Ok(())
+    }
+
+    /// 禁用/启用 Spot Token (禁用后拒绝入金，出金仍然开放)
+    fn process_disable_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        token_index: u16,
+        disabled: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(token_registry_info)?;
+
+        Self::load_vault_config_as_governance(program_id, vault_config_info, governance_authority)?;
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        entry.is_enabled = !disabled;
+        entry.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+
+        msg!("Token {} {}", token_index, if disabled { "disabled" } else { "enabled" });
+        Ok(())
+    }
+
+    /// 设置 Token-2022 转账手续费由谁承担 (仅影响出金；入金始终按实际到账入账)
+    fn process_set_token_fee_policy(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        token_index: u16,
+        vault_pays_transfer_fee: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+
+        let mut vault_config = Self::load_vault_config_as_governance(program_id, vault_config_info, governance_authority)?;
+
+        if token_index == 0 {
+            assert_writable(vault_config_info)?;
+            vault_config.usdc_vault_pays_transfer_fee = vault_pays_transfer_fee;
+            store_vault_config(vault_config_info, &vault_config)?;
+        } else {
+            let token_registry_info = next_account_info(account_info_iter)?;
+            assert_writable(token_registry_info)?;
+            let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+            entry.vault_pays_transfer_fee = vault_pays_transfer_fee;
+            entry.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+        }
+
+        msg!("Token {} transfer fee paid by {}", token_index, if vault_pays_transfer_fee { "vault" } else { "user" });
+        Ok(())
+    }
+
+    /// 创建 Token Vault PDA ["token_vault", mint] (Governance Authority only)
+    ///
+    /// 迁移: 若 mint 已有旧的 vault token account (VaultConfig.vault_token_account
+    /// 或 TokenRegistryEntry.vault_token_account)，须传入该账户，余额会被全部转入
+    /// 新的 Token Vault，并把配置指向新账户。
+    fn process_initialize_token_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let token_vault_info = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let legacy_vault_info = next_account_info(account_info_iter).ok();
+        let token_registry_info = next_account_info(account_info_iter).ok();
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+        assert_writable(token_vault_info)?;
+
+        let (vault_config_pda, vault_config_bump) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let is_usdc = *mint_info.key == vault_config.usdc_mint;
+        let mut entry = match token_registry_info {
+            Some(info) if !is_usdc => {
+                assert_writable(info)?;
+                deserialize_checked(&info.data.borrow(), TokenRegistryEntry::DISCRIMINATOR)?;
+                let entry = deserialize_account::<TokenRegistryEntry>(&info.data.borrow())?;
+                if info.owner != program_id || info.key != &TokenRegistryEntry::derive_pda(program_id, entry.token_index).0 {
+                    return Err(VaultError::InvalidPda.into());
+                }
+                if entry.mint != *mint_info.key {
+                    msg!("❌ TokenRegistryEntry mint {} != {}", entry.mint, mint_info.key);
+                    return Err(VaultError::InvalidAccount.into());
+                }
+                Some((info, entry))
+            }
+            _ => None,
+        };
+
+        // The vault account currently configured for this mint (if any)
+        let configured = if is_usdc {
+            Some(vault_config.vault_token_account)
+        } else {
+            entry.as_ref().map(|(_, e)| e.vault_token_account)
+        };
+        let repoint = configured.filter(|key| key != token_vault_info.key);
+
+        // 已存在的 PDA vault (例如上次调用漏传了 TokenRegistryEntry) 只允许继续迁移，
+        // 否则为重复创建
+        if token_vault_info.data_is_empty() {
+            Self::create_token_vault(
+                program_id, governance_authority, token_vault_info, mint_info, token_program, system_program, &vault_config_pda,
+            )?;
+        } else {
+            if repoint.is_none() {
+                msg!("❌ Token vault for mint {} already exists", mint_info.key);
+                return Err(VaultError::AlreadyInitialized.into());
+            }
+            Self::verify_token_vault(token_vault_info, mint_info.key, program_id)?;
+            let (vault_mint, vault_owner) =
+                token_compat::get_token_account_mint_and_owner(token_vault_info).map_err(|_| VaultError::InvalidAccount)?;
+            if token_vault_info.owner != token_program.key || vault_mint != *mint_info.key || vault_owner != vault_config_pda {
+                msg!("❌ Existing token vault {} is not a {} account of mint {} owned by the VaultConfig PDA", token_vault_info.key, token_program.key, mint_info.key);
+                return Err(VaultError::InvalidAccount.into());
+            }
+        }
+
+        if let Some(configured) = repoint {
+            let Some(legacy_vault_info) = legacy_vault_info else {
+                msg!("❌ Legacy vault {} must be passed to be swept into the new token vault", configured);
+                return Err(VaultError::InvalidAccount.into());
+            };
+            if legacy_vault_info.key != &configured {
+                msg!("❌ Legacy vault mismatch: expected {}, got {}", configured, legacy_vault_info.key);
+                return Err(VaultError::InvalidAccount.into());
+            }
+            let balance = token_compat::get_token_account_amount(legacy_vault_info).map_err(|_| VaultError::InvalidAccount)?;
+            if balance > 0 {
+                let decimals = token_compat::get_mint_decimals(mint_info).map_err(|_| VaultError::InvalidAccount)?;
+                let received = Self::transfer_tokens(
+                    token_program, legacy_vault_info, token_vault_info, vault_config_info,
+                    Some((mint_info, decimals)), true, balance,
+                    Some(&[b"vault_config", &[vault_config_bump]]),
+                )?;
+                msg!("Swept {} from legacy vault {} (received {})", balance, legacy_vault_info.key, received);
+            }
+        }
+
+        if is_usdc {
+            vault_config.vault_token_account = *token_vault_info.key;
+            store_vault_config(vault_config_info, &vault_config)?;
+        } else if let Some((info, entry)) = entry.as_mut() {
+            entry.vault_token_account = *token_vault_info.key;
+            entry.last_update_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+            entry.serialize(&mut &mut info.data.borrow_mut()[..])?;
+        }
+
+        msg!("✅ InitializeTokenVault: mint={} vault={}", mint_info.key, token_vault_info.key);
+        Ok(())
+    }
+
+    // =========================================================================
+    // Governance Multisig: M-of-N 治理提案
+    // =========================================================================
+
+    /// 校验多签成员/阈值参数，返回定长成员数组
+    fn validate_governance_set_params(
+        members: &[Pubkey],
+        threshold: u8,
+        proposal_ttl_secs: i64,
+    ) -> Result<[Pubkey; MAX_GOVERNANCE_MEMBERS], ProgramError> {
+        if members.is_empty() || members.len() > MAX_GOVERNANCE_MEMBERS {
+            msg!("❌ Governance set needs 1..={} members, got {}", MAX_GOVERNANCE_MEMBERS, members.len());
+            return Err(VaultError::InvalidGovernanceAction.into());
+        }
+        if threshold == 0 || threshold as usize > members.len() {
+            msg!("❌ Threshold {} out of range 1..={}", threshold, members.len());
+            return Err(VaultError::InvalidGovernanceAction.into());
+        }
+        if proposal_ttl_secs <= 0 {
+            msg!("❌ proposal_ttl_secs must be positive");
+            return Err(VaultError::InvalidGovernanceAction.into());
+        }
+
+        let mut slots = [Pubkey::default(); MAX_GOVERNANCE_MEMBERS];
+        for (i, member) in members.iter().enumerate() {
+            if *member == Pubkey::default() || members[..i].contains(member) {
+                msg!("❌ Invalid or duplicate governance member: {}", member);
+                return Err(VaultError::InvalidGovernanceAction.into());
+            }
+            slots[i] = *member;
+        }
+        Ok(slots)
+    }
+
+    fn load_governance_set(program_id: &Pubkey, governance_set_info: &AccountInfo) -> Result<GovernanceSet, ProgramError> {
+        let (governance_set_pda, _) = GovernanceSet::derive_pda(program_id);
+        if governance_set_info.key != &governance_set_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let data = governance_set_info.data.borrow();
+        deserialize_checked(&data, GovernanceSet::DISCRIMINATOR)?;
+        Ok(deserialize_account::<GovernanceSet>(&data)?)
+    }
+
+    /// 加载提案并检查仍可批准/执行 (未执行、未过期、成员集未变更)
+    fn load_open_governance_proposal(
+        program_id: &Pubkey,
+        proposal_info: &AccountInfo,
+        proposal_id: u64,
+        governance_set: &GovernanceSet,
+    ) -> Result<GovernanceProposal, ProgramError> {
+        let (proposal_pda, _) = GovernanceProposal::derive_pda(program_id, proposal_id);
+        if proposal_info.key != &proposal_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let proposal = {
+            let data = proposal_info.data.borrow();
+            deserialize_checked(&data, GovernanceProposal::DISCRIMINATOR)?;
+            deserialize_account::<GovernanceProposal>(&data)?
+        };
+
+        if proposal.executed {
+            return Err(VaultError::GovernanceProposalExecuted.into());
+        }
+        if proposal.set_version != governance_set.set_version {
+            msg!("❌ Proposal {} predates governance set version {}", proposal_id, governance_set.set_version);
+            return Err(VaultError::GovernanceProposalExpired.into());
         }
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        if now > proposal.expires_ts {
+            msg!("❌ Proposal {} expired at {}", proposal_id, proposal.expires_ts);
+            return Err(VaultError::GovernanceProposalExpired.into());
+        }
+        Ok(proposal)
+    }
+
+    /// sha256 over the ordered account metas a proposal / timelock action is executed with:
+    /// key (32) + is_signer (1) + is_writable (1) per account, so execution cannot
+    /// change account privileges either
+    fn governance_accounts_hash(accounts: &[AccountInfo]) -> [u8; 32] {
+        let metas: Vec<[u8; 34]> = accounts
+            .iter()
+            .map(|account| {
+                let mut meta = [0u8; 34];
+                meta[..32].copy_from_slice(account.key.as_ref());
+                meta[32] = account.is_signer as u8;
+                meta[33] = account.is_writable as u8;
+                meta
+            })
+            .collect();
+        let slices: Vec<&[u8]> = metas.iter().map(|meta| meta.as_slice()).collect();
+        hashv(&slices).to_bytes()
+    }
+
+    /// 解码提案 / Timelock 中的指令，只接受治理指令
+    fn decode_governance_action(instruction_data: &[u8]) -> Result<VaultInstruction, ProgramError> {
+        if instruction_data.len() > MAX_PROPOSAL_INSTRUCTION_LEN {
+            msg!("❌ Governance instruction too long: {} > {}", instruction_data.len(), MAX_PROPOSAL_INSTRUCTION_LEN);
+            return Err(VaultError::InvalidGovernanceAction.into());
+        }
+        let action = VaultInstruction::try_from_slice(instruction_data)
+            .map_err(|_| VaultError::InvalidGovernanceAction)?;
+        if !action.is_governance_action() {
+            msg!("❌ {:?} is not a governance instruction", action);
+            return Err(VaultError::InvalidGovernanceAction.into());
+        }
+        Ok(action)
+    }
 
-        // Parse V1 data by byte offsets:
-        // [0..424]   = disc + governance_authority + usdc_mint + vault_token_account + authorized_callers
-        // [424..456] = ledger_program (SKIP)
-        // [456..488] = fund_program (SKIP)
-        // [488..569] = delegation_program(32) + total_deposits(8) + total_locked(8) + is_paused(1) + reserved(32)
-        let prefix = data[0..424].to_vec();     // 424 bytes (before ledger_program)
-        let suffix = data[488..569].to_vec();   // 81 bytes (delegation_program onward)
-        drop(data);
+    /// Self-CPI: 以治理 signer PDA (multisig / timelock) 签名执行存储的治理指令
+    fn invoke_governance_action(
+        program_id: &Pubkey,
+        action_accounts: &[AccountInfo],
+        instruction_data: &[u8],
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let signer_pda = Pubkey::create_program_address(signer_seeds, program_id)
+            .map_err(|_| VaultError::InvalidPda)?;
+        let instruction = Instruction {
+            program_id: *program_id,
+            accounts: action_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: account.is_signer || account.key == &signer_pda,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: instruction_data.to_vec(),
+        };
+        invoke_signed(&instruction, action_accounts, &[signer_seeds])
+    }
 
-        // Compact: prefix(424) + suffix(81) = 505 bytes
-        let mut new_data = Vec::with_capacity(VAULT_CONFIG_SIZE);
-        new_data.extend_from_slice(&prefix);
-        new_data.extend_from_slice(&suffix);
-        assert_eq!(new_data.len(), VAULT_CONFIG_SIZE);
+    /// 启用多签治理: 创建 GovernanceSet，governance_authority 移交给 governance signer PDA
+    fn process_initialize_governance_set(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        members: Vec<Pubkey>,
+        threshold: u8,
+        proposal_ttl_secs: i64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let governance_set_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
 
-        // Realloc the account to 505 bytes
-        vault_config_info.realloc(VAULT_CONFIG_SIZE, false)?;
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+        assert_writable(governance_set_info)?;
 
-        // Write the compacted data
-        vault_config_info.data.borrow_mut()[..VAULT_CONFIG_SIZE].copy_from_slice(&new_data);
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
 
-        msg!("MigrateVaultConfig: success (569 → 505 bytes)");
+        let (governance_set_pda, bump) = GovernanceSet::derive_pda(program_id);
+        if governance_set_info.key != &governance_set_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !governance_set_info.data_is_empty() {
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+
+        let member_slots = Self::validate_governance_set_params(&members, threshold, proposal_ttl_secs)?;
+        let (signer_pda, signer_bump) = GovernanceSet::derive_signer_pda(program_id);
+
+        create_pda_account(
+            governance_authority,
+            governance_set_info,
+            system_program,
+            GOVERNANCE_SET_SIZE,
+            program_id,
+            &[GOVERNANCE_SET_SEED, &[bump]],
+        )?;
+
+        let governance_set = GovernanceSet {
+            discriminator: GovernanceSet::DISCRIMINATOR,
+            members: member_slots,
+            member_count: members.len() as u8,
+            threshold,
+            set_version: 0,
+            proposal_ttl_secs,
+            next_proposal_id: 0,
+            signer_bump,
+            bump,
+            reserved: [0u8; 64],
+        };
+        governance_set.serialize(&mut &mut governance_set_info.data.borrow_mut()[..])?;
+
+        // The signer PDA is deterministic, so the handover needs no acceptance step
+        vault_config.governance_authority = signer_pda;
+        vault_config.pending_governance_authority = Pubkey::default();
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "GovernanceSetInitialized: previous={} signer={} members={} threshold={}",
+            governance_authority.key, signer_pda, members.len(), threshold
+        );
+        Ok(())
+    }
+
+    /// 创建治理提案 (成员)，提案人自动计入一票
+    fn process_create_governance_proposal(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: Vec<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let proposer = next_account_info(account_info_iter)?;
+        let governance_set_info = next_account_info(account_info_iter)?;
+        let proposal_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let action_accounts = account_info_iter.as_slice();
+
+        assert_signer(proposer)?;
+        assert_writable(governance_set_info)?;
+        assert_writable(proposal_info)?;
+
+        let mut governance_set = Self::load_governance_set(program_id, governance_set_info)?;
+        let member_index = governance_set
+            .member_index(proposer.key)
+            .ok_or(VaultError::NotGovernanceMember)?;
+
+        let action = Self::decode_governance_action(&instruction_data)?;
+
+        let proposal_id = governance_set.next_proposal_id;
+        let (proposal_pda, bump) = GovernanceProposal::derive_pda(program_id, proposal_id);
+        if proposal_info.key != &proposal_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        create_pda_account(
+            proposer,
+            proposal_info,
+            system_program,
+            GOVERNANCE_PROPOSAL_SIZE,
+            program_id,
+            &[GOVERNANCE_PROPOSAL_SEED, &proposal_id.to_le_bytes(), &[bump]],
+        )?;
+
+        let created_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        let mut stored_instruction = [0u8; MAX_PROPOSAL_INSTRUCTION_LEN];
+        stored_instruction[..instruction_data.len()].copy_from_slice(&instruction_data);
+
+        let proposal = GovernanceProposal {
+            discriminator: GovernanceProposal::DISCRIMINATOR,
+            proposal_id,
+            proposer: *proposer.key,
+            set_version: governance_set.set_version,
+            approvals: 1 << member_index,
+            created_ts,
+            expires_ts: checked_add(created_ts, governance_set.proposal_ttl_secs)?,
+            executed: false,
+            bump,
+            accounts_hash: Self::governance_accounts_hash(action_accounts),
+            instruction_len: instruction_data.len() as u16,
+            instruction_data: stored_instruction,
+            reserved: [0u8; 32],
+        };
+        proposal.serialize(&mut &mut proposal_info.data.borrow_mut()[..])?;
+
+        governance_set.next_proposal_id = proposal_id.checked_add(1).ok_or(VaultError::Overflow)?;
+        governance_set.serialize(&mut &mut governance_set_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "GovernanceProposalCreated: id={} proposer={} expires_ts={} action={:?}",
+            proposal_id, proposer.key, proposal.expires_ts, action
+        );
+        Ok(())
+    }
+
+    /// 批准治理提案 (成员)
+    fn process_approve_governance_proposal(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        proposal_id: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let member = next_account_info(account_info_iter)?;
+        let governance_set_info = next_account_info(account_info_iter)?;
+        let proposal_info = next_account_info(account_info_iter)?;
+
+        assert_signer(member)?;
+        assert_writable(proposal_info)?;
+
+        let governance_set = Self::load_governance_set(program_id, governance_set_info)?;
+        let member_index = governance_set
+            .member_index(member.key)
+            .ok_or(VaultError::NotGovernanceMember)?;
+        let mut proposal = Self::load_open_governance_proposal(program_id, proposal_info, proposal_id, &governance_set)?;
+
+        let bit = 1u16 << member_index;
+        if proposal.approvals & bit != 0 {
+            msg!("Member {} already approved proposal {}", member.key, proposal_id);
+            return Ok(());
+        }
+        proposal.approvals |= bit;
+        proposal.serialize(&mut &mut proposal_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "GovernanceProposalApproved: id={} member={} approvals={}/{}",
+            proposal_id, member.key, governance_set.approval_count(proposal.approvals), governance_set.threshold
+        );
+        Ok(())
+    }
+
+    /// 执行治理提案 (成员): 以 governance signer PDA 签名 self-CPI 调用存储的指令
+    fn process_execute_governance_proposal(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        proposal_id: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let member = next_account_info(account_info_iter)?;
+        let governance_set_info = next_account_info(account_info_iter)?;
+        let proposal_info = next_account_info(account_info_iter)?;
+        let vault_program = next_account_info(account_info_iter)?;
+        let action_accounts = account_info_iter.as_slice();
+
+        assert_signer(member)?;
+        assert_writable(proposal_info)?;
+        if vault_program.key != program_id {
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let governance_set = Self::load_governance_set(program_id, governance_set_info)?;
+        if governance_set.member_index(member.key).is_none() {
+            return Err(VaultError::NotGovernanceMember.into());
+        }
+        let mut proposal = Self::load_open_governance_proposal(program_id, proposal_info, proposal_id, &governance_set)?;
+
+        let approvals = governance_set.approval_count(proposal.approvals);
+        if approvals < governance_set.threshold {
+            msg!("❌ Proposal {} has {}/{} approvals", proposal_id, approvals, governance_set.threshold);
+            return Err(VaultError::GovernanceThresholdNotMet.into());
+        }
+        if Self::governance_accounts_hash(action_accounts) != proposal.accounts_hash {
+            msg!("❌ Accounts do not match those pinned in proposal {}", proposal_id);
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        // Mark executed before the CPI so the proposal cannot be replayed from within it
+        proposal.executed = true;
+        proposal.serialize(&mut &mut proposal_info.data.borrow_mut()[..])?;
+
+        Self::invoke_governance_action(
+            program_id,
+            action_accounts,
+            proposal.instruction(),
+            &[GOVERNANCE_SIGNER_SEED, &[governance_set.signer_bump]],
+        )?;
+
+        msg!(
+            "GovernanceProposalExecuted: id={} executor={} approvals={}/{}",
+            proposal_id, member.key, approvals, governance_set.threshold
+        );
+        Ok(())
+    }
+
+    /// 更新多签成员/阈值 (仅 governance signer PDA，即通过提案执行)
+    fn process_update_governance_set(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        members: Vec<Pubkey>,
+        threshold: u8,
+        proposal_ttl_secs: i64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let governance_set_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(governance_set_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let mut governance_set = Self::load_governance_set(program_id, governance_set_info)?;
+        governance_set.members = Self::validate_governance_set_params(&members, threshold, proposal_ttl_secs)?;
+        governance_set.member_count = members.len() as u8;
+        governance_set.threshold = threshold;
+        governance_set.proposal_ttl_secs = proposal_ttl_secs;
+        // Void approvals collected under the previous membership
+        governance_set.set_version = governance_set.set_version.wrapping_add(1);
+        governance_set.serialize(&mut &mut governance_set_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "GovernanceSetUpdated: version={} members={} threshold={} ttl={}s",
+            governance_set.set_version, members.len(), threshold, proposal_ttl_secs
+        );
+        Ok(())
+    }
+
+    // =========================================================================
+    // Timelock: 敏感治理操作延迟执行
+    // =========================================================================
+
+    fn validate_timelock_delays(min_delay_secs: &[i64; TIMELOCK_ACTION_TYPE_COUNT]) -> ProgramResult {
+        if let Some(delay) = min_delay_secs.iter().find(|d| !(0..=MAX_TIMELOCK_DELAY_SECS).contains(*d)) {
+            msg!("❌ Timelock delay {}s out of range 0..={}", delay, MAX_TIMELOCK_DELAY_SECS);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    fn load_timelock_config(program_id: &Pubkey, timelock_config_info: &AccountInfo) -> Result<TimelockConfig, ProgramError> {
+        let (timelock_config_pda, _) = TimelockConfig::derive_pda(program_id);
+        if timelock_config_info.key != &timelock_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let data = timelock_config_info.data.borrow();
+        deserialize_checked(&data, TimelockConfig::DISCRIMINATOR)?;
+        Ok(deserialize_account::<TimelockConfig>(&data)?)
+    }
+
+    fn timelock_signer_pda(program_id: &Pubkey, timelock_config: &TimelockConfig) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(&[TIMELOCK_SIGNER_SEED, &[timelock_config.signer_bump]], program_id)
+            .map_err(|_| VaultError::InvalidPda.into())
+    }
+
+    /// 加载仍在队列中的操作 (未执行、未取消)
+    fn load_queued_timelock_action(
+        program_id: &Pubkey,
+        timelock_action_info: &AccountInfo,
+        action_id: u64,
+    ) -> Result<TimelockAction, ProgramError> {
+        let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+        if timelock_action_info.key != &timelock_action_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let timelock_action = {
+            let data = timelock_action_info.data.borrow();
+            deserialize_checked(&data, TimelockAction::DISCRIMINATOR)?;
+            deserialize_account::<TimelockAction>(&data)?
+        };
+        if timelock_action.executed || timelock_action.cancelled {
+            msg!("❌ Timelock action {} already {}", action_id,
+                if timelock_action.executed { "executed" } else { "cancelled" });
+            return Err(VaultError::TimelockActionClosed.into());
+        }
+        Ok(timelock_action)
+    }
+
+    /// 启用 Timelock: 当前治理权限成为 admin，governance_authority 移交给 timelock signer PDA
+    fn process_initialize_timelock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+        assert_writable(timelock_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let (timelock_config_pda, bump) = TimelockConfig::derive_pda(program_id);
+        if timelock_config_info.key != &timelock_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !timelock_config_info.data_is_empty() {
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+        Self::validate_timelock_delays(&min_delay_secs)?;
+        let (signer_pda, signer_bump) = TimelockConfig::derive_signer_pda(program_id);
+
+        create_pda_account(
+            governance_authority,
+            timelock_config_info,
+            system_program,
+            TIMELOCK_CONFIG_SIZE,
+            program_id,
+            &[TIMELOCK_CONFIG_SEED, &[bump]],
+        )?;
+
+        let timelock_config = TimelockConfig {
+            discriminator: TimelockConfig::DISCRIMINATOR,
+            admin: *governance_authority.key,
+            min_delay_secs,
+            next_action_id: 0,
+            signer_bump,
+            bump,
+            reserved: [0u8; 64],
+        };
+        timelock_config.serialize(&mut &mut timelock_config_info.data.borrow_mut()[..])?;
+
+        vault_config.governance_authority = signer_pda;
+        vault_config.pending_governance_authority = Pubkey::default();
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "TimelockInitialized: admin={} signer={} min_delay_secs={:?}",
+            governance_authority.key, signer_pda, min_delay_secs
+        );
+        Ok(())
+    }
+
+    /// 排队治理操作 (Timelock admin)
+    fn process_queue_timelock_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: Vec<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter)?;
+        let timelock_action_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let action_accounts = account_info_iter.as_slice();
+
+        assert_signer(admin)?;
+        assert_writable(timelock_config_info)?;
+        assert_writable(timelock_action_info)?;
+
+        let mut timelock_config = Self::load_timelock_config(program_id, timelock_config_info)?;
+        if timelock_config.admin != *admin.key {
+            return Err(VaultError::InvalidTimelockAdmin.into());
+        }
+
+        let action = Self::decode_governance_action(&instruction_data)?;
+        let action_type = action.timelock_action_type().ok_or(VaultError::InvalidGovernanceAction)?;
+
+        let action_id = timelock_config.next_action_id;
+        let (timelock_action_pda, bump) = TimelockAction::derive_pda(program_id, action_id);
+        if timelock_action_info.key != &timelock_action_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        create_pda_account(
+            admin,
+            timelock_action_info,
+            system_program,
+            TIMELOCK_ACTION_SIZE,
+            program_id,
+            &[TIMELOCK_ACTION_SEED, &action_id.to_le_bytes(), &[bump]],
+        )?;
+
+        let queued_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        let mut stored_instruction = [0u8; MAX_PROPOSAL_INSTRUCTION_LEN];
+        stored_instruction[..instruction_data.len()].copy_from_slice(&instruction_data);
+
+        let timelock_action = TimelockAction {
+            discriminator: TimelockAction::DISCRIMINATOR,
+            action_id,
+            action_type: action_type as u8,
+            queued_by: *admin.key,
+            queued_ts,
+            eta: checked_add(queued_ts, timelock_config.min_delay(action_type))?,
+            executed: false,
+            cancelled: false,
+            bump,
+            accounts_hash: Self::governance_accounts_hash(action_accounts),
+            instruction_len: instruction_data.len() as u16,
+            instruction_data: stored_instruction,
+            reserved: [0u8; 32],
+        };
+        timelock_action.serialize(&mut &mut timelock_action_info.data.borrow_mut()[..])?;
+
+        timelock_config.next_action_id = action_id.checked_add(1).ok_or(VaultError::Overflow)?;
+        timelock_config.serialize(&mut &mut timelock_config_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "TimelockActionQueued: id={} type={:?} eta={} action={:?}",
+            action_id, action_type, timelock_action.eta, action
+        );
+        Ok(())
+    }
+
+    /// 执行到期的治理操作 (permissionless): 以 timelock signer PDA 签名 self-CPI
+    fn process_execute_timelock_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        action_id: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let timelock_config_info = next_account_info(account_info_iter)?;
+        let timelock_action_info = next_account_info(account_info_iter)?;
+        let vault_program = next_account_info(account_info_iter)?;
+        let action_accounts = account_info_iter.as_slice();
+
+        assert_writable(timelock_action_info)?;
+        if vault_program.key != program_id {
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let timelock_config = Self::load_timelock_config(program_id, timelock_config_info)?;
+        let mut timelock_action = Self::load_queued_timelock_action(program_id, timelock_action_info, action_id)?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        if now < timelock_action.eta {
+            msg!("❌ Timelock action {} not executable before {} (now {})", action_id, timelock_action.eta, now);
+            return Err(VaultError::TimelockNotReady.into());
+        }
+        if !timelock_action.is_executable_at(now) {
+            msg!("❌ Timelock action {} grace period ended (eta {})", action_id, timelock_action.eta);
+            return Err(VaultError::TimelockActionStale.into());
+        }
+        if Self::governance_accounts_hash(action_accounts) != timelock_action.accounts_hash {
+            msg!("❌ Accounts do not match those pinned in timelock action {}", action_id);
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        timelock_action.executed = true;
+        timelock_action.serialize(&mut &mut timelock_action_info.data.borrow_mut()[..])?;
+
+        Self::invoke_governance_action(
+            program_id,
+            action_accounts,
+            timelock_action.instruction(),
+            &[TIMELOCK_SIGNER_SEED, &[timelock_config.signer_bump]],
+        )?;
+
+        msg!("TimelockActionExecuted: id={} eta={} executed_ts={}", action_id, timelock_action.eta, now);
+        Ok(())
+    }
+
+    /// 取消排队的治理操作 (Timelock admin)
+    ///
+    /// Guardian 取消需记录原因码，走 GuardianCancelTimelockAction。
+    fn process_cancel_timelock_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        action_id: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let canceller = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter)?;
+        let timelock_action_info = next_account_info(account_info_iter)?;
+
+        assert_signer(canceller)?;
+        assert_writable(timelock_action_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        let timelock_config = Self::load_timelock_config(program_id, timelock_config_info)?;
+
+        if timelock_config.admin != *canceller.key {
+            if vault_config.is_guardian(canceller.key) {
+                msg!("❌ Guardians cancel with GuardianCancelTimelockAction (reason code required)");
+            }
+            return Err(VaultError::InvalidTimelockAdmin.into());
+        }
+
+        let mut timelock_action = Self::load_queued_timelock_action(program_id, timelock_action_info, action_id)?;
+        timelock_action.cancelled = true;
+        timelock_action.serialize(&mut &mut timelock_action_info.data.borrow_mut()[..])?;
+
+        msg!("TimelockActionCancelled: id={} by={} (admin)", action_id, canceller.key);
+        Ok(())
+    }
+
+    /// 更新 Timelock admin / 最小延迟 (governance_authority，即经由 Timelock 执行)
+    fn process_update_timelock_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        admin: Pubkey,
+        min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let timelock_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(timelock_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        if admin == Pubkey::default() {
+            msg!("❌ Timelock admin must not be the default pubkey");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Self::validate_timelock_delays(&min_delay_secs)?;
+
+        let mut timelock_config = Self::load_timelock_config(program_id, timelock_config_info)?;
+        timelock_config.admin = admin;
+        timelock_config.min_delay_secs = min_delay_secs;
+        timelock_config.serialize(&mut &mut timelock_config_info.data.borrow_mut()[..])?;
+
+        msg!("TimelockConfigUpdated: admin={} min_delay_secs={:?}", admin, min_delay_secs);
+        Ok(())
+    }
+
+    /// 添加 / 移除 Guardian (Governance Authority)
+    fn process_update_guardians(program_id: &Pubkey, accounts: &[AccountInfo], guardian: Pubkey, add: bool) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        if add {
+            if guardian == Pubkey::default() {
+                msg!("❌ Guardian must not be the default pubkey");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !vault_config.add_guardian(guardian) {
+                msg!("❌ All {} guardian slots are in use", MAX_GUARDIANS);
+                return Err(VaultError::GuardianSetFull.into());
+            }
+        } else if !vault_config.remove_guardian(&guardian) {
+            return Err(VaultError::NotGuardian.into());
+        }
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("{}: guardian={}", if add { "GuardianAdded" } else { "GuardianRemoved" }, guardian);
+        Ok(())
+    }
+
+    /// Guardian 校验 + 记录操作 (原因码、时间戳) 到 VaultConfig.guardian_actions
+    fn record_guardian_action(
+        program_id: &Pubkey,
+        guardian: &AccountInfo,
+        vault_config_info: &AccountInfo,
+        action: GuardianAction,
+        reason_code: u16,
+        target: u64,
+        apply: impl FnOnce(&mut VaultConfig),
+    ) -> Result<i64, ProgramError> {
+        assert_signer(guardian)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if !vault_config.is_guardian(guardian.key) {
+            return Err(VaultError::NotGuardian.into());
+        }
+
+        let timestamp = solana_program::clock::Clock::get()?.unix_timestamp;
+        apply(&mut vault_config);
+        vault_config.record_guardian_action(GuardianActionRecord {
+            guardian: *guardian.key,
+            action,
+            reason_code,
+            target,
+            timestamp,
+        });
+        store_vault_config(vault_config_info, &vault_config)?;
+        Ok(timestamp)
+    }
+
+    /// Guardian 暂停: 只能置位 pause_flags，恢复需 governance (SetPauseFlag)
+    fn process_guardian_pause(program_id: &Pubkey, accounts: &[AccountInfo], pause_flags: u16, reason_code: u16) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let guardian = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        if pause_flags == 0 || pause_flags & !ALL_PAUSE_FLAGS != 0 {
+            msg!("❌ Invalid pause flags 0b{:016b}", pause_flags);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let timestamp = Self::record_guardian_action(
+            program_id, guardian, vault_config_info, GuardianAction::Pause, reason_code, pause_flags as u64,
+            |vault_config| vault_config.pause_flags |= pause_flags,
+        )?;
+
+        msg!("GuardianPaused: guardian={} pause_flags=0b{:08b} reason_code={} ts={}", guardian.key, pause_flags, reason_code, timestamp);
+        Ok(())
+    }
+
+    /// Guardian 取消排队的治理操作
+    fn process_guardian_cancel_timelock_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        action_id: u64,
+        reason_code: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let guardian = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let timelock_action_info = next_account_info(account_info_iter)?;
+
+        assert_writable(timelock_action_info)?;
+        let timestamp = Self::record_guardian_action(
+            program_id, guardian, vault_config_info, GuardianAction::CancelTimelockAction, reason_code, action_id,
+            |_| {},
+        )?;
+
+        let mut timelock_action = Self::load_queued_timelock_action(program_id, timelock_action_info, action_id)?;
+        timelock_action.cancelled = true;
+        timelock_action.serialize(&mut &mut timelock_action_info.data.borrow_mut()[..])?;
+
+        msg!("TimelockActionCancelled: id={} by={} (guardian) reason_code={} ts={}", action_id, guardian.key, reason_code, timestamp);
+        Ok(())
+    }
+
+    // =========================================================================
+    // Relayer Registry: AuthorizedRelayer PDA (Governance Authority only)
+    // =========================================================================
+
+    fn relayer_label(label: &[u8; RELAYER_LABEL_LEN]) -> String {
+        String::from_utf8_lossy(label).trim_end_matches('\0').to_string()
+    }
+
+    fn validate_relayer_params(vault_config: &VaultConfig, relayer: &Pubkey, roles: u8) -> ProgramResult {
+        if *relayer == Pubkey::default() || *relayer == vault_config.governance_authority {
+            msg!("❌ Cannot register {} as a relayer", relayer);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if roles & !ALL_VAULT_ROLES != 0 {
+            msg!("❌ Unknown role bits 0b{:08b}", roles);
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// 创建 AuthorizedRelayer PDA (rent 由 payer 支付)
+    #[allow(clippy::too_many_arguments)]
+    fn create_authorized_relayer<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        authorized_relayer_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        relayer: Pubkey,
+        label: [u8; RELAYER_LABEL_LEN],
+        roles: u8,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+    ) -> Result<AuthorizedRelayer, ProgramError> {
+        let (authorized_relayer_pda, bump) = AuthorizedRelayer::derive_pda(program_id, &relayer);
+        if authorized_relayer_info.key != &authorized_relayer_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !authorized_relayer_info.data_is_empty() {
+            msg!("❌ Relayer {} already registered", relayer);
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+
+        create_pda_account(
+            payer,
+            authorized_relayer_info,
+            system_program,
+            AUTHORIZED_RELAYER_SIZE,
+            program_id,
+            &[AUTHORIZED_RELAYER_SEED, relayer.as_ref(), &[bump]],
+        )?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let authorized_relayer = AuthorizedRelayer {
+            discriminator: AuthorizedRelayer::DISCRIMINATOR,
+            relayer,
+            label,
+            roles,
+            enabled: true,
+            created_ts: now,
+            updated_ts: now,
+            max_deposit_per_tx_e6,
+            max_withdraw_per_tx_e6,
+            bump,
+            rate_limited: false,
+            reserved: [0u8; 63],
+        };
+        authorized_relayer.serialize(&mut &mut authorized_relayer_info.data.borrow_mut()[..])?;
+        Ok(authorized_relayer)
+    }
+
+    /// 注册 Relayer: 创建 AuthorizedRelayer PDA
+    #[allow(clippy::too_many_arguments)]
+    fn process_register_relayer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        relayer: Pubkey,
+        label: [u8; RELAYER_LABEL_LEN],
+        roles: u8,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let authorized_relayer_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+        assert_writable(authorized_relayer_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        Self::validate_relayer_params(&vault_config, &relayer, roles)?;
+        // A key must not hold roles in both places, or disabling its PDA would leave the slot roles
+        if vault_config.is_authorized_caller(&relayer) {
+            msg!("❌ {} is still in authorized_callers; run MigrateAuthorizedCallers", relayer);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Self::create_authorized_relayer(
+            program_id, governance_authority, authorized_relayer_info, system_program,
+            relayer, label, roles, max_deposit_per_tx_e6, max_withdraw_per_tx_e6,
+        )?;
+        if !vault_config.relayer_registry_active {
+            vault_config.relayer_registry_active = true;
+            store_vault_config(vault_config_info, &vault_config)?;
+        }
+
+        msg!("RelayerRegistered: relayer={} label={} roles=0b{:06b} max_deposit_per_tx_e6={} max_withdraw_per_tx_e6={}",
+            relayer, Self::relayer_label(&label), roles, max_deposit_per_tx_e6, max_withdraw_per_tx_e6);
+        Ok(())
+    }
+
+    /// 更新 Relayer 的 label / 角色 / 启用状态 / 单笔限额
+    #[allow(clippy::too_many_arguments)]
+    fn process_update_relayer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        relayer: Pubkey,
+        label: [u8; RELAYER_LABEL_LEN],
+        roles: u8,
+        enabled: bool,
+        max_deposit_per_tx_e6: u64,
+        max_withdraw_per_tx_e6: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let authorized_relayer_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(authorized_relayer_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        Self::validate_relayer_params(&vault_config, &relayer, roles)?;
+
+        let mut authorized_relayer = Self::load_authorized_relayer(program_id, authorized_relayer_info, &relayer)?;
+        authorized_relayer.label = label;
+        authorized_relayer.roles = roles;
+        authorized_relayer.enabled = enabled;
+        authorized_relayer.max_deposit_per_tx_e6 = max_deposit_per_tx_e6;
+        authorized_relayer.max_withdraw_per_tx_e6 = max_withdraw_per_tx_e6;
+        authorized_relayer.updated_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        authorized_relayer.serialize(&mut &mut authorized_relayer_info.data.borrow_mut()[..])?;
+
+        msg!("RelayerUpdated: relayer={} label={} roles=0b{:06b} enabled={} max_deposit_per_tx_e6={} max_withdraw_per_tx_e6={}",
+            relayer, Self::relayer_label(&label), roles, enabled, max_deposit_per_tx_e6, max_withdraw_per_tx_e6);
+        Ok(())
+    }
+
+    /// 将 authorized_callers 中的每个 key (连同角色) 迁移到 AuthorizedRelayer PDA
+    ///
+    /// PDA 不存在时创建 (label "migrated"、无单笔限额)，已存在时合并角色；迁移后清空槽位，
+    /// 并启用 relayer 注册表 (槽位不可再写入，之后所有 relayer 必须传入 PDA)。
+    fn process_migrate_authorized_callers(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+
+        let mut label = [0u8; RELAYER_LABEL_LEN];
+        label[..8].copy_from_slice(b"migrated");
+
+        let mut migrated = 0usize;
+        for slot in 0..vault_config.authorized_callers.len() {
+            let caller = vault_config.authorized_callers[slot];
+            if caller == Pubkey::default() {
+                continue;
+            }
+            let roles = vault_config.authorized_caller_roles[slot];
+            let authorized_relayer_info = next_account_info(account_info_iter)?;
+            assert_writable(authorized_relayer_info)?;
+
+            if authorized_relayer_info.data_is_empty() {
+                Self::create_authorized_relayer(
+                    program_id, governance_authority, authorized_relayer_info, system_program,
+                    caller, label, roles, 0, 0,
+                )?;
+            } else {
+                let mut authorized_relayer = Self::load_authorized_relayer(program_id, authorized_relayer_info, &caller)?;
+                authorized_relayer.roles |= roles;
+                authorized_relayer.updated_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+                authorized_relayer.serialize(&mut &mut authorized_relayer_info.data.borrow_mut()[..])?;
+            }
+
+            vault_config.authorized_callers[slot] = Pubkey::default();
+            vault_config.authorized_caller_roles[slot] = 0;
+            migrated += 1;
+            msg!("RelayerMigrated: relayer={} slot={} roles=0b{:06b}", caller, slot, roles);
+        }
+
+        vault_config.relayer_registry_active = true;
+        store_vault_config(vault_config_info, &vault_config)?;
+        msg!("MigrateAuthorizedCallers: {} caller(s) moved to AuthorizedRelayer PDAs", migrated);
+        Ok(())
+    }
+
+    /// 创建或更新 (relayer, token_index) 的 RelayerRateLimit PDA，并将 AuthorizedRelayer 标记为 rate_limited
+    ///
+    /// bucket_secs 变化时清空已记录的桶 (旧桶 id 与新粒度不可比)。
+    fn process_set_relayer_rate_limit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        relayer: Pubkey,
+        token_index: u16,
+        bucket_secs: i64,
+        max_per_window_e6: [u64; RATE_LIMIT_KIND_COUNT],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let authorized_relayer_info = next_account_info(account_info_iter)?;
+        let rate_limit_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(authorized_relayer_info)?;
+        assert_writable(rate_limit_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        if bucket_secs <= 0 {
+            msg!("❌ bucket_secs must be positive");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut authorized_relayer = Self::load_authorized_relayer(program_id, authorized_relayer_info, &relayer)?;
+
+        let (rate_limit_pda, bump) = RelayerRateLimit::derive_pda(program_id, &relayer, token_index);
+        if rate_limit_info.key != &rate_limit_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut rate_limit = if rate_limit_info.data_is_empty() {
+            create_pda_account(
+                governance_authority,
+                rate_limit_info,
+                system_program,
+                RELAYER_RATE_LIMIT_SIZE,
+                program_id,
+                &[RELAYER_RATE_LIMIT_SEED, relayer.as_ref(), &token_index.to_le_bytes(), &[bump]],
+            )?;
+            RelayerRateLimit {
+                discriminator: RelayerRateLimit::DISCRIMINATOR,
+                relayer,
+                token_index,
+                bucket_secs,
+                max_per_window_e6,
+                bucket_ids: [0; RATE_LIMIT_BUCKETS],
+                amounts_e6: [[0; RATE_LIMIT_BUCKETS]; RATE_LIMIT_KIND_COUNT],
+                bump,
+                reserved: [0u8; 32],
+            }
+        } else {
+            let data = rate_limit_info.data.borrow();
+            deserialize_checked(&data, RelayerRateLimit::DISCRIMINATOR)?;
+            deserialize_account::<RelayerRateLimit>(&data)?
+        };
+
+        if rate_limit.bucket_secs != bucket_secs {
+            rate_limit.bucket_secs = bucket_secs;
+            rate_limit.bucket_ids = [0; RATE_LIMIT_BUCKETS];
+            rate_limit.amounts_e6 = [[0; RATE_LIMIT_BUCKETS]; RATE_LIMIT_KIND_COUNT];
+        }
+        rate_limit.max_per_window_e6 = max_per_window_e6;
+        rate_limit.serialize(&mut &mut rate_limit_info.data.borrow_mut()[..])?;
+
+        authorized_relayer.rate_limited = true;
+        authorized_relayer.updated_ts = solana_program::clock::Clock::get()?.unix_timestamp;
+        authorized_relayer.serialize(&mut &mut authorized_relayer_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "RelayerRateLimitSet: relayer={} token_index={} bucket_secs={} window_secs={} max_deposit_e6={} max_withdraw_e6={}",
+            relayer,
+            token_index,
+            bucket_secs,
+            bucket_secs.saturating_mul(RATE_LIMIT_BUCKETS as i64),
+            max_per_window_e6[RateLimitKind::Deposit as usize],
+            max_per_window_e6[RateLimitKind::Withdraw as usize]
+        );
+        Ok(())
+    }
+
+    // =========================================================================
+    // Escape Hatch: relayer 长期无心跳时的用户自助出金
+    // =========================================================================
+
+    /// 设置逃生舱参数并将心跳重置为当前时间
+    fn process_set_escape_hatch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        inactivity_secs: i64,
+        locked_share_bps: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        if inactivity_secs < 0 || locked_share_bps as u64 > OUTFLOW_BPS_DENOMINATOR {
+            msg!("❌ Invalid escape hatch: inactivity_secs={} locked_share_bps={}", inactivity_secs, locked_share_bps);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        vault_config.escape_hatch = EscapeHatch { inactivity_secs, locked_share_bps, last_heartbeat_ts: now };
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("EscapeHatchSet: inactivity_secs={} locked_share_bps={} heartbeat={}", inactivity_secs, locked_share_bps, now);
+        Ok(())
+    }
+
+    fn assert_escape_hatch_open(vault_config: &VaultConfig, now: i64) -> ProgramResult {
+        let hatch = &vault_config.escape_hatch;
+        if !hatch.is_open(now) {
+            msg!("❌ Escape hatch closed: inactivity_secs={} last_heartbeat_ts={} now={}", hatch.inactivity_secs, hatch.last_heartbeat_ts, now);
+            return Err(VaultError::EscapeHatchClosed.into());
+        }
+        Ok(())
+    }
+
+    /// 逃生舱 USDC 出金: available + locked_share_bps × 锁定余额，随后清零。
+    /// 不受暂停与出金熔断器约束；未支付的锁定部分作为 forfeited_e6 记入 EscapeWithdrawn 事件，供恢复后对账。
+    fn process_escape_withdraw(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let user = next_account_info(account_info_iter)?;
+        let user_account_info = next_account_info(account_info_iter)?;
+        let user_token_account = next_account_info(account_info_iter)?;
+        let vault_token_account = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let usdc_mint_info = next_account_info(account_info_iter).ok();
+
+        assert_signer(user)?;
+        assert_writable(user_account_info)?;
+        assert_writable(vault_config_info)?;
+
+        if !token_compat::is_valid_token_program(token_program.key) {
+            msg!("❌ Invalid token program: expected SPL Token or Token-2022");
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let (vault_config_pda, vault_config_bump) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            msg!("❌ Invalid VaultConfig PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::assert_escape_hatch_open(&vault_config, now)?;
+
+        if vault_token_account.key != &vault_config.vault_token_account {
+            msg!("❌ Invalid vault token account");
+            return Err(VaultError::InvalidAccount.into());
+        }
+        Self::verify_token_vault(vault_token_account, &vault_config.usdc_mint, program_id)?;
+        Self::verify_user_usdc_token_account(user_token_account, &vault_config)?;
+
+        deserialize_checked(&user_account_info.data.borrow(), UserAccount::DISCRIMINATOR)?;
+        let mut user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
+        let (expected_user_pda, _) = UserAccount::derive_pda(program_id, user.key, user_account.account_index);
+        if user_account_info.key != &expected_user_pda {
+            msg!("❌ Invalid UserAccount PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let locked_e6 = [user_account.locked_margin_e6, user_account.spot_locked_e6, user_account.oracle_locked_e6]
+            .iter()
+            .try_fold(0i64, |sum, locked| checked_add(sum, (*locked).max(0)))?;
+        let payout_e6 = vault_config.escape_hatch.payout(user_account.available_balance_e6, locked_e6);
+        if payout_e6 <= 0 {
+            return Err(VaultError::InsufficientBalance.into());
+        }
+        let forfeited_e6 = locked_e6 - vault_config.escape_hatch.locked_payout(locked_e6);
+        let available_e6 = user_account.available_balance_e6;
+        let before = user_account.clone();
+
+        user_account.available_balance_e6 = 0;
+        user_account.locked_margin_e6 = 0;
+        user_account.spot_locked_e6 = 0;
+        user_account.oracle_locked_e6 = 0;
+        user_account.total_withdrawn_e6 = checked_add(user_account.total_withdrawn_e6, payout_e6)?;
+        user_account.last_update_ts = now;
+        Self::track_user_liability(&mut vault_config, Some(&before), &mut user_account)?;
+        user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
+
+        let mint = Self::resolve_usdc_mint(usdc_mint_info, &vault_config)?;
+        let vault_pays_transfer_fee = vault_config.usdc_vault_pays_transfer_fee;
+        let send_amount = Self::withdraw_send_amount(vault_pays_transfer_fee, mint, payout_e6 as u64)?;
+
+        vault_config.total_withdrawn = checked_add_u64(vault_config.total_withdrawn, send_amount)?;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        let received = Self::transfer_tokens(
+            token_program,
+            vault_token_account,
+            user_token_account,
+            vault_config_info,
+            mint,
+            vault_config.require_transfer_checked,
+            send_amount,
+            Some(&[b"vault_config", &[vault_config_bump]]),
+        )?;
+        Self::verify_withdraw_received(vault_pays_transfer_fee, payout_e6 as u64, received)?;
+
+        msg!(
+            "EscapeWithdrawn: user={} account_index={} amount={} available={} locked={} forfeited_e6={}",
+            user.key, user_account.account_index, payout_e6, available_e6, locked_e6, forfeited_e6
+        );
+        Ok(())
+    }
+
+    /// 逃生舱 Spot Token 出金: available_e6 + locked_share_bps × locked_e6 (向下取整为原生数量)。
+    /// 只扣除实际转出的部分: 不足一个原生单位的零头留在 available_e6，未支付的锁定部分
+    /// 作为 forfeited_e6 记入 EscapeSpotWithdrawn 事件。
+    fn process_escape_spot_withdraw(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        token_index: u16,
+        account_index: u32,
+    ) -> ProgramResult {
+        if token_index == 0 {
+            msg!("❌ USDC (token_index=0) must use EscapeWithdraw, not EscapeSpotWithdraw.");
+            return Err(VaultError::QuoteAssetMustUseVaultPath.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let user = next_account_info(account_info_iter)?;
+        let balance_pda_info = next_account_info(account_info_iter)?;
+        let user_token_account = next_account_info(account_info_iter)?;
+        let vault_token_account = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let token_registry_info = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter).ok();
+
+        assert_signer(user)?;
+        assert_writable(token_registry_info)?;
+
+        if !token_compat::is_valid_token_program(token_program.key) {
+            msg!("❌ Invalid token program: expected SPL Token or Token-2022");
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let (vault_config_pda, vault_config_bump) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            msg!("❌ Invalid VaultConfig PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+        let vault_config = load_vault_config(vault_config_info)?;
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        Self::assert_escape_hatch_open(&vault_config, now)?;
+
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+        Self::verify_registered_token_accounts(program_id, &entry, vault_token_account, user_token_account, token_program)?;
+        let mint = Self::resolve_registered_mint(mint_info, &entry)?;
+
+        Self::verify_spot_balance_pda(balance_pda_info, program_id, user.key, account_index, token_index)?;
+        deserialize_checked(&balance_pda_info.data.borrow(), SpotTokenBalance::DISCRIMINATOR)?;
+        let mut balance = deserialize_account::<SpotTokenBalance>(&balance_pda_info.data.borrow())?;
+        let before = balance.clone();
+
+        let payout_e6 = vault_config.escape_hatch.payout(balance.available_e6, balance.locked_e6);
+        let amount = e6_to_native(payout_e6, entry.decimals)?;
+        if amount == 0 {
+            return Err(VaultError::InsufficientBalance.into());
+        }
+        let paid_e6 = native_to_e6(amount, entry.decimals, Rounding::Down)?;
+        let (available_e6, locked_e6) = (balance.available_e6, balance.locked_e6);
+        let forfeited_e6 = locked_e6.max(0) - vault_config.escape_hatch.locked_payout(locked_e6);
+
+        balance.available_e6 = payout_e6 - paid_e6;
+        balance.locked_e6 = 0;
+        balance.last_update_ts = now;
+
+        let send_amount = Self::withdraw_send_amount(entry.vault_pays_transfer_fee, mint, amount)?;
+        let received = Self::transfer_tokens(
+            token_program, vault_token_account, user_token_account, vault_config_info,
+            mint, vault_config.require_transfer_checked, send_amount,
+            Some(&[b"vault_config", &[vault_config_bump]]),
+        )?;
+        Self::verify_withdraw_received(entry.vault_pays_transfer_fee, amount, received)?;
+
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
+        balance.serialize(&mut &mut balance_pda_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+        msg!(
+            "EscapeSpotWithdrawn: user={} token_index={} account_index={} amount_native={} paid_e6={} available_e6={} locked_e6={} forfeited_e6={}",
+            user.key, token_index, account_index, amount, paid_e6, available_e6, locked_e6, forfeited_e6
+        );
+        Ok(())
+    }
+
+    // =========================================================================
+    // Account Migration: 按 layout_version 原地升级账户
+    // =========================================================================
+
+    /// 通用账户迁移 (幂等) — 按 discriminator 分派到对应的 VersionedAccount 解码器
+    fn process_migrate_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(payer)?;
+        assert_writable(account_info)?;
+        if account_info.owner != program_id {
+            return Err(VaultError::InvalidAccount.into());
+        }
+
+        let discriminator = read_discriminator(&account_info.data.borrow())?;
+        match discriminator {
+            VaultConfig::DISCRIMINATOR => {
+                let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+                if account_info.key != &vault_config_pda {
+                    return Err(VaultError::InvalidPda.into());
+                }
+                migration::migrate_account(account_info, payer, system_program, |config: &VaultConfig| {
+                    Self::assert_migration_governance(config, payer)
+                })?;
+            }
+            UserAccount::DISCRIMINATOR => {
+                migration::migrate_account(account_info, payer, system_program, |user: &UserAccount| {
+                    let (expected_pda, _) = UserAccount::derive_pda(program_id, &user.wallet, user.account_index);
+                    if account_info.key != &expected_pda {
+                        return Err(VaultError::InvalidPda.into());
+                    }
+                    Ok(())
+                })?;
+                let vault_config_info = next_account_info(account_info_iter)?;
+                Self::count_migrated_user_account(program_id, account_info, vault_config_info)?;
+            }
+            // SpotTokenBalance does not store account_index, so its PDA cannot be
+            // re-derived; program ownership + discriminator identify it.
+            SpotTokenBalance::DISCRIMINATOR => {
+                migration::migrate_account(account_info, payer, system_program, |_: &SpotTokenBalance| Ok(()))?;
+                let token_registry_info = next_account_info(account_info_iter)?;
+                Self::count_migrated_spot_balance(program_id, account_info, token_registry_info)?;
+            }
+            _ => {
+                msg!("MigrateAccount: unsupported account type 0x{:016X}", discriminator);
+                return Err(VaultError::InvalidAccount.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// MigrateAccount: liability_counted = false 的 UserAccount 整笔计入 VaultConfig 负债计数器
+    fn count_migrated_user_account(
+        program_id: &Pubkey,
+        user_account_info: &AccountInfo,
+        vault_config_info: &AccountInfo,
+    ) -> ProgramResult {
+        let mut user_account = deserialize_account::<UserAccount>(&user_account_info.data.borrow())?;
+        if user_account.liability_counted {
+            return Ok(());
+        }
+        assert_writable(vault_config_info)?;
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            msg!("❌ Invalid VaultConfig PDA");
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+
+        Self::track_user_liability(&mut vault_config, None, &mut user_account)?;
+        user_account.serialize(&mut &mut user_account_info.data.borrow_mut()[..])?;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "LiabilityCounted: account={} liability_e6={} total_liabilities_e6={}",
+            user_account_info.key, user_account.liability_e6(), vault_config.total_liabilities_e6
+        );
+        Ok(())
+    }
+
+    /// MigrateAccount: liability_counted = false 的 SpotTokenBalance 整笔计入 TokenRegistryEntry 负债计数器
+    fn count_migrated_spot_balance(
+        program_id: &Pubkey,
+        balance_info: &AccountInfo,
+        token_registry_info: &AccountInfo,
+    ) -> ProgramResult {
+        let mut balance = deserialize_account::<SpotTokenBalance>(&balance_info.data.borrow())?;
+        if balance.liability_counted {
+            return Ok(());
+        }
+        assert_writable(token_registry_info)?;
+        let mut entry = Self::load_token_registry_entry(token_registry_info, program_id, balance.token_index)?;
+
+        let before = balance.clone();
+        Self::track_spot_liability(&mut entry, &before, &mut balance)?;
+        balance.serialize(&mut &mut balance_info.data.borrow_mut()[..])?;
+        entry.serialize(&mut &mut token_registry_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "LiabilityCounted: account={} token_index={} total_liabilities_e6={}",
+            balance_info.key, balance.token_index, entry.total_liabilities_e6
+        );
+        Ok(())
+    }
+
+    /// VaultConfig 迁移只能由其 governance_authority 发起
+    fn assert_migration_governance(config: &VaultConfig, governance_authority: &AccountInfo) -> ProgramResult {
+        if &config.governance_authority != governance_authority.key {
+            msg!("Migrate: governance_authority mismatch");
+            return Err(VaultError::UnauthorizedUser.into());
+        }
+        Ok(())
+    }
+
+    // =========================================================================
+    // Solvency: 储备 (Token Vault 余额) 与负债计数器对账
+    // =========================================================================
+
+    /// 偿付能力检查 (permissionless): 记录储备/负债比到 Solvency PDA，
+    /// 开启 solvency_auto_pause 且储备持续不足 SOLVENCY_AUTO_PAUSE_DELAY_SECS 后暂停出金类别。
+    /// 负债来自 relayer 的 set-to-value 写入，比率的可信度不高于这些写入。
+    fn process_check_solvency(program_id: &Pubkey, accounts: &[AccountInfo], token_index: u16) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let solvency_info = next_account_info(account_info_iter)?;
+        let vault_token_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(payer)?;
+        assert_writable(solvency_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.solvency_auto_pause {
+            assert_writable(vault_config_info)?;
+        }
+
+        // USDC 记账单位即原生单位 (6 decimals)
+        let (expected_vault, liabilities_e6, decimals) = if token_index == 0 {
+            (vault_config.vault_token_account, vault_config.total_liabilities_e6, 6)
+        } else {
+            let token_registry_info = next_account_info(account_info_iter)?;
+            let entry = Self::load_token_registry_entry(token_registry_info, program_id, token_index)?;
+            (entry.vault_token_account, entry.total_liabilities_e6, entry.decimals)
+        };
+        if vault_token_account.key != &expected_vault {
+            msg!("❌ Invalid vault token account for token_index={}", token_index);
+            return Err(VaultError::InvalidAccount.into());
+        }
+        let reserves = token_compat::get_token_account_amount(vault_token_account)?;
+        let reserves_e6 = native_to_e6(reserves, decimals, Rounding::Down)? as u64;
+
+        let (solvency_pda, bump) = Solvency::derive_pda(program_id, token_index);
+        if solvency_info.key != &solvency_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        let previous_shortfall_since_ts = if solvency_info.data_is_empty() {
+            create_pda_account(
+                payer,
+                solvency_info,
+                system_program,
+                SOLVENCY_SIZE,
+                program_id,
+                &[SOLVENCY_SEED, &token_index.to_le_bytes(), &[bump]],
+            )?;
+            0
+        } else {
+            deserialize_checked(&solvency_info.data.borrow(), Solvency::DISCRIMINATOR)?;
+            deserialize_account::<Solvency>(&solvency_info.data.borrow())?.shortfall_since_ts
+        };
+
+        let clock = solana_program::clock::Clock::get()?;
+        let mut solvency = Solvency {
+            discriminator: Solvency::DISCRIMINATOR,
+            token_index,
+            reserves_e6,
+            liabilities_e6,
+            ratio_bps: Solvency::compute_ratio_bps(reserves_e6, liabilities_e6),
+            checked_ts: clock.unix_timestamp,
+            checked_slot: clock.slot,
+            bump,
+            shortfall_since_ts: 0,
+            reserved: [0u8; 24],
+        };
+        solvency.track_shortfall(previous_shortfall_since_ts);
+        solvency.serialize(&mut &mut solvency_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "SolvencyChecked: token_index={} reserves_e6={} liabilities_e6={} ratio_bps={} ts={} shortfall_since_ts={}",
+            token_index,
+            reserves_e6,
+            liabilities_e6,
+            solvency.ratio_bps,
+            clock.unix_timestamp,
+            solvency.shortfall_since_ts
+        );
+
+        if vault_config.solvency_auto_pause && solvency.auto_pause_due() && vault_config.pause_flags & SOLVENCY_PAUSE_FLAGS != SOLVENCY_PAUSE_FLAGS {
+            vault_config.pause_flags |= SOLVENCY_PAUSE_FLAGS;
+            store_vault_config(vault_config_info, &vault_config)?;
+            msg!(
+                "SolvencyAutoPaused: token_index={} ratio_bps={} pause_flags=0b{:08b}",
+                token_index,
+                solvency.ratio_bps,
+                vault_config.pause_flags
+            );
+        }
+        Ok(())
+    }
+
+    /// 开关偿付不足自动暂停 (恢复出金仍需 SetPauseFlag / SetPaused)
+    fn process_set_solvency_auto_pause(program_id: &Pubkey, accounts: &[AccountInfo], enabled: bool) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let governance_authority = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+
+        assert_signer(governance_authority)?;
+        assert_writable(vault_config_info)?;
+
+        let (vault_config_pda, _) = Pubkey::find_program_address(&[b"vault_config"], program_id);
+        if vault_config_info.key != &vault_config_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+
+        let mut vault_config = load_vault_config(vault_config_info)?;
+        if vault_config.governance_authority != *governance_authority.key {
+            return Err(VaultError::InvalidGovernanceAuthority.into());
+        }
+        vault_config.solvency_auto_pause = enabled;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!("SolvencyAutoPauseSet: enabled={}", enabled);
+        Ok(())
+    }
+
+    // =========================================================================
+    // Proof of Reserves: 负债 Merkle root 发布与包含性验证
+    // =========================================================================
+
+    /// 发布某 epoch 的负债 Merkle 求和树 root (StateWriter)。ReservesEpoch 发布后不可修改。
+    /// total_liabilities_e6 无法在此校验，但必须等于 root 的 sum，否则任何 VerifyInclusion 都会失败。
+    fn process_post_liability_root(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        epoch: u64,
+        root: [u8; 32],
+        total_liabilities_e6: i64,
+        leaf_count: u32,
+    ) -> ProgramResult {
+        let (accounts, authorized_relayer_info, _) = Self::split_relayer_accounts(program_id, accounts, None);
+        let account_info_iter = &mut accounts.iter();
+        let relayer = next_account_info(account_info_iter)?;
+        let vault_config_info = next_account_info(account_info_iter)?;
+        let reserves_epoch_info = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        assert_signer(relayer)?;
+        assert_writable(vault_config_info)?;
+        assert_writable(reserves_epoch_info)?;
+
+        // RBAC: VaultConfig PDA + StateWriter role
+        let (mut vault_config, _) = Self::load_vault_config_with_role(
+            program_id, vault_config_info, relayer, authorized_relayer_info, VaultRole::StateWriter,
+        )?;
+        Self::assert_not_paused(&vault_config, PauseCategory::StateWrite)?;
+        Self::record_heartbeat(vault_config_info, &mut vault_config)?;
+
+        if epoch <= vault_config.latest_liability_epoch {
+            msg!("❌ epoch {} must be greater than latest_liability_epoch {}", epoch, vault_config.latest_liability_epoch);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if total_liabilities_e6 < 0 {
+            msg!("❌ total_liabilities_e6 must not be negative");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (reserves_epoch_pda, bump) = ReservesEpoch::derive_pda(program_id, epoch);
+        if reserves_epoch_info.key != &reserves_epoch_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if !reserves_epoch_info.data_is_empty() {
+            return Err(VaultError::AlreadyInitialized.into());
+        }
+        create_pda_account(
+            relayer,
+            reserves_epoch_info,
+            system_program,
+            RESERVES_EPOCH_SIZE,
+            program_id,
+            &[RESERVES_EPOCH_SEED, &epoch.to_le_bytes(), &[bump]],
+        )?;
+
+        let now = solana_program::clock::Clock::get()?.unix_timestamp;
+        let reserves_epoch = ReservesEpoch {
+            discriminator: ReservesEpoch::DISCRIMINATOR,
+            epoch,
+            root,
+            total_liabilities_e6,
+            leaf_count,
+            posted_by: *relayer.key,
+            posted_ts: now,
+            verified_count: 0,
+            bump,
+            reserved: [0u8; 32],
+        };
+        reserves_epoch.serialize(&mut &mut reserves_epoch_info.data.borrow_mut()[..])?;
+
+        vault_config.latest_liability_epoch = epoch;
+        store_vault_config(vault_config_info, &vault_config)?;
+
+        msg!(
+            "LiabilityRootPosted: epoch={} root={} total_liabilities_e6={} leaf_count={} relayer={}",
+            epoch,
+            solana_program::hash::Hash::new_from_array(root),
+            total_liabilities_e6,
+            leaf_count,
+            relayer.key
+        );
+        Ok(())
+    }
+
+    /// 验证用户叶子在 epoch 的 root 中 (permissionless)，成功后累加 verified_count
+    fn process_verify_inclusion(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        epoch: u64,
+        leaf: LiabilityLeaf,
+        proof: &[SumNode],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let reserves_epoch_info = next_account_info(account_info_iter)?;
+
+        assert_writable(reserves_epoch_info)?;
+
+        let (reserves_epoch_pda, _) = ReservesEpoch::derive_pda(program_id, epoch);
+        if reserves_epoch_info.key != &reserves_epoch_pda {
+            return Err(VaultError::InvalidPda.into());
+        }
+        if reserves_epoch_info.data_is_empty() || reserves_epoch_info.owner != program_id {
+            return Err(VaultError::NotInitialized.into());
+        }
+        deserialize_checked(&reserves_epoch_info.data.borrow(), ReservesEpoch::DISCRIMINATOR)?;
+        let mut reserves_epoch = deserialize_account::<ReservesEpoch>(&reserves_epoch_info.data.borrow())?;
+
+        // 负余额叶子会在求和树中抵消其他用户的负债
+        let Some(liability_e6) = leaf.liability_e6() else {
+            msg!("❌ Leaf balances must not be negative: wallet={} account_index={}", leaf.wallet, leaf.account_index);
+            return Err(ProgramError::InvalidArgument);
+        };
+        // hash 与路径上的 sum 都必须与发布的 root / total_liabilities_e6 一致
+        if !merkle::verify_proof(&leaf, proof, &reserves_epoch.root, reserves_epoch.total_liabilities_e6) {
+            msg!("❌ Leaf not in epoch {} root: wallet={} account_index={}", epoch, leaf.wallet, leaf.account_index);
+            return Err(VaultError::InvalidMerkleProof.into());
+        }
+        reserves_epoch.verified_count = reserves_epoch.verified_count.saturating_add(1);
+        reserves_epoch.serialize(&mut &mut reserves_epoch_info.data.borrow_mut()[..])?;
+
+        msg!(
+            "InclusionVerified: epoch={} wallet={} account_index={} liability_e6={}",
+            epoch,
+            leaf.wallet,
+            leaf.account_index,
+            liability_e6
+        );
         Ok(())
     }
 }
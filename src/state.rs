@@ -13,7 +13,25 @@ use solana_program::pubkey::Pubkey;
 /// 变更记录:
 /// - 2025-12-10: authorized_callers 从 Vec<Pubkey> 改为 [Pubkey; 10] 固定大小数组
 /// - 2026-03-15: 删除 ledger_program 和 fund_program 字段 (569 → 505 bytes)
-///   这两个程序已完全废弃，字段不再保留。链上数据通过 MigrateVaultConfig 指令迁移。
+///   这两个程序已完全废弃，字段不再保留。链上数据通过 MigrateVaultConfig / MigrateAccount 指令迁移。
+/// - 2026-10-16: 从 reserved 划出 require_transfer_checked (1 byte)，总大小不变
+/// - 2026-10-16: 扩容至 1024 bytes (V3)，新增 pending_governance_authority (32 bytes)，
+///   其余作为预留空间。旧账户通过 MigrateVaultConfig / MigrateAccount 指令扩容。
+/// - 2026-10-16: 从 reserved 划出 guardian (32 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 authorized_caller_roles (10 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 outflow_breaker (35 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 pause_flags (2 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 guardians ([Pubkey; 4], 128 bytes) 与 guardian_actions (208 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 escape_hatch (18 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 limits (40 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 layout_version (1 byte)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 total_withdrawn / total_liabilities_e6 (16 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 solvency_auto_pause (1 byte)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 latest_liability_epoch (8 bytes)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 usdc_vault_pays_transfer_fee (1 byte)，总大小不变
+/// - 2026-10-16: 从 reserved 划出 relayer_registry_active (1 byte)，总大小不变
+/// - 2026-10-16: guardian (32 bytes) 并入 guardians，guardians 改为 [Pubkey; MAX_GUARDIANS] (128 → 160 bytes)，
+///   中间字段前移 32 bytes，总大小不变
 pub const VAULT_CONFIG_SIZE: usize = 8 + // discriminator
     32 + // governance_authority
     32 + // usdc_mint
@@ -23,13 +41,30 @@ pub const VAULT_CONFIG_SIZE: usize = 8 + // discriminator
     8 + // total_deposits
     8 + // total_locked
     1 + // is_paused
-    32; // 预留空间
-// Total: 8 + 32 + 32 + 32 + 320 + 32 + 8 + 8 + 1 + 32 = 505 bytes ✓
+    1 + // require_transfer_checked
+    32 + // pending_governance_authority
+    10 + // authorized_caller_roles ([u8; 10])
+    OUTFLOW_BREAKER_SIZE + // outflow_breaker
+    2 + // pause_flags
+    32 * MAX_GUARDIANS + // guardians ([Pubkey; 5])
+    GUARDIAN_ACTION_RECORD_SIZE * GUARDIAN_ACTION_HISTORY + 4 + // guardian_actions + guardian_action_count
+    ESCAPE_HATCH_SIZE + // escape_hatch
+    VAULT_LIMITS_SIZE + // limits
+    1 + // layout_version
+    8 + // total_withdrawn
+    8 + // total_liabilities_e6
+    1 + // solvency_auto_pause
+    8 + // latest_liability_epoch
+    1 + // usdc_vault_pays_transfer_fee
+    1 + // relayer_registry_active
+    17; // 预留空间
+// Total: 8 + 32 + 32 + 32 + 320 + 32 + 8 + 8 + 1 + 1 + 32 + 10 + 35 + 2 + 160 + 208 + 18 + 40 + 1 + 8 + 8 + 1 + 8 + 1 + 1 + 17 = 1024 bytes ✓
 
 /// UserAccount 账户大小 (bytes)
 ///
 /// Layout (153 bytes total):
-///   disc(8) + wallet(32) + bump(1) + 7×i64(56) + account_index(4) + oracle_locked_e6(8) + reserved(44)
+///   disc(8) + wallet(32) + bump(1) + 7×i64(56) + account_index(4) + oracle_locked_e6(8)
+///   + layout_version(1) + liability_counted(1) + sequence(8) + reserved(34)
 pub const USER_ACCOUNT_SIZE: usize = 8 + // discriminator
     32 + // wallet
     1 + // bump
@@ -42,13 +77,32 @@ pub const USER_ACCOUNT_SIZE: usize = 8 + // discriminator
     8 + // spot_locked_e6 (One Account Experience)
     4 + // account_index (u32: sub-account isolation)
     8 + // oracle_locked_e6 (V2: PM Oracle bond)
-    44; // reserved (was 56, reduced by 4+8 for account_index+oracle_locked)
+    1 + // layout_version
+    1 + // liability_counted
+    8 + // sequence
+    34; // reserved (was 56, reduced by 4+8+1+1+8 for account_index+oracle_locked+layout_version+liability_counted+sequence)
 
-/// Vault 全局配置 (505 bytes)
+/// Vault 全局配置 (1024 bytes)
 ///
 /// 变更记录:
 /// - 2025-12-10: authorized_callers 从 Vec<Pubkey> 改为 [Pubkey; 10]
 /// - 2026-03-15: 删除 ledger_program 和 fund_program (569→505 bytes)
+/// - 2026-10-16: reserved[0] → require_transfer_checked
+/// - 2026-10-16: 新增 pending_governance_authority，扩容 505→1024 bytes
+/// - 2026-10-16: reserved → guardian
+/// - 2026-10-16: reserved → authorized_caller_roles (RBAC)
+/// - 2026-10-16: reserved → outflow_breaker
+/// - 2026-10-16: reserved → pause_flags
+/// - 2026-10-16: reserved → guardians, guardian_actions
+/// - 2026-10-16: guardian 并入 guardians
+/// - 2026-10-16: reserved → escape_hatch
+/// - 2026-10-16: reserved → limits
+/// - 2026-10-16: reserved → layout_version (见 migration 模块)
+/// - 2026-10-16: total_deposits / total_locked 由所有余额变更路径维护; reserved → total_withdrawn, total_liabilities_e6
+/// - 2026-10-16: reserved → solvency_auto_pause
+/// - 2026-10-16: reserved → latest_liability_epoch
+/// - 2026-10-16: reserved → usdc_vault_pays_transfer_fee
+/// - 2026-10-16: reserved → relayer_registry_active
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct VaultConfig {
     /// 账户类型标识符 (8 bytes)
@@ -69,60 +123,510 @@ pub struct VaultConfig {
     /// Delegation Program ID (32 bytes)
     pub delegation_program: Pubkey,
     
-    /// 总存款 (e6) (8 bytes)
+    /// 累计 USDC 入金 (e6) (8 bytes) — Deposit / RelayerDeposit
     pub total_deposits: u64,
     
-    /// 总锁定保证金 (e6) (8 bytes)
+    /// 已计入账户的锁定余额合计 (e6) (8 bytes) — Σ UserAccount::locked_e6
     pub total_locked: u64,
     
     /// 是否暂停 (1 byte)
     pub is_paused: bool,
     
-    /// 预留空间 (32 bytes)
-    pub reserved: [u8; 32],
+    /// OC-M6: 是否强制使用 transfer_checked (1 byte)
+    ///
+    /// false (默认, 旧账户 reserved 为 0) = 过渡期，旧账户布局 (不含 Mint) 仍走 legacy transfer；
+    /// true = 所有转账路径必须传入 Mint 账户并使用 transfer_checked。
+    pub require_transfer_checked: bool,
+    
+    /// 待接受的新治理权限 (32 bytes)
+    ///
+    /// 两步转移: ProposeGovernanceAuthority 写入，新 key 签名 AcceptGovernanceAuthority 后生效。
+    /// Pubkey::default() = 无待处理的转移。
+    pub pending_governance_authority: Pubkey,
+    
+    /// 每个 authorized_callers 槽位持有的角色位图 (10 bytes)
+    ///
+    /// authorized_caller_roles[i] 对应 authorized_callers[i]，bit = VaultRole::bit()。
+    /// 由 GrantRole / RevokeRole 维护；角色清空时槽位一并释放。
+    pub authorized_caller_roles: [u8; 10],
+    
+    /// USDC 出金熔断器 (35 bytes) — 窗口内净流出超过 TVL 比例时自动暂停出金
+    pub outflow_breaker: OutflowBreaker,
+    
+    /// 按指令类别暂停的位图 (2 bytes)，bit = PauseCategory::bit()
+    ///
+    /// 由 SetPauseFlag 逐位设置；与 is_paused 叠加 (见 VaultConfig::is_category_paused)。
+    pub pause_flags: u16,
+    
+    /// Guardian 集合 (160 bytes)，Pubkey::default() = 空槽位
+    ///
+    /// Guardian 只能 GuardianPause (置位 pause_flags) 与 GuardianCancelTimelockAction；
+    /// 不能恢复、改角色或动用资金。由 AddGuardian / RemoveGuardian 维护。
+    pub guardians: [Pubkey; MAX_GUARDIANS],
+    
+    /// 最近的 guardian 操作 (208 bytes)，环形缓冲，下标 = guardian_action_count % GUARDIAN_ACTION_HISTORY
+    pub guardian_actions: [GuardianActionRecord; GUARDIAN_ACTION_HISTORY],
+    
+    /// 累计 guardian 操作次数 (4 bytes)
+    pub guardian_action_count: u32,
+    
+    /// 逃生舱 (18 bytes) — relayer 长期无心跳时允许用户直接按链上 PDA 出金
+    pub escape_hatch: EscapeHatch,
+    
+    /// USDC 出入金限额 (40 bytes)，由 SetLimits (token_index = 0) 设置
+    pub limits: VaultLimits,
+    
+    /// 布局版本 (1 byte)，0 = 引入版本号之前创建的账户 (按当前布局解码)
+    pub layout_version: u8,
+    
+    /// 累计 USDC 出金 (e6) (8 bytes) — Withdraw / RelayerWithdraw / RelayerWithdrawAndTransfer / EscapeWithdraw
+    pub total_withdrawn: u64,
+    
+    /// USDC 总负债 (e6) (8 bytes) — Σ UserAccount::liability_e6 (仅 liability_counted 的账户)
+    pub total_liabilities_e6: i64,
+    
+    /// 偿付率跌破 100% 时 CheckSolvency 自动暂停出金 (1 byte)，由 SetSolvencyAutoPause 设置
+    pub solvency_auto_pause: bool,
+    
+    /// 最近一次 PostLiabilityRoot 的 epoch (8 bytes)，0 = 尚未发布
+    pub latest_liability_epoch: u64,
+    
+    /// USDC 出金的 Token-2022 转账手续费由 vault 承担 (1 byte)，由 SetTokenFeePolicy (token_index = 0) 设置
+    pub usdc_vault_pays_transfer_fee: bool,
+    
+    /// Relayer 注册表已启用 (1 byte)，首次 RegisterRelayer / MigrateAuthorizedCallers 时置位。
+    /// 之后不再写入 authorized_callers 槽位，新 relayer 只能通过 AuthorizedRelayer PDA 授权
+    pub relayer_registry_active: bool,
+    
+    /// 预留空间 (17 bytes)
+    pub reserved: [u8; 17],
 }
-// Total: 8 + 32 + 32 + 32 + 320 + 32 + 8 + 8 + 1 + 32 = 505 bytes ✓
+// Total: 8 + 32 + 32 + 32 + 320 + 32 + 8 + 8 + 1 + 1 + 32 + 10 + 35 + 2 + 160 + 208 + 18 + 40 + 1 + 8 + 8 + 1 + 8 + 1 + 1 + 17 = 1024 bytes ✓
 
 /// Old VaultConfig size before migration (ledger_program + fund_program removed)
 pub const VAULT_CONFIG_SIZE_V1: usize = 569;
 
+/// VaultConfig size before pending_governance_authority was added (505 bytes)
+pub const VAULT_CONFIG_SIZE_V2: usize = 505;
+
 impl VaultConfig {
     pub const DISCRIMINATOR: u64 = 0x5641554C545F434F; // "VAULT_CO"
     
-    /// OC-H2: Check if a caller is in the authorized_callers list.
-    /// Used by relayer instructions alongside governance_authority check.
+    /// 当前布局版本: 1 = V1 (569 bytes), 2 = V2 (505 bytes), 3 = 1024 bytes
+    pub const LAYOUT_VERSION: u8 = 3;
+    
+    /// OC-H2: Check if a caller is in the authorized_callers list (with any role).
     pub fn is_authorized_caller(&self, caller: &Pubkey) -> bool {
-        for authorized in &self.authorized_callers {
-            if authorized != &Pubkey::default() && caller == authorized {
-                return true;
+        self.caller_slot(caller).is_some()
+    }
+
+    fn caller_slot(&self, caller: &Pubkey) -> Option<usize> {
+        if caller == &Pubkey::default() {
+            return None;
+        }
+        self.authorized_callers.iter().position(|authorized| authorized == caller)
+    }
+
+    /// Roles held by a key. The governance authority implicitly holds every role.
+    pub fn roles_of(&self, key: &Pubkey) -> u8 {
+        if key == &self.governance_authority {
+            return ALL_VAULT_ROLES;
+        }
+        self.caller_slot(key).map_or(0, |i| self.authorized_caller_roles[i])
+    }
+
+    /// Check if a key holds a role.
+    pub fn has_role(&self, key: &Pubkey, role: VaultRole) -> bool {
+        self.roles_of(key) & role.bit() != 0
+    }
+
+    /// 按 UserAccount 写入前后的差额更新 total_liabilities_e6 / total_locked。
+    /// `before = None` 表示该账户尚未计入 (新建，或 liability_counted = false 的旧账户)，
+    /// 此时整笔计入。溢出或 total_locked 变为负数时返回 None。
+    pub fn apply_user_delta(&mut self, before: Option<&UserAccount>, after: &UserAccount) -> Option<()> {
+        let (liability_before, locked_before) = before.map_or((0, 0), |b| (b.liability_e6(), b.locked_e6()));
+        let liability_delta = after.liability_e6().checked_sub(liability_before)?;
+        let locked_delta = after.locked_e6().checked_sub(locked_before)?;
+        self.total_liabilities_e6 = self.total_liabilities_e6.checked_add(liability_delta)?;
+        let total_locked = i64::try_from(self.total_locked).ok()?.checked_add(locked_delta)?;
+        self.total_locked = u64::try_from(total_locked).ok()?;
+        Some(())
+    }
+
+    /// Grant role bits to a key, taking a free slot if the key is not listed yet.
+    /// Returns false when all slots are in use.
+    pub fn grant_roles(&mut self, key: Pubkey, roles: u8) -> bool {
+        let slot = match self.caller_slot(&key) {
+            Some(i) => i,
+            None => match self.authorized_callers.iter().position(|c| *c == Pubkey::default()) {
+                Some(i) => {
+                    self.authorized_callers[i] = key;
+                    self.authorized_caller_roles[i] = 0;
+                    i
+                }
+                None => return false,
+            },
+        };
+        self.authorized_caller_roles[slot] |= roles;
+        true
+    }
+
+    /// Whether a category is paused: its `pause_flags` bit, or the legacy
+    /// `is_paused` for user-signed paths (relayer paths bypass it, OC-L5).
+    pub fn is_category_paused(&self, category: PauseCategory) -> bool {
+        self.pause_flags & category.bit() != 0 || (self.is_paused && category.is_user_path())
+    }
+
+    /// Check if a key is one of the guardians.
+    pub fn is_guardian(&self, key: &Pubkey) -> bool {
+        key != &Pubkey::default() && self.guardians.contains(key)
+    }
+
+    /// Add a guardian to the first free slot.
+    /// Returns false when all MAX_GUARDIANS slots are in use.
+    pub fn add_guardian(&mut self, key: Pubkey) -> bool {
+        if self.is_guardian(&key) {
+            return true;
+        }
+        match self.guardians.iter_mut().find(|slot| **slot == Pubkey::default()) {
+            Some(slot) => {
+                *slot = key;
+                true
             }
+            None => false,
         }
-        false
     }
 
-    /// OC-H2: Check if a signer is the governance_authority OR an authorized caller.
-    /// Works on raw VaultConfig bytes without full deserialization.
-    /// Layout: discriminator(8) + governance_authority(32) + usdc_mint(32) +
-    ///         vault_token_account(32) + authorized_callers(32*10=320)
-    pub fn is_valid_relayer_from_bytes(data: &[u8], signer: &Pubkey) -> bool {
-        if data.len() < 424 {
+    /// Remove a guardian. Returns false when the key is not a guardian.
+    pub fn remove_guardian(&mut self, key: &Pubkey) -> bool {
+        if !self.is_guardian(key) {
             return false;
         }
-        if let Ok(governance) = Pubkey::try_from(&data[8..40]) {
-            if &governance == signer {
-                return true;
+        for slot in self.guardians.iter_mut() {
+            if slot == key {
+                *slot = Pubkey::default();
             }
         }
-        for i in 0..10 {
-            let start = 104 + i * 32;
-            let end = start + 32;
-            if let Ok(caller) = Pubkey::try_from(&data[start..end]) {
-                if caller != Pubkey::default() && &caller == signer {
-                    return true;
-                }
-            }
+        true
+    }
+
+    /// Append a guardian action to the ring buffer.
+    pub fn record_guardian_action(&mut self, record: GuardianActionRecord) {
+        let index = self.guardian_action_count as usize % GUARDIAN_ACTION_HISTORY;
+        self.guardian_actions[index] = record;
+        self.guardian_action_count = self.guardian_action_count.wrapping_add(1);
+    }
+
+    /// Revoke role bits from a key; the slot is freed once no role is left.
+    /// Returns false when the key is not listed.
+    pub fn revoke_roles(&mut self, key: &Pubkey, roles: u8) -> bool {
+        let Some(slot) = self.caller_slot(key) else {
+            return false;
+        };
+        self.authorized_caller_roles[slot] &= !roles;
+        if self.authorized_caller_roles[slot] == 0 {
+            self.authorized_callers[slot] = Pubkey::default();
+        }
+        true
+    }
+}
+
+// ============================================================
+// Guardians
+// ============================================================
+
+/// Guardian slots in VaultConfig.guardians
+pub const MAX_GUARDIANS: usize = 5;
+
+/// Guardian actions kept in VaultConfig.guardian_actions
+pub const GUARDIAN_ACTION_HISTORY: usize = 4;
+
+/// GuardianActionRecord size (bytes)
+/// guardian(32) + action(1) + reason_code(2) + target(8) + timestamp(8) = 51 bytes
+pub const GUARDIAN_ACTION_RECORD_SIZE: usize = 51;
+
+/// Kind of guardian action
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GuardianAction {
+    /// Empty record
+    #[default]
+    None = 0,
+    /// GuardianPause (target = pause bits set)
+    Pause = 1,
+    /// GuardianCancelTimelockAction (target = action_id)
+    CancelTimelockAction = 2,
+}
+
+/// One guardian action, with the reason code supplied by the guardian
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuardianActionRecord {
+    /// Guardian that acted
+    pub guardian: Pubkey,
+    /// What was done
+    pub action: GuardianAction,
+    /// Off-chain incident / reason code
+    pub reason_code: u16,
+    /// Pause bits set, or the cancelled timelock action id
+    pub target: u64,
+    /// Clock unix_timestamp of the action
+    pub timestamp: i64,
+}
+
+// ============================================================
+// Pause categories
+// ============================================================
+
+/// Number of defined pause categories
+pub const PAUSE_CATEGORY_COUNT: usize = 8;
+
+/// Every pause bit
+pub const ALL_PAUSE_FLAGS: u16 = (1 << PAUSE_CATEGORY_COUNT) - 1;
+
+/// Instruction category that can be paused on its own (bit index in VaultConfig.pause_flags)
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseCategory {
+    /// Deposit
+    UserDeposit = 0,
+    /// Withdraw
+    UserWithdraw = 1,
+    /// SpotDeposit
+    SpotDeposit = 2,
+    /// SpotWithdraw
+    SpotWithdraw = 3,
+    /// RelayerDeposit / RelayerSpotDeposit
+    RelayerCredit = 4,
+    /// RelayerWithdraw / RelayerSpotWithdraw
+    RelayerDebit = 5,
+    /// RelayerWithdrawAndTransfer
+    BridgeTransfer = 6,
+    /// UserAccount / SpotTokenBalance state writes, PostLiabilityRoot
+    StateWrite = 7,
+}
+
+impl PauseCategory {
+    pub const fn bit(self) -> u16 {
+        1 << self as u8
+    }
+
+    /// User-signed paths, which the legacy `is_paused` also stops
+    pub const fn is_user_path(self) -> bool {
+        matches!(
+            self,
+            PauseCategory::UserDeposit | PauseCategory::UserWithdraw | PauseCategory::SpotDeposit | PauseCategory::SpotWithdraw
+        )
+    }
+}
+
+// ============================================================
+// Outflow circuit breaker
+// ============================================================
+
+/// OutflowBreaker size (bytes)
+/// outflow_paused(1) + limit_bps(2) + window_secs(8) + window_start_ts(8)
+/// + window_start_balance(8) + tripped_ts(8) = 35 bytes
+pub const OUTFLOW_BREAKER_SIZE: usize = 35;
+
+/// Basis-point denominator for `OutflowBreaker.limit_bps`
+pub const OUTFLOW_BPS_DENOMINATOR: u64 = 10_000;
+
+/// Global USDC outflow circuit breaker (embedded in VaultConfig)
+///
+/// Net outflow is measured against the vault token balance at the start of the
+/// window, so deposits landing inside the window offset withdrawals. An outflow
+/// that would push net outflow above `limit_bps` of that balance does not move
+/// funds and latches the breaker; every later outflow is rejected until
+/// ResetOutflowBreaker.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutflowBreaker {
+    /// Latched: every USDC outflow is rejected until governance resets it
+    pub outflow_paused: bool,
+    /// Max net outflow per window, in bps of the window-start balance (0 = disabled)
+    pub limit_bps: u16,
+    /// Window length (seconds)
+    pub window_secs: i64,
+    /// Start of the current window (0 = no window open)
+    pub window_start_ts: i64,
+    /// Vault token balance when the window opened (TVL snapshot)
+    pub window_start_balance: u64,
+    /// When the breaker last latched (0 = never)
+    pub tripped_ts: i64,
+}
+
+impl OutflowBreaker {
+    pub fn is_enabled(&self) -> bool {
+        self.limit_bps != 0
+    }
+
+    /// Net outflow allowed in the current window
+    pub fn window_limit(&self) -> u64 {
+        (self.window_start_balance as u128 * self.limit_bps as u128 / OUTFLOW_BPS_DENOMINATOR as u128) as u64
+    }
+
+    /// Record an outflow of `amount` from a vault holding `balance_before` at `now`.
+    /// Opens a new window when the previous one has elapsed. Returns the window's
+    /// net outflow and whether it would exceed `window_limit()`; if so the breaker
+    /// latches and the caller must not move the funds.
+    pub fn record_outflow(&mut self, balance_before: u64, amount: u64, now: i64) -> (u64, bool) {
+        if !self.is_enabled() {
+            return (0, false);
+        }
+        if self.window_start_ts == 0 || now.saturating_sub(self.window_start_ts) >= self.window_secs {
+            self.window_start_ts = now;
+            self.window_start_balance = balance_before;
+        }
+        let net_outflow = self.window_start_balance.saturating_sub(balance_before.saturating_sub(amount));
+        let tripped = net_outflow > self.window_limit();
+        if tripped {
+            self.outflow_paused = true;
+            self.tripped_ts = now;
+        }
+        (net_outflow, tripped)
+    }
+
+    /// Clear the tripped flag and the current window
+    pub fn reset(&mut self) {
+        self.outflow_paused = false;
+        self.window_start_ts = 0;
+        self.window_start_balance = 0;
+    }
+}
+
+// ============================================================
+// Escape hatch
+// ============================================================
+
+/// EscapeHatch size (bytes)
+/// inactivity_secs(8) + locked_share_bps(2) + last_heartbeat_ts(8) = 18 bytes
+pub const ESCAPE_HATCH_SIZE: usize = 18;
+
+/// Self-serve exit when the relayer goes silent (embedded in VaultConfig)
+///
+/// Relayer instructions and state writes bump `last_heartbeat_ts` while the hatch
+/// is enabled. Once no heartbeat has landed for `inactivity_secs`, users can
+/// EscapeWithdraw / EscapeSpotWithdraw against their on-chain PDAs, regardless of pause.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EscapeHatch {
+    /// Inactivity that opens the hatch (seconds, 0 = disabled)
+    pub inactivity_secs: i64,
+    /// Share of locked balances paid out on escape, in bps (see OUTFLOW_BPS_DENOMINATOR)
+    pub locked_share_bps: u16,
+    /// Last relayer activity or state write
+    pub last_heartbeat_ts: i64,
+}
+
+impl EscapeHatch {
+    pub fn is_enabled(&self) -> bool {
+        self.inactivity_secs > 0
+    }
+
+    /// Escape mode: enabled and no heartbeat for `inactivity_secs`
+    pub fn is_open(&self, now: i64) -> bool {
+        self.is_enabled() && now.saturating_sub(self.last_heartbeat_ts) >= self.inactivity_secs
+    }
+
+    /// Escape payout (e6): the whole available balance plus `locked_share_bps` of
+    /// the locked balance. Negative balances count as zero.
+    pub fn payout(&self, available_e6: i64, locked_e6: i64) -> i64 {
+        available_e6.max(0).saturating_add(self.locked_payout(locked_e6))
+    }
+
+    /// Share of a locked balance paid out on escape (e6); the rest is forfeited
+    pub fn locked_payout(&self, locked_e6: i64) -> i64 {
+        (locked_e6.max(0) as u128 * self.locked_share_bps as u128 / OUTFLOW_BPS_DENOMINATOR as u128) as i64
+    }
+}
+
+// ============================================================
+// Deposit / withdraw limits
+// ============================================================
+
+/// VaultLimits size (bytes): 5 × u64 = 40 bytes
+pub const VAULT_LIMITS_SIZE: usize = 40;
+
+/// Per-transaction deposit ceiling set on new and migrated vaults: 10M USDC
+/// (formerly the V-6 constant in RelayerDeposit)
+pub const DEFAULT_MAX_DEPOSIT_PER_TX_E6: u64 = 10_000_000_000_000;
+
+/// Deposit / withdraw limits in e6 of the token they apply to (0 = no limit)
+///
+/// Embedded in VaultConfig for USDC and in TokenRegistryEntry for Spot tokens.
+/// Escape withdrawals and state writes are exempt.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VaultLimits {
+    /// Smallest accepted deposit
+    pub min_deposit_e6: u64,
+    /// Smallest accepted withdrawal
+    pub min_withdraw_e6: u64,
+    /// Largest deposit per transaction
+    pub max_deposit_per_tx_e6: u64,
+    /// Largest withdrawal per transaction
+    pub max_withdraw_per_tx_e6: u64,
+    /// Largest available balance a single (sub-)account may reach through deposits
+    pub max_account_balance_e6: u64,
+}
+
+impl VaultLimits {
+    /// Limits of a newly initialized or migrated VaultConfig
+    pub fn usdc_default() -> Self {
+        Self { max_deposit_per_tx_e6: DEFAULT_MAX_DEPOSIT_PER_TX_E6, ..Self::default() }
+    }
+
+    /// (minimum, per-transaction maximum) of a deposit or withdrawal
+    pub fn amount_bounds(&self, deposit: bool) -> (u64, u64) {
+        if deposit {
+            (self.min_deposit_e6, self.max_deposit_per_tx_e6)
+        } else {
+            (self.min_withdraw_e6, self.max_withdraw_per_tx_e6)
         }
-        false
+    }
+
+    /// Whether an account may hold `balance_e6` after a deposit
+    pub fn allows_account_balance(&self, balance_e6: i64) -> bool {
+        self.max_account_balance_e6 == 0 || balance_e6 <= 0 || balance_e6 as u64 <= self.max_account_balance_e6
+    }
+}
+
+// ============================================================
+// Roles (RBAC)
+// ============================================================
+
+/// Number of defined roles
+///
+/// Bit 5 (former RegistryAdmin) is retired: registry changes are governance-only,
+/// so they go through the timelock once it is enabled.
+pub const VAULT_ROLE_COUNT: usize = 5;
+
+/// Every role bit
+pub const ALL_VAULT_ROLES: u8 = (1 << VAULT_ROLE_COUNT) - 1;
+
+/// Roles of the former flat relayer list: granted by the deprecated AddAuthorizedCaller
+/// and to existing authorized_callers by the V1/V2 → V3 migration
+pub const LEGACY_RELAYER_ROLES: u8 = VaultRole::Depositor.bit()
+    | VaultRole::Withdrawer.bit()
+    | VaultRole::BridgeTransferer.bit()
+    | VaultRole::StateWriter.bit();
+
+/// Role held by an authorized caller (bit index in VaultConfig.authorized_caller_roles)
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultRole {
+    /// RelayerDeposit / RelayerSpotDeposit
+    Depositor = 0,
+    /// RelayerWithdraw / RelayerSpotWithdraw
+    Withdrawer = 1,
+    /// RelayerWithdrawAndTransfer (USDC out to the bridge)
+    BridgeTransferer = 2,
+    /// UserAccount / SpotTokenBalance state writes
+    StateWriter = 3,
+    /// SetPaused(true) only; resuming stays with governance
+    Pauser = 4,
+}
+
+impl VaultRole {
+    pub const fn bit(self) -> u8 {
+        1 << self as u8
     }
 }
 
@@ -176,13 +680,26 @@ pub struct UserAccount {
     /// Borsh-compatible: old PDAs had reserved[9..17]=0 → oracle_locked_e6=0.
     pub oracle_locked_e6: i64,
     
-    /// 预留字段 (扩展用) — from 56 → 44 (4+8 carved for account_index+oracle_locked)
-    pub reserved: [u8; 44],
+    /// 布局版本，0 = 引入版本号之前创建的账户 (reserved 全零，按当前布局解码)
+    pub layout_version: u8,
+    
+    /// 是否已计入 VaultConfig 负债计数。false = 计数引入前创建的账户，下次余额变更时整笔计入。
+    pub liability_counted: bool,
+    
+    /// 最近一次 relayer 写入的 DB 序号 (RelayerDeposit / RelayerWithdraw / RelayerWithdrawAndTransfer / UserAccount)。
+    /// 序号 <= 该值的写入被视为过期。0 = 尚无带序号的写入。
+    pub sequence: u64,
+    
+    /// 预留字段 (扩展用) — from 56 → 34 (4+8+1+1+8 carved for account_index+oracle_locked+layout_version+liability_counted+sequence)
+    pub reserved: [u8; 34],
 }
 
 impl UserAccount {
     pub const DISCRIMINATOR: u64 = 0x555345525F414343; // "USER_ACC"
     
+    /// 当前布局版本
+    pub const LAYOUT_VERSION: u8 = 1;
+    
     pub const USER_SEED: &'static [u8] = b"user";
     
     /// Derive UserAccount PDA address.
@@ -205,6 +722,19 @@ impl UserAccount {
             .saturating_add(self.oracle_locked_e6)
             .saturating_add(self.unrealized_pnl_e6)
     }
+    
+    /// 锁定余额合计 = Perp 锁定保证金 + Spot 锁定 USDC + Oracle 锁定
+    pub fn locked_e6(&self) -> i64 {
+        self.locked_margin_e6
+            .saturating_add(self.spot_locked_e6)
+            .saturating_add(self.oracle_locked_e6)
+    }
+    
+    /// Vault 对该账户的 USDC 负债 = 可用余额 + 锁定余额。
+    /// 未实现盈亏是交易者之间的债权，结算前不计入。
+    pub fn liability_e6(&self) -> i64 {
+        self.available_balance_e6.saturating_add(self.locked_e6())
+    }
 }
 
 // =============================================================================
@@ -228,7 +758,7 @@ pub const SPOT_BALANCE_SEED: &[u8] = b"spot_balance";
 
 /// SpotTokenBalance account size (bytes)
 /// discriminator(8) + wallet(32) + token_index(2) + available_e6(8) + locked_e6(8)
-/// + last_update_ts(8) + bump(1) + reserved(31) = 98 bytes
+/// + last_update_ts(8) + bump(1) + layout_version(1) + liability_counted(1) + sequence(8) + reserved(21) = 98 bytes
 pub const SPOT_TOKEN_BALANCE_SIZE: usize = 98;
 
 /// Per-token balance PDA — one per (wallet, token_index) pair
@@ -252,13 +782,24 @@ pub struct SpotTokenBalance {
     pub last_update_ts: i64,
     /// PDA bump seed
     pub bump: u8,
+    /// Layout version (0 = created before layouts were versioned)
+    pub layout_version: u8,
+    /// Included in TokenRegistryEntry.total_liabilities_e6. False for balances created
+    /// before liability counters existed; they are counted in full on their next change.
+    pub liability_counted: bool,
+    /// Last DB sequence applied by a relayer write (RelayerSpotDeposit / RelayerSpotWithdraw /
+    /// SpotTokenBalance); writes at or below it are stale. 0 = no sequenced write yet.
+    pub sequence: u64,
     /// Reserved for future expansion
-    pub reserved: [u8; 31],
+    pub reserved: [u8; 21],
 }
 
 impl SpotTokenBalance {
     pub const DISCRIMINATOR: u64 = SPOT_TOKEN_BALANCE_DISCRIMINATOR;
 
+    /// Current layout version
+    pub const LAYOUT_VERSION: u8 = 1;
+
     /// Create a new SpotTokenBalance with zero balances
     pub fn new(wallet: Pubkey, token_index: u16, bump: u8, current_ts: i64) -> Self {
         Self {
@@ -269,7 +810,10 @@ impl SpotTokenBalance {
             locked_e6: 0,
             last_update_ts: current_ts,
             bump,
-            reserved: [0u8; 31],
+            layout_version: Self::LAYOUT_VERSION,
+            liability_counted: true,
+            sequence: 0,
+            reserved: [0u8; 21],
         }
     }
 
@@ -318,8 +862,8 @@ impl SpotTokenBalance {
 /// account_index ensures sub-accounts have isolated Spot balances.
 /// Returns (pda_address, bump)
 #[deprecated(
-    since = "OC-H4",
-    note = "Hardcodes account_index=0. Use derive_spot_token_balance_pda_with_index() instead."
+    since = "1.0.0",
+    note = "OC-H4: Hardcodes account_index=0. Use derive_spot_token_balance_pda_with_index() instead."
 )]
 pub fn derive_spot_token_balance_pda(
     program_id: &Pubkey,
@@ -351,129 +895,1235 @@ pub fn derive_spot_token_balance_pda_with_index(
 }
 
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// =============================================================================
+// TokenRegistryEntry — Governance-managed Spot token listing
+// =============================================================================
+//
+// Binds a Spot `token_index` to its mint, decimals, token program and the
+// canonical vault token account. Every Spot path (SpotDeposit, SpotWithdraw,
+// RelayerSpotDeposit, RelayerSpotWithdraw, SpotTokenBalance) requires this PDA
+// and validates the accounts it is given against it.
+//
+// PDA seeds: ["token", token_index.to_le_bytes()]
 
-    #[test]
-    fn test_user_account_equity() {
-        let account = UserAccount {
-            discriminator: UserAccount::DISCRIMINATOR,
-            wallet: Pubkey::new_unique(),
-            bump: 255,
-            available_balance_e6: 1000_000_000,
-            locked_margin_e6: 500_000_000,
-            unrealized_pnl_e6: 200_000_000,
-            total_deposited_e6: 1000_000_000,
-            total_withdrawn_e6: 0,
-            last_update_ts: 0,
-            spot_locked_e6: 300_000_000,
-            account_index: 0,
-            oracle_locked_e6: 100_000_000,
-            reserved: [0; 44],
-        };
-        
-        // equity = available(1000) + locked_margin(500) + spot_locked(300) + oracle_locked(100) + upnl(200) = 2100
-        assert_eq!(account.equity(), 2100_000_000);
-    }
-    
-    #[test]
-    fn test_user_account_size_unchanged() {
-        let account = UserAccount {
-            discriminator: UserAccount::DISCRIMINATOR,
-            wallet: Pubkey::new_unique(),
-            bump: 0,
-            available_balance_e6: 0,
-            locked_margin_e6: 0,
-            unrealized_pnl_e6: 0,
-            total_deposited_e6: 0,
-            total_withdrawn_e6: 0,
-            last_update_ts: 0,
-            spot_locked_e6: 0,
-            account_index: 0,
-            oracle_locked_e6: 0,
-            reserved: [0; 44],
-        };
-        let serialized = borsh::to_vec(&account).unwrap();
-        // 8(disc) + 32(wallet) + 1(bump) + 7*i64(56) + 4(account_index) + 8(oracle_locked) + 44(reserved) = 153
-        assert_eq!(serialized.len(), 153, "UserAccount size must remain 153 bytes");
-    }
-    
-    #[test]
-    fn test_user_account_derive_pda() {
-        let program_id = Pubkey::new_unique();
-        let wallet = Pubkey::new_unique();
-        
-        let (pda_main, _) = UserAccount::derive_pda(&program_id, &wallet, 0);
-        let (pda_sub1, _) = UserAccount::derive_pda(&program_id, &wallet, 1);
-        let (pda_main2, _) = UserAccount::derive_pda(&program_id, &wallet, 0);
-        
-        assert_ne!(pda_main, pda_sub1, "Different account_index → different PDA");
-        assert_eq!(pda_main, pda_main2, "Same inputs → same PDA");
+/// TokenRegistryEntry discriminator — "TOKN_REG" in ASCII hex
+pub const TOKEN_REGISTRY_ENTRY_DISCRIMINATOR: u64 = 0x544F4B4E5F524547;
+
+/// TokenRegistryEntry PDA seed
+pub const TOKEN_REGISTRY_SEED: &[u8] = b"token";
+
+/// TokenRegistryEntry account size (bytes)
+/// discriminator(8) + token_index(2) + mint(32) + decimals(1) + token_program(32)
+/// + vault_token_account(32) + is_enabled(1) + bump(1) + last_update_ts(8)
+/// + vault_pays_transfer_fee(1) + mint_risk_flags(1) + allowed_risk_flags(1)
+/// + limits(40) + total_liabilities_e6(8) + reserved(13) = 181 bytes
+pub const TOKEN_REGISTRY_ENTRY_SIZE: usize = 181;
+
+/// Spot token listing — one per token_index
+///
+/// Created by `AddToken`, re-pointed by `UpdateToken`, toggled by `DisableToken`.
+/// A disabled token rejects new deposits but still allows withdrawals so users can exit.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct TokenRegistryEntry {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Token index (matches DB assets table and SpotTokenBalance.token_index)
+    pub token_index: u16,
+    /// SPL mint address
+    pub mint: Pubkey,
+    /// Mint decimals (read from the mint account at listing time)
+    pub decimals: u8,
+    /// Owning token program (SPL Token or Token-2022)
+    pub token_program: Pubkey,
+    /// Canonical vault token account (owned by the VaultConfig PDA)
+    pub vault_token_account: Pubkey,
+    /// Whether new deposits are accepted
+    pub is_enabled: bool,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Last governance update timestamp (unix seconds)
+    pub last_update_ts: i64,
+    /// Token-2022 transfer-fee policy for withdrawals:
+    /// false = user pays (receives amount - fee), true = vault pays (sends gross-up, user receives amount).
+    /// Deposits always credit the net amount received by the vault.
+    pub vault_pays_transfer_fee: bool,
+    /// `token_compat::MINT_RISK_*` flags detected on the mint at listing time
+    pub mint_risk_flags: u8,
+    /// Risk flags governance explicitly accepted in AddToken (override)
+    pub allowed_risk_flags: u8,
+    /// Deposit / withdraw limits in this token's e6 units (SetLimits)
+    pub limits: VaultLimits,
+    /// Total owed to users in this token (e6): sum of counted SpotTokenBalance totals
+    pub total_liabilities_e6: i64,
+    /// Reserved for future expansion
+    pub reserved: [u8; 13],
+}
+
+impl TokenRegistryEntry {
+    pub const DISCRIMINATOR: u64 = TOKEN_REGISTRY_ENTRY_DISCRIMINATOR;
+
+    /// Risk flags present on the mint but not accepted by governance
+    pub fn unapproved_risk_flags(&self, mint_risk_flags: u8) -> u8 {
+        mint_risk_flags & !self.allowed_risk_flags
     }
 
-    #[test]
-    fn test_vault_config_authorized_caller() {
-        let other = Pubkey::new_unique();
-        let authorized = Pubkey::new_unique();
-        
-        let mut authorized_callers = [Pubkey::default(); 10];
-        authorized_callers[0] = authorized;
-        
-        let config = VaultConfig {
-            discriminator: VaultConfig::DISCRIMINATOR,
-            governance_authority: Pubkey::new_unique(),
-            usdc_mint: Pubkey::new_unique(),
-            vault_token_account: Pubkey::new_unique(),
-            authorized_callers,
-            delegation_program: Pubkey::new_unique(),
-            total_deposits: 0,
-            total_locked: 0,
-            is_paused: false,
-            reserved: [0u8; 32],
+    /// Apply the change of one SpotTokenBalance to `total_liabilities_e6`.
+    /// `before = None` counts `after` in full (balance not counted yet).
+    /// Returns None on overflow.
+    pub fn apply_balance_delta(&mut self, before: Option<&SpotTokenBalance>, after: &SpotTokenBalance) -> Option<()> {
+        let before_total = match before {
+            Some(balance) => balance.total().ok()?,
+            None => 0,
         };
-        
-        assert!(config.is_authorized_caller(&authorized));
-        assert!(!config.is_authorized_caller(&other));
-        assert!(!config.is_authorized_caller(&Pubkey::default()));
+        let delta = after.total().ok()?.checked_sub(before_total)?;
+        self.total_liabilities_e6 = self.total_liabilities_e6.checked_add(delta)?;
+        Some(())
     }
 
-    #[test]
-    fn test_vault_config_size() {
-        let config = VaultConfig {
-            discriminator: VaultConfig::DISCRIMINATOR,
-            governance_authority: Pubkey::new_unique(),
-            usdc_mint: Pubkey::new_unique(),
-            vault_token_account: Pubkey::new_unique(),
-            authorized_callers: [Pubkey::default(); 10],
-            delegation_program: Pubkey::new_unique(),
-            total_deposits: 0,
-            total_locked: 0,
-            is_paused: false,
-            reserved: [0u8; 32],
-        };
-        let serialized = borsh::to_vec(&config).unwrap();
-        assert_eq!(serialized.len(), VAULT_CONFIG_SIZE, "VaultConfig must be 505 bytes");
-        assert_eq!(VAULT_CONFIG_SIZE, 505);
+    /// Derive TokenRegistryEntry PDA address.
+    /// Seeds: ["token", token_index.to_le_bytes()]
+    pub fn derive_pda(program_id: &Pubkey, token_index: u16) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[TOKEN_REGISTRY_SEED, &token_index.to_le_bytes()],
+            program_id,
+        )
     }
+}
 
-    // === SpotTokenBalance Tests (Dynamic Token Balance Architecture) ===
+// =============================================================================
+// Token Vault — program-created vault token account per mint
+// =============================================================================
+//
+// One deterministic token account per mint (SPL Token or Token-2022), created by
+// `Initialize` (USDC) or `InitializeTokenVault`, with the VaultConfig PDA as its
+// authority. VaultConfig.vault_token_account and TokenRegistryEntry.vault_token_account
+// must point at it, and every transfer path requires exactly this account.
+//
+// PDA seeds: ["token_vault", mint]
 
-    #[test]
-    fn test_spot_token_balance_size() {
-        assert_eq!(SPOT_TOKEN_BALANCE_SIZE, 98);
-        let balance = SpotTokenBalance::new(Pubkey::new_unique(), 1, 255, 1000);
-        let serialized = borsh::to_vec(&balance).unwrap();
-        assert_eq!(serialized.len(), SPOT_TOKEN_BALANCE_SIZE);
-    }
+/// Token vault PDA seed
+pub const TOKEN_VAULT_SEED: &[u8] = b"token_vault";
 
-    #[test]
-    fn test_spot_token_balance_serialization() {
-        let wallet = Pubkey::new_unique();
-        let balance = SpotTokenBalance::new(wallet, 1, 200, 1234567890);
+/// Derive the vault token account PDA for `mint`.
+/// Returns (pda_address, bump)
+pub fn derive_token_vault_pda(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TOKEN_VAULT_SEED, mint.as_ref()], program_id)
+}
 
-        let serialized = borsh::to_vec(&balance).unwrap();
-        let deserialized = SpotTokenBalance::try_from_slice(&serialized).unwrap();
+// =============================================================================
+// Governance Multisig — M-of-N governance for VaultConfig
+// =============================================================================
+//
+// `InitializeGovernanceSet` hands VaultConfig.governance_authority to the
+// governance signer PDA. From then on every governance instruction runs as a
+// proposal: members create and approve it, and once `threshold` approvals are
+// collected `ExecuteGovernanceProposal` invokes the stored instruction with the
+// signer PDA as governance authority (self-CPI). The signer PDA is a plain
+// system account and pays rent for accounts created by governance actions.
+//
+// PDA seeds:
+//   GovernanceSet      ["governance_set"]
+//   Governance signer  ["governance_signer"]
+//   GovernanceProposal ["governance_proposal", proposal_id.to_le_bytes()]
+
+/// GovernanceSet discriminator — "GOV_SET_" in ASCII hex
+pub const GOVERNANCE_SET_DISCRIMINATOR: u64 = 0x474F565F5345545F;
+
+/// GovernanceProposal discriminator — "GOV_PROP" in ASCII hex
+pub const GOVERNANCE_PROPOSAL_DISCRIMINATOR: u64 = 0x474F565F50524F50;
+
+/// GovernanceSet PDA seed
+pub const GOVERNANCE_SET_SEED: &[u8] = b"governance_set";
+
+/// Governance signer PDA seed (VaultConfig.governance_authority in multisig mode)
+pub const GOVERNANCE_SIGNER_SEED: &[u8] = b"governance_signer";
+
+/// GovernanceProposal PDA seed
+pub const GOVERNANCE_PROPOSAL_SEED: &[u8] = b"governance_proposal";
+
+/// Maximum number of governance members
+pub const MAX_GOVERNANCE_MEMBERS: usize = 10;
+
+/// Maximum serialized VaultInstruction size stored in a proposal
+pub const MAX_PROPOSAL_INSTRUCTION_LEN: usize = 384;
+
+/// GovernanceSet account size (bytes)
+/// discriminator(8) + members(32*10) + member_count(1) + threshold(1) + set_version(4)
+/// + proposal_ttl_secs(8) + next_proposal_id(8) + signer_bump(1) + bump(1)
+/// + reserved(64) = 416 bytes
+pub const GOVERNANCE_SET_SIZE: usize = 416;
+
+/// GovernanceProposal account size (bytes)
+/// discriminator(8) + proposal_id(8) + proposer(32) + set_version(4) + approvals(2)
+/// + created_ts(8) + expires_ts(8) + executed(1) + bump(1) + accounts_hash(32)
+/// + instruction_len(2) + instruction_data(384) + reserved(32) = 522 bytes
+pub const GOVERNANCE_PROPOSAL_SIZE: usize = 522;
+
+/// M-of-N governance members — singleton
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct GovernanceSet {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Member keys; slots >= member_count are Pubkey::default()
+    pub members: [Pubkey; MAX_GOVERNANCE_MEMBERS],
+    /// Number of active members (N)
+    pub member_count: u8,
+    /// Approvals required to execute (M)
+    pub threshold: u8,
+    /// Bumped on every membership change; proposals from older versions are void
+    pub set_version: u32,
+    /// Proposal lifetime (seconds) from creation
+    pub proposal_ttl_secs: i64,
+    /// Id assigned to the next proposal
+    pub next_proposal_id: u64,
+    /// Governance signer PDA bump seed
+    pub signer_bump: u8,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Reserved for future expansion
+    pub reserved: [u8; 64],
+}
+
+impl GovernanceSet {
+    pub const DISCRIMINATOR: u64 = GOVERNANCE_SET_DISCRIMINATOR;
+
+    /// Slot index of `key` among the active members
+    pub fn member_index(&self, key: &Pubkey) -> Option<usize> {
+        self.members[..self.member_count as usize]
+            .iter()
+            .position(|member| member == key)
+    }
+
+    /// Number of approval bits set for active member slots
+    pub fn approval_count(&self, approvals: u16) -> u8 {
+        let mask = (1u32 << self.member_count) - 1;
+        (approvals as u32 & mask).count_ones() as u8
+    }
+
+    /// Derive GovernanceSet PDA address.
+    /// Seeds: ["governance_set"]
+    pub fn derive_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[GOVERNANCE_SET_SEED], program_id)
+    }
+
+    /// Derive the governance signer PDA (governance_authority in multisig mode).
+    /// Seeds: ["governance_signer"]
+    pub fn derive_signer_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[GOVERNANCE_SIGNER_SEED], program_id)
+    }
+}
+
+/// Pending governance action awaiting M-of-N approval
+///
+/// `instruction_data` holds a borsh-serialized governance `VaultInstruction`;
+/// `accounts_hash` pins the exact account list it will be executed with.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct GovernanceProposal {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Sequential proposal id (PDA seed)
+    pub proposal_id: u64,
+    /// Member who created the proposal
+    pub proposer: Pubkey,
+    /// GovernanceSet.set_version at creation
+    pub set_version: u32,
+    /// Approval bitmask over GovernanceSet.members slots
+    pub approvals: u16,
+    /// Creation timestamp (unix seconds)
+    pub created_ts: i64,
+    /// Approvals and execution are rejected after this timestamp
+    pub expires_ts: i64,
+    /// Whether the proposal has been executed
+    pub executed: bool,
+    /// PDA bump seed
+    pub bump: u8,
+    /// sha256 over the ordered account metas (key, is_signer, is_writable) of the governance instruction
+    pub accounts_hash: [u8; 32],
+    /// Length of the serialized instruction in `instruction_data`
+    pub instruction_len: u16,
+    /// Serialized VaultInstruction (zero padded)
+    pub instruction_data: [u8; MAX_PROPOSAL_INSTRUCTION_LEN],
+    /// Reserved for future expansion
+    pub reserved: [u8; 32],
+}
+
+impl GovernanceProposal {
+    pub const DISCRIMINATOR: u64 = GOVERNANCE_PROPOSAL_DISCRIMINATOR;
+
+    /// Serialized VaultInstruction bytes
+    pub fn instruction(&self) -> &[u8] {
+        &self.instruction_data[..self.instruction_len as usize]
+    }
+
+    /// Derive GovernanceProposal PDA address.
+    /// Seeds: ["governance_proposal", proposal_id.to_le_bytes()]
+    pub fn derive_pda(program_id: &Pubkey, proposal_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[GOVERNANCE_PROPOSAL_SEED, &proposal_id.to_le_bytes()],
+            program_id,
+        )
+    }
+}
+
+// =============================================================================
+// Timelock — delayed execution of sensitive governance actions
+// =============================================================================
+//
+// `InitializeTimelock` hands VaultConfig.governance_authority to the timelock
+// signer PDA and records the previous authority as the timelock admin. The admin
+// queues governance instructions; each becomes executable (by anyone) once its
+// ETA — queue time plus the minimum delay for its action type — has passed, and
+// stays executable for TIMELOCK_GRACE_PERIOD_SECS. The admin or any guardian in
+// `VaultConfig.guardians` can cancel a queued action. The admin may still pause directly.
+//
+// PDA seeds:
+//   TimelockConfig   ["timelock_config"]
+//   Timelock signer  ["timelock_signer"]
+//   TimelockAction   ["timelock_action", action_id.to_le_bytes()]
+
+/// TimelockConfig discriminator — "TL_CONFG" in ASCII hex
+pub const TIMELOCK_CONFIG_DISCRIMINATOR: u64 = 0x544C5F434F4E4647;
+
+/// TimelockAction discriminator — "TL_ACTN_" in ASCII hex
+pub const TIMELOCK_ACTION_DISCRIMINATOR: u64 = 0x544C5F4143544E5F;
+
+/// TimelockConfig PDA seed
+pub const TIMELOCK_CONFIG_SEED: &[u8] = b"timelock_config";
+
+/// Timelock signer PDA seed (VaultConfig.governance_authority while the timelock is active)
+pub const TIMELOCK_SIGNER_SEED: &[u8] = b"timelock_signer";
+
+/// TimelockAction PDA seed
+pub const TIMELOCK_ACTION_SEED: &[u8] = b"timelock_action";
+
+/// Number of per-action-type delay slots in TimelockConfig (one per TimelockActionType)
+pub const TIMELOCK_ACTION_TYPE_COUNT: usize = TimelockActionType::LAST as usize + 1;
+
+/// Upper bound for a configured minimum delay (30 days)
+pub const MAX_TIMELOCK_DELAY_SECS: i64 = 30 * 86_400;
+
+/// Window after the ETA during which a queued action can be executed (14 days)
+pub const TIMELOCK_GRACE_PERIOD_SECS: i64 = 14 * 86_400;
+
+/// TimelockConfig account size (bytes)
+/// discriminator(8) + admin(32) + min_delay_secs(8*7) + next_action_id(8)
+/// + signer_bump(1) + bump(1) + reserved(64) = 170 bytes
+pub const TIMELOCK_CONFIG_SIZE: usize = 170;
+
+/// TimelockAction account size (bytes)
+/// discriminator(8) + action_id(8) + action_type(1) + queued_by(32) + queued_ts(8)
+/// + eta(8) + executed(1) + cancelled(1) + bump(1) + accounts_hash(32)
+/// + instruction_len(2) + instruction_data(384) + reserved(32) = 518 bytes
+pub const TIMELOCK_ACTION_SIZE: usize = 518;
+
+/// Timelock action categories — index into TimelockConfig.min_delay_secs
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelockActionType {
+    /// AddAuthorizedCaller / RemoveAuthorizedCaller / GrantRole / RevokeRole / relayer registry
+    AuthorizedCallers = 0,
+    /// Governance authority transfer, multisig / timelock setup
+    GovernanceTransfer = 1,
+    /// SetPaused / SetPauseFlag (queued; the admin can still pause directly), outflow circuit breaker, escape hatch, solvency auto-pause
+    Pause = 2,
+    /// MigrateVaultConfig / MigrateAccount
+    Migration = 3,
+    /// Token registry and token vault changes
+    TokenRegistry = 4,
+    /// SetRequireTransferChecked / SetLimits
+    TransferPolicy = 5,
+    /// UpdateTimelockConfig / AddGuardian / RemoveGuardian
+    TimelockConfig = 6,
+}
+
+impl TimelockActionType {
+    /// Highest discriminant; TIMELOCK_ACTION_TYPE_COUNT is derived from it
+    pub const LAST: Self = Self::TimelockConfig;
+}
+
+/// Timelock parameters — singleton
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct TimelockConfig {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Key allowed to queue and cancel actions (single key or multisig signer PDA)
+    pub admin: Pubkey,
+    /// Minimum delay (seconds) per TimelockActionType
+    pub min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT],
+    /// Id assigned to the next queued action
+    pub next_action_id: u64,
+    /// Timelock signer PDA bump seed
+    pub signer_bump: u8,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Reserved for future expansion
+    pub reserved: [u8; 64],
+}
+
+impl TimelockConfig {
+    pub const DISCRIMINATOR: u64 = TIMELOCK_CONFIG_DISCRIMINATOR;
+
+    /// Minimum delay for an action type
+    pub fn min_delay(&self, action_type: TimelockActionType) -> i64 {
+        self.min_delay_secs[action_type as usize]
+    }
+
+    /// Derive TimelockConfig PDA address.
+    /// Seeds: ["timelock_config"]
+    pub fn derive_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[TIMELOCK_CONFIG_SEED], program_id)
+    }
+
+    /// Derive the timelock signer PDA (governance_authority while the timelock is active).
+    /// Seeds: ["timelock_signer"]
+    pub fn derive_signer_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[TIMELOCK_SIGNER_SEED], program_id)
+    }
+}
+
+/// Queued governance action
+///
+/// Same instruction encoding and account pinning as GovernanceProposal.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct TimelockAction {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Sequential action id (PDA seed)
+    pub action_id: u64,
+    /// TimelockActionType as u8
+    pub action_type: u8,
+    /// Admin that queued the action
+    pub queued_by: Pubkey,
+    /// Queue timestamp (unix seconds)
+    pub queued_ts: i64,
+    /// Earliest execution timestamp
+    pub eta: i64,
+    /// Whether the action has been executed
+    pub executed: bool,
+    /// Whether the action has been cancelled
+    pub cancelled: bool,
+    /// PDA bump seed
+    pub bump: u8,
+    /// sha256 over the ordered account metas (key, is_signer, is_writable) of the governance instruction
+    pub accounts_hash: [u8; 32],
+    /// Length of the serialized instruction in `instruction_data`
+    pub instruction_len: u16,
+    /// Serialized VaultInstruction (zero padded)
+    pub instruction_data: [u8; MAX_PROPOSAL_INSTRUCTION_LEN],
+    /// Reserved for future expansion
+    pub reserved: [u8; 32],
+}
+
+impl TimelockAction {
+    pub const DISCRIMINATOR: u64 = TIMELOCK_ACTION_DISCRIMINATOR;
+
+    /// Serialized VaultInstruction bytes
+    pub fn instruction(&self) -> &[u8] {
+        &self.instruction_data[..self.instruction_len as usize]
+    }
+
+    /// Executable window: [eta, eta + TIMELOCK_GRACE_PERIOD_SECS]
+    pub fn is_executable_at(&self, now: i64) -> bool {
+        now >= self.eta && now <= self.eta.saturating_add(TIMELOCK_GRACE_PERIOD_SECS)
+    }
+
+    /// Derive TimelockAction PDA address.
+    /// Seeds: ["timelock_action", action_id.to_le_bytes()]
+    pub fn derive_pda(program_id: &Pubkey, action_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[TIMELOCK_ACTION_SEED, &action_id.to_le_bytes()],
+            program_id,
+        )
+    }
+}
+
+
+// =============================================================================
+// AuthorizedRelayer — per-relayer registry entry
+// =============================================================================
+//
+// Replaces the fixed `VaultConfig.authorized_callers` array: one PDA per relayer
+// key, so the number of relayers is unbounded. Role-gated instructions accept the
+// signer's AuthorizedRelayer PDA as an extra trailing account; the entries still
+// in `authorized_callers` keep working until `MigrateAuthorizedCallers` moves them.
+//
+// PDA seeds: ["authorized_relayer", relayer]
+
+/// AuthorizedRelayer discriminator — "AUTH_RLY" in ASCII hex
+pub const AUTHORIZED_RELAYER_DISCRIMINATOR: u64 = 0x415554485F524C59;
+
+/// AuthorizedRelayer PDA seed
+pub const AUTHORIZED_RELAYER_SEED: &[u8] = b"authorized_relayer";
+
+/// Relayer label length (UTF-8, zero padded)
+pub const RELAYER_LABEL_LEN: usize = 32;
+
+/// AuthorizedRelayer account size (bytes)
+/// discriminator(8) + relayer(32) + label(32) + roles(1) + enabled(1)
+/// + created_ts(8) + updated_ts(8) + max_deposit_per_tx_e6(8)
+/// + max_withdraw_per_tx_e6(8) + bump(1) + rate_limited(1) + reserved(63) = 171 bytes
+pub const AUTHORIZED_RELAYER_SIZE: usize = 171;
+
+/// Relayer registry entry — one per relayer key
+///
+/// Created by `RegisterRelayer` (or `MigrateAuthorizedCallers`), changed by
+/// `UpdateRelayer` / `GrantRole` / `RevokeRole`. A disabled relayer holds no role.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AuthorizedRelayer {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Relayer signing key
+    pub relayer: Pubkey,
+    /// Operator label (UTF-8, zero padded)
+    pub label: [u8; RELAYER_LABEL_LEN],
+    /// VaultRole bits
+    pub roles: u8,
+    /// Disabled relayers are rejected by every role check
+    pub enabled: bool,
+    /// Registration timestamp (unix seconds)
+    pub created_ts: i64,
+    /// Last governance update timestamp (unix seconds)
+    pub updated_ts: i64,
+    /// Per-transaction cap on Depositor paths (e6, 0 = no cap)
+    pub max_deposit_per_tx_e6: u64,
+    /// Per-transaction cap on Withdrawer / BridgeTransferer paths (e6, 0 = no cap)
+    pub max_withdraw_per_tx_e6: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Set by `SetRelayerRateLimit`: the relayer must pass its RelayerRateLimit PDA
+    pub rate_limited: bool,
+    /// Reserved for future expansion
+    pub reserved: [u8; 63],
+}
+
+impl AuthorizedRelayer {
+    pub const DISCRIMINATOR: u64 = AUTHORIZED_RELAYER_DISCRIMINATOR;
+
+    /// Check if the relayer is enabled and holds a role
+    pub fn has_role(&self, role: VaultRole) -> bool {
+        self.enabled && self.roles & role.bit() != 0
+    }
+
+    /// Per-transaction cap (e6) that applies to an instruction gated by `role`; 0 = no cap
+    pub fn max_per_tx_e6(&self, role: VaultRole) -> u64 {
+        match role {
+            VaultRole::Depositor => self.max_deposit_per_tx_e6,
+            VaultRole::Withdrawer | VaultRole::BridgeTransferer => self.max_withdraw_per_tx_e6,
+            _ => 0,
+        }
+    }
+
+    /// Derive AuthorizedRelayer PDA address.
+    /// Seeds: ["authorized_relayer", relayer]
+    pub fn derive_pda(program_id: &Pubkey, relayer: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[AUTHORIZED_RELAYER_SEED, relayer.as_ref()], program_id)
+    }
+}
+
+// =============================================================================
+// RelayerRateLimit — per-relayer rolling-window caps
+// =============================================================================
+//
+// The window is RATE_LIMIT_BUCKETS buckets of `bucket_secs` each, keyed by
+// `unix_timestamp / bucket_secs` from the Clock sysvar. A bucket slot is reused
+// (and zeroed) when its id falls out of the window, so the account never grows.
+// One PDA per (relayer, token_index): amounts are e6 of that token, so tokens with
+// different decimals or prices never share a cap. Once `SetRelayerRateLimit` has
+// run, the relayer must pass the PDA of the moved token right after its
+// AuthorizedRelayer PDA.
+//
+// PDA seeds: ["rate_limit", relayer, token_index_le_u16]
+
+/// RelayerRateLimit discriminator — "RATE_LIM" in ASCII hex
+pub const RELAYER_RATE_LIMIT_DISCRIMINATOR: u64 = 0x524154455F4C494D;
+
+/// RelayerRateLimit PDA seed
+pub const RELAYER_RATE_LIMIT_SEED: &[u8] = b"rate_limit";
+
+/// Buckets per rolling window
+pub const RATE_LIMIT_BUCKETS: usize = 24;
+
+/// Number of RateLimitKind series
+pub const RATE_LIMIT_KIND_COUNT: usize = 2;
+
+/// RelayerRateLimit account size (bytes)
+/// discriminator(8) + relayer(32) + token_index(2) + bucket_secs(8) + max_per_window_e6(8×2)
+/// + bucket_ids(8×24) + amounts_e6(8×24×2) + bump(1) + reserved(32) = 675 bytes
+pub const RELAYER_RATE_LIMIT_SIZE: usize = 675;
+
+/// Flow tracked by a RelayerRateLimit
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKind {
+    /// RelayerDeposit (USDC) / RelayerSpotDeposit
+    Deposit = 0,
+    /// RelayerWithdraw / RelayerWithdrawAndTransfer (USDC) / RelayerSpotWithdraw
+    Withdraw = 1,
+}
+
+/// Rolling-window totals for one relayer and one token
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct RelayerRateLimit {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Relayer signing key
+    pub relayer: Pubkey,
+    /// Token the caps apply to (0 = USDC)
+    pub token_index: u16,
+    /// Bucket length (seconds); window = bucket_secs × RATE_LIMIT_BUCKETS
+    pub bucket_secs: i64,
+    /// Cap per window for each RateLimitKind (e6, 0 = no cap)
+    pub max_per_window_e6: [u64; RATE_LIMIT_KIND_COUNT],
+    /// Bucket id (`unix_timestamp / bucket_secs`) held by each slot
+    pub bucket_ids: [i64; RATE_LIMIT_BUCKETS],
+    /// Amount per kind per slot (e6)
+    pub amounts_e6: [[u64; RATE_LIMIT_BUCKETS]; RATE_LIMIT_KIND_COUNT],
+    /// PDA bump seed
+    pub bump: u8,
+    /// Reserved for future expansion
+    pub reserved: [u8; 32],
+}
+
+impl RelayerRateLimit {
+    pub const DISCRIMINATOR: u64 = RELAYER_RATE_LIMIT_DISCRIMINATOR;
+
+    fn current_bucket(&self, now: i64) -> i64 {
+        now.div_euclid(self.bucket_secs.max(1))
+    }
+
+    /// Total recorded for `kind` in the window ending at `now`
+    pub fn window_total(&self, kind: RateLimitKind, now: i64) -> u64 {
+        let current = self.current_bucket(now);
+        let oldest = current - (RATE_LIMIT_BUCKETS as i64 - 1);
+        self.bucket_ids
+            .iter()
+            .zip(self.amounts_e6[kind as usize].iter())
+            .filter(|(id, _)| **id >= oldest && **id <= current)
+            .fold(0u64, |total, (_, amount)| total.saturating_add(*amount))
+    }
+
+    /// Record `amount_e6` for `kind` at `now`.
+    /// Returns false (window total unchanged) when the cap would be exceeded.
+    pub fn try_record(&mut self, kind: RateLimitKind, amount_e6: u64, now: i64) -> bool {
+        let total = self.window_total(kind, now).saturating_add(amount_e6);
+        let cap = self.max_per_window_e6[kind as usize];
+        if cap != 0 && total > cap {
+            return false;
+        }
+        let current = self.current_bucket(now);
+        let slot = current.rem_euclid(RATE_LIMIT_BUCKETS as i64) as usize;
+        if self.bucket_ids[slot] != current {
+            self.bucket_ids[slot] = current;
+            for amounts in self.amounts_e6.iter_mut() {
+                amounts[slot] = 0;
+            }
+        }
+        let amount = &mut self.amounts_e6[kind as usize][slot];
+        *amount = amount.saturating_add(amount_e6);
+        true
+    }
+
+    /// Derive RelayerRateLimit PDA address.
+    /// Seeds: ["rate_limit", relayer, token_index_le_u16]
+    pub fn derive_pda(program_id: &Pubkey, relayer: &Pubkey, token_index: u16) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[RELAYER_RATE_LIMIT_SEED, relayer.as_ref(), &token_index.to_le_bytes()], program_id)
+    }
+}
+
+// =============================================================================
+// Solvency — last reserve / liability check per token
+// =============================================================================
+//
+// Written by the permissionless CheckSolvency. Reserves are the vault token
+// account balance (converted to e6); liabilities are the counter tracked in
+// VaultConfig (USDC, token_index = 0) or the TokenRegistryEntry (Spot tokens).
+//
+// PDA seeds: ["solvency", token_index_le_u16]
+
+/// Solvency discriminator — "SOLVENCY" in ASCII hex
+pub const SOLVENCY_DISCRIMINATOR: u64 = 0x534F4C56454E4359;
+
+/// Solvency PDA seed
+pub const SOLVENCY_SEED: &[u8] = b"solvency";
+
+/// Solvency account size (bytes)
+/// discriminator(8) + token_index(2) + reserves_e6(8) + liabilities_e6(8) + ratio_bps(8)
+/// + checked_ts(8) + checked_slot(8) + bump(1) + shortfall_since_ts(8) + reserved(24) = 83 bytes
+pub const SOLVENCY_SIZE: usize = 83;
+
+/// How long reserves must stay short (across at least two checks) before CheckSolvency
+/// auto-pauses, so a transient gap between a relayer credit and its transfer cannot trip it
+pub const SOLVENCY_AUTO_PAUSE_DELAY_SECS: i64 = 15 * 60;
+
+/// `ratio_bps` when nothing is owed (liabilities <= 0)
+pub const SOLVENCY_RATIO_UNBOUNDED: u64 = u64::MAX;
+
+/// Outflow categories paused by CheckSolvency when auto-pause is on and reserves fall short
+pub const SOLVENCY_PAUSE_FLAGS: u16 = PauseCategory::UserWithdraw.bit()
+    | PauseCategory::SpotWithdraw.bit()
+    | PauseCategory::RelayerDebit.bit()
+    | PauseCategory::BridgeTransfer.bit();
+
+/// Result of the latest CheckSolvency for one token
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct Solvency {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// 0 = USDC, otherwise the Spot token_index
+    pub token_index: u16,
+    /// Vault token account balance (e6, rounded down)
+    pub reserves_e6: u64,
+    /// Tracked liabilities (e6)
+    pub liabilities_e6: i64,
+    /// reserves / liabilities in bps (10_000 = fully covered, SOLVENCY_RATIO_UNBOUNDED when nothing is owed)
+    pub ratio_bps: u64,
+    /// When the check ran
+    pub checked_ts: i64,
+    /// Slot of the check
+    pub checked_slot: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// First check of the current shortfall (0 = reserves covered liabilities at the last check)
+    pub shortfall_since_ts: i64,
+    /// Reserved for future expansion
+    pub reserved: [u8; 24],
+}
+
+impl Solvency {
+    pub const DISCRIMINATOR: u64 = SOLVENCY_DISCRIMINATOR;
+
+    /// reserves / liabilities in bps, rounded down
+    pub fn compute_ratio_bps(reserves_e6: u64, liabilities_e6: i64) -> u64 {
+        if liabilities_e6 <= 0 {
+            return SOLVENCY_RATIO_UNBOUNDED;
+        }
+        let ratio = reserves_e6 as u128 * OUTFLOW_BPS_DENOMINATOR as u128 / liabilities_e6 as u128;
+        u64::try_from(ratio).unwrap_or(SOLVENCY_RATIO_UNBOUNDED)
+    }
+
+    /// Reserves cover liabilities
+    pub fn is_solvent(&self) -> bool {
+        self.ratio_bps >= OUTFLOW_BPS_DENOMINATOR
+    }
+
+    /// Start of the shortfall as of this check: kept from the previous check while
+    /// reserves stay short, reset once they cover liabilities again
+    pub fn track_shortfall(&mut self, previous_shortfall_since_ts: i64) {
+        self.shortfall_since_ts = match (self.is_solvent(), previous_shortfall_since_ts) {
+            (true, _) => 0,
+            (false, 0) => self.checked_ts,
+            (false, since) => since,
+        };
+    }
+
+    /// Reserves have been short for at least SOLVENCY_AUTO_PAUSE_DELAY_SECS
+    pub fn auto_pause_due(&self) -> bool {
+        !self.is_solvent() && self.checked_ts.saturating_sub(self.shortfall_since_ts) >= SOLVENCY_AUTO_PAUSE_DELAY_SECS
+    }
+
+    /// Derive Solvency PDA address.
+    /// Seeds: ["solvency", token_index_le_u16]
+    pub fn derive_pda(program_id: &Pubkey, token_index: u16) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[SOLVENCY_SEED, &token_index.to_le_bytes()], program_id)
+    }
+}
+
+// =============================================================================
+// ReservesEpoch — relayer-posted liability Merkle root
+// =============================================================================
+//
+// One immutable PDA per epoch. The root commits to every user's DB balance and
+// to their sum (see `merkle::LiabilityTree`, a Merkle sum tree); users prove their
+// leaf with VerifyInclusion.
+// Epochs must increase; VaultConfig.latest_liability_epoch points at the newest.
+//
+// PDA seeds: ["reserves_epoch", epoch_le_u64]
+
+/// ReservesEpoch discriminator — "RSV_EPCH" in ASCII hex
+pub const RESERVES_EPOCH_DISCRIMINATOR: u64 = 0x5253565F45504348;
+
+/// ReservesEpoch PDA seed
+pub const RESERVES_EPOCH_SEED: &[u8] = b"reserves_epoch";
+
+/// ReservesEpoch account size (bytes)
+/// discriminator(8) + epoch(8) + root(32) + total_liabilities_e6(8) + leaf_count(4)
+/// + posted_by(32) + posted_ts(8) + verified_count(8) + bump(1) + reserved(32) = 141 bytes
+pub const RESERVES_EPOCH_SIZE: usize = 141;
+
+/// Liability commitment for one epoch
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct ReservesEpoch {
+    /// Account type discriminator
+    pub discriminator: u64,
+    /// Relayer-chosen epoch number (strictly increasing)
+    pub epoch: u64,
+    /// Merkle sum tree root hash over LiabilityLeaf nodes
+    pub root: [u8; 32],
+    /// Σ leaf liabilities (e6), as posted by the relayer; must equal the root's sum for proofs to verify
+    pub total_liabilities_e6: i64,
+    /// Number of leaves
+    pub leaf_count: u32,
+    /// Relayer that posted the root
+    pub posted_by: Pubkey,
+    /// When the root was posted
+    pub posted_ts: i64,
+    /// Successful VerifyInclusion calls against this epoch
+    pub verified_count: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Reserved for future expansion
+    pub reserved: [u8; 32],
+}
+
+impl ReservesEpoch {
+    pub const DISCRIMINATOR: u64 = RESERVES_EPOCH_DISCRIMINATOR;
+
+    /// Derive ReservesEpoch PDA address.
+    /// Seeds: ["reserves_epoch", epoch_le_u64]
+    pub fn derive_pda(program_id: &Pubkey, epoch: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[RESERVES_EPOCH_SEED, &epoch.to_le_bytes()], program_id)
+    }
+}
+
+#[cfg(test)]
+impl VaultConfig {
+    /// 测试用: 当前布局的全零 VaultConfig (只设置 discriminator)，新增字段时无需修改各测试
+    pub(crate) fn zeroed() -> Self {
+        let mut data = vec![0u8; VAULT_CONFIG_SIZE];
+        data[..8].copy_from_slice(&Self::DISCRIMINATOR.to_le_bytes());
+        Self::try_from_slice(&data).expect("VAULT_CONFIG_SIZE matches the current layout")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_account_equity() {
+        let account = UserAccount {
+            discriminator: UserAccount::DISCRIMINATOR,
+            wallet: Pubkey::new_unique(),
+            bump: 255,
+            available_balance_e6: 1_000_000_000,
+            locked_margin_e6: 500_000_000,
+            unrealized_pnl_e6: 200_000_000,
+            total_deposited_e6: 1_000_000_000,
+            total_withdrawn_e6: 0,
+            last_update_ts: 0,
+            spot_locked_e6: 300_000_000,
+            account_index: 0,
+            oracle_locked_e6: 100_000_000,
+            layout_version: UserAccount::LAYOUT_VERSION,
+            liability_counted: true,
+            sequence: 0,
+            reserved: [0; 34],
+        };
+        
+        // equity = available(1000) + locked_margin(500) + spot_locked(300) + oracle_locked(100) + upnl(200) = 2100
+        assert_eq!(account.equity(), 2_100_000_000);
+        assert_eq!(account.locked_e6(), 900_000_000);
+        assert_eq!(account.liability_e6(), 1_900_000_000);
+    }
+
+    #[test]
+    fn test_liability_deltas() {
+        let mut config = VaultConfig::zeroed();
+        let mut account = UserAccount::try_from_slice(&[0u8; USER_ACCOUNT_SIZE]).unwrap();
+        account.available_balance_e6 = 700;
+        account.locked_margin_e6 = 300;
+        account.unrealized_pnl_e6 = -50;
+
+        // Not counted yet: added in full
+        config.apply_user_delta(None, &account).unwrap();
+        assert_eq!((config.total_liabilities_e6, config.total_locked), (1_000, 300));
+
+        let before = account.clone();
+        account.available_balance_e6 = 900;
+        account.locked_margin_e6 = 100;
+        account.spot_locked_e6 = 50;
+        config.apply_user_delta(Some(&before), &account).unwrap();
+        assert_eq!((config.total_liabilities_e6, config.total_locked), (1_050, 150));
+
+        // total_locked cannot go negative
+        let mut drained = account.clone();
+        drained.locked_margin_e6 = -1_000;
+        assert!(config.clone().apply_user_delta(Some(&account), &drained).is_none());
+
+        let mut entry = TokenRegistryEntry::try_from_slice(&[0u8; TOKEN_REGISTRY_ENTRY_SIZE]).unwrap();
+        let mut balance = SpotTokenBalance::new(Pubkey::new_unique(), 1, 255, 0);
+        balance.available_e6 = 40;
+        balance.locked_e6 = 2;
+        entry.apply_balance_delta(None, &balance).unwrap();
+        let before = balance.clone();
+        balance.locked_e6 = 0;
+        entry.apply_balance_delta(Some(&before), &balance).unwrap();
+        assert_eq!(entry.total_liabilities_e6, 40);
+        balance.available_e6 = i64::MAX;
+        balance.locked_e6 = 1;
+        assert!(entry.apply_balance_delta(Some(&before), &balance).is_none());
+    }
+    
+    #[test]
+    fn test_user_account_size_unchanged() {
+        let account = UserAccount {
+            discriminator: UserAccount::DISCRIMINATOR,
+            wallet: Pubkey::new_unique(),
+            bump: 0,
+            available_balance_e6: 0,
+            locked_margin_e6: 0,
+            unrealized_pnl_e6: 0,
+            total_deposited_e6: 0,
+            total_withdrawn_e6: 0,
+            last_update_ts: 0,
+            spot_locked_e6: 0,
+            account_index: 0,
+            oracle_locked_e6: 0,
+            layout_version: UserAccount::LAYOUT_VERSION,
+            liability_counted: true,
+            sequence: 0,
+            reserved: [0; 34],
+        };
+        let serialized = borsh::to_vec(&account).unwrap();
+        // 8(disc) + 32(wallet) + 1(bump) + 7*i64(56) + 4(account_index) + 8(oracle_locked)
+        // + 1(layout_version) + 1(liability_counted) + 8(sequence) + 34(reserved) = 153
+        assert_eq!(serialized.len(), 153, "UserAccount size must remain 153 bytes");
+    }
+    
+    #[test]
+    fn test_user_account_derive_pda() {
+        let program_id = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        
+        let (pda_main, _) = UserAccount::derive_pda(&program_id, &wallet, 0);
+        let (pda_sub1, _) = UserAccount::derive_pda(&program_id, &wallet, 1);
+        let (pda_main2, _) = UserAccount::derive_pda(&program_id, &wallet, 0);
+        
+        assert_ne!(pda_main, pda_sub1, "Different account_index → different PDA");
+        assert_eq!(pda_main, pda_main2, "Same inputs → same PDA");
+    }
+
+    #[test]
+    fn test_vault_config_authorized_caller() {
+        let other = Pubkey::new_unique();
+        let authorized = Pubkey::new_unique();
+        
+        let mut authorized_callers = [Pubkey::default(); 10];
+        authorized_callers[0] = authorized;
+        
+        let mut config = VaultConfig::zeroed();
+        config.authorized_callers = authorized_callers;
+
+        assert!(config.is_authorized_caller(&authorized));
+        assert!(!config.is_authorized_caller(&other));
+        assert!(!config.is_authorized_caller(&Pubkey::default()));
+    }
+
+    #[test]
+    fn test_vault_config_guardians() {
+        let mut config = VaultConfig::zeroed();
+        assert_eq!(borsh::to_vec(&GuardianActionRecord::default()).unwrap().len(), GUARDIAN_ACTION_RECORD_SIZE);
+        assert!(!config.is_guardian(&Pubkey::default()));
+
+        let guardians: Vec<Pubkey> = (0..MAX_GUARDIANS).map(|_| Pubkey::new_unique()).collect();
+        for guardian in &guardians {
+            assert!(config.add_guardian(*guardian));
+        }
+        assert_eq!(config.guardians.to_vec(), guardians);
+        assert!(guardians.iter().all(|g| config.is_guardian(g)));
+        assert!(config.add_guardian(guardians[2]), "re-adding is a no-op");
+        assert!(!config.add_guardian(Pubkey::new_unique()));
+
+        assert!(config.remove_guardian(&guardians[0]));
+        assert!(!config.remove_guardian(&guardians[0]));
+        assert!(!config.is_guardian(&guardians[0]));
+        let newcomer = Pubkey::new_unique();
+        assert!(config.add_guardian(newcomer));
+        assert_eq!(config.guardians[0], newcomer);
+
+        // Ring buffer keeps the latest GUARDIAN_ACTION_HISTORY records
+        for i in 0..6u64 {
+            config.record_guardian_action(GuardianActionRecord {
+                guardian: guardians[1],
+                action: GuardianAction::CancelTimelockAction,
+                reason_code: 7,
+                target: i,
+                timestamp: 100 + i as i64,
+            });
+        }
+        assert_eq!(config.guardian_action_count, 6);
+        let mut targets: Vec<u64> = config.guardian_actions.iter().map(|r| r.target).collect();
+        targets.sort();
+        assert_eq!(targets, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_pause_categories() {
+        let mut config = VaultConfig::zeroed();
+        config.pause_flags = PauseCategory::BridgeTransfer.bit();
+        assert_eq!(ALL_PAUSE_FLAGS, 0xFF);
+        assert!(config.is_category_paused(PauseCategory::BridgeTransfer));
+        assert!(!config.is_category_paused(PauseCategory::RelayerDebit));
+        assert!(!config.is_category_paused(PauseCategory::UserWithdraw));
+
+        // Legacy is_paused stops user paths only
+        config.is_paused = true;
+        assert!(config.is_category_paused(PauseCategory::UserDeposit));
+        assert!(config.is_category_paused(PauseCategory::SpotWithdraw));
+        assert!(!config.is_category_paused(PauseCategory::RelayerCredit));
+        assert!(!config.is_category_paused(PauseCategory::StateWrite));
+    }
+
+    #[test]
+    fn test_outflow_breaker() {
+        let mut breaker = OutflowBreaker::default();
+        assert_eq!(borsh::to_vec(&breaker).unwrap().len(), OUTFLOW_BREAKER_SIZE);
+        assert_eq!(breaker.record_outflow(1_000, 1_000, 100), (0, false), "disabled breaker records nothing");
+
+        // 10% of TVL per hour
+        breaker.limit_bps = 1_000;
+        breaker.window_secs = 3_600;
+        assert_eq!(breaker.record_outflow(1_000_000, 60_000, 100), (60_000, false));
+        assert_eq!(breaker.window_start_balance, 1_000_000);
+
+        // Deposits inside the window offset withdrawals
+        assert_eq!(breaker.record_outflow(990_000, 60_000, 200), (70_000, false));
+
+        // The outflow that would cross the limit latches the breaker
+        assert_eq!(breaker.record_outflow(930_000, 40_000, 300), (110_000, true));
+        assert!(breaker.outflow_paused);
+        assert_eq!(breaker.tripped_ts, 300);
+
+        // Reset opens a fresh window, as does window_secs elapsing
+        breaker.reset();
+        assert!(!breaker.outflow_paused);
+        assert_eq!(breaker.record_outflow(930_000, 50_000, 400), (50_000, false));
+        assert_eq!(breaker.record_outflow(880_000, 50_000, 400 + 3_600), (50_000, false));
+        assert_eq!(breaker.window_start_balance, 880_000);
+    }
+
+    #[test]
+    fn test_vault_limits() {
+        let mut limits = VaultLimits::default();
+        assert_eq!(borsh::to_vec(&limits).unwrap().len(), VAULT_LIMITS_SIZE);
+        assert!(limits.allows_account_balance(i64::MAX), "0 = no cap");
+        assert_eq!(VaultLimits::usdc_default().amount_bounds(true), (0, DEFAULT_MAX_DEPOSIT_PER_TX_E6));
+
+        limits.min_deposit_e6 = 1;
+        limits.max_withdraw_per_tx_e6 = 9;
+        limits.max_account_balance_e6 = 100;
+        assert_eq!(limits.amount_bounds(true), (1, 0));
+        assert_eq!(limits.amount_bounds(false), (0, 9));
+        assert!(limits.allows_account_balance(100));
+        assert!(!limits.allows_account_balance(101));
+        assert!(limits.allows_account_balance(-5));
+    }
+
+    #[test]
+    fn test_escape_hatch() {
+        let mut hatch = EscapeHatch::default();
+        assert_eq!(borsh::to_vec(&hatch).unwrap().len(), ESCAPE_HATCH_SIZE);
+        assert!(!hatch.is_open(i64::MAX), "disabled hatch never opens");
+
+        hatch.inactivity_secs = 7 * 86_400;
+        hatch.last_heartbeat_ts = 1_000;
+        assert!(!hatch.is_open(1_000 + 7 * 86_400 - 1));
+        assert!(hatch.is_open(1_000 + 7 * 86_400));
+
+        // 50% of locked funds on top of the available balance; negatives count as zero
+        hatch.locked_share_bps = 5_000;
+        assert_eq!(hatch.payout(1_000_000, 300_001), 1_150_000);
+        assert_eq!(hatch.payout(-5, 200), 100);
+        assert_eq!(hatch.payout(10, -200), 10);
+        assert_eq!((hatch.locked_payout(300_001), hatch.locked_payout(-200)), (150_000, 0));
+        hatch.locked_share_bps = 0;
+        assert_eq!(hatch.payout(1_000_000, 300_000), 1_000_000);
+    }
+
+    #[test]
+    fn test_solvency_ratio() {
+        let solvency = Solvency::try_from_slice(&[0u8; SOLVENCY_SIZE]).unwrap();
+        assert_eq!(borsh::to_vec(&solvency).unwrap().len(), SOLVENCY_SIZE);
+
+        assert_eq!(Solvency::compute_ratio_bps(1_000, 1_000), 10_000);
+        assert_eq!(Solvency::compute_ratio_bps(999, 1_000), 9_990);
+        assert_eq!(Solvency::compute_ratio_bps(3, 2), 15_000);
+        assert_eq!(Solvency::compute_ratio_bps(0, 0), SOLVENCY_RATIO_UNBOUNDED);
+        assert_eq!(Solvency::compute_ratio_bps(5, -1), SOLVENCY_RATIO_UNBOUNDED);
+        assert_eq!(Solvency::compute_ratio_bps(u64::MAX, 1), SOLVENCY_RATIO_UNBOUNDED);
+
+        let check = |reserves_e6, liabilities_e6| Solvency {
+            ratio_bps: Solvency::compute_ratio_bps(reserves_e6, liabilities_e6),
+            ..solvency.clone()
+        };
+        assert!(check(1_000, 1_000).is_solvent());
+        assert!(!check(999, 1_000).is_solvent());
+        assert!(check(0, 0).is_solvent());
+        assert_eq!(SOLVENCY_PAUSE_FLAGS & PauseCategory::UserDeposit.bit(), 0);
+
+        // A shortfall only pauses once it has lasted SOLVENCY_AUTO_PAUSE_DELAY_SECS
+        let at = |ts: i64, reserves_e6, previous| {
+            let mut solvency = Solvency { checked_ts: ts, ..check(reserves_e6, 1_000) };
+            solvency.track_shortfall(previous);
+            solvency
+        };
+        let first = at(1_000, 999, 0);
+        assert_eq!(first.shortfall_since_ts, 1_000);
+        assert!(!first.auto_pause_due());
+        let later = at(1_000 + SOLVENCY_AUTO_PAUSE_DELAY_SECS - 1, 999, first.shortfall_since_ts);
+        assert!(!later.auto_pause_due());
+        let due = at(1_000 + SOLVENCY_AUTO_PAUSE_DELAY_SECS, 999, later.shortfall_since_ts);
+        assert!(due.auto_pause_due());
+        let recovered = at(5_000, 1_000, due.shortfall_since_ts);
+        assert_eq!(recovered.shortfall_since_ts, 0);
+        assert!(!recovered.auto_pause_due());
+    }
+
+    #[test]
+    fn test_reserves_epoch_size() {
+        let epoch = ReservesEpoch::try_from_slice(&[0u8; RESERVES_EPOCH_SIZE]).unwrap();
+        assert_eq!(borsh::to_vec(&epoch).unwrap().len(), RESERVES_EPOCH_SIZE);
+        assert_ne!(ReservesEpoch::derive_pda(&Pubkey::default(), 1).0, ReservesEpoch::derive_pda(&Pubkey::default(), 2).0);
+    }
+
+    #[test]
+    fn test_vault_config_roles() {
+        let governance = Pubkey::new_unique();
+        let relayer = Pubkey::new_unique();
+        let pauser = Pubkey::new_unique();
+        let mut config = VaultConfig::zeroed();
+        config.governance_authority = governance;
+
+        assert_eq!(config.roles_of(&governance), ALL_VAULT_ROLES);
+        assert!(config.has_role(&governance, VaultRole::Pauser));
+
+        assert!(config.grant_roles(relayer, VaultRole::Depositor.bit()));
+        assert!(config.grant_roles(relayer, VaultRole::StateWriter.bit()));
+        assert!(config.grant_roles(pauser, VaultRole::Pauser.bit()));
+        assert!(config.has_role(&relayer, VaultRole::Depositor));
+        assert!(config.has_role(&relayer, VaultRole::StateWriter));
+        assert!(!config.has_role(&relayer, VaultRole::BridgeTransferer));
+        assert!(!config.has_role(&pauser, VaultRole::Depositor));
+        assert_eq!(config.authorized_callers.iter().filter(|c| **c != Pubkey::default()).count(), 2);
+
+        // Revoking the last role frees the slot
+        assert!(config.revoke_roles(&relayer, VaultRole::Depositor.bit()));
+        assert!(config.is_authorized_caller(&relayer));
+        assert!(config.revoke_roles(&relayer, VaultRole::StateWriter.bit()));
+        assert!(!config.is_authorized_caller(&relayer));
+        assert!(!config.revoke_roles(&relayer, VaultRole::StateWriter.bit()));
+
+        // Full list
+        for _ in 0..9 {
+            assert!(config.grant_roles(Pubkey::new_unique(), LEGACY_RELAYER_ROLES));
+        }
+        assert!(!config.grant_roles(Pubkey::new_unique(), VaultRole::Depositor.bit()));
+        assert!(config.grant_roles(pauser, VaultRole::Withdrawer.bit()));
+        assert!(config.has_role(&pauser, VaultRole::Withdrawer));
+    }
+
+    fn test_authorized_relayer(roles: u8) -> AuthorizedRelayer {
+        AuthorizedRelayer {
+            discriminator: AuthorizedRelayer::DISCRIMINATOR,
+            relayer: Pubkey::new_unique(),
+            label: [0u8; RELAYER_LABEL_LEN],
+            roles,
+            enabled: true,
+            created_ts: 1_700_000_000,
+            updated_ts: 1_700_000_000,
+            max_deposit_per_tx_e6: 1_000_000,
+            max_withdraw_per_tx_e6: 2_000_000,
+            bump: 255,
+            rate_limited: false,
+            reserved: [0u8; 63],
+        }
+    }
+
+    #[test]
+    fn test_authorized_relayer_size_and_roles() {
+        let mut relayer = test_authorized_relayer(VaultRole::Depositor.bit() | VaultRole::BridgeTransferer.bit());
+        assert_eq!(borsh::to_vec(&relayer).unwrap().len(), AUTHORIZED_RELAYER_SIZE);
+
+        assert!(relayer.has_role(VaultRole::Depositor));
+        assert!(!relayer.has_role(VaultRole::StateWriter));
+        assert_eq!(relayer.max_per_tx_e6(VaultRole::Depositor), 1_000_000);
+        assert_eq!(relayer.max_per_tx_e6(VaultRole::BridgeTransferer), 2_000_000);
+        assert_eq!(relayer.max_per_tx_e6(VaultRole::StateWriter), 0);
+
+        relayer.enabled = false;
+        assert!(!relayer.has_role(VaultRole::Depositor));
+
+        let program_id = Pubkey::new_unique();
+        let (pda_a, _) = AuthorizedRelayer::derive_pda(&program_id, &relayer.relayer);
+        let (pda_b, _) = AuthorizedRelayer::derive_pda(&program_id, &Pubkey::new_unique());
+        assert_ne!(pda_a, pda_b);
+    }
+
+    #[test]
+    fn test_relayer_rate_limit_rolling_window() {
+        let mut rate_limit = RelayerRateLimit {
+            discriminator: RelayerRateLimit::DISCRIMINATOR,
+            relayer: Pubkey::new_unique(),
+            token_index: 0,
+            bucket_secs: 3_600,
+            max_per_window_e6: [1_000, 500],
+            bucket_ids: [0; RATE_LIMIT_BUCKETS],
+            amounts_e6: [[0; RATE_LIMIT_BUCKETS]; RATE_LIMIT_KIND_COUNT],
+            bump: 255,
+            reserved: [0u8; 32],
+        };
+        assert_eq!(borsh::to_vec(&rate_limit).unwrap().len(), RELAYER_RATE_LIMIT_SIZE);
+
+        let t0 = 1_700_000_000;
+        assert!(rate_limit.try_record(RateLimitKind::Deposit, 600, t0));
+        assert!(rate_limit.try_record(RateLimitKind::Deposit, 400, t0 + 10 * 3_600));
+        assert!(!rate_limit.try_record(RateLimitKind::Deposit, 1, t0 + 23 * 3_600));
+        assert_eq!(rate_limit.window_total(RateLimitKind::Deposit, t0 + 23 * 3_600), 1_000);
+
+        // The first bucket leaves the window after 24 buckets
+        assert_eq!(rate_limit.window_total(RateLimitKind::Deposit, t0 + 24 * 3_600), 400);
+        assert!(rate_limit.try_record(RateLimitKind::Deposit, 600, t0 + 24 * 3_600));
+
+        // Kinds are independent; 0 = no cap
+        assert!(!rate_limit.try_record(RateLimitKind::Withdraw, 501, t0));
+        assert_eq!(rate_limit.window_total(RateLimitKind::Withdraw, t0), 0);
+        rate_limit.max_per_window_e6[RateLimitKind::Withdraw as usize] = 0;
+        assert!(rate_limit.try_record(RateLimitKind::Withdraw, u64::MAX, t0));
+
+        // Each token has its own PDA
+        let relayer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        assert_ne!(
+            RelayerRateLimit::derive_pda(&program_id, &relayer, 0).0,
+            RelayerRateLimit::derive_pda(&program_id, &relayer, 1).0
+        );
+    }
+
+    #[test]
+    fn test_vault_config_size() {
+        let config = VaultConfig::zeroed();
+        let serialized = borsh::to_vec(&config).unwrap();
+        assert_eq!(serialized.len(), VAULT_CONFIG_SIZE, "VaultConfig must be 1024 bytes");
+        assert_eq!(VAULT_CONFIG_SIZE, 1024);
+    }
+
+    // === SpotTokenBalance Tests (Dynamic Token Balance Architecture) ===
+
+    #[test]
+    fn test_spot_token_balance_size() {
+        assert_eq!(SPOT_TOKEN_BALANCE_SIZE, 98);
+        let balance = SpotTokenBalance::new(Pubkey::new_unique(), 1, 255, 1000);
+        let serialized = borsh::to_vec(&balance).unwrap();
+        assert_eq!(serialized.len(), SPOT_TOKEN_BALANCE_SIZE);
+    }
+
+    #[test]
+    fn test_spot_token_balance_serialization() {
+        let wallet = Pubkey::new_unique();
+        let balance = SpotTokenBalance::new(wallet, 1, 200, 1234567890);
+
+        let serialized = borsh::to_vec(&balance).unwrap();
+        let deserialized = SpotTokenBalance::try_from_slice(&serialized).unwrap();
 
         assert_eq!(deserialized.discriminator, SPOT_TOKEN_BALANCE_DISCRIMINATOR);
         assert_eq!(deserialized.wallet, wallet);
@@ -482,15 +2132,18 @@ mod tests {
         assert_eq!(deserialized.locked_e6, 0);
         assert_eq!(deserialized.last_update_ts, 1234567890);
         assert_eq!(deserialized.bump, 200);
-        assert_eq!(deserialized.reserved, [0u8; 31]);
+        assert_eq!(deserialized.layout_version, SpotTokenBalance::LAYOUT_VERSION);
+        assert!(deserialized.liability_counted);
+        assert_eq!(deserialized.sequence, 0);
+        assert_eq!(deserialized.reserved, [0u8; 21]);
     }
 
     #[test]
     fn test_spot_token_balance_total() {
         let mut balance = SpotTokenBalance::new(Pubkey::new_unique(), 0, 255, 0);
-        balance.available_e6 = 1000_000_000;
+        balance.available_e6 = 1_000_000_000;
         balance.locked_e6 = 500_000_000;
-        assert_eq!(balance.total().unwrap(), 1500_000_000);
+        assert_eq!(balance.total().unwrap(), 1_500_000_000);
     }
 
     #[test]
@@ -515,6 +2168,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_spot_token_balance_pda_derivation() {
         let program_id = Pubkey::new_unique();
         let wallet = Pubkey::new_unique();
@@ -527,29 +2181,29 @@ mod tests {
         assert_ne!(pda1, pda2);
         // Same inputs → same PDA
         assert_eq!(pda1, pda3);
-        // Bumps are valid
-        assert!(bump1 <= 255);
-        assert!(bump2 <= 255);
+        // Legacy helper == account_index 0 (same PDA and bump)
+        assert_eq!((pda1, bump1), derive_spot_token_balance_pda_with_index(&program_id, &wallet, 0, 0));
+        assert_eq!((pda2, bump2), derive_spot_token_balance_pda_with_index(&program_id, &wallet, 0, 1));
     }
 
     #[test]
     fn test_spot_token_balance_lock_unlock() {
         let mut balance = SpotTokenBalance::new(Pubkey::new_unique(), 0, 255, 0);
-        balance.available_e6 = 1000_000_000;
+        balance.available_e6 = 1_000_000_000;
 
         // Lock 400: available=600, locked=400
         balance.available_e6 -= 400_000_000;
         balance.locked_e6 += 400_000_000;
         assert_eq!(balance.available_e6, 600_000_000);
         assert_eq!(balance.locked_e6, 400_000_000);
-        assert_eq!(balance.total().unwrap(), 1000_000_000); // conservation
+        assert_eq!(balance.total().unwrap(), 1_000_000_000); // conservation
 
         // Unlock 200: available=800, locked=200
         balance.locked_e6 -= 200_000_000;
         balance.available_e6 += 200_000_000;
         assert_eq!(balance.available_e6, 800_000_000);
         assert_eq!(balance.locked_e6, 200_000_000);
-        assert_eq!(balance.total().unwrap(), 1000_000_000); // conservation
+        assert_eq!(balance.total().unwrap(), 1_000_000_000); // conservation
 
         // Lock more than available should be caught by processor (checked arithmetic)
         let excess = balance.available_e6 + 1;
@@ -559,27 +2213,232 @@ mod tests {
     #[test]
     fn test_spot_token_balance_allocate_release_logic() {
         // Simulate allocate: UserAccount.available -= X, SpotTokenBalance.available += X
-        let mut user_available: i64 = 10000_000_000; // 10000 USDC
+        let mut user_available: i64 = 10_000_000_000; // 10000 USDC
         let mut spot_available: i64 = 0;
 
-        let amount: i64 = 3000_000_000; // allocate 3000 USDC
+        let amount: i64 = 3_000_000_000; // allocate 3000 USDC
 
         // Allocate
         assert!(user_available >= amount);
         user_available -= amount;
         spot_available += amount;
-        assert_eq!(user_available, 7000_000_000);
-        assert_eq!(spot_available, 3000_000_000);
+        assert_eq!(user_available, 7_000_000_000);
+        assert_eq!(spot_available, 3_000_000_000);
 
         // Release
-        let release: i64 = 1500_000_000;
+        let release: i64 = 1_500_000_000;
         assert!(spot_available >= release);
         spot_available -= release;
         user_available += release;
-        assert_eq!(user_available, 8500_000_000);
-        assert_eq!(spot_available, 1500_000_000);
+        assert_eq!(user_available, 8_500_000_000);
+        assert_eq!(spot_available, 1_500_000_000);
 
         // Conservation: total unchanged
-        assert_eq!(user_available + spot_available, 10000_000_000);
+        assert_eq!(user_available + spot_available, 10_000_000_000);
+    }
+
+    // === TokenRegistryEntry Tests ===
+
+    #[test]
+    fn test_token_registry_entry_size() {
+        let entry = TokenRegistryEntry {
+            discriminator: TokenRegistryEntry::DISCRIMINATOR,
+            token_index: 3,
+            mint: Pubkey::new_unique(),
+            decimals: 8,
+            token_program: Pubkey::new_unique(),
+            vault_token_account: Pubkey::new_unique(),
+            is_enabled: true,
+            bump: 255,
+            last_update_ts: 0,
+            vault_pays_transfer_fee: false,
+            mint_risk_flags: 0,
+            allowed_risk_flags: 0,
+            limits: VaultLimits::default(),
+            total_liabilities_e6: 0,
+            reserved: [0u8; 13],
+        };
+        let serialized = borsh::to_vec(&entry).unwrap();
+        assert_eq!(serialized.len(), TOKEN_REGISTRY_ENTRY_SIZE);
+    }
+
+    #[test]
+    fn test_token_vault_pda_derivation() {
+        let program_id = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let (vault_a, _) = derive_token_vault_pda(&program_id, &mint_a);
+        let (vault_b, _) = derive_token_vault_pda(&program_id, &mint_b);
+        assert_ne!(vault_a, vault_b);
+        assert_eq!(vault_a, derive_token_vault_pda(&program_id, &mint_a).0);
+        // Not the same address space as the registry PDA
+        assert_ne!(vault_a, TokenRegistryEntry::derive_pda(&program_id, 0).0);
+    }
+
+    #[test]
+    fn test_token_registry_entry_pda_derivation() {
+        let program_id = Pubkey::new_unique();
+
+        let (pda1, _) = TokenRegistryEntry::derive_pda(&program_id, 1);
+        let (pda2, _) = TokenRegistryEntry::derive_pda(&program_id, 2);
+        let (pda3, _) = TokenRegistryEntry::derive_pda(&program_id, 1);
+
+        assert_ne!(pda1, pda2, "Different token_index → different PDA");
+        assert_eq!(pda1, pda3, "Same inputs → same PDA");
+    }
+
+    fn test_governance_set(member_count: u8, threshold: u8) -> GovernanceSet {
+        let mut members = [Pubkey::default(); MAX_GOVERNANCE_MEMBERS];
+        for member in members.iter_mut().take(member_count as usize) {
+            *member = Pubkey::new_unique();
+        }
+        GovernanceSet {
+            discriminator: GovernanceSet::DISCRIMINATOR,
+            members,
+            member_count,
+            threshold,
+            set_version: 0,
+            proposal_ttl_secs: 86_400,
+            next_proposal_id: 0,
+            signer_bump: 255,
+            bump: 255,
+            reserved: [0u8; 64],
+        }
+    }
+
+    #[test]
+    fn test_governance_account_sizes() {
+        let set = test_governance_set(3, 2);
+        assert_eq!(borsh::to_vec(&set).unwrap().len(), GOVERNANCE_SET_SIZE);
+
+        let proposal = GovernanceProposal {
+            discriminator: GovernanceProposal::DISCRIMINATOR,
+            proposal_id: 0,
+            proposer: Pubkey::new_unique(),
+            set_version: 0,
+            approvals: 0,
+            created_ts: 0,
+            expires_ts: 0,
+            executed: false,
+            bump: 255,
+            accounts_hash: [0u8; 32],
+            instruction_len: 0,
+            instruction_data: [0u8; MAX_PROPOSAL_INSTRUCTION_LEN],
+            reserved: [0u8; 32],
+        };
+        assert_eq!(borsh::to_vec(&proposal).unwrap().len(), GOVERNANCE_PROPOSAL_SIZE);
+    }
+
+    #[test]
+    fn test_governance_set_members_and_approvals() {
+        let set = test_governance_set(3, 2);
+
+        assert_eq!(set.member_index(&set.members[0]), Some(0));
+        assert_eq!(set.member_index(&set.members[2]), Some(2));
+        assert_eq!(set.member_index(&Pubkey::new_unique()), None);
+        // Empty slots are never members
+        assert_eq!(set.member_index(&Pubkey::default()), None);
+
+        assert_eq!(set.approval_count(0b101), 2);
+        // Bits for inactive slots (e.g. left over from a larger set) are ignored
+        assert_eq!(set.approval_count(0b1111_1000), 0);
+        assert_eq!(set.approval_count(u16::MAX), 3);
+
+        let full = test_governance_set(MAX_GOVERNANCE_MEMBERS as u8, 10);
+        assert_eq!(full.approval_count(u16::MAX), 10);
+    }
+
+    #[test]
+    fn test_timelock_account_sizes() {
+        let config = TimelockConfig {
+            discriminator: TimelockConfig::DISCRIMINATOR,
+            admin: Pubkey::new_unique(),
+            min_delay_secs: [86_400; TIMELOCK_ACTION_TYPE_COUNT],
+            next_action_id: 0,
+            signer_bump: 255,
+            bump: 255,
+            reserved: [0u8; 64],
+        };
+        assert_eq!(borsh::to_vec(&config).unwrap().len(), TIMELOCK_CONFIG_SIZE);
+        assert_eq!(config.min_delay(TimelockActionType::TimelockConfig), 86_400);
+
+        // Exhaustive: a new variant fails to compile here until LAST is updated
+        for action_type in [
+            TimelockActionType::AuthorizedCallers,
+            TimelockActionType::GovernanceTransfer,
+            TimelockActionType::Pause,
+            TimelockActionType::Migration,
+            TimelockActionType::TokenRegistry,
+            TimelockActionType::TransferPolicy,
+            TimelockActionType::TimelockConfig,
+        ] {
+            match action_type {
+                TimelockActionType::AuthorizedCallers
+                | TimelockActionType::GovernanceTransfer
+                | TimelockActionType::Pause
+                | TimelockActionType::Migration
+                | TimelockActionType::TokenRegistry
+                | TimelockActionType::TransferPolicy
+                | TimelockActionType::TimelockConfig => assert!((action_type as usize) < TIMELOCK_ACTION_TYPE_COUNT),
+            }
+        }
+        assert_eq!(TIMELOCK_ACTION_TYPE_COUNT, 7);
+
+        let action = TimelockAction {
+            discriminator: TimelockAction::DISCRIMINATOR,
+            action_id: 0,
+            action_type: TimelockActionType::AuthorizedCallers as u8,
+            queued_by: Pubkey::new_unique(),
+            queued_ts: 0,
+            eta: 0,
+            executed: false,
+            cancelled: false,
+            bump: 255,
+            accounts_hash: [0u8; 32],
+            instruction_len: 0,
+            instruction_data: [0u8; MAX_PROPOSAL_INSTRUCTION_LEN],
+            reserved: [0u8; 32],
+        };
+        assert_eq!(borsh::to_vec(&action).unwrap().len(), TIMELOCK_ACTION_SIZE);
+    }
+
+    #[test]
+    fn test_timelock_action_execution_window() {
+        let mut action = TimelockAction {
+            discriminator: TimelockAction::DISCRIMINATOR,
+            action_id: 0,
+            action_type: 0,
+            queued_by: Pubkey::new_unique(),
+            queued_ts: 1_000,
+            eta: 1_000 + 86_400,
+            executed: false,
+            cancelled: false,
+            bump: 255,
+            accounts_hash: [0u8; 32],
+            instruction_len: 0,
+            instruction_data: [0u8; MAX_PROPOSAL_INSTRUCTION_LEN],
+            reserved: [0u8; 32],
+        };
+        assert!(!action.is_executable_at(action.eta - 1));
+        assert!(action.is_executable_at(action.eta));
+        assert!(action.is_executable_at(action.eta + TIMELOCK_GRACE_PERIOD_SECS));
+        assert!(!action.is_executable_at(action.eta + TIMELOCK_GRACE_PERIOD_SECS + 1));
+
+        action.eta = i64::MAX;
+        assert!(action.is_executable_at(i64::MAX));
+    }
+
+    #[test]
+    fn test_governance_pda_derivation() {
+        let program_id = Pubkey::new_unique();
+        let (set_pda, _) = GovernanceSet::derive_pda(&program_id);
+        let (signer_pda, _) = GovernanceSet::derive_signer_pda(&program_id);
+        assert_ne!(set_pda, signer_pda);
+
+        let (proposal_0, _) = GovernanceProposal::derive_pda(&program_id, 0);
+        let (proposal_1, _) = GovernanceProposal::derive_pda(&program_id, 1);
+        assert_ne!(proposal_0, proposal_1);
+        assert_eq!(proposal_0, GovernanceProposal::derive_pda(&program_id, 0).0);
     }
 }
@@ -10,7 +10,8 @@
 use solana_program::{
     account_info::AccountInfo,
     entrypoint::ProgramResult,
-    program::{invoke, invoke_signed},
+    program::{get_return_data, invoke, invoke_signed},
+    program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
 };
@@ -40,6 +41,303 @@ pub fn get_mint_size(_token_program_id: &Pubkey) -> usize {
     spl_token::state::Mint::LEN
 }
 
+/// Read `decimals` from a mint account owned by SPL Token or Token-2022.
+/// Mint layout: mint_authority COption(36) + supply(8) + decimals(1) + is_initialized(1) + ...
+pub fn get_mint_decimals(mint: &AccountInfo) -> Result<u8, ProgramError> {
+    if !is_valid_token_program(mint.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = mint.try_borrow_data()?;
+    if data.len() < spl_token::state::Mint::LEN || data[45] != 1 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(data[44])
+}
+
+/// Read `(mint, owner)` from a token account owned by SPL Token or Token-2022.
+/// Token Account layout: mint(32) + owner(32) + ...
+pub fn get_token_account_mint_and_owner(account: &AccountInfo) -> Result<(Pubkey, Pubkey), ProgramError> {
+    if !is_valid_token_program(account.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < spl_token::state::Account::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let mint = Pubkey::try_from(&data[0..32]).map_err(|_| ProgramError::InvalidAccountData)?;
+    let owner = Pubkey::try_from(&data[32..64]).map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok((mint, owner))
+}
+
+/// Read `amount` from a token account owned by SPL Token or Token-2022.
+/// Token Account layout: mint(32) + owner(32) + amount(8) + ...
+pub fn get_token_account_amount(account: &AccountInfo) -> Result<u64, ProgramError> {
+    if !is_valid_token_program(account.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < spl_token::state::Account::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let amount_bytes: [u8; 8] = data[64..72].try_into().map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(amount_bytes))
+}
+
+// =========================================================================
+// Token-2022 extensions (TLV)
+// =========================================================================
+//
+// A Token-2022 mint with extensions is laid out as:
+//   base Mint (82) + zero padding up to Account::LEN (165) + AccountType (1) + TLV entries
+// Each TLV entry: extension_type (u16 LE) + length (u16 LE) + value.
+
+/// Offset of the AccountType byte in an extended Token-2022 account
+const TOKEN_2022_ACCOUNT_TYPE_OFFSET: usize = 165;
+
+/// Token-2022 `ExtensionType::TransferFeeConfig`
+pub const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+/// Token-2022 `ExtensionType::MintCloseAuthority`
+pub const EXTENSION_MINT_CLOSE_AUTHORITY: u16 = 3;
+/// Token-2022 `ExtensionType::DefaultAccountState`
+pub const EXTENSION_DEFAULT_ACCOUNT_STATE: u16 = 6;
+/// Token-2022 `ExtensionType::InterestBearingConfig`
+pub const EXTENSION_INTEREST_BEARING_CONFIG: u16 = 10;
+/// Token-2022 `ExtensionType::PermanentDelegate`
+pub const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+/// Token-2022 `ExtensionType::TransferHook`
+pub const EXTENSION_TRANSFER_HOOK: u16 = 14;
+/// Token-2022 `ExtensionType::MetadataPointer` / `TokenMetadata`
+pub const EXTENSION_METADATA_POINTER: u16 = 18;
+pub const EXTENSION_TOKEN_METADATA: u16 = 19;
+
+/// Extensions that cannot move or lock vault custody
+const BENIGN_MINT_EXTENSIONS: [u16; 5] = [
+    EXTENSION_TRANSFER_FEE_CONFIG, // handled by net-received accounting
+    EXTENSION_MINT_CLOSE_AUTHORITY, // only closable at zero supply
+    EXTENSION_INTEREST_BEARING_CONFIG, // UI amount only
+    EXTENSION_METADATA_POINTER,
+    EXTENSION_TOKEN_METADATA,
+];
+
+// Mint risk flags (bitmask recorded in TokenRegistryEntry at listing time).
+// Any flag that is set must be explicitly allowed by governance in AddToken.
+
+/// Mint authority is set (supply can be inflated)
+pub const MINT_RISK_MINT_AUTHORITY: u8 = 1 << 0;
+/// Freeze authority is set (vault token account can be frozen)
+pub const MINT_RISK_FREEZE_AUTHORITY: u8 = 1 << 1;
+/// PermanentDelegate can transfer/burn from any token account, including the vault's
+pub const MINT_RISK_PERMANENT_DELEGATE: u8 = 1 << 2;
+/// TransferHook runs arbitrary program logic on every transfer
+pub const MINT_RISK_TRANSFER_HOOK: u8 = 1 << 3;
+/// DefaultAccountState=Frozen (new accounts start frozen)
+pub const MINT_RISK_DEFAULT_FROZEN: u8 = 1 << 4;
+/// Extension this program does not recognise
+pub const MINT_RISK_UNKNOWN_EXTENSION: u8 = 1 << 5;
+
+/// 100% in basis points
+const ONE_IN_BASIS_POINTS: u128 = 10_000;
+
+/// TLV entry whose length runs past the end of the account data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MalformedExtension;
+
+/// Iterator over the Token-2022 TLV entries of raw mint data, yielding
+/// `(extension_type, value)`. Plain SPL Token mints have no entries.
+/// Stops at ExtensionType::Uninitialized; a truncated entry yields one
+/// `Err(MalformedExtension)` and ends the iteration.
+struct MintExtensions<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+fn mint_extensions(mint_data: &[u8]) -> MintExtensions<'_> {
+    MintExtensions { data: mint_data, offset: TOKEN_2022_ACCOUNT_TYPE_OFFSET + 1 }
+}
+
+impl<'a> Iterator for MintExtensions<'a> {
+    type Item = Result<(u16, &'a [u8]), MalformedExtension>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.data.get(self.offset..self.offset + 4)?;
+        let ext_type = u16::from_le_bytes([header[0], header[1]]);
+        // ExtensionType::Uninitialized marks the end of the TLV area
+        if ext_type == 0 {
+            return None;
+        }
+        let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let value_start = self.offset + 4;
+        let Some(value) = self.data.get(value_start..value_start + length) else {
+            self.offset = self.data.len();
+            return Some(Err(MalformedExtension));
+        };
+        self.offset = value_start + length;
+        Some(Ok((ext_type, value)))
+    }
+}
+
+/// Find the value of a Token-2022 extension in raw mint data.
+/// Returns None for plain SPL Token mints and mints without that extension.
+pub fn get_mint_extension_data(mint_data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    mint_extensions(mint_data)
+        .map_while(Result::ok)
+        .find(|(ext_type, _)| *ext_type == extension_type)
+        .map(|(_, value)| value)
+}
+
+/// Scan a mint (SPL Token or Token-2022) and return its `MINT_RISK_*` flags.
+///
+/// Mint layout: mint_authority COption(4+32) + supply(8) + decimals(1)
+/// + is_initialized(1) + freeze_authority COption(4+32), then Token-2022 TLV extensions.
+pub fn get_mint_risk_flags(mint_data: &[u8]) -> u8 {
+    let mut flags = 0u8;
+    if mint_data.len() < spl_token::state::Mint::LEN {
+        return flags;
+    }
+    if mint_data[0..4] == [1, 0, 0, 0] {
+        flags |= MINT_RISK_MINT_AUTHORITY;
+    }
+    if mint_data[46..50] == [1, 0, 0, 0] {
+        flags |= MINT_RISK_FREEZE_AUTHORITY;
+    }
+
+    for extension in mint_extensions(mint_data) {
+        let Ok((ext_type, value)) = extension else {
+            return flags | MINT_RISK_UNKNOWN_EXTENSION;
+        };
+        match ext_type {
+            EXTENSION_PERMANENT_DELEGATE => {
+                // OptionalNonZeroPubkey: all zeroes = no delegate
+                if value.iter().any(|b| *b != 0) {
+                    flags |= MINT_RISK_PERMANENT_DELEGATE;
+                }
+            }
+            EXTENSION_TRANSFER_HOOK => flags |= MINT_RISK_TRANSFER_HOOK,
+            EXTENSION_DEFAULT_ACCOUNT_STATE => {
+                // AccountState: 0 = Uninitialized, 1 = Initialized, 2 = Frozen
+                if value.first() == Some(&2) {
+                    flags |= MINT_RISK_DEFAULT_FROZEN;
+                }
+            }
+            t if BENIGN_MINT_EXTENSIONS.contains(&t) => {}
+            _ => flags |= MINT_RISK_UNKNOWN_EXTENSION,
+        }
+    }
+    flags
+}
+
+/// One epoch's transfer fee (Token-2022 `TransferFee`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 18 {
+            return None;
+        }
+        Some(Self {
+            epoch: u64::from_le_bytes(data[0..8].try_into().ok()?),
+            maximum_fee: u64::from_le_bytes(data[8..16].try_into().ok()?),
+            transfer_fee_basis_points: u16::from_le_bytes(data[16..18].try_into().ok()?),
+        })
+    }
+
+    /// Fee withheld when `amount` is sent (rounded up, capped at `maximum_fee`)
+    pub fn calculate_fee(&self, amount: u64) -> Option<u64> {
+        let bps = self.transfer_fee_basis_points as u128;
+        if bps == 0 || amount == 0 {
+            return Some(0);
+        }
+        let numerator = (amount as u128).checked_mul(bps)?;
+        let raw_fee = numerator.checked_add(ONE_IN_BASIS_POINTS - 1)? / ONE_IN_BASIS_POINTS;
+        let fee = u64::try_from(raw_fee).ok()?;
+        Some(fee.min(self.maximum_fee))
+    }
+
+    /// Gross amount to send so that the recipient receives `post_fee_amount`
+    pub fn calculate_pre_fee_amount(&self, post_fee_amount: u64) -> Option<u64> {
+        let bps = self.transfer_fee_basis_points as u128;
+        if bps == 0 || self.maximum_fee == 0 {
+            return Some(post_fee_amount);
+        }
+        if bps == ONE_IN_BASIS_POINTS {
+            return post_fee_amount.checked_add(self.maximum_fee);
+        }
+        let numerator = (post_fee_amount as u128).checked_mul(ONE_IN_BASIS_POINTS)?;
+        let denominator = ONE_IN_BASIS_POINTS - bps;
+        let raw_pre_fee = numerator.checked_add(denominator - 1)? / denominator;
+        if raw_pre_fee - post_fee_amount as u128 >= self.maximum_fee as u128 {
+            post_fee_amount.checked_add(self.maximum_fee)
+        } else {
+            u64::try_from(raw_pre_fee).ok()
+        }
+    }
+}
+
+/// Transfer fee in effect at `epoch` for a Token-2022 mint with `TransferFeeConfig`.
+/// Returns None when the mint has no transfer-fee extension.
+///
+/// TransferFeeConfig layout: transfer_fee_config_authority(32) + withdraw_withheld_authority(32)
+/// + withheld_amount(8) + older_transfer_fee(18) + newer_transfer_fee(18)
+pub fn get_transfer_fee(mint_data: &[u8], epoch: u64) -> Option<TransferFee> {
+    let config = get_mint_extension_data(mint_data, EXTENSION_TRANSFER_FEE_CONFIG)?;
+    if config.len() < 108 {
+        return None;
+    }
+    let older = TransferFee::unpack(&config[72..90])?;
+    let newer = TransferFee::unpack(&config[90..108])?;
+    Some(if epoch >= newer.epoch { newer } else { older })
+}
+
+/// Ask the token program how large a token account for `mint` must be
+/// (`GetAccountDataSize`, instruction 21 in both v1 and v2). Token-2022 mints
+/// with extensions need extra account space, e.g. TransferFeeAmount.
+pub fn get_account_data_size<'a>(
+    token_program: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+) -> Result<u64, ProgramError> {
+    let ix = solana_program::instruction::Instruction {
+        program_id: *token_program.key,
+        accounts: vec![solana_program::instruction::AccountMeta::new_readonly(*mint.key, false)],
+        data: vec![21u8],
+    };
+    invoke(&ix, std::slice::from_ref(mint))?;
+
+    let (returning_program, data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if returning_program != *token_program.key || data.len() != 8 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let size_bytes: [u8; 8] = data[..8].try_into().map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(size_bytes))
+}
+
+/// InitializeAccount3 (instruction 18 in both v1 and v2): initialize an allocated
+/// token account for `mint` with `owner` as its authority. No Rent sysvar needed.
+pub fn initialize_account3<'a>(
+    token_program: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    owner: &Pubkey,
+) -> ProgramResult {
+    let mut data = Vec::with_capacity(33);
+    data.push(18u8);
+    data.extend_from_slice(owner.as_ref());
+
+    let ix = solana_program::instruction::Instruction {
+        program_id: *token_program.key,
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*account.key, false),
+            solana_program::instruction::AccountMeta::new_readonly(*mint.key, false),
+        ],
+        data,
+    };
+    invoke(&ix, &[account.clone(), mint.clone()])
+}
+
 /// Create a TransferChecked instruction (works for both v1 and v2).
 /// Preferred over Transfer for Token-2022 compatibility — validates decimals
 /// and mint, preventing silent truncation or wrong-mint transfers.
@@ -73,6 +371,7 @@ pub fn create_transfer_checked_instruction(
 
 /// TransferChecked with dynamic program support.
 /// Preferred for Token-2022 tokens; requires the mint account to validate decimals.
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_checked<'a>(
     token_program: &AccountInfo<'a>,
     source: &AccountInfo<'a>,
@@ -141,8 +440,10 @@ pub fn create_transfer_instruction(
 /// (Token v1) where Transfer is safe, but SpotDeposit/SpotWithdraw should
 /// migrate to `transfer_checked` when adding Token-2022 asset support.
 ///
-/// OC-M6: Migration to `transfer_checked` requires adding mint account to each
-/// instruction's accounts list. Deferred until Token-2022 support is implemented.
+/// OC-M6: Every transfer path now accepts a trailing Mint account and uses
+/// `transfer_checked` when it is present. This function only serves legacy
+/// account layouts (no Mint) during the transition window, and is rejected
+/// once governance sets `VaultConfig.require_transfer_checked`.
 #[deprecated(note = "OC-M6: Use transfer_checked with mint account for Token-2022 compatibility")]
 pub fn transfer<'a>(
     token_program: &AccountInfo<'a>,
@@ -195,4 +496,108 @@ mod tests {
         assert_eq!(get_mint_size(&TOKEN_PROGRAM_V1), 82);
         assert_eq!(get_mint_size(&TOKEN_PROGRAM_V2), 82);
     }
+
+    fn transfer_fee_mint_data(older: TransferFee, newer: TransferFee) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_2022_ACCOUNT_TYPE_OFFSET];
+        data.push(1); // AccountType::Mint
+        data.extend_from_slice(&EXTENSION_TRANSFER_FEE_CONFIG.to_le_bytes());
+        data.extend_from_slice(&108u16.to_le_bytes());
+        data.extend_from_slice(&[0u8; 72]);
+        for fee in [older, newer] {
+            data.extend_from_slice(&fee.epoch.to_le_bytes());
+            data.extend_from_slice(&fee.maximum_fee.to_le_bytes());
+            data.extend_from_slice(&fee.transfer_fee_basis_points.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_get_transfer_fee() {
+        let older = TransferFee { epoch: 0, maximum_fee: 1_000, transfer_fee_basis_points: 50 };
+        let newer = TransferFee { epoch: 10, maximum_fee: 5_000, transfer_fee_basis_points: 100 };
+        let data = transfer_fee_mint_data(older, newer);
+
+        assert_eq!(get_transfer_fee(&data, 9), Some(older));
+        assert_eq!(get_transfer_fee(&data, 10), Some(newer));
+        // Plain SPL Token mint: no extensions
+        assert_eq!(get_transfer_fee(&[0u8; 82], 10), None);
+    }
+
+    fn push_extension(data: &mut Vec<u8>, ext_type: u16, value: &[u8]) {
+        data.extend_from_slice(&ext_type.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+
+    #[test]
+    fn test_get_mint_risk_flags() {
+        // Plain SPL Token mint with mint + freeze authority
+        let mut base = vec![0u8; spl_token::state::Mint::LEN];
+        base[0] = 1;
+        base[46] = 1;
+        assert_eq!(get_mint_risk_flags(&base), MINT_RISK_MINT_AUTHORITY | MINT_RISK_FREEZE_AUTHORITY);
+        assert_eq!(get_mint_risk_flags(&[0u8; 82]), 0);
+
+        // Token-2022 mint with a mix of extensions
+        let mut data = vec![0u8; TOKEN_2022_ACCOUNT_TYPE_OFFSET];
+        data.push(1); // AccountType::Mint
+        push_extension(&mut data, EXTENSION_TOKEN_METADATA, &[7u8; 10]);
+        push_extension(&mut data, EXTENSION_PERMANENT_DELEGATE, &[0u8; 32]); // no delegate
+        push_extension(&mut data, EXTENSION_DEFAULT_ACCOUNT_STATE, &[1]); // Initialized
+        assert_eq!(get_mint_risk_flags(&data), 0);
+
+        push_extension(&mut data, EXTENSION_PERMANENT_DELEGATE, &[9u8; 32]);
+        push_extension(&mut data, EXTENSION_TRANSFER_HOOK, &[0u8; 64]);
+        push_extension(&mut data, EXTENSION_DEFAULT_ACCOUNT_STATE, &[2]); // Frozen
+        push_extension(&mut data, 999, &[]);
+        assert_eq!(
+            get_mint_risk_flags(&data),
+            MINT_RISK_PERMANENT_DELEGATE | MINT_RISK_TRANSFER_HOOK | MINT_RISK_DEFAULT_FROZEN | MINT_RISK_UNKNOWN_EXTENSION
+        );
+    }
+
+    #[test]
+    fn test_mint_extensions_tlv() {
+        let mut data = vec![0u8; TOKEN_2022_ACCOUNT_TYPE_OFFSET];
+        data.push(1); // AccountType::Mint
+        push_extension(&mut data, EXTENSION_METADATA_POINTER, &[3u8; 64]);
+        push_extension(&mut data, EXTENSION_MINT_CLOSE_AUTHORITY, &[4u8; 32]);
+        assert_eq!(get_mint_extension_data(&data, EXTENSION_MINT_CLOSE_AUTHORITY), Some(&[4u8; 32][..]));
+        assert_eq!(get_mint_extension_data(&data, EXTENSION_TRANSFER_HOOK), None);
+        assert_eq!(get_mint_risk_flags(&data), 0);
+
+        // Entries after ExtensionType::Uninitialized are ignored by both readers
+        let mut terminated = data.clone();
+        push_extension(&mut terminated, 0, &[]);
+        push_extension(&mut terminated, EXTENSION_TRANSFER_HOOK, &[0u8; 64]);
+        assert_eq!(get_mint_extension_data(&terminated, EXTENSION_TRANSFER_HOOK), None);
+        assert_eq!(get_mint_risk_flags(&terminated), 0);
+
+        // Truncated entry: not found, and flagged as unknown
+        data.extend_from_slice(&EXTENSION_TRANSFER_HOOK.to_le_bytes());
+        data.extend_from_slice(&64u16.to_le_bytes());
+        data.extend_from_slice(&[0u8; 10]);
+        assert_eq!(get_mint_extension_data(&data, EXTENSION_TRANSFER_HOOK), None);
+        assert_eq!(get_mint_extension_data(&data, EXTENSION_MINT_CLOSE_AUTHORITY), Some(&[4u8; 32][..]));
+        assert_eq!(get_mint_risk_flags(&data), MINT_RISK_UNKNOWN_EXTENSION);
+    }
+
+    #[test]
+    fn test_transfer_fee_calculation() {
+        let fee = TransferFee { epoch: 0, maximum_fee: 5_000, transfer_fee_basis_points: 100 }; // 1%
+
+        assert_eq!(fee.calculate_fee(0), Some(0));
+        assert_eq!(fee.calculate_fee(10_000), Some(100));
+        assert_eq!(fee.calculate_fee(10_001), Some(101)); // rounded up
+        assert_eq!(fee.calculate_fee(10_000_000), Some(5_000)); // capped
+
+        // Gross-up: recipient receives exactly the post-fee amount
+        for post in [1u64, 9_900, 10_000, 123_456, 10_000_000] {
+            let pre = fee.calculate_pre_fee_amount(post).unwrap();
+            assert_eq!(pre - fee.calculate_fee(pre).unwrap(), post);
+        }
+
+        let no_fee = TransferFee { epoch: 0, maximum_fee: 0, transfer_fee_basis_points: 0 };
+        assert_eq!(no_fee.calculate_pre_fee_amount(42), Some(42));
+    }
 }
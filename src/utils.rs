@@ -39,6 +39,54 @@ pub fn checked_add_u64(a: u64, b: u64) -> Result<u64, ProgramError> {
     a.checked_add(b).ok_or(VaultError::Overflow.into())
 }
 
+/// 原生数量 → e6 精度换算的取整方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// 向下取整 (入金: 记账不超过实际收到的数量)
+    Down,
+    /// 向上取整 (出金: 扣账不少于实际转出的数量)
+    Up,
+}
+
+/// 将原生 token 数量按 mint decimals 换算为 e6 精度
+///
+/// - decimals <= 6: 精确放大 `amount * 10^(6 - decimals)`
+/// - decimals > 6: 缩小 `amount / 10^(decimals - 6)`，余数按 `rounding` 处理
+// is_multiple_of 需要 Rust 1.87+，Solana 1.18 的 SBF 工具链无法编译
+#[allow(clippy::manual_is_multiple_of)]
+pub fn native_to_e6(amount: u64, decimals: u8, rounding: Rounding) -> Result<i64, ProgramError> {
+    let amount = amount as u128;
+    let value = if decimals <= 6 {
+        let factor = 10u128.pow((6 - decimals) as u32);
+        amount.checked_mul(factor).ok_or(VaultError::Overflow)?
+    } else {
+        let divisor = 10u128
+            .checked_pow((decimals - 6) as u32)
+            .ok_or(VaultError::Overflow)?;
+        let quotient = amount / divisor;
+        if rounding == Rounding::Up && amount % divisor != 0 {
+            quotient + 1
+        } else {
+            quotient
+        }
+    };
+    i64::try_from(value).map_err(|_| VaultError::Overflow.into())
+}
+
+/// 将 e6 精度换算为原生 token 数量，向下取整 (出金不超过记账金额)
+pub fn e6_to_native(amount_e6: i64, decimals: u8) -> Result<u64, ProgramError> {
+    let amount = u128::try_from(amount_e6).map_err(|_| VaultError::InvalidAmount)?;
+    let value = if decimals >= 6 {
+        let factor = 10u128
+            .checked_pow((decimals - 6) as u32)
+            .ok_or(VaultError::Overflow)?;
+        amount.checked_mul(factor).ok_or(VaultError::Overflow)?
+    } else {
+        amount / 10u128.pow((6 - decimals) as u32)
+    };
+    u64::try_from(value).map_err(|_| VaultError::Overflow.into())
+}
+
 /// 获取当前时间戳
 /// 
 /// 注意: 在 BPF 环境中应使用 Clock sysvar
@@ -78,5 +126,32 @@ mod tests {
         assert_eq!(checked_sub(100, 200).unwrap(), -100);
         assert!(checked_sub(i64::MIN, 1).is_err());
     }
+
+    #[test]
+    fn test_native_to_e6() {
+        // 6 decimals: identity
+        assert_eq!(native_to_e6(1_500_000, 6, Rounding::Down).unwrap(), 1_500_000);
+        // wBTC (8 decimals): 1 BTC = 1e8 sats → 1e6
+        assert_eq!(native_to_e6(100_000_000, 8, Rounding::Down).unwrap(), 1_000_000);
+        // 1 sat is dust: rounds down to 0, up to 1
+        assert_eq!(native_to_e6(1, 8, Rounding::Down).unwrap(), 0);
+        assert_eq!(native_to_e6(1, 8, Rounding::Up).unwrap(), 1);
+        assert_eq!(native_to_e6(150, 8, Rounding::Down).unwrap(), 1);
+        assert_eq!(native_to_e6(150, 8, Rounding::Up).unwrap(), 2);
+        // Fewer than 6 decimals scale up exactly
+        assert_eq!(native_to_e6(7, 2, Rounding::Down).unwrap(), 70_000);
+        // Out of i64 range
+        assert!(native_to_e6(u64::MAX, 0, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn test_e6_to_native() {
+        assert_eq!(e6_to_native(1_500_000, 6).unwrap(), 1_500_000);
+        assert_eq!(e6_to_native(1_000_000, 8).unwrap(), 100_000_000);
+        // Fewer than 6 decimals round down
+        assert_eq!(e6_to_native(79_999, 2).unwrap(), 7);
+        assert!(e6_to_native(-1, 6).is_err());
+        assert!(e6_to_native(i64::MAX, 18).is_err());
+    }
 }
 
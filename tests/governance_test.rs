@@ -0,0 +1,2717 @@
+//! Governance Integration Tests
+//!
+//! Covers: two-step governance authority transfer (Propose / Accept / Cancel),
+//!         deprecated UpdateGovernanceAuthority, MigrateVaultConfig V2 → V3, lazy VaultConfig
+//!         migration on write, MigrateAccount,
+//!         multisig governance, timelock, role-based access control, relayer rate limits,
+//!         pause flags, guardians, the outflow circuit breaker, the escape hatch, limits,
+//!         liability counters, the solvency check and liability Merkle roots.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_program_test::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    packet::PACKET_DATA_SIZE,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use vault_program::{
+    error::VaultError,
+    instruction::{
+        ExpectedUserAccount, StateWriteResult, UserAccountUpdate, VaultInstruction, WithdrawOutcome,
+        MAX_BATCH_USER_ACCOUNT_UPDATES, MAX_TRANSACTION_SIZE, USER_ACCOUNT_UPDATE_SIZE,
+    },
+    merkle::{LiabilityLeaf, LiabilityTree, SumNode},
+    state::*,
+};
+
+fn derive_vault_config_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault_config"], program_id)
+}
+
+/// 全零的当前布局 VaultConfig，只填测试关心的字段 (新增字段时无需修改)
+fn test_vault_config(governance_authority: &Pubkey) -> VaultConfig {
+    let mut data = vec![0u8; VAULT_CONFIG_SIZE];
+    data[..8].copy_from_slice(&VaultConfig::DISCRIMINATOR.to_le_bytes());
+    let mut config = VaultConfig::try_from_slice(&data).unwrap();
+    config.governance_authority = *governance_authority;
+    config.usdc_mint = Pubkey::new_unique();
+    config.vault_token_account = Pubkey::new_unique();
+    config.delegation_program = Pubkey::new_unique();
+    config.layout_version = VaultConfig::LAYOUT_VERSION;
+    config
+}
+
+fn add_funded_signer(program_test: &mut ProgramTest, key: &Pubkey) {
+    program_test.add_account(*key, Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+}
+
+/// A PDA someone griefed by sending it a lamport before the program created it
+fn add_prefunded_pda(program_test: &mut ProgramTest, address: Pubkey) {
+    program_test.add_account(address, Account {
+        lamports: 1,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+}
+
+/// ProgramTest with a preloaded VaultConfig PDA (raw bytes) and funded governance key
+fn setup_vault(program_id: &Pubkey, governance: &Keypair, vault_config_data: Vec<u8>) -> ProgramTest {
+    let mut program_test = ProgramTest::new(
+        "vault_program",
+        *program_id,
+        processor!(vault_program::processor::process_instruction),
+    );
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    program_test.add_account(vault_config_pda, Account {
+        lamports: Rent::default().minimum_balance(vault_config_data.len()),
+        data: vault_config_data,
+        owner: *program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    add_funded_signer(&mut program_test, &governance.pubkey());
+    program_test
+}
+
+fn build_governance_ix(program_id: &Pubkey, signer: &Pubkey, instruction: VaultInstruction) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(vault_config_pda, false),
+        ],
+        data: instruction.try_to_vec().unwrap(),
+    }
+}
+
+async fn send(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    signer: &Keypair,
+    ix: Instruction,
+) -> Result<(), BanksClientError> {
+    // Fresh blockhash so a repeated instruction is never deduplicated
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    banks_client
+        .process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer, signer], bh))
+        .await
+}
+
+async fn read_vault_config(banks_client: &mut BanksClient, program_id: &Pubkey) -> VaultConfig {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    VaultConfig::try_from_slice(&account.data).unwrap()
+}
+
+// ============================================================
+// Test: Propose → Accept
+// ============================================================
+#[tokio::test]
+async fn test_governance_transfer_propose_accept() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let new_governance = Keypair::new();
+    let impostor = Keypair::new();
+    let program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // Only the current governance authority may propose
+    let ix = build_governance_ix(&program_id, &impostor.pubkey(), VaultInstruction::ProposeGovernanceAuthority { new_authority: impostor.pubkey() });
+    assert!(send(&mut banks_client, &payer, &impostor, ix).await.is_err());
+
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::ProposeGovernanceAuthority { new_authority: new_governance.pubkey() });
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, governance.pubkey());
+    assert_eq!(vault_config.pending_governance_authority, new_governance.pubkey());
+
+    // Accept must be signed by the pending key
+    let ix = build_governance_ix(&program_id, &impostor.pubkey(), VaultInstruction::AcceptGovernanceAuthority);
+    assert!(send(&mut banks_client, &payer, &impostor, ix).await.is_err());
+
+    let ix = build_governance_ix(&program_id, &new_governance.pubkey(), VaultInstruction::AcceptGovernanceAuthority);
+    send(&mut banks_client, &payer, &new_governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, new_governance.pubkey());
+    assert_eq!(vault_config.pending_governance_authority, Pubkey::default());
+
+    // The previous authority has lost governance
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+}
+
+// ============================================================
+// Test: Cancel, and UpdateGovernanceAuthority only proposes
+// ============================================================
+#[tokio::test]
+async fn test_governance_transfer_cancel() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let typo = Keypair::new();
+    let program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // Nothing pending yet
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::CancelGovernanceTransfer);
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+
+    // Legacy instruction no longer overwrites governance_authority
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::UpdateGovernanceAuthority { new_governance_authority: typo.pubkey() });
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, governance.pubkey());
+    assert_eq!(vault_config.pending_governance_authority, typo.pubkey());
+
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::CancelGovernanceTransfer);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, governance.pubkey());
+    assert_eq!(vault_config.pending_governance_authority, Pubkey::default());
+
+    // A cancelled proposal can no longer be accepted
+    let ix = build_governance_ix(&program_id, &typo.pubkey(), VaultInstruction::AcceptGovernanceAuthority);
+    assert!(send(&mut banks_client, &payer, &typo, ix).await.is_err());
+}
+
+// ============================================================
+// Test: MigrateVaultConfig V2 (505 bytes) → V3 (1024 bytes)
+// ============================================================
+#[tokio::test]
+async fn test_migrate_vault_config_v2_to_v3() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.total_deposits = 42_000_000;
+    vault_config.is_paused = true;
+    vault_config.require_transfer_checked = true;
+    let relayer = Pubkey::new_unique();
+    vault_config.authorized_callers[3] = relayer;
+
+    // V2 layout = V3 prefix up to require_transfer_checked + 31 zero reserved bytes
+    let mut v2_data = vault_config.try_to_vec().unwrap();
+    v2_data.truncate(VAULT_CONFIG_SIZE_V2);
+    v2_data[474..].fill(0);
+
+    let program_test = setup_vault(&program_id, &governance, v2_data);
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::MigrateVaultConfig.try_to_vec().unwrap(),
+    };
+    send(&mut banks_client, &payer, &governance, ix.clone()).await.unwrap();
+
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), VAULT_CONFIG_SIZE);
+    assert!(account.lamports >= Rent::default().minimum_balance(VAULT_CONFIG_SIZE));
+
+    let migrated = VaultConfig::try_from_slice(&account.data).unwrap();
+    assert_eq!(migrated.governance_authority, governance.pubkey());
+    assert_eq!(migrated.usdc_mint, vault_config.usdc_mint);
+    assert_eq!(migrated.delegation_program, vault_config.delegation_program);
+    assert_eq!(migrated.total_deposits, 42_000_000);
+    assert!(migrated.is_paused);
+    assert!(migrated.require_transfer_checked);
+    assert_eq!(migrated.pending_governance_authority, Pubkey::default());
+    // Existing callers keep their relayer powers as roles
+    assert_eq!(migrated.roles_of(&relayer), LEGACY_RELAYER_ROLES);
+    assert_eq!(migrated.limits, VaultLimits::usdc_default());
+    assert!(!migrated.has_role(&relayer, VaultRole::Pauser));
+
+    // Already at V3
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+}
+
+// ============================================================
+// Test: an unmigrated V2 VaultConfig keeps working after the upgrade
+// and is migrated in place by the first write
+// ============================================================
+#[tokio::test]
+async fn test_unmigrated_vault_config_lazy_migration() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.total_deposits = 42_000_000;
+    let relayer = Pubkey::new_unique();
+    vault_config.authorized_callers[3] = relayer;
+    let mut v2_data = vault_config.try_to_vec().unwrap();
+    v2_data.truncate(VAULT_CONFIG_SIZE_V2);
+    v2_data[474..].fill(0);
+
+    let program_test = setup_vault(&program_id, &governance, v2_data);
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    // Decoded fine, but the 505-byte account cannot grow without rent for 1024 bytes
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    assert_eq!(
+        send(&mut banks_client, &payer, &governance, ix.clone()).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::AccountNotRentExempt),
+    );
+
+    // Pre-fund the PDA (the documented upgrade step); the next write migrates it
+    let top_up = Rent::default().minimum_balance(VAULT_CONFIG_SIZE) - Rent::default().minimum_balance(VAULT_CONFIG_SIZE_V2);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let transfer = solana_sdk::system_instruction::transfer(&payer.pubkey(), &vault_config_pda, top_up);
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[transfer], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), VAULT_CONFIG_SIZE);
+    let migrated = VaultConfig::try_from_slice(&account.data).unwrap();
+    assert!(migrated.is_paused);
+    assert_eq!(migrated.layout_version, VaultConfig::LAYOUT_VERSION);
+    assert_eq!(migrated.total_deposits, 42_000_000);
+    assert_eq!(migrated.roles_of(&relayer), LEGACY_RELAYER_ROLES);
+    assert_eq!(migrated.limits, VaultLimits::usdc_default());
+}
+
+// ============================================================
+// Test: MigrateAccount — versioned layouts, idempotent
+// ============================================================
+
+fn build_migrate_account_ix(program_id: &Pubkey, payer: &Pubkey, account: &Pubkey, liability_counter: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(liability_counter.map(|counter| AccountMeta::new(*counter, false)));
+    Instruction { program_id: *program_id, accounts, data: VaultInstruction::MigrateAccount.try_to_vec().unwrap() }
+}
+
+#[tokio::test]
+async fn test_migrate_account() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let anyone = Keypair::new();
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.total_deposits = 9_000_000;
+    let relayer = Pubkey::new_unique();
+    vault_config.authorized_callers[1] = relayer;
+
+    // V1 layout: shared prefix + ledger_program + fund_program + V2 body + reserved
+    let v3 = vault_config.try_to_vec().unwrap();
+    let mut v1_data = Vec::with_capacity(VAULT_CONFIG_SIZE_V1);
+    v1_data.extend_from_slice(&v3[..424]);
+    v1_data.extend_from_slice(&[7u8; 64]);
+    v1_data.extend_from_slice(&v3[424..474]);
+    v1_data.resize(VAULT_CONFIG_SIZE_V1, 0);
+
+    let mut program_test = setup_vault(&program_id, &governance, v1_data);
+    add_funded_signer(&mut program_test, &anyone.pubkey());
+
+    // UserAccount created before layout_version existed
+    let wallet = Pubkey::new_unique();
+    let (user_pda, bump) = UserAccount::derive_pda(&program_id, &wallet, 0);
+    let legacy_user = UserAccount {
+        discriminator: UserAccount::DISCRIMINATOR,
+        wallet,
+        bump,
+        available_balance_e6: 3_000_000,
+        locked_margin_e6: 0,
+        unrealized_pnl_e6: 0,
+        total_deposited_e6: 3_000_000,
+        total_withdrawn_e6: 0,
+        last_update_ts: 0,
+        spot_locked_e6: 0,
+        account_index: 0,
+        oracle_locked_e6: 0,
+        layout_version: 0,
+        liability_counted: false,
+        sequence: 0,
+        reserved: [0; 34],
+    };
+    // Same bytes at a non-PDA address
+    let stray_user = Pubkey::new_unique();
+    for address in [user_pda, stray_user] {
+        program_test.add_account(address, Account {
+            lamports: Rent::default().minimum_balance(USER_ACCOUNT_SIZE),
+            data: legacy_user.try_to_vec().unwrap(),
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        });
+    }
+
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    // VaultConfig: governance only
+    let outsider_ix = build_migrate_account_ix(&program_id, &anyone.pubkey(), &vault_config_pda, None);
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &anyone, outsider_ix).await),
+        Some(VaultError::UnauthorizedUser as u32)
+    );
+
+    let ix = build_migrate_account_ix(&program_id, &governance.pubkey(), &vault_config_pda, None);
+    send(&mut banks_client, &payer, &governance, ix.clone()).await.unwrap();
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), VAULT_CONFIG_SIZE);
+    assert!(account.lamports >= Rent::default().minimum_balance(VAULT_CONFIG_SIZE));
+    let migrated = VaultConfig::try_from_slice(&account.data).unwrap();
+    assert_eq!(migrated.layout_version, VaultConfig::LAYOUT_VERSION);
+    assert_eq!(migrated.total_deposits, 9_000_000);
+    assert_eq!(migrated.delegation_program, vault_config.delegation_program);
+    assert_eq!(migrated.roles_of(&relayer), LEGACY_RELAYER_ROLES);
+    assert_eq!(migrated.limits, VaultLimits::usdc_default());
+
+    // Idempotent: a second run succeeds and changes nothing
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    let rerun = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(rerun.data, account.data);
+
+    // UserAccount: permissionless, PDA checked, backfilled into the liability counters
+    assert_eq!(migrated.total_liabilities_e6, 0);
+    let mut ix = build_migrate_account_ix(&program_id, &anyone.pubkey(), &user_pda, Some(&vault_config_pda));
+    ix.accounts[3].is_writable = false;
+    assert!(send(&mut banks_client, &payer, &anyone, ix.clone()).await.is_err());
+    ix.accounts[3].is_writable = true;
+    send(&mut banks_client, &payer, &anyone, ix.clone()).await.unwrap();
+    let data = banks_client.get_account(user_pda).await.unwrap().unwrap().data;
+    let migrated_user = UserAccount::try_from_slice(&data).unwrap();
+    assert_eq!(migrated_user.layout_version, UserAccount::LAYOUT_VERSION);
+    assert_eq!(migrated_user.wallet, wallet);
+    assert_eq!(migrated_user.available_balance_e6, 3_000_000);
+    assert!(migrated_user.liability_counted);
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.total_liabilities_e6, 3_000_000);
+
+    // Re-running counts nothing twice
+    send(&mut banks_client, &payer, &anyone, ix).await.unwrap();
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.total_liabilities_e6, 3_000_000);
+
+    let stray_ix = build_migrate_account_ix(&program_id, &anyone.pubkey(), &stray_user, Some(&vault_config_pda));
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &anyone, stray_ix).await),
+        Some(VaultError::InvalidPda as u32)
+    );
+    let stray = banks_client.get_account(stray_user).await.unwrap().unwrap();
+    assert_eq!(stray.data, legacy_user.try_to_vec().unwrap());
+}
+
+// ============================================================
+// Multisig governance helpers
+// ============================================================
+
+fn build_initialize_governance_set_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    members: Vec<Pubkey>,
+    threshold: u8,
+    proposal_ttl_secs: i64,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*governance_authority, true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(governance_set_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::InitializeGovernanceSet { members, threshold, proposal_ttl_secs }
+            .try_to_vec()
+            .unwrap(),
+    }
+}
+
+fn build_create_proposal_ix(
+    program_id: &Pubkey,
+    proposer: &Pubkey,
+    proposal_id: u64,
+    action: VaultInstruction,
+    action_accounts: &[AccountMeta],
+) -> Instruction {
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(program_id);
+    let (proposal_pda, _) = GovernanceProposal::derive_pda(program_id, proposal_id);
+    let mut accounts = vec![
+        AccountMeta::new(*proposer, true),
+        AccountMeta::new(governance_set_pda, false),
+        AccountMeta::new(proposal_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend_from_slice(action_accounts);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::CreateGovernanceProposal { instruction_data: action.try_to_vec().unwrap() }
+            .try_to_vec()
+            .unwrap(),
+    }
+}
+
+fn build_approve_proposal_ix(program_id: &Pubkey, member: &Pubkey, proposal_id: u64) -> Instruction {
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(program_id);
+    let (proposal_pda, _) = GovernanceProposal::derive_pda(program_id, proposal_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*member, true),
+            AccountMeta::new_readonly(governance_set_pda, false),
+            AccountMeta::new(proposal_pda, false),
+        ],
+        data: VaultInstruction::ApproveGovernanceProposal { proposal_id }.try_to_vec().unwrap(),
+    }
+}
+
+fn build_execute_proposal_ix(
+    program_id: &Pubkey,
+    member: &Pubkey,
+    proposal_id: u64,
+    action_accounts: &[AccountMeta],
+) -> Instruction {
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(program_id);
+    let (proposal_pda, _) = GovernanceProposal::derive_pda(program_id, proposal_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*member, true),
+        AccountMeta::new_readonly(governance_set_pda, false),
+        AccountMeta::new(proposal_pda, false),
+        AccountMeta::new_readonly(*program_id, false),
+    ];
+    accounts.extend_from_slice(action_accounts);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::ExecuteGovernanceProposal { proposal_id }.try_to_vec().unwrap(),
+    }
+}
+
+/// Accounts of a `[signer] governance, [writable] VaultConfig` governance instruction
+fn signer_and_vault_config(program_id: &Pubkey) -> Vec<AccountMeta> {
+    let (signer_pda, _) = GovernanceSet::derive_signer_pda(program_id);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    vec![
+        AccountMeta::new_readonly(signer_pda, false),
+        AccountMeta::new(vault_config_pda, false),
+    ]
+}
+
+/// Governance key handed over to a 2-of-3 multisig; returns (program_test, members)
+fn setup_multisig_members(program_id: &Pubkey, governance: &Keypair) -> (ProgramTest, Vec<Keypair>) {
+    let mut program_test = setup_vault(program_id, governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let members: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+    for member in &members {
+        add_funded_signer(&mut program_test, &member.pubkey());
+    }
+    (program_test, members)
+}
+
+// ============================================================
+// Test: 2-of-3 multisig proposal lifecycle
+// ============================================================
+#[tokio::test]
+async fn test_multisig_governance_proposal_lifecycle() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let outsider = Keypair::new();
+    let (mut program_test, members) = setup_multisig_members(&program_id, &governance);
+    add_funded_signer(&mut program_test, &outsider.pubkey());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (signer_pda, _) = GovernanceSet::derive_signer_pda(&program_id);
+
+    let member_keys: Vec<Pubkey> = members.iter().map(|m| m.pubkey()).collect();
+    let ix = build_initialize_governance_set_ix(&program_id, &governance.pubkey(), member_keys, 2, 86_400);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, signer_pda);
+
+    // The former single key no longer governs
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+
+    // Only members may propose, and only governance instructions
+    let action_accounts = signer_and_vault_config(&program_id);
+    let ix = build_create_proposal_ix(&program_id, &outsider.pubkey(), 0, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    assert!(send(&mut banks_client, &payer, &outsider, ix).await.is_err());
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 0, VaultInstruction::InitializeUser { account_index: 0 }, &action_accounts);
+    assert!(send(&mut banks_client, &payer, &members[0], ix).await.is_err());
+
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 0, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    send(&mut banks_client, &payer, &members[0], ix).await.unwrap();
+
+    // 1 of 2 approvals
+    let ix = build_execute_proposal_ix(&program_id, &members[0].pubkey(), 0, &action_accounts);
+    assert!(send(&mut banks_client, &payer, &members[0], ix).await.is_err());
+
+    let ix = build_approve_proposal_ix(&program_id, &outsider.pubkey(), 0);
+    assert!(send(&mut banks_client, &payer, &outsider, ix).await.is_err());
+    let ix = build_approve_proposal_ix(&program_id, &members[2].pubkey(), 0);
+    send(&mut banks_client, &payer, &members[2], ix).await.unwrap();
+
+    // Accounts differing from the pinned list are rejected
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(&program_id);
+    let wrong_accounts = vec![
+        AccountMeta::new_readonly(signer_pda, false),
+        AccountMeta::new(governance_set_pda, false),
+    ];
+    let ix = build_execute_proposal_ix(&program_id, &members[1].pubkey(), 0, &wrong_accounts);
+    assert!(send(&mut banks_client, &payer, &members[1], ix).await.is_err());
+
+    // Same keys with different privileges are rejected too
+    let readonly_accounts = vec![
+        AccountMeta::new_readonly(signer_pda, false),
+        AccountMeta::new_readonly(derive_vault_config_pda(&program_id).0, false),
+    ];
+    let ix = build_execute_proposal_ix(&program_id, &members[1].pubkey(), 0, &readonly_accounts);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &members[1], ix).await), Some(VaultError::InvalidAccount as u32));
+
+    let ix = build_execute_proposal_ix(&program_id, &members[1].pubkey(), 0, &action_accounts);
+    send(&mut banks_client, &payer, &members[1], ix).await.unwrap();
+    assert!(read_vault_config(&mut banks_client, &program_id).await.is_paused);
+
+    let (proposal_pda, _) = GovernanceProposal::derive_pda(&program_id, 0);
+    let proposal_account = banks_client.get_account(proposal_pda).await.unwrap().unwrap();
+    let proposal = GovernanceProposal::try_from_slice(&proposal_account.data).unwrap();
+    assert!(proposal.executed);
+    assert_eq!(proposal.approvals, 0b101);
+
+    // No replay
+    let ix = build_execute_proposal_ix(&program_id, &members[1].pubkey(), 0, &action_accounts);
+    assert!(send(&mut banks_client, &payer, &members[1], ix).await.is_err());
+}
+
+// ============================================================
+// Test: a pre-funded proposal PDA does not block proposals
+// ============================================================
+#[tokio::test]
+async fn test_multisig_proposal_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let (mut program_test, members) = setup_multisig_members(&program_id, &governance);
+    let (proposal_pda, _) = GovernanceProposal::derive_pda(&program_id, 0);
+    add_prefunded_pda(&mut program_test, proposal_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let member_keys: Vec<Pubkey> = members.iter().map(|m| m.pubkey()).collect();
+    let ix = build_initialize_governance_set_ix(&program_id, &governance.pubkey(), member_keys, 2, 86_400);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let action_accounts = signer_and_vault_config(&program_id);
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 0, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    send(&mut banks_client, &payer, &members[0], ix).await.unwrap();
+
+    let proposal_account = banks_client.get_account(proposal_pda).await.unwrap().unwrap();
+    assert_eq!(proposal_account.owner, program_id);
+    assert_eq!(proposal_account.data.len(), GOVERNANCE_PROPOSAL_SIZE);
+    assert!(proposal_account.lamports >= Rent::default().minimum_balance(GOVERNANCE_PROPOSAL_SIZE));
+    assert_eq!(GovernanceProposal::try_from_slice(&proposal_account.data).unwrap().proposal_id, 0);
+}
+
+#[tokio::test]
+async fn test_multisig_governance_set_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let (mut program_test, members) = setup_multisig_members(&program_id, &governance);
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(&program_id);
+    add_prefunded_pda(&mut program_test, governance_set_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let member_keys: Vec<Pubkey> = members.iter().map(|m| m.pubkey()).collect();
+    let ix = build_initialize_governance_set_ix(&program_id, &governance.pubkey(), member_keys, 2, 86_400);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let account = banks_client.get_account(governance_set_pda).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(account.lamports >= Rent::default().minimum_balance(GOVERNANCE_SET_SIZE));
+    assert_eq!(GovernanceSet::try_from_slice(&account.data).unwrap().threshold, 2);
+    let (signer_pda, _) = GovernanceSet::derive_signer_pda(&program_id);
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.governance_authority, signer_pda);
+}
+
+// ============================================================
+// Test: membership change voids open proposals; expiry
+// ============================================================
+#[tokio::test]
+async fn test_multisig_governance_set_update_and_expiry() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let (program_test, members) = setup_multisig_members(&program_id, &governance);
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    let (signer_pda, _) = GovernanceSet::derive_signer_pda(&program_id);
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (governance_set_pda, _) = GovernanceSet::derive_pda(&program_id);
+
+    let member_keys: Vec<Pubkey> = members.iter().map(|m| m.pubkey()).collect();
+    let ix = build_initialize_governance_set_ix(&program_id, &governance.pubkey(), member_keys.clone(), 2, 3_600);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    // Proposal 0: pause (stays open)
+    let action_accounts = signer_and_vault_config(&program_id);
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 0, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    send(&mut context.banks_client, &payer, &members[0], ix).await.unwrap();
+
+    // Proposal 1: drop members[2], threshold 2-of-2
+    let update_accounts = vec![
+        AccountMeta::new_readonly(signer_pda, false),
+        AccountMeta::new_readonly(vault_config_pda, false),
+        AccountMeta::new(governance_set_pda, false),
+    ];
+    let update = VaultInstruction::UpdateGovernanceSet {
+        members: member_keys[..2].to_vec(),
+        threshold: 2,
+        proposal_ttl_secs: 3_600,
+    };
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 1, update, &update_accounts);
+    send(&mut context.banks_client, &payer, &members[0], ix).await.unwrap();
+    let ix = build_approve_proposal_ix(&program_id, &members[1].pubkey(), 1);
+    send(&mut context.banks_client, &payer, &members[1], ix).await.unwrap();
+    let ix = build_execute_proposal_ix(&program_id, &members[1].pubkey(), 1, &update_accounts);
+    send(&mut context.banks_client, &payer, &members[1], ix).await.unwrap();
+
+    let set_account = context.banks_client.get_account(governance_set_pda).await.unwrap().unwrap();
+    let governance_set = GovernanceSet::try_from_slice(&set_account.data).unwrap();
+    assert_eq!(governance_set.member_count, 2);
+    assert_eq!(governance_set.set_version, 1);
+    assert_eq!(governance_set.member_index(&members[2].pubkey()), None);
+
+    // Proposal 0 was created under the old membership
+    let ix = build_approve_proposal_ix(&program_id, &members[1].pubkey(), 0);
+    assert!(send(&mut context.banks_client, &payer, &members[1], ix).await.is_err());
+
+    // Proposal 2 expires before reaching threshold
+    let ix = build_create_proposal_ix(&program_id, &members[0].pubkey(), 2, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    send(&mut context.banks_client, &payer, &members[0], ix).await.unwrap();
+
+    let mut clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += 3_601;
+    context.set_sysvar(&clock);
+
+    let ix = build_approve_proposal_ix(&program_id, &members[1].pubkey(), 2);
+    assert!(send(&mut context.banks_client, &payer, &members[1], ix).await.is_err());
+    assert!(!read_vault_config(&mut context.banks_client, &program_id).await.is_paused);
+}
+
+// ============================================================
+// Timelock helpers
+// ============================================================
+
+fn build_initialize_timelock_ix(program_id: &Pubkey, governance_authority: &Pubkey, min_delay_secs: [i64; TIMELOCK_ACTION_TYPE_COUNT]) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*governance_authority, true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(timelock_config_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::InitializeTimelock { min_delay_secs }.try_to_vec().unwrap(),
+    }
+}
+
+fn build_queue_timelock_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    action_id: u64,
+    action: VaultInstruction,
+    action_accounts: &[AccountMeta],
+) -> Instruction {
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(program_id);
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+    let mut accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(timelock_config_pda, false),
+        AccountMeta::new(timelock_action_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend_from_slice(action_accounts);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::QueueTimelockAction { instruction_data: action.try_to_vec().unwrap() }
+            .try_to_vec()
+            .unwrap(),
+    }
+}
+
+fn build_execute_timelock_ix(program_id: &Pubkey, action_id: u64, action_accounts: &[AccountMeta]) -> Instruction {
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(program_id);
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(timelock_config_pda, false),
+        AccountMeta::new(timelock_action_pda, false),
+        AccountMeta::new_readonly(*program_id, false),
+    ];
+    accounts.extend_from_slice(action_accounts);
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::ExecuteTimelockAction { action_id }.try_to_vec().unwrap(),
+    }
+}
+
+fn build_cancel_timelock_ix(program_id: &Pubkey, canceller: &Pubkey, action_id: u64) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(program_id);
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*canceller, true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new_readonly(timelock_config_pda, false),
+            AccountMeta::new(timelock_action_pda, false),
+        ],
+        data: VaultInstruction::CancelTimelockAction { action_id }.try_to_vec().unwrap(),
+    }
+}
+
+fn build_guardian_cancel_timelock_ix(program_id: &Pubkey, guardian: &Pubkey, action_id: u64, reason_code: u16) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*guardian, true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(timelock_action_pda, false),
+        ],
+        data: VaultInstruction::GuardianCancelTimelockAction { action_id, reason_code }.try_to_vec().unwrap(),
+    }
+}
+
+/// Accounts of a `[signer] governance, [writable] VaultConfig` instruction run by the timelock
+fn timelock_signer_and_vault_config(program_id: &Pubkey) -> Vec<AccountMeta> {
+    let (signer_pda, _) = TimelockConfig::derive_signer_pda(program_id);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    vec![
+        AccountMeta::new_readonly(signer_pda, false),
+        AccountMeta::new(vault_config_pda, false),
+    ]
+}
+
+async fn advance_clock(context: &mut ProgramTestContext, secs: i64) {
+    let mut clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += secs;
+    context.set_sysvar(&clock);
+}
+
+// ============================================================
+// Test: timelock queue / ETA / execute / cancel / emergency pause
+// ============================================================
+#[tokio::test]
+async fn test_timelock_queue_execute_cancel() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let guardian = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &guardian.pubkey());
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    let (signer_pda, _) = TimelockConfig::derive_signer_pda(&program_id);
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(&program_id);
+    let delay = 3_600;
+
+    let ix = build_initialize_timelock_ix(&program_id, &governance.pubkey(), [delay; TIMELOCK_ACTION_TYPE_COUNT]);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    let vault_config = read_vault_config(&mut context.banks_client, &program_id).await;
+    assert_eq!(vault_config.governance_authority, signer_pda);
+
+    // Direct governance calls no longer take effect
+    let caller = Pubkey::new_unique();
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddAuthorizedCaller { caller });
+    assert!(send(&mut context.banks_client, &payer, &governance, ix).await.is_err());
+
+    // Action 0: add an authorized caller after the delay
+    let action_accounts = timelock_signer_and_vault_config(&program_id);
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 0, VaultInstruction::AddAuthorizedCaller { caller }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let ix = build_execute_timelock_ix(&program_id, 0, &action_accounts);
+    assert!(send(&mut context.banks_client, &payer, &payer, ix).await.is_err());
+
+    // Action 1: add a guardian (queued at the same time)
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 1, VaultInstruction::AddGuardian { guardian: guardian.pubkey() }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    advance_clock(&mut context, delay).await;
+    let ix = build_execute_timelock_ix(&program_id, 0, &action_accounts);
+    send(&mut context.banks_client, &payer, &payer, ix).await.unwrap();
+    let ix = build_execute_timelock_ix(&program_id, 1, &action_accounts);
+    send(&mut context.banks_client, &payer, &payer, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut context.banks_client, &program_id).await;
+    assert!(vault_config.is_authorized_caller(&caller));
+    assert!(vault_config.is_guardian(&guardian.pubkey()));
+
+    // Emergency pause is exempt for the admin; resume is not
+    let mut ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    ix.accounts.push(AccountMeta::new_readonly(timelock_config_pda, false));
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    assert!(read_vault_config(&mut context.banks_client, &program_id).await.is_paused);
+
+    let mut ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: false });
+    ix.accounts.push(AccountMeta::new_readonly(timelock_config_pda, false));
+    assert!(send(&mut context.banks_client, &payer, &governance, ix).await.is_err());
+
+    // Action 2: removal cancelled by the guardian; outsiders cannot cancel
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 2, VaultInstruction::RemoveAuthorizedCaller { caller }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let outsider = Keypair::new();
+    let ix = build_cancel_timelock_ix(&program_id, &outsider.pubkey(), 2);
+    assert!(send(&mut context.banks_client, &payer, &outsider, ix).await.is_err());
+    let ix = build_cancel_timelock_ix(&program_id, &guardian.pubkey(), 2);
+    assert!(send(&mut context.banks_client, &payer, &guardian, ix).await.is_err(), "guardians must give a reason code");
+    let ix = build_guardian_cancel_timelock_ix(&program_id, &guardian.pubkey(), 2, 42);
+    send(&mut context.banks_client, &payer, &guardian, ix).await.unwrap();
+    let record = read_vault_config(&mut context.banks_client, &program_id).await.guardian_actions[0];
+    assert_eq!((record.guardian, record.action, record.reason_code, record.target), (guardian.pubkey(), GuardianAction::CancelTimelockAction, 42, 2));
+
+    advance_clock(&mut context, delay).await;
+    let ix = build_execute_timelock_ix(&program_id, 2, &action_accounts);
+    assert!(send(&mut context.banks_client, &payer, &payer, ix).await.is_err());
+    assert!(read_vault_config(&mut context.banks_client, &program_id).await.is_authorized_caller(&caller));
+
+    // Action 3: stale after the grace period
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 3, VaultInstruction::SetPaused { paused: false }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    advance_clock(&mut context, delay + TIMELOCK_GRACE_PERIOD_SECS + 1).await;
+    let ix = build_execute_timelock_ix(&program_id, 3, &action_accounts);
+    assert!(send(&mut context.banks_client, &payer, &payer, ix).await.is_err());
+    assert!(read_vault_config(&mut context.banks_client, &program_id).await.is_paused);
+}
+
+// ============================================================
+// Test: a pre-funded timelock action PDA does not block queueing
+// ============================================================
+#[tokio::test]
+async fn test_timelock_queue_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(&program_id, 0);
+    add_prefunded_pda(&mut program_test, timelock_action_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let ix = build_initialize_timelock_ix(&program_id, &governance.pubkey(), [3_600; TIMELOCK_ACTION_TYPE_COUNT]);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let action_accounts = timelock_signer_and_vault_config(&program_id);
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 0, VaultInstruction::SetPaused { paused: true }, &action_accounts);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let account = banks_client.get_account(timelock_action_pda).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(account.lamports >= Rent::default().minimum_balance(TIMELOCK_ACTION_SIZE));
+    assert_eq!(read_timelock_action(&mut banks_client, &program_id, 0).await.action_id, 0);
+}
+
+// ============================================================
+// Test: a pre-funded TimelockConfig PDA does not block enabling the timelock
+// ============================================================
+#[tokio::test]
+async fn test_timelock_config_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (timelock_config_pda, _) = TimelockConfig::derive_pda(&program_id);
+    add_prefunded_pda(&mut program_test, timelock_config_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let ix = build_initialize_timelock_ix(&program_id, &governance.pubkey(), [3_600; TIMELOCK_ACTION_TYPE_COUNT]);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let account = banks_client.get_account(timelock_config_pda).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(account.lamports >= Rent::default().minimum_balance(TIMELOCK_CONFIG_SIZE));
+    let (signer_pda, _) = TimelockConfig::derive_signer_pda(&program_id);
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.governance_authority, signer_pda);
+}
+
+async fn read_timelock_action(banks_client: &mut BanksClient, program_id: &Pubkey, action_id: u64) -> TimelockAction {
+    let (timelock_action_pda, _) = TimelockAction::derive_pda(program_id, action_id);
+    let account = banks_client.get_account(timelock_action_pda).await.unwrap().unwrap();
+    TimelockAction::try_from_slice(&account.data).unwrap()
+}
+
+async fn set_clock(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+// ============================================================
+// Test: each action type waits for its own delay; admin cancel
+// ============================================================
+#[tokio::test]
+async fn test_timelock_per_type_delay_enforcement() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+
+    // AuthorizedCallers = 100s ... TimelockConfig = 700s
+    let mut delays = [0i64; TIMELOCK_ACTION_TYPE_COUNT];
+    for (index, delay) in delays.iter_mut().enumerate() {
+        *delay = (index as i64 + 1) * 100;
+    }
+    let ix = build_initialize_timelock_ix(&program_id, &governance.pubkey(), delays);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let action_accounts = timelock_signer_and_vault_config(&program_id);
+    let caller = Pubkey::new_unique();
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 0, VaultInstruction::AddAuthorizedCaller { caller }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    let set_limits = VaultInstruction::SetLimits {
+        token_index: 0,
+        min_deposit_e6: 1_000_000,
+        min_withdraw_e6: 0,
+        max_deposit_per_tx_e6: 0,
+        max_withdraw_per_tx_e6: 0,
+        max_account_balance_e6: 0,
+    };
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 1, set_limits, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let callers_action = read_timelock_action(&mut context.banks_client, &program_id, 0).await;
+    let limits_action = read_timelock_action(&mut context.banks_client, &program_id, 1).await;
+    assert_eq!(callers_action.action_type, TimelockActionType::AuthorizedCallers as u8);
+    assert_eq!(callers_action.eta, callers_action.queued_ts + 100);
+    assert_eq!(limits_action.action_type, TimelockActionType::TransferPolicy as u8);
+    assert_eq!(limits_action.eta, limits_action.queued_ts + 600);
+
+    // One second early → TimelockNotReady; at the ETA → executes
+    set_clock(&mut context, callers_action.eta - 1).await;
+    let ix = build_execute_timelock_ix(&program_id, 0, &action_accounts);
+    assert_eq!(custom_error(send(&mut context.banks_client, &payer, &payer, ix).await), Some(VaultError::TimelockNotReady as u32));
+    set_clock(&mut context, callers_action.eta).await;
+    let ix = build_execute_timelock_ix(&program_id, 0, &action_accounts);
+    send(&mut context.banks_client, &payer, &payer, ix).await.unwrap();
+    assert!(read_vault_config(&mut context.banks_client, &program_id).await.is_authorized_caller(&caller));
+
+    // The transfer-policy action still waits for its longer delay
+    let ix = build_execute_timelock_ix(&program_id, 1, &action_accounts);
+    assert_eq!(custom_error(send(&mut context.banks_client, &payer, &payer, ix).await), Some(VaultError::TimelockNotReady as u32));
+    set_clock(&mut context, limits_action.eta).await;
+    let ix = build_execute_timelock_ix(&program_id, 1, &action_accounts);
+    send(&mut context.banks_client, &payer, &payer, ix).await.unwrap();
+    assert_eq!(read_vault_config(&mut context.banks_client, &program_id).await.limits.min_deposit_e6, 1_000_000);
+
+    // Executed actions cannot run again
+    let ix = build_execute_timelock_ix(&program_id, 0, &action_accounts);
+    assert_eq!(custom_error(send(&mut context.banks_client, &payer, &payer, ix).await), Some(VaultError::TimelockActionClosed as u32));
+
+    // Admin cancel: the action never runs, and cannot be cancelled twice
+    let ix = build_queue_timelock_ix(&program_id, &governance.pubkey(), 2, VaultInstruction::RemoveAuthorizedCaller { caller }, &action_accounts);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    let ix = build_cancel_timelock_ix(&program_id, &governance.pubkey(), 2);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    assert!(read_timelock_action(&mut context.banks_client, &program_id, 2).await.cancelled);
+    let ix = build_cancel_timelock_ix(&program_id, &governance.pubkey(), 2);
+    assert_eq!(custom_error(send(&mut context.banks_client, &payer, &governance, ix).await), Some(VaultError::TimelockActionClosed as u32));
+
+    advance_clock(&mut context, 100).await;
+    let ix = build_execute_timelock_ix(&program_id, 2, &action_accounts);
+    assert_eq!(custom_error(send(&mut context.banks_client, &payer, &payer, ix).await), Some(VaultError::TimelockActionClosed as u32));
+    assert!(read_vault_config(&mut context.banks_client, &program_id).await.is_authorized_caller(&caller));
+}
+
+// ============================================================
+// Roles (RBAC)
+// ============================================================
+
+/// 单调递增的 DB 序号 (relayer 写入要求 sequence 严格递增)
+fn next_sequence() -> u64 {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+}
+
+fn custom_error(result: Result<(), BanksClientError>) -> Option<u32> {
+    match result {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(_, InstructionError::Custom(code)))) => Some(code),
+        _ => None,
+    }
+}
+
+fn build_relayer_deposit_ix(program_id: &Pubkey, relayer: &Pubkey, vault_config: &Pubkey, user_wallet: &Pubkey, amount: u64) -> Instruction {
+    let (user_account_pda, _) = UserAccount::derive_pda(program_id, user_wallet, 0);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*relayer, true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new(*vault_config, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::RelayerDeposit { user_wallet: *user_wallet, amount, account_index: 0, sequence: next_sequence() }
+            .try_to_vec()
+            .unwrap(),
+    }
+}
+
+fn build_user_account_ix(program_id: &Pubkey, relayer: &Pubkey, user_wallet: &Pubkey, available_balance_e6: i64) -> Instruction {
+    let (user_account_pda, _) = UserAccount::derive_pda(program_id, user_wallet, 0);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*relayer, true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::UserAccount {
+            user_wallet: *user_wallet,
+            account_index: 0,
+            available_balance_e6,
+            locked_margin_e6: 0,
+            spot_locked_e6: 0,
+            oracle_locked_e6: 0,
+            sequence: next_sequence(),
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+// ============================================================
+// Test: each relayer path requires its own role
+// ============================================================
+#[tokio::test]
+async fn test_roles_grant_revoke_and_enforcement() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let depositor = Keypair::new();
+    let state_writer = Keypair::new();
+    let pauser = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+
+    // Spoofed VaultConfig listing the depositor as governance authority
+    let spoofed_config = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    program_test.add_account(spoofed_config, Account {
+        lamports: Rent::default().minimum_balance(VAULT_CONFIG_SIZE),
+        data: test_vault_config(&depositor.pubkey()).try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    for key in [depositor.pubkey(), state_writer.pubkey(), pauser.pubkey()] {
+        add_funded_signer(&mut program_test, &key);
+    }
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    for (account, role) in [
+        (depositor.pubkey(), VaultRole::Depositor),
+        (state_writer.pubkey(), VaultRole::StateWriter),
+        (pauser.pubkey(), VaultRole::Pauser),
+    ] {
+        let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::GrantRole { account, role });
+        send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    }
+
+    // Only governance can grant
+    let ix = build_governance_ix(&program_id, &depositor.pubkey(), VaultInstruction::GrantRole {
+        account: depositor.pubkey(),
+        role: VaultRole::BridgeTransferer,
+    });
+    assert!(send(&mut banks_client, &payer, &depositor, ix).await.is_err());
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(vault_config.roles_of(&depositor.pubkey()), VaultRole::Depositor.bit());
+    assert_eq!(vault_config.roles_of(&state_writer.pubkey()), VaultRole::StateWriter.bit());
+
+    // Depositor credits; it cannot overwrite state, and a spoofed VaultConfig is rejected
+    let ix = build_relayer_deposit_ix(&program_id, &depositor.pubkey(), &vault_config_pda, &user_wallet, 1_000_000);
+    send(&mut banks_client, &payer, &depositor, ix).await.unwrap();
+    let ix = build_user_account_ix(&program_id, &depositor.pubkey(), &user_wallet, 0);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &depositor, ix).await), Some(VaultError::MissingRole as u32));
+    let ix = build_relayer_deposit_ix(&program_id, &depositor.pubkey(), &spoofed_config, &user_wallet, 1_000_000);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &depositor, ix).await), Some(VaultError::InvalidPda as u32));
+
+    // State writer overwrites state; it cannot credit deposits
+    let ix = build_user_account_ix(&program_id, &state_writer.pubkey(), &user_wallet, 5_000_000);
+    send(&mut banks_client, &payer, &state_writer, ix).await.unwrap();
+    let ix = build_relayer_deposit_ix(&program_id, &state_writer.pubkey(), &vault_config_pda, &user_wallet, 1_000_000);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &state_writer, ix).await), Some(VaultError::MissingRole as u32));
+
+    // Pauser pauses but cannot resume
+    let ix = build_governance_ix(&program_id, &pauser.pubkey(), VaultInstruction::SetPaused { paused: true });
+    send(&mut banks_client, &payer, &pauser, ix).await.unwrap();
+    assert!(read_vault_config(&mut banks_client, &program_id).await.is_paused);
+    let ix = build_governance_ix(&program_id, &pauser.pubkey(), VaultInstruction::SetPaused { paused: false });
+    assert!(send(&mut banks_client, &payer, &pauser, ix).await.is_err());
+
+    // Revoking the only role frees the slot and removes the power
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::RevokeRole {
+        account: depositor.pubkey(),
+        role: VaultRole::Depositor,
+    });
+    send(&mut banks_client, &payer, &governance, ix.clone()).await.unwrap();
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &governance, ix).await), Some(VaultError::MissingRole as u32));
+    assert!(!read_vault_config(&mut banks_client, &program_id).await.is_authorized_caller(&depositor.pubkey()));
+    let ix = build_relayer_deposit_ix(&program_id, &depositor.pubkey(), &vault_config_pda, &user_wallet, 1_000_000);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &depositor, ix).await), Some(VaultError::MissingRole as u32));
+
+    // Deprecated AddAuthorizedCaller grants the legacy relayer roles
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddAuthorizedCaller { caller: depositor.pubkey() });
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.roles_of(&depositor.pubkey()), LEGACY_RELAYER_ROLES);
+
+    // Registry changes are governance-only, whatever roles the signer holds
+    for (signer, expected) in [(&depositor, false), (&pauser, false), (&governance, true)] {
+        let ix = build_governance_ix(&program_id, &signer.pubkey(), VaultInstruction::SetTokenFeePolicy {
+            token_index: 0,
+            vault_pays_transfer_fee: true,
+        });
+        let result = send(&mut banks_client, &payer, signer, ix).await;
+        if expected {
+            result.unwrap();
+        } else {
+            assert_eq!(custom_error(result), Some(VaultError::InvalidGovernanceAuthority as u32));
+        }
+    }
+    assert!(read_vault_config(&mut banks_client, &program_id).await.usdc_vault_pays_transfer_fee);
+}
+
+// ============================================================
+// AuthorizedRelayer registry
+// ============================================================
+
+fn relayer_label(label: &str) -> [u8; RELAYER_LABEL_LEN] {
+    let mut out = [0u8; RELAYER_LABEL_LEN];
+    out[..label.len()].copy_from_slice(label.as_bytes());
+    out
+}
+
+fn with_authorized_relayer(mut ix: Instruction, program_id: &Pubkey, relayer: &Pubkey) -> Instruction {
+    let (authorized_relayer_pda, _) = AuthorizedRelayer::derive_pda(program_id, relayer);
+    ix.accounts.push(AccountMeta::new_readonly(authorized_relayer_pda, false));
+    ix
+}
+
+async fn read_authorized_relayer(banks_client: &mut BanksClient, program_id: &Pubkey, relayer: &Pubkey) -> AuthorizedRelayer {
+    let (authorized_relayer_pda, _) = AuthorizedRelayer::derive_pda(program_id, relayer);
+    let account = banks_client.get_account(authorized_relayer_pda).await.unwrap().unwrap();
+    AuthorizedRelayer::try_from_slice(&account.data).unwrap()
+}
+
+// ============================================================
+// Test: register / limits / disable / migrate from authorized_callers
+// ============================================================
+#[tokio::test]
+async fn test_authorized_relayer_registry_and_migration() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let legacy = Keypair::new();
+    let fresh = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+
+    // Full authorized_callers list, slot 0 = legacy relayer
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    for (slot, caller) in vault_config.authorized_callers.iter_mut().enumerate() {
+        *caller = if slot == 0 { legacy.pubkey() } else { Pubkey::new_unique() };
+    }
+    vault_config.authorized_caller_roles = [LEGACY_RELAYER_ROLES; 10];
+    let callers = vault_config.authorized_callers;
+
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &legacy.pubkey());
+    add_funded_signer(&mut program_test, &fresh.pubkey());
+    // A pre-funded relayer PDA does not block registration
+    let (fresh_pda, _) = AuthorizedRelayer::derive_pda(&program_id, &fresh.pubkey());
+    add_prefunded_pda(&mut program_test, fresh_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddAuthorizedCaller { caller: fresh.pubkey() });
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &governance, ix).await), Some(VaultError::AuthorizedCallersFull as u32));
+
+    // Register beyond the ten-slot cap
+    let register = |relayer: Pubkey, roles: u8| Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(AuthorizedRelayer::derive_pda(&program_id, &relayer).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::RegisterRelayer {
+            relayer,
+            label: relayer_label("bridge-1"),
+            roles,
+            max_deposit_per_tx_e6: 2_000_000,
+            max_withdraw_per_tx_e6: 0,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    // A key still in authorized_callers cannot also get a PDA
+    assert!(send(&mut banks_client, &payer, &governance, register(legacy.pubkey(), VaultRole::Depositor.bit())).await.is_err());
+    assert!(!read_vault_config(&mut banks_client, &program_id).await.relayer_registry_active);
+    send(&mut banks_client, &payer, &governance, register(fresh.pubkey(), VaultRole::Depositor.bit())).await.unwrap();
+    assert!(send(&mut banks_client, &payer, &governance, register(fresh.pubkey(), VaultRole::Depositor.bit())).await.is_err());
+    assert!(read_vault_config(&mut banks_client, &program_id).await.relayer_registry_active);
+
+    let record = read_authorized_relayer(&mut banks_client, &program_id, &fresh.pubkey()).await;
+    assert_eq!(record.relayer, fresh.pubkey());
+    assert_eq!(record.label, relayer_label("bridge-1"));
+    assert!(record.enabled);
+    assert!(record.created_ts > 0);
+
+    // Signer must pass its PDA; per-tx cap applies
+    let deposit = |relayer: &Pubkey, amount: u64| build_relayer_deposit_ix(&program_id, relayer, &vault_config_pda, &user_wallet, amount);
+    send(&mut banks_client, &payer, &fresh, with_authorized_relayer(deposit(&fresh.pubkey(), 1_000_000), &program_id, &fresh.pubkey())).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &fresh, deposit(&fresh.pubkey(), 1_000_000)).await),
+        Some(VaultError::MissingRole as u32),
+    );
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &fresh, with_authorized_relayer(deposit(&fresh.pubkey(), 3_000_000), &program_id, &fresh.pubkey())).await),
+        Some(VaultError::RelayerLimitExceeded as u32),
+    );
+
+    // Disabled relayer holds no role
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(governance.pubkey(), true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(fresh_pda, false),
+        ],
+        data: VaultInstruction::UpdateRelayer {
+            relayer: fresh.pubkey(),
+            label: relayer_label("bridge-1"),
+            roles: VaultRole::Depositor.bit(),
+            enabled: false,
+            max_deposit_per_tx_e6: 2_000_000,
+            max_withdraw_per_tx_e6: 0,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &fresh, with_authorized_relayer(deposit(&fresh.pubkey(), 1_000_000), &program_id, &fresh.pubkey())).await),
+        Some(VaultError::MissingRole as u32),
+    );
+
+    // The disabled key cannot be put back into a slot once the registry is active
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::GrantRole {
+        account: fresh.pubkey(),
+        role: VaultRole::Depositor,
+    });
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &governance, ix).await), Some(VaultError::RelayerPdaRequired as u32));
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddAuthorizedCaller { caller: fresh.pubkey() });
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &governance, ix).await), Some(VaultError::RelayerPdaRequired as u32));
+    assert!(!read_vault_config(&mut banks_client, &program_id).await.is_authorized_caller(&fresh.pubkey()));
+
+    // Migrate all ten callers into PDAs
+    let mut accounts = vec![
+        AccountMeta::new(governance.pubkey(), true),
+        AccountMeta::new(vault_config_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(callers.iter().map(|caller| AccountMeta::new(AuthorizedRelayer::derive_pda(&program_id, caller).0, false)));
+    let ix = Instruction {
+        program_id,
+        accounts,
+        data: VaultInstruction::MigrateAuthorizedCallers.try_to_vec().unwrap(),
+    };
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let vault_config = read_vault_config(&mut banks_client, &program_id).await;
+    assert!(vault_config.authorized_callers.iter().all(|caller| *caller == Pubkey::default()));
+    assert_eq!(vault_config.authorized_caller_roles, [0u8; 10]);
+    for caller in callers.iter() {
+        let record = read_authorized_relayer(&mut banks_client, &program_id, caller).await;
+        assert_eq!(record.roles, LEGACY_RELAYER_ROLES);
+        assert_eq!(record.label, relayer_label("migrated"));
+    }
+
+    // The legacy relayer now authenticates through its PDA
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &legacy, deposit(&legacy.pubkey(), 1_000_000)).await),
+        Some(VaultError::MissingRole as u32),
+    );
+    send(&mut banks_client, &payer, &legacy, with_authorized_relayer(deposit(&legacy.pubkey(), 1_000_000), &program_id, &legacy.pubkey())).await.unwrap();
+
+    // GrantRole targets the PDA when it is passed
+    let (legacy_pda, _) = AuthorizedRelayer::derive_pda(&program_id, &legacy.pubkey());
+    let mut ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::GrantRole {
+        account: legacy.pubkey(),
+        role: VaultRole::Pauser,
+    });
+    ix.accounts.push(AccountMeta::new(legacy_pda, false));
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    let record = read_authorized_relayer(&mut banks_client, &program_id, &legacy.pubkey()).await;
+    assert_eq!(record.roles, LEGACY_RELAYER_ROLES | VaultRole::Pauser.bit());
+    assert!(!read_vault_config(&mut banks_client, &program_id).await.is_authorized_caller(&legacy.pubkey()));
+}
+
+fn with_rate_limit(mut ix: Instruction, program_id: &Pubkey, relayer: &Pubkey, token_index: u16) -> Instruction {
+    let (rate_limit_pda, _) = RelayerRateLimit::derive_pda(program_id, relayer, token_index);
+    ix = with_authorized_relayer(ix, program_id, relayer);
+    ix.accounts.push(AccountMeta::new(rate_limit_pda, false));
+    ix
+}
+
+// ============================================================
+// Test: rolling-window rate limit per relayer and token
+// ============================================================
+#[tokio::test]
+async fn test_relayer_rate_limit_rolling_window() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let relayer = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+    let spot_token_index = 1u16;
+
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &relayer.pubkey());
+    let (entry_pda, entry_bump) = TokenRegistryEntry::derive_pda(&program_id, spot_token_index);
+    let mut entry = TokenRegistryEntry::try_from_slice(&[0u8; TOKEN_REGISTRY_ENTRY_SIZE]).unwrap();
+    entry.discriminator = TokenRegistryEntry::DISCRIMINATOR;
+    entry.token_index = spot_token_index;
+    entry.mint = Pubkey::new_unique();
+    entry.decimals = 8;
+    entry.is_enabled = true;
+    entry.bump = entry_bump;
+    program_test.add_account(entry_pda, Account {
+        lamports: Rent::default().minimum_balance(TOKEN_REGISTRY_ENTRY_SIZE),
+        data: entry.try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    // The relayer pre-funds its own rate limit PDA; governance can still install the cap
+    let (rate_limit_pda, _) = RelayerRateLimit::derive_pda(&program_id, &relayer.pubkey(), 0);
+    add_prefunded_pda(&mut program_test, rate_limit_pda);
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (authorized_relayer_pda, _) = AuthorizedRelayer::derive_pda(&program_id, &relayer.pubkey());
+
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(authorized_relayer_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::RegisterRelayer {
+            relayer: relayer.pubkey(),
+            label: relayer_label("bridge-1"),
+            roles: VaultRole::Depositor.bit() | VaultRole::Withdrawer.bit(),
+            max_deposit_per_tx_e6: 0,
+            max_withdraw_per_tx_e6: 0,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let set_rate_limit = |token_index: u16, max_deposit_per_window_e6: u64, max_withdraw_per_window_e6: u64| Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(authorized_relayer_pda, false),
+            AccountMeta::new(RelayerRateLimit::derive_pda(&program_id, &relayer.pubkey(), token_index).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::SetRelayerRateLimit {
+            relayer: relayer.pubkey(),
+            token_index,
+            bucket_secs: 3_600,
+            max_deposit_per_window_e6,
+            max_withdraw_per_window_e6,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+
+    // 24 × 1h window: 5 USDC in, 3 USDC out
+    send(&mut context.banks_client, &payer, &governance, set_rate_limit(0, 5_000_000, 3_000_000)).await.unwrap();
+    assert!(read_authorized_relayer(&mut context.banks_client, &program_id, &relayer.pubkey()).await.rate_limited);
+
+    let deposit = |amount: u64| build_relayer_deposit_ix(&program_id, &relayer.pubkey(), &vault_config_pda, &user_wallet, amount);
+    let withdraw = |amount: u64| {
+        let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(relayer.pubkey(), true),
+                AccountMeta::new(user_account_pda, false),
+                AccountMeta::new(vault_config_pda, false),
+            ],
+            data: VaultInstruction::RelayerWithdraw { user_wallet, amount, account_index: 0, sequence: next_sequence() }.try_to_vec().unwrap(),
+        }
+    };
+
+    // A rate-limited relayer must pass its RelayerRateLimit PDA
+    assert!(send(&mut context.banks_client, &payer, &relayer, with_authorized_relayer(deposit(1_000_000), &program_id, &relayer.pubkey())).await.is_err());
+
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(deposit(3_000_000), &program_id, &relayer.pubkey(), 0)).await.unwrap();
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(deposit(2_000_000), &program_id, &relayer.pubkey(), 0)).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &relayer, with_rate_limit(deposit(1), &program_id, &relayer.pubkey(), 0)).await),
+        Some(VaultError::RateLimitExceeded as u32),
+    );
+
+    // Withdrawals draw on a separate cap
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(withdraw(3_000_000), &program_id, &relayer.pubkey(), 0)).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &relayer, with_rate_limit(withdraw(1), &program_id, &relayer.pubkey(), 0)).await),
+        Some(VaultError::RateLimitExceeded as u32),
+    );
+
+    // Buckets roll out of the window 24h after they were recorded
+    advance_clock(&mut context, 23 * 3_600).await;
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &relayer, with_rate_limit(deposit(1), &program_id, &relayer.pubkey(), 0)).await),
+        Some(VaultError::RateLimitExceeded as u32),
+    );
+    advance_clock(&mut context, 3_600).await;
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(deposit(5_000_000), &program_id, &relayer.pubkey(), 0)).await.unwrap();
+
+    let account = context.banks_client.get_account(rate_limit_pda).await.unwrap().unwrap();
+    let rate_limit = RelayerRateLimit::try_from_slice(&account.data).unwrap();
+    let clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(rate_limit.window_total(RateLimitKind::Deposit, clock.unix_timestamp), 5_000_000);
+    assert_eq!(rate_limit.window_total(RateLimitKind::Withdraw, clock.unix_timestamp), 0);
+
+    // Spot tokens have their own window in their own units; USDC headroom does not apply
+    let (balance_pda, _) = derive_spot_token_balance_pda_with_index(&program_id, &user_wallet, 0, spot_token_index);
+    let spot = |deposit: bool, amount_e6: i64| {
+        let mut accounts = vec![
+            AccountMeta::new(relayer.pubkey(), true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+        ];
+        let data = if deposit {
+            accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+            VaultInstruction::RelayerSpotDeposit {
+                user_wallet,
+                token_index: spot_token_index,
+                amount: amount_e6 as u64,
+                account_index: 0,
+                amount_e6,
+                sequence: next_sequence(),
+            }
+        } else {
+            VaultInstruction::RelayerSpotWithdraw {
+                user_wallet,
+                token_index: spot_token_index,
+                amount: amount_e6 as u64,
+                account_index: 0,
+                amount_e6,
+                sequence: next_sequence(),
+            }
+        };
+        Instruction { program_id, accounts, data: data.try_to_vec().unwrap() }
+    };
+
+    // A rate-limited relayer needs the PDA of the token it moves; the USDC one is rejected
+    assert!(send(&mut context.banks_client, &payer, &relayer, with_rate_limit(spot(true, 1), &program_id, &relayer.pubkey(), 0)).await.is_err());
+    send(&mut context.banks_client, &payer, &governance, set_rate_limit(spot_token_index, 0, 20_000_000)).await.unwrap();
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(spot(true, 50_000_000), &program_id, &relayer.pubkey(), spot_token_index)).await.unwrap();
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(spot(false, 20_000_000), &program_id, &relayer.pubkey(), spot_token_index)).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &relayer, with_rate_limit(spot(false, 1), &program_id, &relayer.pubkey(), spot_token_index)).await),
+        Some(VaultError::RateLimitExceeded as u32),
+    );
+    // The spot withdrawal did not touch the USDC window
+    send(&mut context.banks_client, &payer, &relayer, with_rate_limit(withdraw(3_000_000), &program_id, &relayer.pubkey(), 0)).await.unwrap();
+}
+
+fn add_token_account(program_test: &mut ProgramTest, address: Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) {
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(address, Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+}
+
+/// Send a USDC withdrawal and decode its `WithdrawOutcome` return data
+async fn send_withdraw(banks_client: &mut BanksClient, payer: &Keypair, signer: &Keypair, ix: Instruction) -> WithdrawOutcome {
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer, signer], bh);
+    let outcome = banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+    WithdrawOutcome::try_from_slice(&outcome.metadata.unwrap().return_data.unwrap().data).unwrap()
+}
+
+// ============================================================
+// Test: outflow circuit breaker latches, blocks outflows, resets
+// ============================================================
+#[tokio::test]
+async fn test_outflow_circuit_breaker() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+    let usdc_mint = Pubkey::new_unique();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let relayer_token_account = Pubkey::new_unique();
+
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.usdc_mint = usdc_mint;
+    vault_config.vault_token_account = token_vault;
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint { mint_authority: COption::None, supply: 0, decimals: 6, is_initialized: true, freeze_authority: COption::None }
+        .pack_into_slice(&mut mint_data);
+    program_test.add_account(usdc_mint, Account { lamports: 1_000_000_000, data: mint_data, owner: spl_token::id(), executable: false, rent_epoch: 0 });
+    add_token_account(&mut program_test, token_vault, &usdc_mint, &vault_config_pda, 1_000_000_000);
+    add_token_account(&mut program_test, relayer_token_account, &usdc_mint, &governance.pubkey(), 0);
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+
+    let ix = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 1_000_000_000);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    // 10% of the vault balance per hour
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetOutflowBreaker { limit_bps: 1_000, window_secs: 3_600 });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let withdraw = |amount: u64, vault_config_writable: bool| {
+        let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(governance.pubkey(), true),
+                AccountMeta::new(user_account_pda, false),
+                if vault_config_writable { AccountMeta::new(vault_config_pda, false) } else { AccountMeta::new_readonly(vault_config_pda, false) },
+                AccountMeta::new(token_vault, false),
+                AccountMeta::new(relayer_token_account, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: VaultInstruction::RelayerWithdrawAndTransfer { user_wallet, amount, account_index: 0, sequence: next_sequence() }.try_to_vec().unwrap(),
+        }
+    };
+
+    // The breaker state and liability counters live in VaultConfig, which must be writable
+    assert!(send(&mut context.banks_client, &payer, &governance, withdraw(60_000_000, false)).await.is_err());
+    assert_eq!(
+        send_withdraw(&mut context.banks_client, &payer, &governance, withdraw(60_000_000, true)).await,
+        WithdrawOutcome::Transferred,
+    );
+    let breaker = read_vault_config(&mut context.banks_client, &program_id).await.outflow_breaker;
+    assert!(!breaker.outflow_paused);
+    assert_eq!(breaker.window_start_balance, 1_000_000_000);
+
+    let token_amount = |account: Account| spl_token::state::Account::unpack(&account.data).unwrap().amount;
+    let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+    let user_balance = |account: Account| UserAccount::try_from_slice(&account.data).unwrap().available_balance_e6;
+
+    // Crossing 100 USDC net outflow latches the breaker; that withdrawal succeeds but moves nothing
+    assert_eq!(
+        send_withdraw(&mut context.banks_client, &payer, &governance, withdraw(50_000_000, true)).await,
+        WithdrawOutcome::BreakerTripped,
+    );
+    let breaker = read_vault_config(&mut context.banks_client, &program_id).await.outflow_breaker;
+    assert!(breaker.outflow_paused);
+    assert!(breaker.tripped_ts > 0);
+    assert_eq!(token_amount(context.banks_client.get_account(token_vault).await.unwrap().unwrap()), 940_000_000);
+    assert_eq!(user_balance(context.banks_client.get_account(user_account_pda).await.unwrap().unwrap()), 940_000_000);
+
+    // Every later outflow is rejected, however small, even once the window has elapsed
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &governance, withdraw(1, true)).await),
+        Some(VaultError::OutflowPaused as u32),
+    );
+    set_clock(&mut context, breaker.tripped_ts + 3_601).await;
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &governance, withdraw(1, true)).await),
+        Some(VaultError::OutflowPaused as u32),
+    );
+
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::ResetOutflowBreaker);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(
+        send_withdraw(&mut context.banks_client, &payer, &governance, withdraw(1_000_000, true)).await,
+        WithdrawOutcome::Transferred,
+    );
+    let breaker = read_vault_config(&mut context.banks_client, &program_id).await.outflow_breaker;
+    assert!(!breaker.outflow_paused);
+    assert_eq!(breaker.window_start_balance, 940_000_000);
+
+    assert_eq!(token_amount(context.banks_client.get_account(token_vault).await.unwrap().unwrap()), 939_000_000);
+    assert_eq!(token_amount(context.banks_client.get_account(relayer_token_account).await.unwrap().unwrap()), 61_000_000);
+}
+
+// ============================================================
+// Test: per-category pause flags
+// ============================================================
+#[tokio::test]
+async fn test_pause_flags_per_category() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let pauser = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.authorized_callers[0] = pauser.pubkey();
+    vault_config.authorized_caller_roles[0] = VaultRole::Pauser.bit();
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &pauser.pubkey());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let deposit = || build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 1_000_000);
+    let set_flag = |signer: &Pubkey, category: PauseCategory, paused: bool| {
+        build_governance_ix(&program_id, signer, VaultInstruction::SetPauseFlag { category, paused })
+    };
+
+    // Legacy is_paused leaves relayer paths running (OC-L5)
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    send(&mut banks_client, &payer, &governance, deposit()).await.unwrap();
+
+    // A Pauser stops relayer credits only
+    send(&mut banks_client, &payer, &pauser, set_flag(&pauser.pubkey(), PauseCategory::RelayerCredit, true)).await.unwrap();
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.pause_flags, PauseCategory::RelayerCredit.bit());
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, deposit()).await),
+        Some(VaultError::VaultPaused as u32),
+    );
+    send(&mut banks_client, &payer, &governance, build_user_account_ix(&program_id, &governance.pubkey(), &user_wallet, 5_000_000)).await.unwrap();
+
+    send(&mut banks_client, &payer, &pauser, set_flag(&pauser.pubkey(), PauseCategory::StateWrite, true)).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, build_user_account_ix(&program_id, &governance.pubkey(), &user_wallet, 6_000_000)).await),
+        Some(VaultError::VaultPaused as u32),
+    );
+
+    // Only governance resumes, one flag at a time
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &pauser, set_flag(&pauser.pubkey(), PauseCategory::RelayerCredit, false)).await),
+        Some(VaultError::InvalidGovernanceAuthority as u32),
+    );
+    send(&mut banks_client, &payer, &governance, set_flag(&governance.pubkey(), PauseCategory::RelayerCredit, false)).await.unwrap();
+    send(&mut banks_client, &payer, &governance, deposit()).await.unwrap();
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.pause_flags, PauseCategory::StateWrite.bit());
+}
+
+// ============================================================
+// Test: guardian set pauses with a reason code, never resumes
+// ============================================================
+#[tokio::test]
+async fn test_guardians_pause_and_record_actions() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let guardian = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &guardian.pubkey());
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddGuardian { guardian: guardian.pubkey() });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    for _ in 1..MAX_GUARDIANS {
+        let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddGuardian { guardian: Pubkey::new_unique() });
+        send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    }
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::AddGuardian { guardian: Pubkey::new_unique() });
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &governance, ix).await),
+        Some(VaultError::GuardianSetFull as u32),
+    );
+
+    let guardian_pause = |signer: &Pubkey, pause_flags: u16, reason_code: u16| Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(*signer, true), AccountMeta::new(vault_config_pda, false)],
+        data: VaultInstruction::GuardianPause { pause_flags, reason_code }.try_to_vec().unwrap(),
+    };
+    let outsider = Keypair::new();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &outsider, guardian_pause(&outsider.pubkey(), PauseCategory::BridgeTransfer.bit(), 1)).await),
+        Some(VaultError::NotGuardian as u32),
+    );
+
+    advance_clock(&mut context, 60).await;
+    let flags = PauseCategory::RelayerCredit.bit() | PauseCategory::BridgeTransfer.bit();
+    send(&mut context.banks_client, &payer, &guardian, guardian_pause(&guardian.pubkey(), flags, 501)).await.unwrap();
+    let clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    let vault_config = read_vault_config(&mut context.banks_client, &program_id).await;
+    assert_eq!(vault_config.pause_flags, flags);
+    assert_eq!(vault_config.guardian_action_count, 1);
+    assert_eq!(
+        vault_config.guardian_actions[0],
+        GuardianActionRecord {
+            guardian: guardian.pubkey(),
+            action: GuardianAction::Pause,
+            reason_code: 501,
+            target: flags as u64,
+            timestamp: clock.unix_timestamp,
+        }
+    );
+    let deposit = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 1_000_000);
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &governance, deposit).await),
+        Some(VaultError::VaultPaused as u32),
+    );
+
+    // Guardians cannot resume, unpause or change roles
+    let resume = build_governance_ix(&program_id, &guardian.pubkey(), VaultInstruction::SetPauseFlag { category: PauseCategory::RelayerCredit, paused: false });
+    assert!(send(&mut context.banks_client, &payer, &guardian, resume).await.is_err());
+    let unpause = build_governance_ix(&program_id, &guardian.pubkey(), VaultInstruction::SetPaused { paused: false });
+    assert!(send(&mut context.banks_client, &payer, &guardian, unpause).await.is_err());
+    let grant = build_governance_ix(&program_id, &guardian.pubkey(), VaultInstruction::GrantRole { account: guardian.pubkey(), role: VaultRole::Depositor });
+    assert!(send(&mut context.banks_client, &payer, &guardian, grant).await.is_err());
+
+    // A removed guardian loses its powers
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::RemoveGuardian { guardian: guardian.pubkey() });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &guardian, guardian_pause(&guardian.pubkey(), PauseCategory::StateWrite.bit(), 2)).await),
+        Some(VaultError::NotGuardian as u32),
+    );
+}
+
+// ============================================================
+// Test: escape hatch opens after relayer inactivity
+// ============================================================
+#[tokio::test]
+async fn test_escape_hatch_withdraw() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let user = Keypair::new();
+    let usdc_mint = Pubkey::new_unique();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user.pubkey(), 0);
+    let user_token_account = Pubkey::new_unique();
+
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.usdc_mint = usdc_mint;
+    vault_config.vault_token_account = token_vault;
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint { mint_authority: COption::None, supply: 0, decimals: 6, is_initialized: true, freeze_authority: COption::None }
+        .pack_into_slice(&mut mint_data);
+    program_test.add_account(usdc_mint, Account { lamports: 1_000_000_000, data: mint_data, owner: spl_token::id(), executable: false, rent_epoch: 0 });
+    add_token_account(&mut program_test, token_vault, &usdc_mint, &vault_config_pda, 1_000_000_000);
+    add_token_account(&mut program_test, user_token_account, &usdc_mint, &user.pubkey(), 0);
+    add_funded_signer(&mut program_test, &user.pubkey());
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+
+    // Escape mode after 7 days of silence; half of locked funds are paid out
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetEscapeHatch { inactivity_secs: 7 * 86_400, locked_share_bps: 5_000 });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    let state_write = |vault_config_writable: bool| Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(user_account_pda, false),
+            if vault_config_writable { AccountMeta::new(vault_config_pda, false) } else { AccountMeta::new_readonly(vault_config_pda, false) },
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::UserAccount {
+            user_wallet: user.pubkey(),
+            account_index: 0,
+            available_balance_e6: 100_000_000,
+            locked_margin_e6: 40_000_000,
+            spot_locked_e6: 20_000_000,
+            oracle_locked_e6: 0,
+            sequence: next_sequence(),
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    let escape_withdraw = || Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.pubkey(), true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new(user_token_account, false),
+            AccountMeta::new(token_vault, false),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: VaultInstruction::EscapeWithdraw.try_to_vec().unwrap(),
+    };
+
+    // State writes carry the heartbeat and liability counters, so VaultConfig must be writable
+    assert!(send(&mut context.banks_client, &payer, &governance, state_write(false)).await.is_err());
+    advance_clock(&mut context, 86_400).await;
+    send(&mut context.banks_client, &payer, &governance, state_write(true)).await.unwrap();
+    let clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(read_vault_config(&mut context.banks_client, &program_id).await.escape_hatch.last_heartbeat_ts, clock.unix_timestamp);
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &user, escape_withdraw()).await),
+        Some(VaultError::EscapeHatchClosed as u32),
+    );
+
+    // Pause does not block the escape hatch
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetPaused { paused: true });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+    advance_clock(&mut context, 7 * 86_400).await;
+    let bh = context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[escape_withdraw()], Some(&payer.pubkey()), &[&payer, &user], bh);
+    let outcome = context.banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+    // Half of the 60 USDC locked is paid out; the other half is forfeited and logged
+    let logs = outcome.metadata.unwrap().log_messages;
+    assert!(logs.iter().any(|log| log.contains("EscapeWithdrawn") && log.contains("forfeited_e6=30000000")), "{:?}", logs);
+
+    let token_account = context.banks_client.get_account(user_token_account).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&token_account.data).unwrap().amount, 130_000_000);
+    let account = context.banks_client.get_account(user_account_pda).await.unwrap().unwrap();
+    let user_account = UserAccount::try_from_slice(&account.data).unwrap();
+    assert_eq!((user_account.available_balance_e6, user_account.locked_margin_e6, user_account.spot_locked_e6), (0, 0, 0));
+    assert_eq!(user_account.total_withdrawn_e6, 130_000_000);
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &user, escape_withdraw()).await),
+        Some(VaultError::InsufficientBalance as u32),
+    );
+
+    // The relayer coming back closes the hatch again
+    send(&mut context.banks_client, &payer, &governance, state_write(true)).await.unwrap();
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &user, escape_withdraw()).await),
+        Some(VaultError::EscapeHatchClosed as u32),
+    );
+}
+
+// ============================================================
+// Test: governance-configured deposit / withdraw limits
+// ============================================================
+#[tokio::test]
+async fn test_vault_limits() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let outsider = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &outsider.pubkey());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let set_limits = VaultInstruction::SetLimits {
+        token_index: 0,
+        min_deposit_e6: 1_000_000,
+        min_withdraw_e6: 2_000_000,
+        max_deposit_per_tx_e6: 500_000_000,
+        max_withdraw_per_tx_e6: 100_000_000,
+        max_account_balance_e6: 1_000_000_000,
+    };
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &outsider, build_governance_ix(&program_id, &outsider.pubkey(), set_limits.clone())).await),
+        Some(VaultError::InvalidGovernanceAuthority as u32),
+    );
+    send(&mut banks_client, &payer, &governance, build_governance_ix(&program_id, &governance.pubkey(), set_limits)).await.unwrap();
+    assert_eq!(
+        read_vault_config(&mut banks_client, &program_id).await.limits,
+        VaultLimits {
+            min_deposit_e6: 1_000_000,
+            min_withdraw_e6: 2_000_000,
+            max_deposit_per_tx_e6: 500_000_000,
+            max_withdraw_per_tx_e6: 100_000_000,
+            max_account_balance_e6: 1_000_000_000,
+        }
+    );
+
+    let deposit = |amount: u64| build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, amount);
+    let withdraw = |amount: u64| {
+        let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(governance.pubkey(), true),
+                AccountMeta::new(user_account_pda, false),
+                AccountMeta::new(vault_config_pda, false),
+            ],
+            data: VaultInstruction::RelayerWithdraw { user_wallet, amount, account_index: 0, sequence: next_sequence() }.try_to_vec().unwrap(),
+        }
+    };
+
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, deposit(999_999)).await),
+        Some(VaultError::AmountBelowMinimum as u32),
+    );
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, deposit(500_000_001)).await),
+        Some(VaultError::AmountAboveMaximum as u32),
+    );
+    send(&mut banks_client, &payer, &governance, deposit(500_000_000)).await.unwrap();
+    send(&mut banks_client, &payer, &governance, deposit(400_000_000)).await.unwrap();
+    // 900 + 200 USDC exceeds the 1000 USDC per-account cap
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, deposit(200_000_000)).await),
+        Some(VaultError::AccountCapExceeded as u32),
+    );
+
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, withdraw(1_999_999)).await),
+        Some(VaultError::AmountBelowMinimum as u32),
+    );
+    assert_eq!(
+        custom_error(send(&mut banks_client, &payer, &governance, withdraw(100_000_001)).await),
+        Some(VaultError::AmountAboveMaximum as u32),
+    );
+    send(&mut banks_client, &payer, &governance, withdraw(100_000_000)).await.unwrap();
+    send(&mut banks_client, &payer, &governance, deposit(200_000_000)).await.unwrap();
+
+    // A minimum above the maximum is rejected
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetLimits {
+        token_index: 0,
+        min_deposit_e6: 10,
+        min_withdraw_e6: 0,
+        max_deposit_per_tx_e6: 5,
+        max_withdraw_per_tx_e6: 0,
+        max_account_balance_e6: 0,
+    });
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+}
+
+// ============================================================
+// Test: liability counters follow every USDC balance change
+// ============================================================
+#[tokio::test]
+async fn test_liability_counters() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+
+    // Account created before the counters existed: counted in full on its first change
+    let legacy_wallet = Pubkey::new_unique();
+    let (legacy_pda, legacy_bump) = UserAccount::derive_pda(&program_id, &legacy_wallet, 0);
+    let legacy = UserAccount {
+        discriminator: UserAccount::DISCRIMINATOR,
+        wallet: legacy_wallet,
+        bump: legacy_bump,
+        available_balance_e6: 70_000_000,
+        locked_margin_e6: 10_000_000,
+        unrealized_pnl_e6: 5_000_000,
+        total_deposited_e6: 80_000_000,
+        total_withdrawn_e6: 0,
+        last_update_ts: 0,
+        spot_locked_e6: 0,
+        account_index: 0,
+        oracle_locked_e6: 0,
+        layout_version: 0,
+        liability_counted: false,
+        sequence: 0,
+        reserved: [0; 34],
+    };
+    program_test.add_account(legacy_pda, Account {
+        lamports: Rent::default().minimum_balance(USER_ACCOUNT_SIZE),
+        data: legacy.try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let user_wallet = Pubkey::new_unique();
+
+    let withdraw = |wallet: Pubkey, amount: u64| {
+        let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &wallet, 0);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(governance.pubkey(), true),
+                AccountMeta::new(user_account_pda, false),
+                AccountMeta::new(vault_config_pda, false),
+            ],
+            data: VaultInstruction::RelayerWithdraw { user_wallet: wallet, amount, account_index: 0, sequence: next_sequence() }.try_to_vec().unwrap(),
+        }
+    };
+    let state_write = |available_balance_e6: i64, locked_margin_e6: i64, spot_locked_e6: i64| {
+        let mut ix = build_user_account_ix(&program_id, &governance.pubkey(), &user_wallet, available_balance_e6);
+        ix.data = VaultInstruction::UserAccount {
+            user_wallet,
+            account_index: 0,
+            available_balance_e6,
+            locked_margin_e6,
+            spot_locked_e6,
+            oracle_locked_e6: 0,
+            sequence: next_sequence(),
+        }
+        .try_to_vec()
+        .unwrap();
+        ix
+    };
+
+    // New account via RelayerDeposit
+    let ix = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 500_000_000);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!((config.total_deposits, config.total_liabilities_e6, config.total_locked), (500_000_000, 500_000_000, 0));
+
+    // Set-to-value write moves the counters by the difference
+    send(&mut banks_client, &payer, &governance, state_write(300_000_000, 150_000_000, 20_000_000)).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (470_000_000, 170_000_000));
+
+    send(&mut banks_client, &payer, &governance, withdraw(user_wallet, 100_000_000)).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(config.total_withdrawn, 100_000_000);
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (370_000_000, 170_000_000));
+
+    send(&mut banks_client, &payer, &governance, state_write(200_000_000, 0, 0)).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (200_000_000, 0));
+
+    // Legacy account: 70 + 10 locked - 20 withdrawn = 60 added; unrealized PnL is not a liability
+    send(&mut banks_client, &payer, &governance, withdraw(legacy_wallet, 20_000_000)).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(config.total_withdrawn, 120_000_000);
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (260_000_000, 10_000_000));
+    let data = banks_client.get_account(legacy_pda).await.unwrap().unwrap().data;
+    assert!(UserAccount::try_from_slice(&data).unwrap().liability_counted);
+
+    send(&mut banks_client, &payer, &governance, withdraw(legacy_wallet, 10_000_000)).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (250_000_000, 10_000_000));
+
+    // VaultConfig must be writable for the counters
+    let mut ix = withdraw(user_wallet, 1_000_000);
+    ix.accounts[2] = AccountMeta::new_readonly(vault_config_pda, false);
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+}
+
+// ============================================================
+// Test: permissionless solvency check and auto-pause
+// ============================================================
+fn build_check_solvency_ix(program_id: &Pubkey, payer: &Pubkey, vault_token_account: &Pubkey, vault_config_writable: bool) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (solvency_pda, _) = Solvency::derive_pda(program_id, 0);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            if vault_config_writable { AccountMeta::new(vault_config_pda, false) } else { AccountMeta::new_readonly(vault_config_pda, false) },
+            AccountMeta::new(solvency_pda, false),
+            AccountMeta::new_readonly(*vault_token_account, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::CheckSolvency { token_index: 0 }.try_to_vec().unwrap(),
+    }
+}
+
+async fn read_solvency(banks_client: &mut BanksClient, program_id: &Pubkey, token_index: u16) -> Solvency {
+    let (solvency_pda, _) = Solvency::derive_pda(program_id, token_index);
+    let account = banks_client.get_account(solvency_pda).await.unwrap().unwrap();
+    Solvency::try_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_check_solvency() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let checker = Keypair::new();
+    let user_wallet = Pubkey::new_unique();
+    let usdc_mint = Pubkey::new_unique();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let stray_token_account = Pubkey::new_unique();
+
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.usdc_mint = usdc_mint;
+    vault_config.vault_token_account = token_vault;
+    vault_config.total_liabilities_e6 = 250_000_000;
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &checker.pubkey());
+    add_token_account(&mut program_test, token_vault, &usdc_mint, &vault_config_pda, 300_000_000);
+    add_token_account(&mut program_test, stray_token_account, &usdc_mint, &vault_config_pda, 900_000_000);
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+
+    // Anyone can run it; the Solvency PDA is created on first use
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, false);
+    send(&mut context.banks_client, &payer, &checker, ix).await.unwrap();
+    let solvency = read_solvency(&mut context.banks_client, &program_id, 0).await;
+    assert_eq!((solvency.reserves_e6, solvency.liabilities_e6, solvency.ratio_bps), (300_000_000, 250_000_000, 12_000));
+    assert!(solvency.is_solvent());
+    assert!(solvency.checked_ts > 0);
+
+    // Reserves must come from the registered vault token account
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &stray_token_account, false);
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &checker, ix).await),
+        Some(VaultError::InvalidAccount as u32),
+    );
+
+    let ix = build_governance_ix(&program_id, &checker.pubkey(), VaultInstruction::SetSolvencyAutoPause { enabled: true });
+    assert!(send(&mut context.banks_client, &payer, &checker, ix).await.is_err());
+    let ix = build_governance_ix(&program_id, &governance.pubkey(), VaultInstruction::SetSolvencyAutoPause { enabled: true });
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    // Credit without a matching transfer: liabilities 350, reserves 300
+    let ix = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 100_000_000);
+    send(&mut context.banks_client, &payer, &governance, ix).await.unwrap();
+
+    // Auto-pause writes VaultConfig, which must then be writable
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, false);
+    assert!(send(&mut context.banks_client, &payer, &checker, ix).await.is_err());
+
+    // The first short check only starts the shortfall clock
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, true);
+    send(&mut context.banks_client, &payer, &checker, ix).await.unwrap();
+    let solvency = read_solvency(&mut context.banks_client, &program_id, 0).await;
+    assert_eq!((solvency.liabilities_e6, solvency.ratio_bps), (350_000_000, 8_571));
+    assert!(!solvency.is_solvent());
+    assert_eq!(solvency.shortfall_since_ts, solvency.checked_ts);
+    let shortfall_since_ts = solvency.shortfall_since_ts;
+    assert_eq!(read_vault_config(&mut context.banks_client, &program_id).await.pause_flags, 0);
+
+    // Still short, but not for long enough
+    advance_clock(&mut context, SOLVENCY_AUTO_PAUSE_DELAY_SECS - 60).await;
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, true);
+    send(&mut context.banks_client, &payer, &checker, ix).await.unwrap();
+    assert_eq!(read_solvency(&mut context.banks_client, &program_id, 0).await.shortfall_since_ts, shortfall_since_ts);
+    assert_eq!(read_vault_config(&mut context.banks_client, &program_id).await.pause_flags, 0);
+
+    // Short for the whole delay: outflows are paused
+    advance_clock(&mut context, 60).await;
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, true);
+    send(&mut context.banks_client, &payer, &checker, ix).await.unwrap();
+    let config = read_vault_config(&mut context.banks_client, &program_id).await;
+    assert_eq!(config.pause_flags, SOLVENCY_PAUSE_FLAGS);
+    assert!(!config.is_category_paused(PauseCategory::RelayerCredit));
+
+    let withdraw = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(UserAccount::derive_pda(&program_id, &user_wallet, 0).0, false),
+            AccountMeta::new(vault_config_pda, false),
+        ],
+        data: VaultInstruction::RelayerWithdraw { user_wallet, amount: 1_000_000, account_index: 0, sequence: next_sequence() }.try_to_vec().unwrap(),
+    };
+    assert_eq!(
+        custom_error(send(&mut context.banks_client, &payer, &governance, withdraw).await),
+        Some(VaultError::VaultPaused as u32),
+    );
+}
+
+
+#[tokio::test]
+async fn test_check_solvency_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let checker = Keypair::new();
+    let usdc_mint = Pubkey::new_unique();
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let (solvency_pda, _) = Solvency::derive_pda(&program_id, 0);
+
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.usdc_mint = usdc_mint;
+    vault_config.vault_token_account = token_vault;
+    vault_config.total_liabilities_e6 = 250_000_000;
+    let mut program_test = setup_vault(&program_id, &governance, vault_config.try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &checker.pubkey());
+    add_token_account(&mut program_test, token_vault, &usdc_mint, &vault_config_pda, 300_000_000);
+    add_prefunded_pda(&mut program_test, solvency_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let ix = build_check_solvency_ix(&program_id, &checker.pubkey(), &token_vault, false);
+    send(&mut banks_client, &payer, &checker, ix).await.unwrap();
+    let account = banks_client.get_account(solvency_pda).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(account.lamports >= Rent::default().minimum_balance(SOLVENCY_SIZE));
+    assert_eq!(read_solvency(&mut banks_client, &program_id, 0).await.ratio_bps, 12_000);
+}
+
+// ============================================================
+// Test: liability Merkle root posting and inclusion proofs
+// ============================================================
+fn build_post_liability_root_ix(program_id: &Pubkey, relayer: &Pubkey, epoch: u64, tree: &LiabilityTree) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*relayer, true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(ReservesEpoch::derive_pda(program_id, epoch).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::PostLiabilityRoot {
+            epoch,
+            root: tree.root(),
+            total_liabilities_e6: tree.total_liabilities_e6(),
+            leaf_count: tree.leaf_count(),
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+fn build_verify_inclusion_ix(program_id: &Pubkey, epoch: u64, leaf: &LiabilityLeaf, proof: Vec<SumNode>) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new(ReservesEpoch::derive_pda(program_id, epoch).0, false)],
+        data: VaultInstruction::VerifyInclusion {
+            epoch,
+            wallet: leaf.wallet,
+            account_index: leaf.account_index,
+            available_balance_e6: leaf.available_balance_e6,
+            locked_e6: leaf.locked_e6,
+            proof,
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+async fn send_unsigned(banks_client: &mut BanksClient, payer: &Keypair, ix: Instruction) -> Result<(), BanksClientError> {
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], bh)).await
+}
+
+#[tokio::test]
+async fn test_liability_root_and_inclusion() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let outsider = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &outsider.pubkey());
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let mut account = UserAccount::try_from_slice(&[0u8; USER_ACCOUNT_SIZE]).unwrap();
+    account.wallet = Pubkey::new_unique();
+    account.account_index = 2;
+    account.available_balance_e6 = 40_000_000;
+    account.locked_margin_e6 = 5_000_000;
+    account.oracle_locked_e6 = 1_000_000;
+    let leaves = vec![
+        LiabilityLeaf { wallet: Pubkey::new_unique(), account_index: 0, available_balance_e6: 100_000_000, locked_e6: 0 },
+        LiabilityLeaf::from_user_account(&account),
+        LiabilityLeaf { wallet: Pubkey::new_unique(), account_index: 0, available_balance_e6: 7_000_000, locked_e6: 3_000_000 },
+    ];
+    let tree = LiabilityTree::new(&leaves).unwrap();
+    assert_eq!(tree.total_liabilities_e6(), 156_000_000);
+
+    // StateWriter role required
+    let ix = build_post_liability_root_ix(&program_id, &outsider.pubkey(), 1, &tree);
+    assert_eq!(custom_error(send(&mut banks_client, &payer, &outsider, ix).await), Some(VaultError::MissingRole as u32));
+
+    let ix = build_post_liability_root_ix(&program_id, &governance.pubkey(), 1, &tree);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.latest_liability_epoch, 1);
+
+    // Epochs only move forward; a posted root cannot be replaced
+    let ix = build_post_liability_root_ix(&program_id, &governance.pubkey(), 1, &LiabilityTree::new(&leaves[..1]).unwrap());
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+
+    // Anyone can prove a leaf; only the fee payer signs
+    let ix = build_verify_inclusion_ix(&program_id, 1, &leaves[1], tree.proof(1).unwrap());
+    send_unsigned(&mut banks_client, &payer, ix).await.unwrap();
+    let ix = build_verify_inclusion_ix(&program_id, 1, &leaves[2], tree.proof(2).unwrap());
+    send_unsigned(&mut banks_client, &payer, ix).await.unwrap();
+
+    let data = banks_client.get_account(ReservesEpoch::derive_pda(&program_id, 1).0).await.unwrap().unwrap().data;
+    let epoch = ReservesEpoch::try_from_slice(&data).unwrap();
+    assert_eq!((epoch.root, epoch.total_liabilities_e6, epoch.leaf_count), (tree.root(), 156_000_000, 3));
+    assert_eq!((epoch.posted_by, epoch.verified_count), (governance.pubkey(), 2));
+
+    let mut inflated = leaves[1];
+    inflated.available_balance_e6 += 1;
+    let ix = build_verify_inclusion_ix(&program_id, 1, &inflated, tree.proof(1).unwrap());
+    assert_eq!(custom_error(send_unsigned(&mut banks_client, &payer, ix).await), Some(VaultError::InvalidMerkleProof as u32));
+
+    // Negative balances cannot be leaves
+    let negative = LiabilityLeaf { available_balance_e6: -1, ..leaves[0] };
+    let ix = build_verify_inclusion_ix(&program_id, 1, &negative, tree.proof(0).unwrap());
+    let err = send_unsigned(&mut banks_client, &payer, ix).await.unwrap_err().unwrap();
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+
+    // Unposted epoch
+    let ix = build_verify_inclusion_ix(&program_id, 2, &leaves[1], tree.proof(1).unwrap());
+    assert_eq!(custom_error(send_unsigned(&mut banks_client, &payer, ix).await), Some(VaultError::NotInitialized as u32));
+
+    // An understated total is accepted when posted, but no proof verifies against it
+    let mut ix = build_post_liability_root_ix(&program_id, &governance.pubkey(), 2, &tree);
+    ix.data = VaultInstruction::PostLiabilityRoot {
+        epoch: 2,
+        root: tree.root(),
+        total_liabilities_e6: tree.total_liabilities_e6() - 100_000_000,
+        leaf_count: tree.leaf_count(),
+    }
+    .try_to_vec()
+    .unwrap();
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    for (index, leaf) in leaves.iter().enumerate() {
+        let ix = build_verify_inclusion_ix(&program_id, 2, leaf, tree.proof(index).unwrap());
+        assert_eq!(custom_error(send_unsigned(&mut banks_client, &payer, ix).await), Some(VaultError::InvalidMerkleProof as u32));
+    }
+}
+
+#[tokio::test]
+async fn test_liability_root_prefunded_epoch() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (reserves_epoch_pda, _) = ReservesEpoch::derive_pda(&program_id, 1);
+    add_prefunded_pda(&mut program_test, reserves_epoch_pda);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let leaves = vec![LiabilityLeaf { wallet: Pubkey::new_unique(), account_index: 0, available_balance_e6: 100_000_000, locked_e6: 0 }];
+    let tree = LiabilityTree::new(&leaves).unwrap();
+    let ix = build_post_liability_root_ix(&program_id, &governance.pubkey(), 1, &tree);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let account = banks_client.get_account(reserves_epoch_pda).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert!(account.lamports >= Rent::default().minimum_balance(RESERVES_EPOCH_SIZE));
+    assert_eq!(ReservesEpoch::try_from_slice(&account.data).unwrap().root, tree.root());
+    assert_eq!(read_vault_config(&mut banks_client, &program_id).await.latest_liability_epoch, 1);
+}
+
+// ============================================================
+// Test: relayer writes are ordered by DB sequence
+// ============================================================
+#[tokio::test]
+async fn test_sequence_ordering() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let user_wallet = Pubkey::new_unique();
+    let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+
+    let with_sequence = |mut ix: Instruction, sequence: u64| {
+        let mut data = ix.data[..ix.data.len() - 8].to_vec();
+        data.extend_from_slice(&sequence.to_le_bytes());
+        ix.data = data;
+        ix
+    };
+    let deposit = |amount: u64, sequence: u64| {
+        with_sequence(build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, amount), sequence)
+    };
+    let state_write = |available_balance_e6: i64, sequence: u64| {
+        with_sequence(build_user_account_ix(&program_id, &governance.pubkey(), &user_wallet, available_balance_e6), sequence)
+    };
+    let read_user_account = |data: Vec<u8>| UserAccount::try_from_slice(&data).unwrap();
+
+    send(&mut banks_client, &payer, &governance, deposit(100_000_000, 5)).await.unwrap();
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.sequence), (100_000_000, 5));
+
+    // Delta paths reject a sequence that is not newer
+    for sequence in [4, 5] {
+        let result = send(&mut banks_client, &payer, &governance, deposit(1_000_000, sequence)).await;
+        assert_eq!(custom_error(result), Some(VaultError::StaleSequence as u32));
+    }
+
+    send(&mut banks_client, &payer, &governance, state_write(80_000_000, 10)).await.unwrap();
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.sequence), (80_000_000, 10));
+
+    // A stale snapshot arriving late is a no-op rather than an error
+    send(&mut banks_client, &payer, &governance, state_write(999_000_000, 7)).await.unwrap();
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.sequence), (80_000_000, 10));
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(config.total_liabilities_e6, 80_000_000);
+
+    // Gaps are allowed
+    send(&mut banks_client, &payer, &governance, deposit(20_000_000, 42)).await.unwrap();
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.sequence), (100_000_000, 42));
+}
+
+// ============================================================
+// Test: batched UserAccount state writes
+// ============================================================
+fn build_batch_user_account_ix(program_id: &Pubkey, relayer: &Pubkey, updates: Vec<UserAccountUpdate>) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*relayer, true),
+        AccountMeta::new(vault_config_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(
+        updates.iter().map(|update| AccountMeta::new(UserAccount::derive_pda(program_id, &update.user_wallet, update.account_index).0, false)),
+    );
+    Instruction { program_id: *program_id, accounts, data: VaultInstruction::BatchUserAccount { updates }.try_to_vec().unwrap() }
+}
+
+#[tokio::test]
+async fn test_batch_user_account() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (existing, created) = (Pubkey::new_unique(), Pubkey::new_unique());
+    // A pre-funded PDA is still created instead of aborting the batch
+    add_prefunded_pda(&mut program_test, UserAccount::derive_pda(&program_id, &created, 0).0);
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+
+    let ix = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &existing, 100_000_000);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let update = |user_wallet: Pubkey, available_balance_e6: i64, sequence: u64| UserAccountUpdate {
+        user_wallet,
+        account_index: 0,
+        available_balance_e6,
+        locked_margin_e6: 10_000_000,
+        spot_locked_e6: 0,
+        oracle_locked_e6: 0,
+        sequence,
+    };
+    let read_user_account = |data: Vec<u8>| UserAccount::try_from_slice(&data).unwrap();
+    let existing_pda = UserAccount::derive_pda(&program_id, &existing, 0).0;
+    let created_pda = UserAccount::derive_pda(&program_id, &created, 0).0;
+
+    // The same PDA twice: the second entry carries an older sequence and is skipped
+    let updates = vec![update(existing, 60_000_000, 1 << 40), update(created, 30_000_000, 1 << 40), update(existing, 1, 7)];
+    let ix = build_batch_user_account_ix(&program_id, &governance.pubkey(), updates);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &governance], bh);
+    let outcome = banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+    let return_data = outcome.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    assert_eq!(
+        Vec::<StateWriteResult>::try_from_slice(&return_data.data).unwrap(),
+        vec![StateWriteResult::Updated, StateWriteResult::Created, StateWriteResult::Stale],
+    );
+
+    let account = read_user_account(banks_client.get_account(existing_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.locked_margin_e6, account.sequence), (60_000_000, 10_000_000, 1 << 40));
+    let account = read_user_account(banks_client.get_account(created_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.wallet, account.available_balance_e6, account.sequence), (created, 30_000_000, 1 << 40));
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!((config.total_liabilities_e6, config.total_locked), (110_000_000, 20_000_000));
+
+    // A PDA out of order fails the whole batch
+    let mut ix = build_batch_user_account_ix(
+        &program_id, &governance.pubkey(), vec![update(existing, 0, 1 << 41), update(created, 0, 1 << 41)],
+    );
+    ix.accounts.swap(3, 4);
+    let result = send(&mut banks_client, &payer, &governance, ix).await;
+    assert_eq!(custom_error(result), Some(VaultError::InvalidPda as u32));
+    let account = read_user_account(banks_client.get_account(existing_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.sequence), (60_000_000, 1 << 40));
+
+    // Missing PDA, empty batch and oversized batch are rejected
+    let mut ix = build_batch_user_account_ix(&program_id, &governance.pubkey(), vec![update(existing, 0, 1 << 41)]);
+    ix.accounts.pop();
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+    let ix = build_batch_user_account_ix(&program_id, &governance.pubkey(), vec![]);
+    assert!(send(&mut banks_client, &payer, &governance, ix).await.is_err());
+    let updates = (0..=MAX_BATCH_USER_ACCOUNT_UPDATES as u64).map(|i| update(existing, 0, (1 << 41) + i)).collect();
+    let ix = build_batch_user_account_ix(&program_id, &governance.pubkey(), updates);
+    let err = send(&mut banks_client, &payer, &governance, ix).await.unwrap_err().unwrap();
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+
+    // StateWriter role required
+    let outsider = Keypair::new();
+    let ix = build_batch_user_account_ix(&program_id, &outsider.pubkey(), vec![update(existing, 0, 1 << 41)]);
+    assert!(send(&mut banks_client, &payer, &outsider, ix).await.is_err());
+}
+
+/// Serialized size of a legacy transaction (compact-u16 signature count + signatures + message)
+fn transaction_size(tx: &Transaction) -> usize {
+    1 + tx.signatures.len() * 64 + tx.message_data().len()
+}
+
+#[tokio::test]
+async fn test_batch_user_account_full_batch() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let relayer = Keypair::new();
+    let mut program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    add_funded_signer(&mut program_test, &relayer.pubkey());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    assert_eq!(MAX_TRANSACTION_SIZE, PACKET_DATA_SIZE);
+
+    // Worst case: a registered relayer that pays its own fees, with the AuthorizedRelayer PDA attached
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(AuthorizedRelayer::derive_pda(&program_id, &relayer.pubkey()).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::RegisterRelayer {
+            relayer: relayer.pubkey(),
+            label: relayer_label("batch"),
+            roles: VaultRole::StateWriter.bit(),
+            max_deposit_per_tx_e6: 0,
+            max_withdraw_per_tx_e6: 0,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let update = |sequence: u64| UserAccountUpdate {
+        user_wallet: Pubkey::new_unique(),
+        account_index: 0,
+        available_balance_e6: 1_000_000,
+        locked_margin_e6: 0,
+        spot_locked_e6: 0,
+        oracle_locked_e6: 0,
+        sequence,
+    };
+    assert_eq!(update(1).try_to_vec().unwrap().len(), USER_ACCOUNT_UPDATE_SIZE);
+    let batch_tx = |count: usize, bh| {
+        let updates = (0..count).map(|i| update(i as u64 + 1)).collect();
+        let ix = with_authorized_relayer(build_batch_user_account_ix(&program_id, &relayer.pubkey(), updates), &program_id, &relayer.pubkey());
+        Transaction::new_signed_with_payer(&[ix], Some(&relayer.pubkey()), &[&relayer], bh)
+    };
+
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    // One more entry would not fit in a transaction
+    assert!(transaction_size(&batch_tx(MAX_BATCH_USER_ACCOUNT_UPDATES + 1, bh)) > PACKET_DATA_SIZE);
+    let tx = batch_tx(MAX_BATCH_USER_ACCOUNT_UPDATES, bh);
+    assert!(transaction_size(&tx) <= PACKET_DATA_SIZE);
+
+    // Every entry creates its PDA, the most expensive path. Compute units are only fully
+    // metered against the SBF build (`cargo test-sbf`); the native processor counts CPIs only.
+    let outcome = banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+    let metadata = outcome.metadata.unwrap();
+    assert!(metadata.compute_units_consumed <= 200_000, "compute units: {}", metadata.compute_units_consumed);
+    assert_eq!(
+        Vec::<StateWriteResult>::try_from_slice(&metadata.return_data.unwrap().data).unwrap(),
+        vec![StateWriteResult::Created; MAX_BATCH_USER_ACCOUNT_UPDATES],
+    );
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(config.total_liabilities_e6, MAX_BATCH_USER_ACCOUNT_UPDATES as i64 * 1_000_000);
+}
+
+// ============================================================
+// Test: compare-and-set UserAccount writes
+// ============================================================
+fn build_cas_user_account_ix(program_id: &Pubkey, relayer: &Pubkey, update: UserAccountUpdate, expected: ExpectedUserAccount) -> Instruction {
+    let mut ix = build_user_account_ix(program_id, relayer, &update.user_wallet, update.available_balance_e6);
+    ix.data = VaultInstruction::CasUserAccount { update, expected }.try_to_vec().unwrap();
+    ix
+}
+
+#[tokio::test]
+async fn test_cas_user_account() {
+    let program_id = Pubkey::new_unique();
+    let governance = Keypair::new();
+    let program_test = setup_vault(&program_id, &governance, test_vault_config(&governance.pubkey()).try_to_vec().unwrap());
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let user_wallet = Pubkey::new_unique();
+    let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user_wallet, 0);
+
+    let update = |user_wallet: Pubkey, available_balance_e6: i64| UserAccountUpdate {
+        user_wallet,
+        account_index: 0,
+        available_balance_e6,
+        locked_margin_e6: 5_000_000,
+        spot_locked_e6: 0,
+        oracle_locked_e6: 0,
+        sequence: next_sequence(),
+    };
+    let expected = |available_balance_e6: i64, locked_margin_e6: i64| ExpectedUserAccount {
+        available_balance_e6,
+        locked_margin_e6,
+        ..ExpectedUserAccount::default()
+    };
+    let read_user_account = |data: Vec<u8>| UserAccount::try_from_slice(&data).unwrap();
+
+    let ix = build_relayer_deposit_ix(&program_id, &governance.pubkey(), &vault_config_pda, &user_wallet, 100_000_000);
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), update(user_wallet, 80_000_000), expected(100_000_000, 0));
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!((account.available_balance_e6, account.locked_margin_e6), (80_000_000, 5_000_000));
+
+    // The balance moves on-chain; a write based on the old snapshot is refused
+    let withdraw = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(governance.pubkey(), true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new(vault_config_pda, false),
+        ],
+        data: VaultInstruction::RelayerWithdraw { user_wallet, amount: 10_000_000, account_index: 0, sequence: next_sequence() }
+            .try_to_vec()
+            .unwrap(),
+    };
+    send(&mut banks_client, &payer, &governance, withdraw).await.unwrap();
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), update(user_wallet, 90_000_000), expected(80_000_000, 5_000_000));
+    let result = send(&mut banks_client, &payer, &governance, ix).await;
+    assert_eq!(custom_error(result), Some(VaultError::StateMismatch as u32));
+    let account = read_user_account(banks_client.get_account(user_account_pda).await.unwrap().unwrap().data);
+    assert_eq!(account.available_balance_e6, 70_000_000);
+
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), update(user_wallet, 90_000_000), expected(70_000_000, 5_000_000));
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    let config = read_vault_config(&mut banks_client, &program_id).await;
+    assert_eq!(config.total_liabilities_e6, 95_000_000);
+
+    // A stale sequence stays a no-op whatever the expected values
+    let mut stale = update(user_wallet, 1);
+    stale.sequence = 1;
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), stale, expected(1, 1));
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+
+    // A missing PDA is expected to be all zero
+    let new_wallet = Pubkey::new_unique();
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), update(new_wallet, 1_000_000), expected(1, 0));
+    let result = send(&mut banks_client, &payer, &governance, ix).await;
+    assert_eq!(custom_error(result), Some(VaultError::StateMismatch as u32));
+    let new_pda = UserAccount::derive_pda(&program_id, &new_wallet, 0).0;
+    assert!(banks_client.get_account(new_pda).await.unwrap().is_none());
+    let ix = build_cas_user_account_ix(&program_id, &governance.pubkey(), update(new_wallet, 1_000_000), ExpectedUserAccount::default());
+    send(&mut banks_client, &payer, &governance, ix).await.unwrap();
+    assert_eq!(read_user_account(banks_client.get_account(new_pda).await.unwrap().unwrap().data).available_balance_e6, 1_000_000);
+}
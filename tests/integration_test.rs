@@ -24,7 +24,7 @@ use vault_program::{
 #[tokio::test]
 async fn test_initialize_user() {
     let program_id = Pubkey::new_unique();
-    let mut program_test = ProgramTest::new(
+    let program_test = ProgramTest::new(
         "vault_program",
         program_id,
         processor!(vault_program::processor::process_instruction),
@@ -98,24 +98,27 @@ async fn test_state_calculations() {
         discriminator: UserAccount::DISCRIMINATOR,
         wallet: Pubkey::new_unique(),
         bump: 255,
-        available_balance_e6: 1000_000_000, // 1000 USDC
+        available_balance_e6: 1_000_000_000, // 1000 USDC
         locked_margin_e6: 500_000_000,      // 500 USDC
         unrealized_pnl_e6: 200_000_000,     // 200 USDC
-        total_deposited_e6: 1000_000_000,
+        total_deposited_e6: 1_000_000_000,
         total_withdrawn_e6: 0,
         last_update_ts: 0,
         spot_locked_e6: 100_000_000,        // 100 USDC (One Account Experience)
         account_index: 0,
         oracle_locked_e6: 0,
-        reserved: [0; 44],
+        layout_version: UserAccount::LAYOUT_VERSION,
+        liability_counted: true,
+        sequence: 0,
+        reserved: [0; 34],
     };
 
     // equity = available + locked_margin + spot_locked + unrealized_pnl
     // = 1000 + 500 + 100 + 200 = 1800 USDC
-    assert_eq!(user_account.equity(), 1800_000_000);
+    assert_eq!(user_account.equity(), 1_800_000_000);
     
     // 验证结构体字段
-    assert_eq!(user_account.available_balance_e6, 1000_000_000);
+    assert_eq!(user_account.available_balance_e6, 1_000_000_000);
     assert_eq!(user_account.locked_margin_e6, 500_000_000);
     assert_eq!(user_account.unrealized_pnl_e6, 200_000_000);
 }
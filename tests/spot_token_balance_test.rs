@@ -2,24 +2,43 @@
 //!
 //! Tests the per-token PDA system.
 //! Covers: RelayerSpotDeposit, RelayerSpotWithdraw,
-//!         auto-init, and insufficient balance.
+//!         auto-init, insufficient balance, TokenRegistry validation, per-token limits,
+//!         EscapeSpotWithdraw, Token-2022 transfer-fee policies and the
+//!         MigrateAccount liability backfill.
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     system_program,
 };
 use solana_program_test::*;
+use std::sync::atomic::{AtomicU64, Ordering};
 use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    packet::PACKET_DATA_SIZE,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use vault_program::{
-    instruction::VaultInstruction,
+    error::VaultError,
+    instruction::{
+        BatchMode, ExpectedSpotTokenBalance, SpotTokenBalanceUpdate, StateWriteResult, VaultInstruction,
+        MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES, SPOT_TOKEN_BALANCE_UPDATE_SIZE,
+    },
     state::*,
+    token_compat::{EXTENSION_TRANSFER_FEE_CONFIG, MINT_RISK_FREEZE_AUTHORITY, TOKEN_PROGRAM_V2},
 };
 
+/// Monotonic DB sequence (relayer writes require a strictly increasing sequence)
+fn next_sequence() -> u64 {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+}
+
 fn derive_balance_pda(program_id: &Pubkey, wallet: &Pubkey, token_index: u16) -> (Pubkey, u8) {
     derive_balance_pda_with_index(program_id, wallet, 0, token_index)
 }
@@ -47,15 +66,157 @@ async fn read_spot_balance(
         .map(|acc| SpotTokenBalance::try_from_slice(&acc.data).unwrap())
 }
 
+async fn read_token_entry(banks_client: &mut BanksClient, program_id: &Pubkey, token_index: u16) -> TokenRegistryEntry {
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+    let account = banks_client.get_account(entry_pda).await.unwrap().unwrap();
+    TokenRegistryEntry::try_from_slice(&account.data).unwrap()
+}
+
+/// Fixed USDC mint used by `initialize_vault_config`
+fn test_usdc_mint() -> Pubkey {
+    Pubkey::new_from_array([6u8; 32])
+}
+
 async fn setup_vault(program_id: &Pubkey) -> ProgramTest {
     let mut program_test = ProgramTest::new(
         "vault_program",
         *program_id,
         processor!(vault_program::processor::process_instruction),
     );
+    add_mint(&mut program_test, &test_usdc_mint(), 6, COption::None);
     program_test
 }
 
+/// 全零的当前布局 VaultConfig，只填测试关心的字段 (新增字段时无需修改)
+fn test_vault_config(governance_authority: &Pubkey) -> VaultConfig {
+    let mut data = vec![0u8; VAULT_CONFIG_SIZE];
+    data[..8].copy_from_slice(&VaultConfig::DISCRIMINATOR.to_le_bytes());
+    let mut config = VaultConfig::try_from_slice(&data).unwrap();
+    config.governance_authority = *governance_authority;
+    config.delegation_program = Pubkey::new_unique();
+    config.layout_version = VaultConfig::LAYOUT_VERSION;
+    config
+}
+
+fn add_mint(program_test: &mut ProgramTest, mint: &Pubkey, decimals: u8, freeze_authority: COption<Pubkey>) {
+    let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: 0,
+        decimals,
+        is_initialized: true,
+        freeze_authority,
+    }
+    .pack_into_slice(&mut mint_data);
+    program_test.add_account(*mint, Account {
+        lamports: 1_000_000_000,
+        data: mint_data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+}
+
+/// Pre-load an SPL mint and its token vault PDA (authority = VaultConfig PDA)
+/// so `AddToken` can list them. Returns (mint, vault_token_account).
+fn add_spot_token_accounts(program_test: &mut ProgramTest, program_id: &Pubkey, decimals: u8) -> (Pubkey, Pubkey) {
+    add_spot_token_accounts_with_freeze_authority(program_test, program_id, decimals, COption::None)
+}
+
+fn add_spot_token_accounts_with_freeze_authority(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    decimals: u8,
+    freeze_authority: COption<Pubkey>,
+) -> (Pubkey, Pubkey) {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let mint = Pubkey::new_unique();
+    let (vault_token_account, _) = derive_token_vault_pda(program_id, &mint);
+    add_mint(program_test, &mint, decimals, freeze_authority);
+
+    let mut ta_data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint,
+        owner: vault_config_pda,
+        amount: 0,
+        delegate: COption::None,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut ta_data);
+    program_test.add_account(vault_token_account, Account {
+        lamports: 1_000_000_000,
+        data: ta_data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+
+    (mint, vault_token_account)
+}
+
+fn build_add_token_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    token_index: u16,
+    mint: &Pubkey,
+    vault_token_account: &Pubkey,
+    allowed_risk_flags: u8,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*governance_authority, true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*vault_token_account, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::AddToken { token_index, allowed_risk_flags }.try_to_vec().unwrap(),
+    }
+}
+
+async fn add_token(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    token_index: u16,
+    mint: &Pubkey,
+    vault_token_account: &Pubkey,
+) {
+    let ix = build_add_token_ix(program_id, &payer.pubkey(), token_index, mint, vault_token_account, 0);
+
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], recent_blockhash);
+    banks_client.process_transaction(tx).await.unwrap();
+}
+
+fn build_disable_token_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    token_index: u16,
+    disabled: bool,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*governance_authority, true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+        ],
+        data: VaultInstruction::DisableToken { token_index, disabled }.try_to_vec().unwrap(),
+    }
+}
+
 async fn initialize_vault_config(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -64,8 +225,8 @@ async fn initialize_vault_config(
     let (vault_config_pda, _) = derive_vault_config_pda(program_id);
 
     let delegation = Pubkey::new_unique();
-    let usdc_mint = Pubkey::new_unique();
-    let vault_token_account = Pubkey::new_unique();
+    let usdc_mint = test_usdc_mint();
+    let (vault_token_account, _) = derive_token_vault_pda(program_id, &usdc_mint);
 
     let ix = Instruction {
         program_id: *program_id,
@@ -73,8 +234,9 @@ async fn initialize_vault_config(
             AccountMeta::new(payer.pubkey(), true),
             AccountMeta::new(vault_config_pda, false),
             AccountMeta::new_readonly(usdc_mint, false),
-            AccountMeta::new_readonly(vault_token_account, false),
+            AccountMeta::new(vault_token_account, false),
             AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
         ],
         data: VaultInstruction::Initialize {
             delegation_program: delegation,
@@ -102,6 +264,7 @@ fn build_relayer_spot_deposit_ix(
 ) -> Instruction {
     let (balance_pda, _) = derive_balance_pda(program_id, user_wallet, token_index);
     let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
 
     Instruction {
         program_id: *program_id,
@@ -109,6 +272,7 @@ fn build_relayer_spot_deposit_ix(
             AccountMeta::new(*governance_authority, true),
             AccountMeta::new(balance_pda, false),
             AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
             AccountMeta::new_readonly(system_program::id(), false),
         ],
         data: VaultInstruction::RelayerSpotDeposit {
@@ -117,6 +281,7 @@ fn build_relayer_spot_deposit_ix(
             amount,
             account_index: 0,
             amount_e6: amount as i64,
+            sequence: next_sequence(),
         }
         .try_to_vec()
         .unwrap(),
@@ -132,6 +297,7 @@ fn build_relayer_spot_withdraw_ix(
 ) -> Instruction {
     let (balance_pda, _) = derive_balance_pda(program_id, user_wallet, token_index);
     let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
 
     Instruction {
         program_id: *program_id,
@@ -139,6 +305,7 @@ fn build_relayer_spot_withdraw_ix(
             AccountMeta::new(*governance_authority, true),
             AccountMeta::new(balance_pda, false),
             AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
         ],
         data: VaultInstruction::RelayerSpotWithdraw {
             user_wallet: *user_wallet,
@@ -146,6 +313,7 @@ fn build_relayer_spot_withdraw_ix(
             amount,
             account_index: 0,
             amount_e6: amount as i64,
+            sequence: next_sequence(),
         }
         .try_to_vec()
         .unwrap(),
@@ -158,18 +326,20 @@ fn build_relayer_spot_withdraw_ix(
 #[tokio::test]
 async fn test_relayer_spot_deposit_auto_init() {
     let program_id = Pubkey::new_unique();
-    let program_test = setup_vault(&program_id).await;
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1; // wBTC
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
     let (mut banks_client, payer, _) = program_test.start().await;
 
     initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
 
     let user = Pubkey::new_unique();
-    let token_index: u16 = 1; // wBTC
     let (balance_pda, _) = derive_balance_pda(&program_id, &user, token_index);
 
     assert!(read_spot_balance(&mut banks_client, &balance_pda).await.is_none());
 
-    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1000_000_000);
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1_000_000_000);
     let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
     let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], recent_blockhash);
     banks_client.process_transaction(tx).await.unwrap();
@@ -178,7 +348,7 @@ async fn test_relayer_spot_deposit_auto_init() {
     assert_eq!(balance.discriminator, SPOT_TOKEN_BALANCE_DISCRIMINATOR);
     assert_eq!(balance.wallet, user);
     assert_eq!(balance.token_index, token_index);
-    assert_eq!(balance.available_e6, 1000_000_000);
+    assert_eq!(balance.available_e6, 1_000_000_000);
     assert_eq!(balance.locked_e6, 0);
 
     let ix2 = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 500_000_000);
@@ -187,7 +357,7 @@ async fn test_relayer_spot_deposit_auto_init() {
     banks_client.process_transaction(tx2).await.unwrap();
 
     let balance2 = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
-    assert_eq!(balance2.available_e6, 1500_000_000); // 1000 + 500
+    assert_eq!(balance2.available_e6, 1_500_000_000); // 1000 + 500
 }
 
 // ============================================================
@@ -196,15 +366,17 @@ async fn test_relayer_spot_deposit_auto_init() {
 #[tokio::test]
 async fn test_relayer_spot_withdraw() {
     let program_id = Pubkey::new_unique();
-    let program_test = setup_vault(&program_id).await;
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
     let (mut banks_client, payer, _) = program_test.start().await;
 
     initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
 
     let user = Pubkey::new_unique();
-    let token_index: u16 = 1;
 
-    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1000_000_000);
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1_000_000_000);
     let bh = banks_client.get_latest_blockhash().await.unwrap();
     banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
 
@@ -215,9 +387,1279 @@ async fn test_relayer_spot_withdraw() {
     let (balance_pda, _) = derive_balance_pda(&program_id, &user, token_index);
     let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
     assert_eq!(balance.available_e6, 600_000_000); // 1000 - 400
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.total_liabilities_e6, 600_000_000);
 
     let ix3 = build_relayer_spot_withdraw_ix(&program_id, &payer.pubkey(), &user, token_index, 700_000_000);
     let bh = banks_client.get_latest_blockhash().await.unwrap();
     let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix3], Some(&payer.pubkey()), &[&payer], bh)).await;
     assert!(result.is_err()); // InsufficientBalance
+
+    // Relayer credits moved no tokens: the vault holds nothing against 600 owed
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (solvency_pda, _) = Solvency::derive_pda(&program_id, token_index);
+    let ix4 = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(solvency_pda, false),
+            AccountMeta::new_readonly(vault_ta, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(TokenRegistryEntry::derive_pda(&program_id, token_index).0, false),
+        ],
+        data: VaultInstruction::CheckSolvency { token_index }.try_to_vec().unwrap(),
+    };
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix4], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+    let data = banks_client.get_account(solvency_pda).await.unwrap().unwrap().data;
+    let solvency = Solvency::try_from_slice(&data).unwrap();
+    assert_eq!((solvency.token_index, solvency.reserves_e6, solvency.liabilities_e6, solvency.ratio_bps), (token_index, 0, 600_000_000, 0));
+}
+
+// ============================================================
+// Test: TokenRegistry — listing, unregistered and disabled tokens
+// ============================================================
+#[tokio::test]
+async fn test_token_registry_listing() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 3;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    // A lamport sent to the entry PDA ahead of listing does not block AddToken
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    program_test.add_account(entry_pda, Account {
+        lamports: 1,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+
+    // Unregistered token_index is rejected
+    let user = Pubkey::new_unique();
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1_000_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+    assert!(result.is_err()); // TokenNotRegistered
+
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    let entry_account = banks_client.get_account(entry_pda).await.unwrap().unwrap();
+    let entry = TokenRegistryEntry::try_from_slice(&entry_account.data).unwrap();
+    assert_eq!(entry.discriminator, TOKEN_REGISTRY_ENTRY_DISCRIMINATOR);
+    assert_eq!(entry.token_index, token_index);
+    assert_eq!(entry.mint, mint);
+    assert_eq!(entry.decimals, 8);
+    assert_eq!(entry.token_program, spl_token::id());
+    assert_eq!(entry.vault_token_account, vault_ta);
+    assert!(entry.is_enabled);
+
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 1_000_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    // Disabled token rejects deposits but still allows withdrawals
+    let ix = build_disable_token_ix(&program_id, &payer.pubkey(), token_index, true);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 500_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+    assert!(result.is_err()); // TokenDisabled
+
+    let ix = build_relayer_spot_withdraw_ix(&program_id, &payer.pubkey(), &user, token_index, 400_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user, token_index);
+    let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
+    assert_eq!(balance.available_e6, 600_000_000); // 1000 - 400
+}
+
+/// Pre-load a user token account for `mint` holding `amount` native units.
+fn add_user_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let token_account = Pubkey::new_unique();
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(token_account, Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    token_account
+}
+
+fn build_spot_deposit_ix(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vault_token_account: &Pubkey,
+    token_index: u16,
+    amount: u64,
+    amount_e6: i64,
+) -> Instruction {
+    let (balance_pda, _) = derive_balance_pda(program_id, user, token_index);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(*vault_token_account, false),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::SpotDeposit {
+            token_index,
+            amount,
+            account_index: 0,
+            amount_e6,
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+// ============================================================
+// Test: SpotDeposit derives e6 from the registered decimals
+// ============================================================
+#[tokio::test]
+async fn test_spot_deposit_derives_e6_from_decimals() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1; // wBTC, 8 decimals
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    let user = Keypair::new();
+    let user_ta = add_user_token_account(&mut program_test, &mint, &user.pubkey(), 200_000_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000, // rent for SpotTokenBalance auto-init
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    // 1 satoshi claiming 1 BTC of e6 credit is rejected
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 1, 1_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err()); // InvalidAmount (dust)
+
+    // 1.5 BTC with a mismatched amount_e6 is rejected
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 150_000_000, 2_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err()); // InvalidAmount (mismatch)
+
+    // 1.5 BTC → 1_500_000 e6
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 150_000_000, 1_500_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user.pubkey(), token_index);
+    let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
+    assert_eq!(balance.available_e6, 1_500_000);
+
+    let vault_account = banks_client.get_account(vault_ta).await.unwrap().unwrap();
+    let vault_state = spl_token::state::Account::unpack(&vault_account.data).unwrap();
+    assert_eq!(vault_state.amount, 150_000_000);
+}
+
+fn build_set_require_transfer_checked_ix(program_id: &Pubkey, governance_authority: &Pubkey, required: bool) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*governance_authority, true),
+            AccountMeta::new(vault_config_pda, false),
+        ],
+        data: VaultInstruction::SetRequireTransferChecked { required }.try_to_vec().unwrap(),
+    }
+}
+
+// ============================================================
+// Test: SpotDeposit uses transfer_checked with a trailing Mint;
+// legacy layout is rejected once require_transfer_checked is on
+// ============================================================
+#[tokio::test]
+async fn test_spot_deposit_transfer_checked_transition() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 2; // wETH, 8 decimals
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    let user = Keypair::new();
+    let user_ta = add_user_token_account(&mut program_test, &mint, &user.pubkey(), 300_000_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    // Transition window: legacy layout (no Mint) still works
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 100_000_000, 1_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    // Wrong Mint account is rejected
+    let mut ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 100_000_000, 1_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(Pubkey::new_unique(), false));
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err());
+
+    // Governance ends the transition window
+    let ix = build_set_require_transfer_checked_ix(&program_id, &payer.pubkey(), true);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let config_account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    let vault_config = VaultConfig::try_from_slice(&config_account.data).unwrap();
+    assert!(vault_config.require_transfer_checked);
+
+    // Legacy layout → TransferCheckedRequired
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 100_000_000, 1_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err());
+
+    // New layout with Mint → transfer_checked
+    let mut ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 100_000_000, 1_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(mint, false));
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user.pubkey(), token_index);
+    let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
+    assert_eq!(balance.available_e6, 2_000_000);
+
+    let vault_account = banks_client.get_account(vault_ta).await.unwrap().unwrap();
+    let vault_state = spl_token::state::Account::unpack(&vault_account.data).unwrap();
+    assert_eq!(vault_state.amount, 200_000_000);
+}
+
+fn build_spot_withdraw_ix(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vault_token_account: &Pubkey,
+    token_index: u16,
+    amount: u64,
+    amount_e6: i64,
+) -> Instruction {
+    let (balance_pda, _) = derive_balance_pda(program_id, user, token_index);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(*vault_token_account, false),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: VaultInstruction::SpotWithdraw {
+            token_index,
+            amount,
+            account_index: 0,
+            amount_e6,
+        }
+        .try_to_vec()
+        .unwrap(),
+    }
+}
+
+fn build_set_token_fee_policy_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    token_index: u16,
+    vault_pays_transfer_fee: bool,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*governance_authority, true),
+        AccountMeta::new(vault_config_pda, false),
+    ];
+    if token_index != 0 {
+        let (entry_pda, _) = TokenRegistryEntry::derive_pda(program_id, token_index);
+        accounts.push(AccountMeta::new(entry_pda, false));
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::SetTokenFeePolicy { token_index, vault_pays_transfer_fee }.try_to_vec().unwrap(),
+    }
+}
+
+// ============================================================
+// Test: vault-paid transfer fee policy on SpotWithdraw
+// ============================================================
+#[tokio::test]
+async fn test_spot_withdraw_vault_pays_fee_policy() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 4;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 6);
+    let user = Keypair::new();
+    let user_ta = add_user_token_account(&mut program_test, &mint, &user.pubkey(), 5_000_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 5_000_000, 5_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    // Only governance can change the policy
+    let ix = build_set_token_fee_policy_ix(&program_id, &user.pubkey(), token_index, true);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err()); // InvalidGovernanceAuthority
+
+    let ix = build_set_token_fee_policy_ix(&program_id, &payer.pubkey(), token_index, true);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let entry_account = banks_client.get_account(entry_pda).await.unwrap().unwrap();
+    let entry = TokenRegistryEntry::try_from_slice(&entry_account.data).unwrap();
+    assert!(entry.vault_pays_transfer_fee);
+
+    // Vault-paid fees need the Mint account to compute the gross-up
+    let ix = build_spot_withdraw_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 2_000_000, 2_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err()); // TransferCheckedRequired
+
+    let mut ix = build_spot_withdraw_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 2_000_000, 2_000_000);
+    ix.accounts.push(AccountMeta::new_readonly(mint, false));
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    let user_account = banks_client.get_account(user_ta).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&user_account.data).unwrap().amount, 2_000_000);
+
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user.pubkey(), token_index);
+    let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
+    assert_eq!(balance.available_e6, 3_000_000);
+}
+
+/// Token-2022 mint with a TransferFeeConfig extension (same fee for both epochs).
+/// Layout: base Mint (82) + padding to 165 + account type (1 = Mint) + TLV(type 1, len 108).
+fn token_2022_fee_mint_data(decimals: u8, fee_bps: u16, maximum_fee: u64) -> Vec<u8> {
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: 0,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data[..spl_token::state::Mint::LEN]);
+    data.push(1);
+    data.extend_from_slice(&EXTENSION_TRANSFER_FEE_CONFIG.to_le_bytes());
+    data.extend_from_slice(&108u16.to_le_bytes());
+    data.extend_from_slice(&[0u8; 64]); // config + withdraw_withheld authorities
+    data.extend_from_slice(&0u64.to_le_bytes()); // withheld_amount
+    for _ in 0..2 {
+        data.extend_from_slice(&0u64.to_le_bytes()); // epoch
+        data.extend_from_slice(&maximum_fee.to_le_bytes());
+        data.extend_from_slice(&fee_bps.to_le_bytes());
+    }
+    data
+}
+
+/// Pre-load a Token-2022 account of a transfer-fee mint.
+/// Layout: base Account (165) + account type (2 = Account) + TLV TransferFeeAmount(type 2, len 8).
+fn add_token_2022_fee_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let token_account = Pubkey::new_unique();
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    data.push(2);
+    data.extend_from_slice(&2u16.to_le_bytes());
+    data.extend_from_slice(&8u16.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    program_test.add_account(token_account, Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: TOKEN_PROGRAM_V2,
+        executable: false,
+        rent_epoch: 0,
+    });
+    token_account
+}
+
+async fn read_token_amount(banks_client: &mut BanksClient, token_account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*token_account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data[..spl_token::state::Account::LEN]).unwrap().amount
+}
+
+fn build_usdc_transfer_ix(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    usdc_mint: &Pubkey,
+    data: VaultInstruction,
+) -> Instruction {
+    let (user_account_pda, _) = UserAccount::derive_pda(program_id, user, 0);
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (vault_token_account, _) = derive_token_vault_pda(program_id, usdc_mint);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(vault_token_account, false),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_V2, false),
+        ],
+        data: data.try_to_vec().unwrap(),
+    }
+}
+
+// ============================================================
+// Test: Token-2022 transfer-fee USDC mint on Deposit / Withdraw
+// ============================================================
+#[tokio::test]
+async fn test_usdc_token_2022_transfer_fee_policy() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "vault_program",
+        program_id,
+        processor!(vault_program::processor::process_instruction),
+    );
+    // 1% fee, effectively uncapped
+    let usdc_mint = Pubkey::new_unique();
+    program_test.add_account(usdc_mint, Account {
+        lamports: 1_000_000_000,
+        data: token_2022_fee_mint_data(6, 100, u64::MAX),
+        owner: TOKEN_PROGRAM_V2,
+        executable: false,
+        rent_epoch: 0,
+    });
+    let user = Keypair::new();
+    let user_ta = add_token_2022_fee_account(&mut program_test, &usdc_mint, &user.pubkey(), 10_000_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // Initialize creates the Token-2022 vault PDA sized for the fee mint
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (vault_ta, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new_readonly(usdc_mint, false),
+            AccountMeta::new(vault_ta, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_V2, false),
+        ],
+        data: VaultInstruction::Initialize { delegation_program: Pubkey::new_unique() }.try_to_vec().unwrap(),
+    };
+    process(&mut banks_client, &payer, ix).await.unwrap();
+    let (user_account_pda, _) = UserAccount::derive_pda(&program_id, &user.pubkey(), 0);
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(user.pubkey(), true),
+            AccountMeta::new(user_account_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::InitializeUser { account_index: 0 }.try_to_vec().unwrap(),
+    };
+    process_signed(&mut banks_client, &payer, &user, ix).await.unwrap();
+
+    // Deposit credits what the vault received: 5_000_000 - 1% = 4_950_000
+    let mut ix = build_usdc_transfer_ix(&program_id, &user.pubkey(), &user_ta, &usdc_mint, VaultInstruction::Deposit { amount: 5_000_000 });
+    ix.accounts.push(AccountMeta::new_readonly(usdc_mint, false));
+    process_signed(&mut banks_client, &payer, &user, ix).await.unwrap();
+    assert_eq!(read_token_amount(&mut banks_client, &vault_ta).await, 4_950_000);
+
+    // Default policy: the user pays, receiving 1_000_000 - 1%
+    let mut ix = build_usdc_transfer_ix(&program_id, &user.pubkey(), &user_ta, &usdc_mint, VaultInstruction::Withdraw { amount: 1_000_000 });
+    ix.accounts.push(AccountMeta::new_readonly(usdc_mint, false));
+    process_signed(&mut banks_client, &payer, &user, ix).await.unwrap();
+    assert_eq!(read_token_amount(&mut banks_client, &user_ta).await, 5_000_000 + 990_000);
+    assert_eq!(read_token_amount(&mut banks_client, &vault_ta).await, 3_950_000);
+
+    // token_index = 0 switches USDC to vault-paid fees
+    let ix = build_set_token_fee_policy_ix(&program_id, &payer.pubkey(), 0, true);
+    process(&mut banks_client, &payer, ix).await.unwrap();
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert!(VaultConfig::try_from_slice(&account.data).unwrap().usdc_vault_pays_transfer_fee);
+
+    // The gross-up needs the Mint account
+    let ix = build_usdc_transfer_ix(&program_id, &user.pubkey(), &user_ta, &usdc_mint, VaultInstruction::Withdraw { amount: 1_000_000 });
+    assert_eq!(
+        process_signed(&mut banks_client, &payer, &user, ix).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(VaultError::TransferCheckedRequired as u32)),
+    );
+
+    let mut ix = build_usdc_transfer_ix(&program_id, &user.pubkey(), &user_ta, &usdc_mint, VaultInstruction::Withdraw { amount: 1_000_000 });
+    ix.accounts.push(AccountMeta::new_readonly(usdc_mint, false));
+    process_signed(&mut banks_client, &payer, &user, ix).await.unwrap();
+    assert_eq!(read_token_amount(&mut banks_client, &user_ta).await, 5_000_000 + 990_000 + 1_000_000);
+    let sent = 3_950_000 - read_token_amount(&mut banks_client, &vault_ta).await;
+    assert!(sent > 1_000_000);
+
+    // The user is debited `amount`; total_withdrawn counts what left the vault
+    let account = banks_client.get_account(user_account_pda).await.unwrap().unwrap();
+    assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().available_balance_e6, 2_950_000);
+    let account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(VaultConfig::try_from_slice(&account.data).unwrap().total_withdrawn, 1_000_000 + sent);
+}
+
+// ============================================================
+// Test: mints with unaccepted risk flags cannot be listed
+// ============================================================
+#[tokio::test]
+async fn test_add_token_requires_risk_override() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 5;
+    let freeze_authority = Pubkey::new_unique();
+    let (mint, vault_ta) = add_spot_token_accounts_with_freeze_authority(
+        &mut program_test, &program_id, 6, COption::Some(freeze_authority),
+    );
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+
+    // Freeze authority present, no override → UnsafeMint
+    let ix = build_add_token_ix(&program_id, &payer.pubkey(), token_index, &mint, &vault_ta, 0);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+    assert!(result.is_err());
+
+    // Governance explicitly accepts the freeze authority
+    let ix = build_add_token_ix(&program_id, &payer.pubkey(), token_index, &mint, &vault_ta, MINT_RISK_FREEZE_AUTHORITY);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let entry_account = banks_client.get_account(entry_pda).await.unwrap().unwrap();
+    let entry = TokenRegistryEntry::try_from_slice(&entry_account.data).unwrap();
+    assert_eq!(entry.mint_risk_flags, MINT_RISK_FREEZE_AUTHORITY);
+    assert_eq!(entry.allowed_risk_flags, MINT_RISK_FREEZE_AUTHORITY);
+    assert_eq!(entry.unapproved_risk_flags(entry.mint_risk_flags), 0);
+}
+
+fn build_initialize_token_vault_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    mint: &Pubkey,
+    legacy_vault: Option<&Pubkey>,
+    token_registry_entry: Option<&Pubkey>,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let (token_vault, _) = derive_token_vault_pda(program_id, mint);
+
+    let mut accounts = vec![
+        AccountMeta::new(*governance_authority, true),
+        AccountMeta::new(vault_config_pda, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(token_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some(legacy_vault) = legacy_vault {
+        accounts.push(AccountMeta::new(*legacy_vault, false));
+    }
+    if let Some(entry) = token_registry_entry {
+        accounts.push(AccountMeta::new(*entry, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: VaultInstruction::InitializeTokenVault.try_to_vec().unwrap(),
+    }
+}
+
+// ============================================================
+// Test: InitializeTokenVault creates the PDA vault; listing and
+// deposits require exactly that account
+// ============================================================
+#[tokio::test]
+async fn test_initialize_token_vault_and_listing() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 6;
+    let mint = Pubkey::new_unique();
+    add_mint(&mut program_test, &mint, 6, COption::None);
+    let user = Keypair::new();
+    let user_ta = add_user_token_account(&mut program_test, &mint, &user.pubkey(), 1_000_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+
+    // A competing vault account for the same mint, also owned by the VaultConfig PDA
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let rogue_vault = add_user_token_account(&mut program_test, &mint, &vault_config_pda, 0);
+
+    let (mut banks_client, payer, _) = program_test.start().await;
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+
+    // USDC vault was created by Initialize
+    let (usdc_vault, _) = derive_token_vault_pda(&program_id, &test_usdc_mint());
+    let usdc_vault_account = banks_client.get_account(usdc_vault).await.unwrap().unwrap();
+    let usdc_vault_state = spl_token::state::Account::unpack(&usdc_vault_account.data).unwrap();
+    assert_eq!(usdc_vault_state.owner, vault_config_pda);
+
+    // Listing against a non-PDA vault account is rejected
+    let ix = build_add_token_ix(&program_id, &payer.pubkey(), token_index, &mint, &rogue_vault, 0);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+    assert!(result.is_err());
+
+    let ix = build_initialize_token_vault_ix(&program_id, &payer.pubkey(), &mint, None, None);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &mint);
+    let token_vault_account = banks_client.get_account(token_vault).await.unwrap().unwrap();
+    assert_eq!(token_vault_account.owner, spl_token::id());
+    let token_vault_state = spl_token::state::Account::unpack(&token_vault_account.data).unwrap();
+    assert_eq!(token_vault_state.mint, mint);
+    assert_eq!(token_vault_state.owner, vault_config_pda);
+
+    // Second creation fails (fresh blockhash: an identical transaction would be deduplicated)
+    let ix = build_initialize_token_vault_ix(&program_id, &payer.pubkey(), &mint, None, None);
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+    assert!(result.is_err()); // AlreadyInitialized
+
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &token_vault).await;
+
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &token_vault, token_index, 1_000_000, 1_000_000);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+
+    let token_vault_account = banks_client.get_account(token_vault).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&token_vault_account.data).unwrap().amount, 1_000_000);
+}
+
+// ============================================================
+// Test: InitializeTokenVault migrates a legacy USDC vault account
+// ============================================================
+#[tokio::test]
+async fn test_initialize_token_vault_migrates_legacy_usdc_vault() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let usdc_mint = test_usdc_mint();
+
+    // Deployment from before token vault PDAs: arbitrary USDC vault account
+    let legacy_vault = add_user_token_account(&mut program_test, &usdc_mint, &vault_config_pda, 7_000_000);
+    let governance = Keypair::new();
+    let mut vault_config = test_vault_config(&governance.pubkey());
+    vault_config.usdc_mint = usdc_mint;
+    vault_config.vault_token_account = legacy_vault;
+    vault_config.total_deposits = 7_000_000;
+    program_test.add_account(vault_config_pda, Account {
+        lamports: 1_000_000_000,
+        data: vault_config.try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    program_test.add_account(governance.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    // The legacy vault must be passed so its balance is not stranded
+    let ix = build_initialize_token_vault_ix(&program_id, &governance.pubkey(), &usdc_mint, None, None);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &governance], bh)).await;
+    assert!(result.is_err());
+
+    let ix = build_initialize_token_vault_ix(&program_id, &governance.pubkey(), &usdc_mint, Some(&legacy_vault), None);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &governance], bh)).await.unwrap();
+
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &usdc_mint);
+    let token_vault_account = banks_client.get_account(token_vault).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&token_vault_account.data).unwrap().amount, 7_000_000);
+    let legacy_account = banks_client.get_account(legacy_vault).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&legacy_account.data).unwrap().amount, 0);
+
+    let config_account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    let vault_config = VaultConfig::try_from_slice(&config_account.data).unwrap();
+    assert_eq!(vault_config.vault_token_account, token_vault);
+}
+
+// ============================================================
+// Test: a PDA vault created without the TokenRegistryEntry of a listed
+// mint can still receive the legacy balance
+// ============================================================
+#[tokio::test]
+async fn test_initialize_token_vault_sweeps_into_existing_pda() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let token_index: u16 = 9;
+    let mint = Pubkey::new_unique();
+    add_mint(&mut program_test, &mint, 6, COption::None);
+
+    // Listed before token vault PDAs existed: the entry points at an arbitrary account
+    let legacy_vault = add_user_token_account(&mut program_test, &mint, &vault_config_pda, 3_000_000);
+    let (entry_pda, bump) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let mut data = vec![0u8; TOKEN_REGISTRY_ENTRY_SIZE];
+    data[..8].copy_from_slice(&TokenRegistryEntry::DISCRIMINATOR.to_le_bytes());
+    let mut entry = TokenRegistryEntry::try_from_slice(&data).unwrap();
+    entry.token_index = token_index;
+    entry.mint = mint;
+    entry.decimals = 6;
+    entry.token_program = spl_token::id();
+    entry.vault_token_account = legacy_vault;
+    entry.is_enabled = true;
+    entry.bump = bump;
+    program_test.add_account(entry_pda, Account {
+        lamports: 1_000_000_000,
+        data: entry.try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+
+    // Entry omitted: the PDA is created but nothing is re-pointed
+    let ix = build_initialize_token_vault_ix(&program_id, &payer.pubkey(), &mint, None, None);
+    process(&mut banks_client, &payer, ix).await.unwrap();
+    let (token_vault, _) = derive_token_vault_pda(&program_id, &mint);
+    assert_eq!(read_token_amount(&mut banks_client, &token_vault).await, 0);
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.vault_token_account, legacy_vault);
+
+    // Retry with the legacy vault and entry: sweep into the existing PDA
+    let ix = build_initialize_token_vault_ix(&program_id, &payer.pubkey(), &mint, Some(&legacy_vault), Some(&entry_pda));
+    process(&mut banks_client, &payer, ix).await.unwrap();
+    assert_eq!(read_token_amount(&mut banks_client, &token_vault).await, 3_000_000);
+    assert_eq!(read_token_amount(&mut banks_client, &legacy_vault).await, 0);
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.vault_token_account, token_vault);
+
+    // Nothing left to migrate
+    let ix = build_initialize_token_vault_ix(&program_id, &payer.pubkey(), &mint, Some(&legacy_vault), Some(&entry_pda));
+    assert_eq!(
+        process(&mut banks_client, &payer, ix).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(VaultError::AlreadyInitialized as u32)),
+    );
+}
+
+// ============================================================
+// Test: EscapeSpotWithdraw pays available + a share of locked,
+// debits only what was paid and logs the forfeited amount
+// ============================================================
+#[tokio::test]
+async fn test_escape_spot_withdraw() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1; // 4 decimals, so e6 amounts below 100 cannot be paid out
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 4);
+    let user = Keypair::new();
+    let user_ta = add_user_token_account(&mut program_test, &mint, &user.pubkey(), 20_000);
+    program_test.add_account(user.pubkey(), Account {
+        lamports: 1_000_000_000,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+
+    initialize_vault_config(&mut context.banks_client, &payer, &program_id).await;
+    add_token(&mut context.banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user.pubkey(), token_index);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let state_write = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: VaultInstruction::SpotTokenBalance {
+            user_wallet: user.pubkey(),
+            account_index: 0,
+            token_index,
+            available_e6: 1_000_000,
+            locked_e6: 400_200,
+            sequence: next_sequence(),
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    let set_escape_hatch = Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(payer.pubkey(), true), AccountMeta::new(vault_config_pda, false)],
+        data: VaultInstruction::SetEscapeHatch { inactivity_secs: 3_600, locked_share_bps: 2_500 }.try_to_vec().unwrap(),
+    };
+    let escape_withdraw = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.pubkey(), true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new(user_ta, false),
+            AccountMeta::new(vault_ta, false),
+            AccountMeta::new_readonly(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: VaultInstruction::EscapeSpotWithdraw { token_index, account_index: 0 }.try_to_vec().unwrap(),
+    };
+
+    // Fund the vault with 1.5 tokens, then record 1 available + 0.4002 locked
+    let ix = build_spot_deposit_ix(&program_id, &user.pubkey(), &user_ta, &vault_ta, token_index, 15_000, 1_500_000);
+    let bh = context.get_new_latest_blockhash().await.unwrap();
+    context.banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, &user], bh)).await.unwrap();
+    assert_eq!(read_token_entry(&mut context.banks_client, &program_id, token_index).await.total_liabilities_e6, 1_500_000);
+    let bh = context.get_new_latest_blockhash().await.unwrap();
+    context.banks_client.process_transaction(Transaction::new_signed_with_payer(&[state_write, set_escape_hatch], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+    // Set-to-value: 1.5 → 1.0 available + 0.4002 locked
+    assert_eq!(read_token_entry(&mut context.banks_client, &program_id, token_index).await.total_liabilities_e6, 1_400_200);
+
+    let bh = context.get_new_latest_blockhash().await.unwrap();
+    let result = context.banks_client.process_transaction(Transaction::new_signed_with_payer(std::slice::from_ref(&escape_withdraw), Some(&payer.pubkey()), &[&payer, &user], bh)).await;
+    assert!(result.is_err()); // EscapeHatchClosed
+
+    let mut clock: solana_program::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += 3_600;
+    context.set_sysvar(&clock);
+    let bh = context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[escape_withdraw], Some(&payer.pubkey()), &[&payer, &user], bh);
+    let outcome = context.banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+
+    // 1.0 + 25% of 0.4002 = 1.100050, of which 1.1000 is payable in native units
+    let user_account = context.banks_client.get_account(user_ta).await.unwrap().unwrap();
+    assert_eq!(spl_token::state::Account::unpack(&user_account.data).unwrap().amount, 5_000 + 11_000);
+    let logs = outcome.metadata.unwrap().log_messages;
+    assert!(logs.iter().any(|log| log.contains("paid_e6=1100000") && log.contains("forfeited_e6=300150")), "{:?}", logs);
+
+    // The unpaid dust stays available; the forfeited locked part is gone
+    let balance = read_spot_balance(&mut context.banks_client, &balance_pda).await.unwrap();
+    assert_eq!((balance.available_e6, balance.locked_e6), (50, 0));
+    assert_eq!(read_token_entry(&mut context.banks_client, &program_id, token_index).await.total_liabilities_e6, 50);
+}
+
+// ============================================================
+// Test: MigrateAccount adds an uncounted SpotTokenBalance to its token's liabilities
+// ============================================================
+#[tokio::test]
+async fn test_migrate_account_counts_spot_liability() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+
+    // Created before the liability counters existed
+    let wallet = Pubkey::new_unique();
+    let (balance_pda, bump) = derive_balance_pda(&program_id, &wallet, token_index);
+    let mut legacy = SpotTokenBalance::new(wallet, token_index, bump, 0);
+    legacy.available_e6 = 700_000;
+    legacy.locked_e6 = 300_000;
+    legacy.layout_version = 0;
+    legacy.liability_counted = false;
+    program_test.add_account(balance_pda, Account {
+        lamports: 1_000_000_000,
+        data: legacy.try_to_vec().unwrap(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    let (mut banks_client, payer, _) = program_test.start().await;
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.total_liabilities_e6, 0);
+
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let migrate = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new(balance_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(entry_pda, false),
+        ],
+        data: VaultInstruction::MigrateAccount.try_to_vec().unwrap(),
+    };
+    for _ in 0..2 {
+        let bh = banks_client.get_latest_blockhash().await.unwrap();
+        let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+        banks_client.process_transaction(Transaction::new_signed_with_payer(std::slice::from_ref(&migrate), Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+        // Counted once; the rerun changes nothing
+        assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.total_liabilities_e6, 1_000_000);
+    }
+    let balance = read_spot_balance(&mut banks_client, &balance_pda).await.unwrap();
+    assert!(balance.liability_counted);
+    assert_eq!(balance.layout_version, SpotTokenBalance::LAYOUT_VERSION);
+}
+
+// ============================================================
+// Test: SetLimits on a Spot token applies to its TokenRegistryEntry
+// ============================================================
+#[tokio::test]
+async fn test_spot_token_limits() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(vault_config_pda, false),
+            AccountMeta::new(entry_pda, false),
+        ],
+        data: VaultInstruction::SetLimits {
+            token_index,
+            min_deposit_e6: 0,
+            min_withdraw_e6: 0,
+            max_deposit_per_tx_e6: 1_000_000,
+            max_withdraw_per_tx_e6: 0,
+            max_account_balance_e6: 1_500_000,
+        }
+        .try_to_vec()
+        .unwrap(),
+    };
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+
+    let entry_account = banks_client.get_account(entry_pda).await.unwrap().unwrap();
+    let entry = TokenRegistryEntry::try_from_slice(&entry_account.data).unwrap();
+    assert_eq!(entry.limits.max_deposit_per_tx_e6, 1_000_000);
+    // USDC limits are untouched
+    let vault_config_account = banks_client.get_account(vault_config_pda).await.unwrap().unwrap();
+    assert_eq!(VaultConfig::try_from_slice(&vault_config_account.data).unwrap().limits, VaultLimits::usdc_default());
+
+    let user = Pubkey::new_unique();
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user, token_index);
+    for (amount, ok) in [(1_000_001, false), (1_000_000, true), (600_000, false), (500_000, true)] {
+        let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, amount);
+        let bh = banks_client.get_latest_blockhash().await.unwrap();
+        let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+        let result = banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await;
+        assert_eq!(result.is_ok(), ok, "deposit of {}", amount);
+    }
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_pda).await.unwrap().available_e6, 1_500_000);
+}
+
+// ============================================================
+// Test: BatchSpotTokenBalance across wallets and tokens
+// ============================================================
+fn build_batch_spot_token_balance_ix(
+    program_id: &Pubkey,
+    governance_authority: &Pubkey,
+    updates: Vec<SpotTokenBalanceUpdate>,
+    mode: BatchMode,
+) -> Instruction {
+    let (vault_config_pda, _) = derive_vault_config_pda(program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*governance_authority, true),
+        AccountMeta::new_readonly(vault_config_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for update in &updates {
+        let (balance_pda, _) = derive_balance_pda_with_index(program_id, &update.user_wallet, update.account_index, update.token_index);
+        accounts.push(AccountMeta::new(balance_pda, false));
+        accounts.push(AccountMeta::new(TokenRegistryEntry::derive_pda(program_id, update.token_index).0, false));
+    }
+    Instruction { program_id: *program_id, accounts, data: VaultInstruction::BatchSpotTokenBalance { updates, mode }.try_to_vec().unwrap() }
+}
+
+async fn process_batch(banks_client: &mut BanksClient, payer: &Keypair, ix: Instruction) -> Result<Vec<StateWriteResult>, TransactionError> {
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], bh);
+    let outcome = banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result?;
+    Ok(Vec::try_from_slice(&outcome.metadata.unwrap().return_data.unwrap().data).unwrap())
+}
+
+#[tokio::test]
+async fn test_batch_spot_token_balance() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let (mint_1, vault_ta_1) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    let (mint_2, vault_ta_2) = add_spot_token_accounts(&mut program_test, &program_id, 6);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, 1, &mint_1, &vault_ta_1).await;
+    add_token(&mut banks_client, &payer, &program_id, 2, &mint_2, &vault_ta_2).await;
+
+    let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let update = |user_wallet: Pubkey, account_index: u32, token_index: u16, available_e6: i64, sequence: u64| SpotTokenBalanceUpdate {
+        user_wallet,
+        account_index,
+        token_index,
+        available_e6,
+        locked_e6: 0,
+        sequence,
+    };
+    let balance_of = |wallet: &Pubkey, account_index: u32, token_index: u16| {
+        derive_balance_pda_with_index(&program_id, wallet, account_index, token_index).0
+    };
+
+    // Missing PDAs are created across wallets, sub-accounts and tokens
+    let updates = vec![update(alice, 0, 1, 1_000, 10), update(bob, 0, 1, 500, 10), update(alice, 1, 2, 300, 10)];
+    let ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), updates, BatchMode::AllOrNothing);
+    assert_eq!(process_batch(&mut banks_client, &payer, ix).await.unwrap(), vec![StateWriteResult::Created; 3]);
+    let balance = read_spot_balance(&mut banks_client, &balance_of(&alice, 1, 2)).await.unwrap();
+    assert_eq!((balance.wallet, balance.token_index, balance.available_e6, balance.sequence), (alice, 2, 300, 10));
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, 1).await.total_liabilities_e6, 1_500);
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, 2).await.total_liabilities_e6, 300);
+
+    // AllOrNothing: one unregistered token fails the batch
+    let updates = vec![update(alice, 0, 1, 2_000, 20), update(bob, 0, 9, 1, 20)];
+    let ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), updates.clone(), BatchMode::AllOrNothing);
+    assert!(process_batch(&mut banks_client, &payer, ix).await.is_err());
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_of(&alice, 0, 1)).await.unwrap().available_e6, 1_000);
+
+    // SkipInvalid: invalid entries are skipped, the rest is written
+    let mut updates = updates;
+    updates.push(update(bob, 0, 1, 700, 20));
+    updates.push(update(bob, 0, 2, 50, 20));
+    updates.push(update(alice, 0, 1, 9_999, 5));
+    let mut ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), updates, BatchMode::SkipInvalid);
+    ix.accounts[7] = AccountMeta::new(balance_of(&alice, 0, 1), false); // wrong PDA for bob/token 1
+    assert_eq!(
+        process_batch(&mut banks_client, &payer, ix).await.unwrap(),
+        vec![
+            StateWriteResult::Updated,
+            StateWriteResult::Skipped,
+            StateWriteResult::Skipped,
+            StateWriteResult::Created,
+            StateWriteResult::Stale,
+        ],
+    );
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_of(&alice, 0, 1)).await.unwrap().available_e6, 2_000);
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_of(&bob, 0, 1)).await.unwrap().available_e6, 500);
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_of(&bob, 0, 2)).await.unwrap().available_e6, 50);
+    assert!(read_spot_balance(&mut banks_client, &balance_of(&bob, 0, 9)).await.is_none());
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, 1).await.total_liabilities_e6, 2_500);
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, 2).await.total_liabilities_e6, 350);
+
+    // A new PDA is created even at sequence 0, never reported Stale
+    let ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), vec![update(bob, 1, 1, 40, 0)], BatchMode::SkipInvalid);
+    assert_eq!(process_batch(&mut banks_client, &payer, ix).await.unwrap(), vec![StateWriteResult::Created]);
+    let balance = read_spot_balance(&mut banks_client, &balance_of(&bob, 1, 1)).await.unwrap();
+    assert_eq!((balance.available_e6, balance.sequence), (40, 0));
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, 1).await.total_liabilities_e6, 2_540);
+
+    // Account list must hold a (balance, registry) pair per update
+    let mut ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), vec![update(alice, 0, 1, 1, 30)], BatchMode::SkipInvalid);
+    ix.accounts.pop();
+    assert!(process_batch(&mut banks_client, &payer, ix).await.is_err());
+}
+
+#[tokio::test]
+async fn test_batch_spot_token_balance_full_batch() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let count = MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES + 1;
+    let tokens: Vec<_> = (0..count).map(|_| add_spot_token_accounts(&mut program_test, &program_id, 8)).collect();
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    for (i, (mint, vault_ta)) in tokens.iter().enumerate() {
+        add_token(&mut banks_client, &payer, &program_id, i as u16 + 1, mint, vault_ta).await;
+    }
+
+    // Worst case: every entry names a different token, so no registry account is shared
+    let updates: Vec<_> = (0..count)
+        .map(|i| SpotTokenBalanceUpdate {
+            user_wallet: Pubkey::new_unique(),
+            account_index: 0,
+            token_index: i as u16 + 1,
+            available_e6: 1_000,
+            locked_e6: 0,
+            sequence: next_sequence(),
+        })
+        .collect();
+    assert_eq!(updates[0].try_to_vec().unwrap().len(), SPOT_TOKEN_BALANCE_UPDATE_SIZE);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let batch_tx = |updates: &[SpotTokenBalanceUpdate]| {
+        let ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), updates.to_vec(), BatchMode::AllOrNothing);
+        Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)
+    };
+    // Serialized size plus room for the optional AuthorizedRelayer PDA (key + index)
+    let size = |tx: &Transaction| 1 + tx.signatures.len() * 64 + tx.message_data().len() + 33;
+
+    assert!(size(&batch_tx(&updates)) > PACKET_DATA_SIZE);
+    let tx = batch_tx(&updates[..MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES]);
+    assert!(size(&tx) <= PACKET_DATA_SIZE);
+
+    // Every entry creates its PDA. Compute units are only fully metered against the
+    // SBF build (`cargo test-sbf`); the native processor counts CPIs only.
+    let outcome = banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    outcome.result.unwrap();
+    let metadata = outcome.metadata.unwrap();
+    assert!(metadata.compute_units_consumed <= 200_000, "compute units: {}", metadata.compute_units_consumed);
+    assert_eq!(
+        Vec::<StateWriteResult>::try_from_slice(&metadata.return_data.unwrap().data).unwrap(),
+        vec![StateWriteResult::Created; MAX_BATCH_SPOT_TOKEN_BALANCE_UPDATES],
+    );
+
+    // The program rejects an oversized batch on its own as well
+    let ix = build_batch_spot_token_balance_ix(&program_id, &payer.pubkey(), updates, BatchMode::AllOrNothing);
+    assert_eq!(
+        process_batch(&mut banks_client, &payer, ix).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::InvalidArgument),
+    );
+}
+
+// ============================================================
+// Test: compare-and-set SpotTokenBalance writes
+// ============================================================
+async fn process_signed(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    signer: &Keypair,
+    ix: Instruction,
+) -> Result<(), TransactionError> {
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer, signer], bh);
+    banks_client.process_transaction_with_metadata(tx).await.unwrap().result
+}
+
+async fn process(banks_client: &mut BanksClient, payer: &Keypair, ix: Instruction) -> Result<(), TransactionError> {
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    let bh = banks_client.get_new_latest_blockhash(&bh).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], bh);
+    banks_client.process_transaction_with_metadata(tx).await.unwrap().result
+}
+
+#[tokio::test]
+async fn test_cas_spot_token_balance() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup_vault(&program_id).await;
+    let token_index: u16 = 1;
+    let (mint, vault_ta) = add_spot_token_accounts(&mut program_test, &program_id, 8);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    initialize_vault_config(&mut banks_client, &payer, &program_id).await;
+    add_token(&mut banks_client, &payer, &program_id, token_index, &mint, &vault_ta).await;
+
+    let user = Pubkey::new_unique();
+    let (balance_pda, _) = derive_balance_pda(&program_id, &user, token_index);
+    let (vault_config_pda, _) = derive_vault_config_pda(&program_id);
+    let (entry_pda, _) = TokenRegistryEntry::derive_pda(&program_id, token_index);
+    let cas = |available_e6: i64, expected_available_e6: i64| {
+        let update = SpotTokenBalanceUpdate { user_wallet: user, account_index: 0, token_index, available_e6, locked_e6: 0, sequence: next_sequence() };
+        let expected = ExpectedSpotTokenBalance { available_e6: expected_available_e6, locked_e6: 0 };
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(balance_pda, false),
+                AccountMeta::new_readonly(vault_config_pda, false),
+                AccountMeta::new(entry_pda, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: VaultInstruction::CasSpotTokenBalance { update, expected }.try_to_vec().unwrap(),
+        }
+    };
+    let mismatch = Err(TransactionError::InstructionError(0, InstructionError::Custom(VaultError::StateMismatch as u32)));
+
+    assert_eq!(process(&mut banks_client, &payer, cas(1_000, 7)).await, mismatch);
+    assert!(read_spot_balance(&mut banks_client, &balance_pda).await.is_none());
+    process(&mut banks_client, &payer, cas(1_000, 0)).await.unwrap();
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_pda).await.unwrap().available_e6, 1_000);
+
+    // An on-chain credit since the snapshot makes the old expected value fail
+    let ix = build_relayer_spot_deposit_ix(&program_id, &payer.pubkey(), &user, token_index, 500);
+    let bh = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client.process_transaction(Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], bh)).await.unwrap();
+    assert_eq!(process(&mut banks_client, &payer, cas(2_000, 1_000)).await, mismatch);
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_pda).await.unwrap().available_e6, 1_500);
+
+    process(&mut banks_client, &payer, cas(2_000, 1_500)).await.unwrap();
+    assert_eq!(read_spot_balance(&mut banks_client, &balance_pda).await.unwrap().available_e6, 2_000);
+    assert_eq!(read_token_entry(&mut banks_client, &program_id, token_index).await.total_liabilities_e6, 2_000);
 }